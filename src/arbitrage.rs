@@ -0,0 +1,674 @@
+use chrono::Utc;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::{ArbitrageOpportunity, BaseCurrency, TokenPair};
+use crate::util::Pct;
+
+/// A venue name and the price it quoted
+pub type VenuePrice = (&'static str, Decimal);
+
+/// Cheapest venue to buy on and most expensive to sell on, with the spread
+/// between them. `None` unless at least two venues have a price.
+pub fn best_route(prices: &[VenuePrice]) -> Option<(VenuePrice, VenuePrice, Decimal)> {
+    if prices.len() < 2 {
+        return None;
+    }
+
+    let buy = *prices.iter().min_by_key(|(_, price)| *price)?;
+    let sell = *prices.iter().max_by_key(|(_, price)| *price)?;
+    if buy.1 <= Decimal::ZERO {
+        return None;
+    }
+
+    Some((buy, sell, (sell.1 - buy.1) / buy.1))
+}
+
+/// A directed buy -> sell venue pair, written `Buy->Sell` in config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub buy_dex: String,
+    pub sell_dex: String,
+}
+
+impl FromStr for Route {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once("->") {
+            Some((buy, sell)) if !buy.trim().is_empty() && !sell.trim().is_empty() => Ok(Route {
+                buy_dex: buy.trim().to_string(),
+                sell_dex: sell.trim().to_string(),
+            }),
+            _ => Err(format!("expected BuyDex->SellDex, got {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}->{}", self.buy_dex, self.sell_dex)
+    }
+}
+
+/// Profit and slippage gates for one pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Fraction, smallest spread worth quoting both legs for
+    pub min_profit_threshold: Decimal,
+    /// USDC, smallest net profit that is executed
+    pub min_net_profit: Decimal,
+    /// Fixed slippage tolerance, unless adaptive slippage is on
+    pub max_slippage_bps: u16,
+}
+
+impl Thresholds {
+    /// These (global) thresholds with `pair`'s overrides applied, if it has any
+    pub fn for_pair(self, pair: &str, overrides: &[PairThresholds]) -> Thresholds {
+        let Some(pair) = overrides.iter().find(|configured| configured.pair.eq_ignore_ascii_case(pair)) else {
+            return self;
+        };
+        Thresholds {
+            min_profit_threshold: pair.min_profit_threshold.unwrap_or(self.min_profit_threshold),
+            min_net_profit: pair.min_net_profit.unwrap_or(self.min_net_profit),
+            max_slippage_bps: pair.max_slippage_bps.unwrap_or(self.max_slippage_bps),
+        }
+    }
+}
+
+/// One pair's overrides of the global `Thresholds`, written
+/// `SYMBOL:key=value[:key=value...]` in config with keys `min_profit`,
+/// `min_net_profit` and `slippage_bps`. Keys left out keep the global value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairThresholds {
+    pub pair: String,
+    pub min_profit_threshold: Option<Decimal>,
+    pub min_net_profit: Option<Decimal>,
+    pub max_slippage_bps: Option<u16>,
+}
+
+impl FromStr for PairThresholds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':').map(str::trim);
+        let pair = parts.next().filter(|pair| !pair.is_empty()).ok_or_else(|| format!("missing pair in {:?}", s))?;
+        let overrides: Vec<&str> = parts.collect();
+        if overrides.is_empty() {
+            return Err(format!("expected SYMBOL:key=value, got {:?}", s));
+        }
+        let mut thresholds = PairThresholds {
+            pair: pair.to_string(),
+            min_profit_threshold: None,
+            min_net_profit: None,
+            max_slippage_bps: None,
+        };
+        for part in overrides {
+            let (key, value) = part.split_once('=').ok_or_else(|| format!("expected key=value, got {:?}", part))?;
+            let value = value.trim();
+            let invalid = |e: &dyn fmt::Display| format!("invalid {} in {:?}: {}", key.trim(), s, e);
+            match key.trim() {
+                "min_profit" => {
+                    let threshold: Decimal = value.parse().map_err(|e| invalid(&e))?;
+                    if threshold < Decimal::ZERO {
+                        return Err(invalid(&"must not be negative"));
+                    }
+                    thresholds.min_profit_threshold = Some(threshold);
+                }
+                "min_net_profit" => thresholds.min_net_profit = Some(value.parse().map_err(|e| invalid(&e))?),
+                "slippage_bps" => {
+                    let bps: u16 = value.parse().map_err(|e| invalid(&e))?;
+                    if bps >= 10_000 {
+                        return Err(invalid(&"must be below 10000 (100%)"));
+                    }
+                    thresholds.max_slippage_bps = Some(bps);
+                }
+                other => return Err(format!("unknown key {:?} (expected min_profit, min_net_profit or slippage_bps)", other)),
+            }
+        }
+        Ok(thresholds)
+    }
+}
+
+/// Operator policy on which routes may be traded. A route must be on the
+/// allowlist (when one is set) and not on the denylist.
+#[derive(Debug, Clone, Default)]
+pub struct RoutePolicy {
+    pub allow: Vec<Route>,
+    pub deny: Vec<Route>,
+}
+
+impl RoutePolicy {
+    pub fn permits(&self, buy_dex: &str, sell_dex: &str) -> bool {
+        let matches = |route: &Route| route.buy_dex == buy_dex && route.sell_dex == sell_dex;
+        (self.allow.is_empty() || self.allow.iter().any(matches)) && !self.deny.iter().any(matches)
+    }
+
+    /// Widest-spread permitted route across `prices`. Falls through past
+    /// forbidden routes to the best permitted one, `None` if there is none.
+    pub fn best_route(&self, prices: &[VenuePrice]) -> Option<(VenuePrice, VenuePrice, Decimal)> {
+        if self.allow.is_empty() && self.deny.is_empty() {
+            return best_route(prices);
+        }
+
+        let mut best: Option<(VenuePrice, VenuePrice, Decimal)> = None;
+        for &buy in prices.iter().filter(|(_, price)| *price > Decimal::ZERO) {
+            for &sell in prices.iter().filter(|(name, _)| *name != buy.0) {
+                let spread = (sell.1 - buy.1) / buy.1;
+                if !self.permits(buy.0, sell.0) {
+                    if spread > Decimal::ZERO {
+                        log::debug!("Route {}->{} ({}) suppressed by policy", buy.0, sell.0, Pct(spread));
+                    }
+                    continue;
+                }
+                if best.is_none_or(|(_, _, best_spread)| spread > best_spread) {
+                    best = Some((buy, sell, spread));
+                }
+            }
+        }
+
+        best
+    }
+}
+
+/// How far `spot` sits from `twap`, in basis points of the TWAP
+pub fn twap_deviation_bps(spot: Decimal, twap: Decimal) -> Option<Decimal> {
+    if twap <= Decimal::ZERO {
+        return None;
+    }
+    Some((spot - twap).abs() / twap * Decimal::from(10_000))
+}
+
+/// Return of swapping `amount_in` quote tokens into the base token and
+/// straight back on one venue, in basis points of `amount_in`. A sane venue
+/// loses a little to fees and price impact; a gain means stale state,
+/// inverted math or manipulation.
+pub fn round_trip_bps(amount_in: Decimal, amount_back: Decimal) -> Option<Decimal> {
+    if amount_in <= Decimal::ZERO {
+        return None;
+    }
+    Some((amount_back - amount_in) / amount_in * Decimal::from(10_000))
+}
+
+/// Quoted outputs of the two swaps of a round trip. Both come straight from
+/// the venues' on-chain quotes, so each venue's swap fee (e.g. 0.05%-1% on
+/// Uniswap V3, 0.3% on SushiSwap) is already deducted exactly once.
+///
+/// USDC-funded trips buy first: `tokens_bought` is the output for the trade
+/// amount and `usdc_received` the output for `tokens_bought`. WETH-funded
+/// trips sell first: `usdc_received` is the output for the trade amount and
+/// `tokens_bought` the output for `usdc_received`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LegQuotes {
+    /// Base tokens received on the buy venue
+    pub tokens_bought: Decimal,
+    /// Quote tokens received on the sell venue
+    pub usdc_received: Decimal,
+    /// Uniswap V3 fee tier each leg was quoted on, for execution to trade
+    /// through the same pools
+    pub buy_fee_tier: Option<u32>,
+    pub sell_fee_tier: Option<u32>,
+    /// Gas cost in USDC of the round trip priced from the quoters' own swap
+    /// estimates, which replaces the route's estimate
+    pub gas_cost: Option<Decimal>,
+    /// Largest pool price move either leg causes, in basis points, where
+    /// the venues report it
+    pub price_impact_bps: Option<Decimal>,
+}
+
+/// How amounts stored in an opportunity are rounded to their decimal places
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// Half up (away from zero), and the profit gate runs at full precision
+    #[default]
+    Nearest,
+    /// Gains (profit, sell price) down and costs (gas, buy price) up, and
+    /// the profit gate sees the rounded-down profit, so no stored estimate
+    /// is ever better than the quotes support
+    Conservative,
+}
+
+impl RoundingMode {
+    /// Round a gain to `decimals` places
+    pub fn gain(self, value: Decimal, decimals: u32) -> Decimal {
+        match self {
+            RoundingMode::Nearest => value.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero),
+            RoundingMode::Conservative => value.round_dp_with_strategy(decimals, RoundingStrategy::ToNegativeInfinity),
+        }
+    }
+
+    /// Round a cost to `decimals` places
+    pub fn cost(self, value: Decimal, decimals: u32) -> Decimal {
+        match self {
+            RoundingMode::Nearest => value.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero),
+            RoundingMode::Conservative => value.round_dp_with_strategy(decimals, RoundingStrategy::ToPositiveInfinity),
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" => Ok(RoundingMode::Nearest),
+            "conservative" => Ok(RoundingMode::Conservative),
+            other => Err(format!("unknown rounding mode {:?} (expected nearest or conservative)", other)),
+        }
+    }
+}
+
+/// Profit model inputs that stay fixed for one cycle
+#[derive(Debug, Clone)]
+pub struct ProfitModel {
+    pub min_profit_threshold: Decimal,
+    /// Estimated gas cost in USDC of a route without its own estimate,
+    /// converted when profit is counted in WETH
+    pub gas_cost: Decimal,
+    /// Estimated gas cost in USDC of each (buy venue, sell venue) route
+    pub route_gas_costs: HashMap<(String, String), Decimal>,
+    /// Asset the round trip starts and ends in
+    pub base_currency: BaseCurrency,
+    /// Fraction of each swap leg lost to fee-on-transfer tokens
+    pub transfer_fee: Decimal,
+    /// Decimal places kept in the stored buy/sell prices
+    pub price_decimals: u32,
+    /// Decimal places kept in the stored profit and gas amounts
+    pub usdc_decimals: u32,
+    /// How stored prices and amounts are rounded
+    pub rounding: RoundingMode,
+    /// Routes the operator allows to be traded
+    pub routes: RoutePolicy,
+    /// Spread or profit (as a fraction of the trade) above which an
+    /// opportunity is too good to be true
+    pub max_plausible_profit_pct: Decimal,
+}
+
+impl ProfitModel {
+    /// Best permitted route across `prices` if its spread clears the
+    /// threshold; worth quoting both legs for
+    pub fn route(&self, prices: &[VenuePrice]) -> Option<(VenuePrice, VenuePrice, Decimal)> {
+        self.routes.best_route(prices).filter(|(_, _, price_diff)| *price_diff >= self.min_profit_threshold)
+    }
+
+    /// Estimated gas cost in USDC of buying on `buy_dex` and selling on `sell_dex`
+    pub fn gas_cost(&self, buy_dex: &str, sell_dex: &str) -> Decimal {
+        self.route_gas_costs
+            .get(&(buy_dex.to_string(), sell_dex.to_string()))
+            .copied()
+            .unwrap_or(self.gas_cost)
+    }
+
+    /// False when the spread or the profit relative to the trade exceeds the
+    /// plausible maximum, which points at a quoting bug or manipulation
+    pub fn is_plausible(&self, opportunity: &ArbitrageOpportunity) -> bool {
+        let profit_pct = if opportunity.trade_amount > Decimal::ZERO {
+            opportunity.estimated_profit / opportunity.trade_amount
+        } else {
+            Decimal::ZERO
+        };
+        opportunity.price_difference_pct <= self.max_plausible_profit_pct
+            && profit_pct <= self.max_plausible_profit_pct
+    }
+
+    /// Opportunity for round-tripping `trade_amount` of the base currency
+    /// between `buy_dex` and `sell_dex`, if the quoted legs net a profit after gas.
+    ///
+    /// Profit comes only from the quoted outputs: venue fees are inside them
+    /// and slippage is left to the executor as a minimum-output tolerance, so
+    /// neither is applied again here. The stored buy/sell prices are the
+    /// effective, fee-inclusive prices of the two legs, in USDC per WETH
+    /// whichever currency funds the trip.
+    ///
+    /// The profitability check runs at full precision, or on the rounded-down
+    /// profit under conservative rounding; otherwise only the values stored in
+    /// the opportunity are rounded.
+    pub fn evaluate(
+        &self,
+        pair: &TokenPair,
+        (buy_dex, sell_dex): (&str, &str),
+        price_diff_pct: Decimal,
+        trade_amount: Decimal,
+        legs: LegQuotes,
+    ) -> Option<ArbitrageOpportunity> {
+        if legs.tokens_bought <= Decimal::ZERO || legs.usdc_received <= Decimal::ZERO {
+            return None;
+        }
+
+        // Each leg transfers both tokens once; the second quote was for the
+        // untaxed amount, which scales linearly for trades small relative to the pool
+        let kept = Decimal::ONE - self.transfer_fee;
+        let route_gas_cost = legs.gas_cost.unwrap_or_else(|| self.gas_cost(buy_dex, sell_dex));
+        let (tokens_bought, usdc_received, amount_out, gas_cost) = match self.base_currency {
+            BaseCurrency::Usdc => {
+                let usdc_received = legs.usdc_received * kept * kept;
+                (legs.tokens_bought * kept, usdc_received, usdc_received, route_gas_cost)
+            }
+            BaseCurrency::Weth => {
+                let usdc_received = legs.usdc_received * kept;
+                let tokens_bought = legs.tokens_bought * kept * kept;
+                // Gas is paid at the price WETH was bought back at
+                let gas_cost = route_gas_cost * tokens_bought / usdc_received;
+                (tokens_bought, usdc_received, tokens_bought, gas_cost)
+            }
+        };
+
+        let gross_profit = amount_out - trade_amount;
+        let net_profit = gross_profit - gas_cost;
+        let estimated_profit = self.rounding.gain(net_profit, self.usdc_decimals);
+        let gated_profit = match self.rounding {
+            RoundingMode::Nearest => net_profit,
+            RoundingMode::Conservative => estimated_profit,
+        };
+        if gated_profit <= Decimal::ZERO {
+            log::debug!(
+                "Opportunity at {} {} found but not profitable after fees and gas",
+                trade_amount, self.base_currency
+            );
+            return None;
+        }
+
+        let (buy_price, sell_price) = match self.base_currency {
+            BaseCurrency::Usdc => (trade_amount / tokens_bought, usdc_received / tokens_bought),
+            BaseCurrency::Weth => (usdc_received / tokens_bought, usdc_received / trade_amount),
+        };
+
+        Some(ArbitrageOpportunity {
+            id: uuid::Uuid::new_v4(),
+            timestamp: Utc::now(),
+            block_number: None,
+            block_hash: None,
+            token_pair: pair.symbol.clone(),
+            buy_dex: buy_dex.to_string(),
+            sell_dex: sell_dex.to_string(),
+            buy_price: self.rounding.cost(buy_price, self.price_decimals),
+            sell_price: self.rounding.gain(sell_price, self.price_decimals),
+            price_difference_pct: price_diff_pct,
+            trade_amount,
+            estimated_profit,
+            gas_cost: self.rounding.cost(gas_cost, self.usdc_decimals),
+            profit_currency: self.base_currency.as_str().to_string(),
+            execution_status: None,
+            realized_profit: None,
+            buy_fee_tier: legs.buy_fee_tier.map(|fee| fee as i32),
+            sell_fee_tier: legs.sell_fee_tier.map(|fee| fee as i32),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PairBase;
+    use rust_decimal_macros::dec;
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+            token1: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: PairBase::Token0,
+        }
+    }
+
+    fn model(transfer_fee: Decimal) -> ProfitModel {
+        ProfitModel {
+            min_profit_threshold: dec!(0.005),
+            gas_cost: dec!(5),
+            route_gas_costs: HashMap::new(),
+            base_currency: BaseCurrency::Usdc,
+            transfer_fee,
+            // Full precision, so tests can assert exact values
+            price_decimals: 28,
+            usdc_decimals: 28,
+            rounding: RoundingMode::Nearest,
+            routes: RoutePolicy::default(),
+            max_plausible_profit_pct: dec!(0.2),
+        }
+    }
+
+    /// Legs consistent with fee-inclusive prices quoted at `size`
+    fn legs(size: Decimal, buy_price: Decimal, sell_price: Decimal) -> LegQuotes {
+        let tokens_bought = size / buy_price;
+        LegQuotes { tokens_bought, usdc_received: tokens_bought * sell_price, ..LegQuotes::default() }
+    }
+
+    #[test]
+    fn test_best_route_needs_two_venues() {
+        assert!(best_route(&[]).is_none());
+        assert!(best_route(&[("Uniswap", dec!(2000))]).is_none());
+
+        let (buy, sell, spread) = best_route(&[
+            ("Uniswap", dec!(2020)),
+            ("SushiSwap", dec!(2000)),
+            ("QuickSwap", dec!(2010)),
+        ])
+        .unwrap();
+        assert_eq!(buy, ("SushiSwap", dec!(2000)));
+        assert_eq!(sell, ("Uniswap", dec!(2020)));
+        assert_eq!(spread, dec!(0.01));
+    }
+
+    #[test]
+    fn test_route_policy_falls_through_to_permitted_route() {
+        let prices = [("Uniswap", dec!(2020)), ("SushiSwap", dec!(2000)), ("QuickSwap", dec!(2010))];
+        let route = |policy: &RoutePolicy| policy.best_route(&prices).map(|(buy, sell, _)| (buy.0, sell.0));
+        let parse = |routes: &[&str]| routes.iter().map(|r| r.parse().unwrap()).collect::<Vec<Route>>();
+
+        let open = RoutePolicy::default();
+        assert_eq!(route(&open), Some(("SushiSwap", "Uniswap")));
+
+        let deny_best = RoutePolicy { deny: parse(&["SushiSwap->Uniswap"]), ..Default::default() };
+        assert_eq!(route(&deny_best), Some(("SushiSwap", "QuickSwap")));
+        assert_eq!(deny_best.best_route(&prices).unwrap().2, dec!(0.005));
+
+        let allow_one = RoutePolicy { allow: parse(&["QuickSwap->Uniswap"]), ..Default::default() };
+        assert_eq!(route(&allow_one), Some(("QuickSwap", "Uniswap")));
+
+        let nothing = RoutePolicy { allow: parse(&["Uniswap->Balancer"]), ..Default::default() };
+        assert_eq!(route(&nothing), None);
+
+        assert!("Uniswap".parse::<Route>().is_err());
+    }
+
+    #[test]
+    fn test_implausible_profit_is_flagged() {
+        let model = model(Decimal::ZERO);
+        let evaluate = |tokens_bought, usdc_received, price_diff| {
+            let legs = LegQuotes { tokens_bought, usdc_received, ..LegQuotes::default() };
+            model.evaluate(&pair(), ("Uniswap", "SushiSwap"), price_diff, dec!(1000), legs).unwrap()
+        };
+
+        // A normal 1.5% spread netting 6.45 USDC passes
+        assert!(model.is_plausible(&evaluate(dec!(0.49975), dec!(1011.4490225), dec!(0.015))));
+
+        // Exactly 20% profit is the limit; anything above is refused
+        assert!(model.is_plausible(&evaluate(dec!(0.5), dec!(1205), dec!(0.2))));
+        assert!(!model.is_plausible(&evaluate(dec!(0.5), dec!(1205.01), dec!(0.2))));
+
+        // As is a decimals mix-up turning a small spread into thousands of percent
+        assert!(!model.is_plausible(&evaluate(dec!(0.5), dec!(1011), dec!(25))));
+    }
+
+    #[test]
+    fn test_twap_deviation_bps() {
+        assert_eq!(twap_deviation_bps(dec!(2010), dec!(2000)), Some(dec!(50)));
+        assert_eq!(twap_deviation_bps(dec!(1990), dec!(2000)), Some(dec!(50)));
+        assert_eq!(twap_deviation_bps(dec!(2000), Decimal::ZERO), None);
+    }
+
+    #[test]
+    fn test_round_trip_bps() {
+        // 0.3% fee each way
+        assert_eq!(round_trip_bps(dec!(1000), dec!(994.009)), Some(dec!(-59.91)));
+        assert_eq!(round_trip_bps(dec!(1000), dec!(1005)), Some(dec!(50)));
+        assert_eq!(round_trip_bps(Decimal::ZERO, dec!(1)), None);
+    }
+
+    #[test]
+    fn test_profit_uses_fee_inclusive_quotes_once() {
+        // Mid prices 2000 (Uniswap V3, 0.05% tier) and 2030 (SushiSwap, 0.3%)
+        // Buy leg:  1000 USDC * (1 - 0.0005) / 2000 = 0.49975 WETH
+        // Sell leg: 0.49975 WETH * 2030 * (1 - 0.003) = 1011.4490225 USDC
+        let tokens_bought = dec!(1000) * (Decimal::ONE - dec!(0.0005)) / dec!(2000);
+        let usdc_received = tokens_bought * dec!(2030) * (Decimal::ONE - dec!(0.003));
+        assert_eq!(tokens_bought, dec!(0.49975));
+        assert_eq!(usdc_received, dec!(1011.4490225));
+
+        let opportunity = model(Decimal::ZERO)
+            .evaluate(
+                &pair(),
+                ("Uniswap", "SushiSwap"),
+                dec!(0.015),
+                dec!(1000),
+                LegQuotes { tokens_bought, usdc_received, ..LegQuotes::default() },
+            )
+            .unwrap();
+
+        // 1011.4490225 - 1000 - 5 gas, with no extra slippage or fee haircut
+        assert_eq!(opportunity.estimated_profit, dec!(6.4490225));
+        assert_eq!(opportunity.buy_price, dec!(1000) / dec!(0.49975));
+        assert_eq!(opportunity.sell_price, dec!(2023.91));
+        assert_eq!(opportunity.profit_currency, "USDC");
+    }
+
+    #[test]
+    fn test_weth_funded_profit_is_counted_in_weth() {
+        // Same venues, funded with 0.5 WETH: sell first, then buy back
+        // Sell leg: 0.5 WETH * 2030 * (1 - 0.003) = 1011.955 USDC
+        // Buy leg:  1011.955 USDC * (1 - 0.0005) / 2000 = 0.50572451125 WETH
+        let usdc_received = dec!(0.5) * dec!(2030) * (Decimal::ONE - dec!(0.003));
+        let tokens_bought = usdc_received * (Decimal::ONE - dec!(0.0005)) / dec!(2000);
+        assert_eq!(tokens_bought, dec!(0.50572451125));
+
+        let model = ProfitModel { base_currency: BaseCurrency::Weth, ..model(Decimal::ZERO) };
+        let opportunity = model
+            .evaluate(
+                &pair(),
+                ("Uniswap", "SushiSwap"),
+                dec!(0.015),
+                dec!(0.5),
+                LegQuotes { tokens_bought, usdc_received, ..LegQuotes::default() },
+            )
+            .unwrap();
+
+        // 5 USDC of gas at the 2001.0005 USDC/WETH buy-back price
+        assert_eq!(opportunity.gas_cost, dec!(0.00249875));
+        assert_eq!(opportunity.estimated_profit, dec!(0.00322576125));
+        assert_eq!(opportunity.profit_currency, "WETH");
+        assert_eq!(opportunity.sell_price, dec!(2023.91));
+        assert!(opportunity.buy_price < opportunity.sell_price);
+        opportunity.validate().unwrap();
+
+        // Gas alone outweighs a thinner spread
+        let legs = LegQuotes { tokens_bought: dec!(0.501), usdc_received: dec!(1005), ..LegQuotes::default() };
+        assert!(model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.005), dec!(0.5), legs).is_none());
+    }
+
+    #[test]
+    fn test_ladder_records_each_profitable_size() {
+        // The spread shrinks as size grows; gas swamps the smallest size
+        let ladder = [
+            (dec!(500), vec![("SushiSwap", dec!(2000)), ("Uniswap", dec!(2020))]),
+            (dec!(1000), vec![("SushiSwap", dec!(2001)), ("Uniswap", dec!(2019))]),
+            (dec!(5000), vec![("SushiSwap", dec!(2004)), ("Uniswap", dec!(2016))]),
+            (dec!(10000), vec![("SushiSwap", dec!(2008)), ("Uniswap", dec!(2012))]),
+        ];
+
+        let profitable_sizes = |model: ProfitModel| -> Vec<Decimal> {
+            ladder
+                .iter()
+                .filter_map(|(size, prices)| {
+                    let (buy, sell, price_diff) = model.route(prices)?;
+                    model.evaluate(&pair(), (buy.0, sell.0), price_diff, *size, legs(*size, buy.1, sell.1))
+                })
+                .map(|opportunity| opportunity.trade_amount)
+                .collect()
+        };
+
+        assert_eq!(profitable_sizes(model(Decimal::ZERO)), vec![dec!(1000), dec!(5000)]);
+
+        // A 0.2% transfer fee per leg wipes out the thinner of the two
+        assert_eq!(profitable_sizes(model(dec!(0.002))), vec![dec!(5000)]);
+    }
+
+    #[test]
+    fn test_rounds_stored_values_only() {
+        let model = ProfitModel { price_decimals: 2, usdc_decimals: 2, ..model(Decimal::ZERO) };
+        let legs = LegQuotes { tokens_bought: dec!(0.49975), usdc_received: dec!(1011.4490225), ..LegQuotes::default() };
+
+        let opportunity = model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), legs).unwrap();
+        assert_eq!(opportunity.buy_price, dec!(2001.00));
+        assert_eq!(opportunity.sell_price, dec!(2023.91));
+        assert_eq!(opportunity.estimated_profit, dec!(6.45));
+
+        // 0.004 USDC net rounds to zero but is still profitable at full precision
+        let legs = LegQuotes { tokens_bought: dec!(0.5), usdc_received: dec!(1005.004), ..LegQuotes::default() };
+        let opportunity = model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), legs).unwrap();
+        assert_eq!(opportunity.estimated_profit, dec!(0.00));
+
+        let legs = LegQuotes { tokens_bought: dec!(0.5), usdc_received: dec!(1004.996), ..LegQuotes::default() };
+        assert!(model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), legs).is_none());
+    }
+
+    #[test]
+    fn test_gas_follows_the_route() {
+        let model = ProfitModel {
+            route_gas_costs: HashMap::from([
+                (("Uniswap".to_string(), "SushiSwap".to_string()), dec!(3)),
+                (("SushiSwap".to_string(), "SushiSwap V2".to_string()), dec!(2.6)),
+            ]),
+            ..model(Decimal::ZERO)
+        };
+        let legs = LegQuotes { tokens_bought: dec!(0.49975), usdc_received: dec!(1011.4490225), ..LegQuotes::default() };
+        let evaluate = |route| model.evaluate(&pair(), route, dec!(0.015), dec!(1000), legs).unwrap();
+
+        assert_eq!(evaluate(("Uniswap", "SushiSwap")).gas_cost, dec!(3));
+        assert_eq!(evaluate(("Uniswap", "SushiSwap")).estimated_profit, dec!(8.4490225));
+        assert_eq!(evaluate(("SushiSwap", "SushiSwap V2")).gas_cost, dec!(2.6));
+        // No estimate of its own: the flat fallback
+        assert_eq!(evaluate(("SushiSwap", "Uniswap")).gas_cost, dec!(5));
+
+        // The quoters' own estimate beats the route's
+        let quoted = LegQuotes { gas_cost: Some(dec!(1.2)), ..legs };
+        let opportunity = model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), quoted).unwrap();
+        assert_eq!(opportunity.gas_cost, dec!(1.2));
+    }
+
+    #[test]
+    fn test_conservative_rounding_favours_costs() {
+        let nearest = ProfitModel { price_decimals: 2, usdc_decimals: 2, gas_cost: dec!(4.994), ..model(Decimal::ZERO) };
+        let conservative = ProfitModel { rounding: RoundingMode::Conservative, ..nearest.clone() };
+        let legs = LegQuotes { tokens_bought: dec!(0.49975), usdc_received: dec!(1011.4490225), ..LegQuotes::default() };
+        let evaluate = |model: &ProfitModel, legs| model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), legs);
+
+        // Net 6.4550225: half up to 6.46, down to 6.45; gas 4.994 up to 5.00
+        let rounded = evaluate(&nearest, legs).unwrap();
+        assert_eq!((rounded.estimated_profit, rounded.gas_cost), (dec!(6.46), dec!(4.99)));
+        let rounded = evaluate(&conservative, legs).unwrap();
+        assert_eq!((rounded.estimated_profit, rounded.gas_cost), (dec!(6.45), dec!(5.00)));
+        // Buy price 2001.0005 up, sell price 2023.9100 down
+        assert_eq!((rounded.buy_price, rounded.sell_price), (dec!(2001.01), dec!(2023.91)));
+
+        // A profit that only exists below the stored precision fails the conservative gate
+        let legs = LegQuotes { tokens_bought: dec!(0.5), usdc_received: dec!(1004.998), ..LegQuotes::default() };
+        assert_eq!(evaluate(&nearest, legs).unwrap().estimated_profit, dec!(0.00));
+        assert!(evaluate(&conservative, legs).is_none());
+    }
+
+    #[test]
+    fn test_records_the_fee_tier_each_leg_was_quoted_on() {
+        let model = model(Decimal::ZERO);
+        let legs = LegQuotes {
+            tokens_bought: dec!(0.49975),
+            usdc_received: dec!(1011.4490225),
+            buy_fee_tier: Some(500),
+            ..LegQuotes::default()
+        };
+
+        let opportunity = model.evaluate(&pair(), ("Uniswap", "SushiSwap"), dec!(0.015), dec!(1000), legs).unwrap();
+        assert_eq!((opportunity.buy_fee_tier, opportunity.sell_fee_tier), (Some(500), None));
+    }
+}
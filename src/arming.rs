@@ -0,0 +1,164 @@
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// How a dry-run executor on warm standby goes live once its shadow trades
+/// have proven out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArmMode {
+    /// Arm as soon as the policy is satisfied
+    #[default]
+    Auto,
+    /// Arm only once the policy is satisfied and the operator confirms
+    Manual,
+}
+
+impl FromStr for ArmMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ArmMode::Auto),
+            "manual" => Ok(ArmMode::Manual),
+            other => Err(format!("unknown arm mode {:?} (expected auto or manual)", other)),
+        }
+    }
+}
+
+/// A trade the executor only pretended to make: its estimated net profit,
+/// and what simulating it with `eth_call` at a later block says it would
+/// have made (`None` when the simulation reverted or could not run)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowTrade {
+    pub estimated_profit: Decimal,
+    pub shadow_profit: Option<Decimal>,
+}
+
+impl ShadowTrade {
+    /// Still profitable, at a profit within `max_deviation` (a fraction of
+    /// the estimate) of what was estimated
+    fn confirms(&self, max_deviation: Decimal) -> bool {
+        match self.shadow_profit {
+            Some(shadow) if shadow > Decimal::ZERO && self.estimated_profit > Decimal::ZERO => {
+                (shadow - self.estimated_profit).abs() <= self.estimated_profit * max_deviation
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Keeps a dry-run executor disarmed until `after_cycles` consecutive cycles
+/// with dry-run trades saw every one of them confirmed by its simulation, so
+/// the bot is only trusted with funds once its estimates have held up. A
+/// trade that does not confirm restarts the count; cycles without trades
+/// neither count nor reset it.
+#[derive(Debug, Clone)]
+pub struct ExecutorArmingPolicy {
+    mode: ArmMode,
+    after_cycles: u64,
+    max_deviation: Decimal,
+    streak: u64,
+    armed: bool,
+}
+
+impl ExecutorArmingPolicy {
+    pub fn new(mode: ArmMode, after_cycles: u64, max_deviation: Decimal) -> Self {
+        Self {
+            mode,
+            after_cycles,
+            max_deviation,
+            streak: 0,
+            armed: false,
+        }
+    }
+
+    /// Consecutive confirmed cycles so far
+    pub fn streak(&self) -> u64 {
+        self.streak
+    }
+
+    pub fn after_cycles(&self) -> u64 {
+        self.after_cycles
+    }
+
+    /// Record one cycle's dry-run trades
+    pub fn record_cycle(&mut self, trades: &[ShadowTrade]) {
+        if trades.is_empty() {
+            return;
+        }
+        if trades.iter().all(|trade| trade.confirms(self.max_deviation)) {
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+        }
+    }
+
+    /// Whether enough consecutive cycles have confirmed
+    pub fn is_satisfied(&self) -> bool {
+        self.streak >= self.after_cycles
+    }
+
+    /// Whether to arm now, given whether the operator has confirmed. True
+    /// once: an armed executor stays armed.
+    pub fn arm(&mut self, operator_confirmed: bool) -> bool {
+        if self.armed || !self.is_satisfied() {
+            return false;
+        }
+        self.armed = match self.mode {
+            ArmMode::Auto => true,
+            ArmMode::Manual => operator_confirmed,
+        };
+        self.armed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn trade(estimated: Decimal, shadow: Option<Decimal>) -> ShadowTrade {
+        ShadowTrade { estimated_profit: estimated, shadow_profit: shadow }
+    }
+
+    #[test]
+    fn test_auto_arms_after_consecutive_confirmed_cycles() {
+        let mut policy = ExecutorArmingPolicy::new(ArmMode::Auto, 3, dec!(0.2));
+        let good = [trade(dec!(10), Some(dec!(9))), trade(dec!(4), Some(dec!(4.5)))];
+
+        policy.record_cycle(&good);
+        policy.record_cycle(&good);
+        assert!(!policy.arm(false));
+
+        // Off by more than 20%, then evaporated: each restarts the count
+        policy.record_cycle(&[trade(dec!(10), Some(dec!(7)))]);
+        assert_eq!(policy.streak(), 0);
+        policy.record_cycle(&good);
+        policy.record_cycle(&[trade(dec!(10), Some(dec!(10))), trade(dec!(10), None)]);
+        assert_eq!(policy.streak(), 0);
+
+        // Quiet cycles neither count nor reset
+        policy.record_cycle(&good);
+        policy.record_cycle(&[]);
+        policy.record_cycle(&good);
+        assert!(!policy.arm(false));
+        policy.record_cycle(&good);
+        assert!(policy.arm(false));
+        assert!(!policy.arm(false));
+    }
+
+    #[test]
+    fn test_manual_mode_waits_for_the_operator() {
+        let mut policy = ExecutorArmingPolicy::new(ArmMode::Manual, 2, dec!(0.2));
+        let good = [trade(dec!(10), Some(dec!(10)))];
+
+        // A confirmation before the policy is satisfied does not arm
+        policy.record_cycle(&good);
+        assert!(!policy.arm(true));
+
+        policy.record_cycle(&good);
+        assert!(policy.is_satisfied());
+        assert!(!policy.arm(false));
+        assert!(!policy.arm(false));
+        assert!(policy.arm(true));
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result, bail};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::config::redact_url;
+use crate::util::http_call;
+
+/// Best bid and ask in Binance's `/api/v3/ticker/bookTicker` shape, which
+/// several other exchanges mirror
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BookTicker {
+    bid_price: Decimal,
+    ask_price: Decimal,
+}
+
+/// Live order-book reference price from a centralized exchange, used to
+/// catch DEX-wide mispricings that venue-to-venue comparisons cannot see
+pub struct CexReference {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl CexReference {
+    pub fn new(url: &str) -> Self {
+        log::info!("Checking DEX prices against CEX ticker {}", redact_url(url));
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+        }
+    }
+
+    /// Mid of the current best bid and ask
+    pub async fn mid_price(&self) -> Result<Decimal> {
+        let ticker: BookTicker = http_call(|| async {
+            self.client
+                .get(&self.url)
+                .send()
+                .await
+                .context("CEX ticker request failed")?
+                .error_for_status()
+                .context("CEX rejected ticker request")?
+                .json()
+                .await
+                .context("Invalid CEX ticker response")
+        })
+        .await?;
+
+        if ticker.bid_price <= Decimal::ZERO || ticker.ask_price < ticker.bid_price {
+            bail!("Implausible CEX book: bid {} ask {}", ticker.bid_price, ticker.ask_price);
+        }
+        Ok((ticker.bid_price + ticker.ask_price) / Decimal::TWO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one canned JSON response on a local port, returning its URL
+    async fn serve_once(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        format!("http://{}/api/v3/ticker/bookTicker?symbol=ETHUSDT", address)
+    }
+
+    #[tokio::test]
+    async fn test_mid_price_from_book_ticker() {
+        let url = serve_once(
+            r#"{"symbol":"ETHUSDT","bidPrice":"2000.10000000","bidQty":"12.5","askPrice":"2000.30000000","askQty":"3.1"}"#,
+        )
+        .await;
+
+        let mid = CexReference::new(&url).mid_price().await.unwrap();
+        assert_eq!(mid, dec!(2000.2));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_crossed_book() {
+        let url = serve_once(r#"{"bidPrice":"2001","askPrice":"2000"}"#).await;
+
+        let err = CexReference::new(&url).mid_price().await.unwrap_err();
+        assert!(err.to_string().contains("Implausible CEX book"));
+    }
+}
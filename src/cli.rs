@@ -0,0 +1,40 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Value following `flag` in a subcommand's arguments, e.g. `--out report.csv`
+pub fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Parse a timestamp given either as RFC 3339 or as a plain `YYYY-MM-DD` (midnight UTC)
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date {:?}, expected YYYY-MM-DD or RFC 3339", value))?;
+    match date.and_hms_opt(0, 0, 0) {
+        Some(midnight) => Ok(midnight.and_utc()),
+        None => bail!("Invalid date {:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime_formats() {
+        let day = parse_datetime("2024-03-01").unwrap();
+        assert_eq!(day.to_rfc3339(), "2024-03-01T00:00:00+00:00");
+
+        let exact = parse_datetime("2024-03-01T12:30:00+02:00").unwrap();
+        assert_eq!(exact.to_rfc3339(), "2024-03-01T10:30:00+00:00");
+
+        assert!(parse_datetime("yesterday").is_err());
+    }
+}
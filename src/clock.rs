@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+
+/// Source of "now" for the scan loop and price validation, so simulations
+/// can run them on virtual time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system wall clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Wall time that follows tokio's clock from a fixed start, so it stands
+/// still and jumps along with `tokio::time::pause`/`advance`
+#[cfg(test)]
+pub struct TokioClock {
+    start: DateTime<Utc>,
+    origin: tokio::time::Instant,
+}
+
+#[cfg(test)]
+impl TokioClock {
+    pub fn starting_at(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            origin: tokio::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Clock for TokioClock {
+    fn now(&self) -> DateTime<Utc> {
+        let elapsed = chrono::Duration::from_std(self.origin.elapsed()).unwrap_or(chrono::Duration::MAX);
+        self.start + elapsed
+    }
+}
+
+/// Wall time that only moves when advanced, for unit tests of time-dependent
+/// checks outside a tokio runtime
+#[cfg(test)]
+pub struct MockClock {
+    now: parking_lot::Mutex<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn starting_at(start: DateTime<Utc>) -> Self {
+        Self {
+            now: parking_lot::Mutex::new(start),
+        }
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.now.lock() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock()
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use futures::stream::BoxStream;
+use rust_decimal::Decimal;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::models::{ArbitrageOpportunity, DexHealth};
+use postgres::PostgresStore;
+use sqlite::SqliteStore;
+pub use tiered::TieredStore;
+
+pub mod postgres;
+pub mod sqlite;
+mod tiered;
+
+const CSV_HEADER: [&str; 14] = [
+    "id", "timestamp", "token_pair", "buy_dex", "sell_dex",
+    "buy_price", "sell_price", "price_difference_pct",
+    "trade_amount", "estimated_profit", "gas_cost", "profit_currency",
+    "execution_status", "realized_profit",
+];
+
+/// Persistence for detected opportunities. Each backend owns its schema and
+/// type mapping; callers only see `ArbitrageOpportunity`.
+#[async_trait]
+pub trait OpportunityStore: Send + Sync {
+    /// Create or migrate the schema
+    async fn init(&self) -> Result<()>;
+
+    /// Apply the schema inside a transaction that is always rolled back, proving
+    /// the connection and migrations work without changing the database
+    async fn check_schema(&self) -> Result<()>;
+
+    /// Insert an opportunity, returning false without changing anything when
+    /// one with the same id was already saved
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool>;
+
+    /// Record the execution status and realized profit of a saved opportunity
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()>;
+
+    /// Record only the execution status of a saved opportunity, leaving its
+    /// realized profit as it is in the store
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()>;
+
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>>;
+
+    /// Every opportunity recorded since `since`, oldest first, streamed
+    /// rather than loaded into memory
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>>;
+
+    /// Delete every opportunity recorded before `cutoff`, returning how many
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64>;
+
+    /// Insert or replace a venue's circuit-breaker state
+    async fn upsert_dex_health(&self, health: &DexHealth) -> Result<()>;
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>>;
+}
+
+/// Store for read-only mode: reads pass through, every write is refused
+/// with an error (and logged as one, since the bot should never try)
+pub struct ReadOnlyStore {
+    inner: Box<dyn OpportunityStore>,
+}
+
+impl ReadOnlyStore {
+    pub fn new(inner: Box<dyn OpportunityStore>) -> Self {
+        Self { inner }
+    }
+
+    fn refuse<T>(&self, write: &str) -> Result<T> {
+        log::error!("Read-only mode: refusing to {}", write);
+        bail!("read-only mode forbids database writes ({})", write)
+    }
+}
+
+#[async_trait]
+impl OpportunityStore for ReadOnlyStore {
+    /// Never creates or migrates the schema
+    async fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn check_schema(&self) -> Result<()> {
+        self.inner.check_schema().await
+    }
+
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        self.refuse(&format!("save opportunity {}", opportunity.id))
+    }
+
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.refuse(&format!("update execution of {}", opportunity.id))
+    }
+
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.refuse(&format!("update execution status of {}", opportunity.id))
+    }
+
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>> {
+        self.inner.get_recent_opportunities(limit).await
+    }
+
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>> {
+        self.inner.opportunities_since(since)
+    }
+
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        self.refuse(&format!("delete opportunities before {}", cutoff))
+    }
+
+    async fn upsert_dex_health(&self, health: &DexHealth) -> Result<()> {
+        self.refuse(&format!("store {} health", health.dex_name))
+    }
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>> {
+        self.inner.get_dex_health().await
+    }
+}
+
+/// Postgres TLS requirement, as in libpq's `sslmode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    /// Encrypt, without verifying the server certificate
+    Require,
+    /// Encrypt and verify the certificate against the root certificates
+    VerifyCa,
+    /// As `VerifyCa`, and the certificate must name the host
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            other => Err(format!(
+                "unknown sslmode {:?} (expected disable, prefer, require, verify-ca or verify-full)",
+                other
+            )),
+        }
+    }
+}
+
+/// Connection pool sizing and TLS. Only Postgres uses a real pool; SQLite
+/// keeps its single connection and only honours the acquire timeout.
+#[derive(Debug, Clone)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    /// Connections kept open even when idle
+    pub min_connections: u32,
+    /// How long to wait for a free (or new) connection before failing
+    pub acquire_timeout: Duration,
+    /// Overrides the `sslmode` of `DATABASE_URL` when set
+    pub ssl_mode: Option<SslMode>,
+    /// PEM root certificate for `verify-ca` / `verify-full`, overriding `sslrootcert`
+    pub ssl_root_cert: Option<String>,
+    /// Open SQLite databases read-only, never creating one
+    pub read_only: bool,
+}
+
+/// Open the backend named by the `DATABASE_URL` scheme: `postgres://` (or
+/// `postgresql://`) for production, `sqlite://` for local runs and backtests
+pub async fn connect(database_url: &str, settings: &PoolSettings) -> Result<Box<dyn OpportunityStore>> {
+    if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+        Ok(Box::new(PostgresStore::new(database_url, settings).await?))
+    } else if database_url.starts_with("sqlite:") {
+        Ok(Box::new(SqliteStore::new(database_url, settings.acquire_timeout, settings.read_only).await?))
+    } else {
+        bail!("Unsupported DATABASE_URL scheme (expected postgres:// or sqlite://)");
+    }
+}
+
+/// The configured store: `DATABASE_URL`, or a tiered store fronting
+/// `COLD_DATABASE_URL` with it when one is set
+pub async fn open(config: &Config) -> Result<Box<dyn OpportunityStore>> {
+    match open_tiered(config).await? {
+        Some(tiered) => Ok(Box::new(tiered)),
+        None => connect(&config.database_url, &config.pool_settings()).await,
+    }
+}
+
+/// The tiered store, None without a `COLD_DATABASE_URL`
+pub async fn open_tiered(config: &Config) -> Result<Option<TieredStore>> {
+    let Some(cold_url) = &config.cold_database_url else {
+        return Ok(None);
+    };
+    let hot = connect(&config.database_url, &config.pool_settings()).await?;
+    let cold = connect(cold_url, &config.pool_settings()).await.context("Failed to connect to the cold store")?;
+    Ok(Some(TieredStore::new(hot, cold)))
+}
+
+/// Stream every opportunity recorded since `since` to `writer` as CSV,
+/// oldest first, without loading the result set into memory
+pub async fn export_csv(store: &dyn OpportunityStore, since: DateTime<Utc>, writer: impl Write) -> Result<()> {
+    let mut csv = csv::Writer::from_writer(writer);
+    csv.write_record(CSV_HEADER)?;
+
+    let mut rows = store.opportunities_since(since);
+    let mut count = 0;
+    while let Some(opportunity) = rows.try_next().await? {
+        csv.write_record(csv_record(&opportunity))?;
+        count += 1;
+    }
+    csv.flush()?;
+
+    log::info!("Exported {} opportunities since {}", count, since);
+    Ok(())
+}
+
+/// One CSV row with spreadsheet-friendly values: plain (non-scientific)
+/// decimals, `YYYY-MM-DD HH:MM:SS` UTC timestamps and empty cells for NULLs
+fn csv_record(opportunity: &ArbitrageOpportunity) -> [String; 14] {
+    let decimal = |value: Decimal| value.normalize().to_string();
+
+    [
+        opportunity.id.to_string(),
+        opportunity.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+        opportunity.token_pair.clone(),
+        opportunity.buy_dex.clone(),
+        opportunity.sell_dex.clone(),
+        decimal(opportunity.buy_price),
+        decimal(opportunity.sell_price),
+        decimal(opportunity.price_difference_pct),
+        decimal(opportunity.trade_amount),
+        decimal(opportunity.estimated_profit),
+        decimal(opportunity.gas_cost),
+        opportunity.profit_currency.clone(),
+        opportunity.execution_status.clone().unwrap_or_default(),
+        opportunity.realized_profit.map(decimal).unwrap_or_default(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_csv_rows_are_spreadsheet_friendly() {
+        let opportunity = ArbitrageOpportunity {
+            id: uuid::Uuid::nil(),
+            timestamp: Utc.with_ymd_and_hms(2024, 3, 1, 9, 5, 0).unwrap(),
+            buy_price: dec!(2000.500000),
+            sell_price: dec!(2020.25),
+            price_difference_pct: dec!(0.0098725318670332),
+            estimated_profit: dec!(4.10),
+            ..ArbitrageOpportunity::sample()
+        };
+
+        let mut csv = csv::Writer::from_writer(Vec::new());
+        csv.write_record(CSV_HEADER).unwrap();
+        csv.write_record(csv_record(&opportunity)).unwrap();
+        let output = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+
+        let mut lines = output.lines();
+        assert!(lines.next().unwrap().starts_with("id,timestamp,token_pair,"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "00000000-0000-0000-0000-000000000000,2024-03-01 09:05:00,WETH/USDC,SushiSwap,Uniswap,\
+             2000.5,2020.25,0.0098725318670332,1000,4.1,5,USDC,,"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use sqlx::PgPool;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
+use std::str::FromStr;
+
+use super::{OpportunityStore, PoolSettings, SslMode};
+use crate::models::{ArbitrageOpportunity, DexHealth};
+
+const SCHEMA_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS arbitrage_opportunities (
+        id UUID PRIMARY KEY,
+        timestamp TIMESTAMPTZ NOT NULL,
+        token_pair VARCHAR NOT NULL,
+        buy_dex VARCHAR NOT NULL,
+        sell_dex VARCHAR NOT NULL,
+        buy_price DECIMAL NOT NULL,
+        sell_price DECIMAL NOT NULL,
+        price_difference_pct DECIMAL NOT NULL,
+        trade_amount DECIMAL NOT NULL,
+        estimated_profit DECIMAL NOT NULL,
+        gas_cost DECIMAL NOT NULL,
+        profit_currency VARCHAR NOT NULL DEFAULT 'USDC',
+        execution_status VARCHAR,
+        realized_profit DECIMAL,
+        block_number BIGINT,
+        block_hash VARCHAR,
+        buy_fee_tier INTEGER,
+        sell_fee_tier INTEGER
+    );
+
+    ALTER TABLE arbitrage_opportunities
+    ADD COLUMN IF NOT EXISTS execution_status VARCHAR,
+    ADD COLUMN IF NOT EXISTS realized_profit DECIMAL,
+    ADD COLUMN IF NOT EXISTS profit_currency VARCHAR NOT NULL DEFAULT 'USDC',
+    ADD COLUMN IF NOT EXISTS block_number BIGINT,
+    ADD COLUMN IF NOT EXISTS block_hash VARCHAR,
+    ADD COLUMN IF NOT EXISTS buy_fee_tier INTEGER,
+    ADD COLUMN IF NOT EXISTS sell_fee_tier INTEGER;
+
+    CREATE INDEX IF NOT EXISTS idx_arbitrage_timestamp
+    ON arbitrage_opportunities (timestamp);
+
+    CREATE INDEX IF NOT EXISTS idx_arbitrage_token_pair
+    ON arbitrage_opportunities (token_pair);
+
+    CREATE TABLE IF NOT EXISTS dex_health (
+        dex_name VARCHAR PRIMARY KEY,
+        last_price DECIMAL NOT NULL,
+        last_updated TIMESTAMPTZ NOT NULL,
+        consecutive_errors INTEGER NOT NULL
+    );
+"#;
+
+/// Production store: native UUID, TIMESTAMPTZ and DECIMAL columns
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// Connect with `settings`' pool sizes. TLS follows the URL's `sslmode`
+    /// (sqlx defaults to `prefer`) unless `settings` overrides it.
+    pub async fn new(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        if settings.max_connections == 0 {
+            bail!("DB_MAX_CONNECTIONS must be at least 1");
+        }
+        let mut options = PgConnectOptions::from_str(database_url).context("Invalid Postgres DATABASE_URL")?;
+        if let Some(mode) = settings.ssl_mode {
+            options = options.ssl_mode(pg_ssl_mode(mode));
+        }
+        if let Some(cert) = &settings.ssl_root_cert {
+            options = options.ssl_root_cert(cert);
+        }
+        let target = format!(
+            "{}:{} ({:?})",
+            options.get_host(),
+            options.get_port(),
+            options.get_ssl_mode()
+        );
+
+        let pool = PgPoolOptions::new()
+            .max_connections(settings.max_connections)
+            .min_connections(settings.min_connections)
+            .acquire_timeout(settings.acquire_timeout)
+            .connect_with(options)
+            .await
+            .map_err(|e| connect_error(e, &target))?;
+        Ok(Self { pool })
+    }
+}
+
+fn pg_ssl_mode(mode: SslMode) -> PgSslMode {
+    match mode {
+        SslMode::Disable => PgSslMode::Disable,
+        SslMode::Prefer => PgSslMode::Prefer,
+        SslMode::Require => PgSslMode::Require,
+        SslMode::VerifyCa => PgSslMode::VerifyCa,
+        SslMode::VerifyFull => PgSslMode::VerifyFull,
+    }
+}
+
+/// Name the server and the likely fix for the ways a managed Postgres
+/// usually turns a connection away
+fn connect_error(error: sqlx::Error, target: &str) -> anyhow::Error {
+    let hint = match &error {
+        sqlx::Error::Tls(_) => "TLS failed; check DB_SSL_MODE and DB_SSL_ROOT_CERT",
+        sqlx::Error::PoolTimedOut => "no connection within DB_ACQUIRE_TIMEOUT_SECONDS; is the server reachable?",
+        sqlx::Error::Database(db) => match db.code().as_deref() {
+            Some("28000") => "rejected by pg_hba.conf; the server may require TLS (DB_SSL_MODE=require)",
+            Some("28P01") => "authentication failed; check the DATABASE_URL credentials",
+            _ => "rejected by the server",
+        },
+        _ => "connection failed",
+    };
+    anyhow!("Postgres at {} refused the connection: {}: {}", target, hint, error)
+}
+
+#[async_trait]
+impl OpportunityStore for PostgresStore {
+    async fn init(&self) -> Result<()> {
+        sqlx::query(SCHEMA_SQL).execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    async fn check_schema(&self) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(SCHEMA_SQL).execute(&mut *tx).await?;
+        tx.rollback().await?;
+
+        Ok(())
+    }
+
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO arbitrage_opportunities (
+                id, timestamp, token_pair, buy_dex, sell_dex,
+                buy_price, sell_price, price_difference_pct,
+                trade_amount, estimated_profit, gas_cost, profit_currency,
+                execution_status, realized_profit, block_number, block_hash,
+                buy_fee_tier, sell_fee_tier
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(opportunity.id)
+        .bind(opportunity.timestamp)
+        .bind(&opportunity.token_pair)
+        .bind(&opportunity.buy_dex)
+        .bind(&opportunity.sell_dex)
+        .bind(opportunity.buy_price)
+        .bind(opportunity.sell_price)
+        .bind(opportunity.price_difference_pct)
+        .bind(opportunity.trade_amount)
+        .bind(opportunity.estimated_profit)
+        .bind(opportunity.gas_cost)
+        .bind(&opportunity.profit_currency)
+        .bind(&opportunity.execution_status)
+        .bind(opportunity.realized_profit)
+        .bind(opportunity.block_number)
+        .bind(&opportunity.block_hash)
+        .bind(opportunity.buy_fee_tier)
+        .bind(opportunity.sell_fee_tier)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE arbitrage_opportunities
+            SET execution_status = $2, realized_profit = $3
+            WHERE id = $1
+            "#,
+        )
+        .bind(opportunity.id)
+        .bind(&opportunity.execution_status)
+        .bind(opportunity.realized_profit)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        sqlx::query("UPDATE arbitrage_opportunities SET execution_status = $2 WHERE id = $1")
+            .bind(opportunity.id)
+            .bind(&opportunity.execution_status)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>> {
+        let opportunities = sqlx::query_as::<_, ArbitrageOpportunity>(
+            "SELECT * FROM arbitrage_opportunities ORDER BY timestamp DESC LIMIT $1"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(opportunities)
+    }
+
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>> {
+        sqlx::query_as::<_, ArbitrageOpportunity>(
+            "SELECT * FROM arbitrage_opportunities WHERE timestamp >= $1 ORDER BY timestamp"
+        )
+        .bind(since)
+        .fetch(&self.pool)
+        .map_err(anyhow::Error::from)
+        .boxed()
+    }
+
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM arbitrage_opportunities WHERE timestamp < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn upsert_dex_health(&self, health: &DexHealth) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO dex_health (dex_name, last_price, last_updated, consecutive_errors)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (dex_name) DO UPDATE
+            SET last_price = $2, last_updated = $3, consecutive_errors = $4
+            "#,
+        )
+        .bind(&health.dex_name)
+        .bind(health.last_price)
+        .bind(health.last_updated)
+        .bind(health.consecutive_errors)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>> {
+        let health = sqlx::query_as::<_, DexHealth>("SELECT * FROM dex_health ORDER BY dex_name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(health)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn settings(ssl_mode: Option<SslMode>) -> PoolSettings {
+        PoolSettings {
+            max_connections: 2,
+            min_connections: 1,
+            acquire_timeout: Duration::from_secs(10),
+            ssl_mode,
+            ssl_root_cert: None,
+            read_only: false,
+        }
+    }
+
+    #[test]
+    fn test_connect_errors_name_server_and_fix() {
+        let error = connect_error(sqlx::Error::Tls("handshake failure".into()), "db.example:5432 (Require)");
+        assert_eq!(
+            error.to_string(),
+            "Postgres at db.example:5432 (Require) refused the connection: \
+             TLS failed; check DB_SSL_MODE and DB_SSL_ROOT_CERT: \
+             error occurred while attempting to establish a TLS connection: handshake failure"
+        );
+    }
+
+    /// Needs a Postgres that only accepts TLS, e.g. with `hostssl` rules in
+    /// pg_hba.conf: `TLS_DATABASE_URL=postgres://... cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn test_connects_to_tls_only_server() {
+        let url = std::env::var("TLS_DATABASE_URL").expect("TLS_DATABASE_URL is not set");
+
+        let store = PostgresStore::new(&url, &settings(Some(SslMode::Require))).await.unwrap();
+        store.check_schema().await.unwrap();
+
+        let refused = PostgresStore::new(&url, &settings(Some(SslMode::Disable))).await.err().unwrap();
+        assert!(refused.to_string().contains("DB_SSL_MODE=require"), "{}", refused);
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use rust_decimal::Decimal;
+use sqlx::Row;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions, SqliteRow};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::OpportunityStore;
+use crate::models::{ArbitrageOpportunity, DexHealth};
+
+/// SQLite has no UUID, timestamp or decimal types, so everything but the
+/// strings is stored as TEXT: ids hyphenated, timestamps as fixed-width
+/// RFC 3339 (so they sort and compare as text) and decimals at full precision.
+const SCHEMA_SQL: &str = r#"
+    CREATE TABLE IF NOT EXISTS arbitrage_opportunities (
+        id TEXT PRIMARY KEY,
+        timestamp TEXT NOT NULL,
+        token_pair TEXT NOT NULL,
+        buy_dex TEXT NOT NULL,
+        sell_dex TEXT NOT NULL,
+        buy_price TEXT NOT NULL,
+        sell_price TEXT NOT NULL,
+        price_difference_pct TEXT NOT NULL,
+        trade_amount TEXT NOT NULL,
+        estimated_profit TEXT NOT NULL,
+        gas_cost TEXT NOT NULL,
+        profit_currency TEXT NOT NULL DEFAULT 'USDC',
+        execution_status TEXT,
+        realized_profit TEXT,
+        block_number INTEGER,
+        block_hash TEXT,
+        buy_fee_tier INTEGER,
+        sell_fee_tier INTEGER
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_arbitrage_timestamp
+    ON arbitrage_opportunities (timestamp);
+
+    CREATE INDEX IF NOT EXISTS idx_arbitrage_token_pair
+    ON arbitrage_opportunities (token_pair);
+
+    CREATE TABLE IF NOT EXISTS dex_health (
+        dex_name TEXT PRIMARY KEY,
+        last_price TEXT NOT NULL,
+        last_updated TEXT NOT NULL,
+        consecutive_errors INTEGER NOT NULL
+    );
+"#;
+
+/// Lightweight store for local runs and backtests, e.g. `sqlite://arb.db`
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Open `database_url`, creating the file if it is missing. A `read_only`
+    /// store never creates it, and SQLite itself refuses every write.
+    pub async fn new(database_url: &str, acquire_timeout: Duration, read_only: bool) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(!read_only)
+            .read_only(read_only);
+        // One connection: writes are sequential anyway, and an in-memory
+        // database only exists on the connection that created it
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .acquire_timeout(acquire_timeout)
+            .connect_with(options)
+            .await?;
+        Ok(Self { pool })
+    }
+}
+
+/// Create the tables, then add columns introduced since, which SQLite's
+/// ALTER TABLE cannot do conditionally
+async fn apply_schema(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(SCHEMA_SQL).execute(&mut *conn).await?;
+
+    let added_columns = [
+        ("profit_currency", "TEXT NOT NULL DEFAULT 'USDC'"),
+        ("block_number", "INTEGER"),
+        ("block_hash", "TEXT"),
+        ("buy_fee_tier", "INTEGER"),
+        ("sell_fee_tier", "INTEGER"),
+    ];
+    for (column, definition) in added_columns {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('arbitrage_opportunities') WHERE name = ?1",
+        )
+        .bind(column)
+        .fetch_one(&mut *conn)
+        .await?;
+        if !exists {
+            sqlx::query(&format!("ALTER TABLE arbitrage_opportunities ADD COLUMN {} {}", column, definition))
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn timestamp_text(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn decimal_column(row: &SqliteRow, column: &str) -> Result<Decimal> {
+    let text: String = row.try_get(column)?;
+    Decimal::from_str(&text).with_context(|| format!("Invalid {} {:?}", column, text))
+}
+
+fn timestamp_column(row: &SqliteRow, column: &str) -> Result<DateTime<Utc>> {
+    let text: String = row.try_get(column)?;
+    Ok(DateTime::parse_from_rfc3339(&text)
+        .with_context(|| format!("Invalid {} {:?}", column, text))?
+        .with_timezone(&Utc))
+}
+
+fn dex_health_from_row(row: &SqliteRow) -> Result<DexHealth> {
+    Ok(DexHealth {
+        dex_name: row.try_get("dex_name")?,
+        last_price: decimal_column(row, "last_price")?,
+        last_updated: timestamp_column(row, "last_updated")?,
+        consecutive_errors: row.try_get("consecutive_errors")?,
+    })
+}
+
+fn opportunity_from_row(row: &SqliteRow) -> Result<ArbitrageOpportunity> {
+    let id: String = row.try_get("id")?;
+    let realized_profit: Option<String> = row.try_get("realized_profit")?;
+
+    Ok(ArbitrageOpportunity {
+        id: uuid::Uuid::parse_str(&id).with_context(|| format!("Invalid id {:?}", id))?,
+        timestamp: timestamp_column(row, "timestamp")?,
+        block_number: row.try_get("block_number")?,
+        block_hash: row.try_get("block_hash")?,
+        token_pair: row.try_get("token_pair")?,
+        buy_dex: row.try_get("buy_dex")?,
+        sell_dex: row.try_get("sell_dex")?,
+        buy_price: decimal_column(row, "buy_price")?,
+        sell_price: decimal_column(row, "sell_price")?,
+        price_difference_pct: decimal_column(row, "price_difference_pct")?,
+        trade_amount: decimal_column(row, "trade_amount")?,
+        estimated_profit: decimal_column(row, "estimated_profit")?,
+        gas_cost: decimal_column(row, "gas_cost")?,
+        profit_currency: row.try_get("profit_currency")?,
+        execution_status: row.try_get("execution_status")?,
+        realized_profit: realized_profit
+            .map(|text| Decimal::from_str(&text).with_context(|| format!("Invalid realized_profit {:?}", text)))
+            .transpose()?,
+        buy_fee_tier: row.try_get("buy_fee_tier")?,
+        sell_fee_tier: row.try_get("sell_fee_tier")?,
+    })
+}
+
+#[async_trait]
+impl OpportunityStore for SqliteStore {
+    async fn init(&self) -> Result<()> {
+        apply_schema(&mut *self.pool.acquire().await?).await
+    }
+
+    async fn check_schema(&self) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        apply_schema(&mut tx).await?;
+        tx.rollback().await?;
+
+        Ok(())
+    }
+
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO arbitrage_opportunities (
+                id, timestamp, token_pair, buy_dex, sell_dex,
+                buy_price, sell_price, price_difference_pct,
+                trade_amount, estimated_profit, gas_cost, profit_currency,
+                execution_status, realized_profit, block_number, block_hash,
+                buy_fee_tier, sell_fee_tier
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(opportunity.id.to_string())
+        .bind(timestamp_text(opportunity.timestamp))
+        .bind(&opportunity.token_pair)
+        .bind(&opportunity.buy_dex)
+        .bind(&opportunity.sell_dex)
+        .bind(opportunity.buy_price.to_string())
+        .bind(opportunity.sell_price.to_string())
+        .bind(opportunity.price_difference_pct.to_string())
+        .bind(opportunity.trade_amount.to_string())
+        .bind(opportunity.estimated_profit.to_string())
+        .bind(opportunity.gas_cost.to_string())
+        .bind(&opportunity.profit_currency)
+        .bind(&opportunity.execution_status)
+        .bind(opportunity.realized_profit.map(|profit| profit.to_string()))
+        .bind(opportunity.block_number)
+        .bind(&opportunity.block_hash)
+        .bind(opportunity.buy_fee_tier)
+        .bind(opportunity.sell_fee_tier)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE arbitrage_opportunities
+            SET execution_status = ?2, realized_profit = ?3
+            WHERE id = ?1
+            "#,
+        )
+        .bind(opportunity.id.to_string())
+        .bind(&opportunity.execution_status)
+        .bind(opportunity.realized_profit.map(|profit| profit.to_string()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        sqlx::query("UPDATE arbitrage_opportunities SET execution_status = ?2 WHERE id = ?1")
+            .bind(opportunity.id.to_string())
+            .bind(&opportunity.execution_status)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>> {
+        let rows = sqlx::query("SELECT * FROM arbitrage_opportunities ORDER BY timestamp DESC LIMIT ?1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(opportunity_from_row).collect()
+    }
+
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>> {
+        sqlx::query("SELECT * FROM arbitrage_opportunities WHERE timestamp >= ?1 ORDER BY timestamp")
+            .bind(timestamp_text(since))
+            .fetch(&self.pool)
+            .map_err(anyhow::Error::from)
+            .and_then(|row| async move { opportunity_from_row(&row) })
+            .boxed()
+    }
+
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM arbitrage_opportunities WHERE timestamp < ?1")
+            .bind(timestamp_text(cutoff))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn upsert_dex_health(&self, health: &DexHealth) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO dex_health (dex_name, last_price, last_updated, consecutive_errors)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (dex_name) DO UPDATE
+            SET last_price = ?2, last_updated = ?3, consecutive_errors = ?4
+            "#,
+        )
+        .bind(&health.dex_name)
+        .bind(health.last_price.to_string())
+        .bind(timestamp_text(health.last_updated))
+        .bind(health.consecutive_errors)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>> {
+        let rows = sqlx::query("SELECT * FROM dex_health ORDER BY dex_name")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(dex_health_from_row).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::ReadOnlyStore;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    fn opportunity(minute: u32) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            timestamp: Utc.with_ymd_and_hms(2024, 3, 1, 9, minute, 0).unwrap(),
+            buy_price: dec!(2000.500000),
+            sell_price: dec!(2020.25),
+            price_difference_pct: dec!(0.0098725318670332),
+            estimated_profit: dec!(4.10),
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trips_opportunities_as_text() {
+        let store = SqliteStore::new("sqlite::memory:", Duration::from_secs(30), false).await.unwrap();
+        store.init().await.unwrap();
+        store.check_schema().await.unwrap();
+
+        let first = opportunity(5);
+        let mut second = opportunity(10);
+        second.block_number = Some(54_000_000);
+        second.block_hash = Some(format!("0x{}", "ab".repeat(32)));
+        assert!(store.save_opportunity(&first).await.unwrap());
+        assert!(store.save_opportunity(&second).await.unwrap());
+        assert!(!store.save_opportunity(&second).await.unwrap(), "duplicate id must not be saved");
+
+        second.execution_status = Some("executed".to_string());
+        second.realized_profit = Some(dec!(3.95));
+        store.update_execution(&second).await.unwrap();
+
+        let recent = store.get_recent_opportunities(10).await.unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].id, second.id);
+        assert_eq!(recent[0].timestamp, second.timestamp);
+        assert_eq!(recent[0].realized_profit, Some(dec!(3.95)));
+        assert_eq!(recent[0].block_number, Some(54_000_000));
+        assert_eq!(recent[0].block_hash, second.block_hash);
+        assert_eq!(recent[1].block_number, None);
+        assert_eq!(recent[1].price_difference_pct, dec!(0.0098725318670332));
+        assert_eq!(recent[1].buy_price.to_string(), "2000.500000");
+        assert_eq!(recent[1].realized_profit, None);
+        assert_eq!(recent[1].profit_currency, "USDC");
+
+        let since = Utc.with_ymd_and_hms(2024, 3, 1, 9, 10, 0).unwrap();
+        let exported: Vec<ArbitrageOpportunity> = store.opportunities_since(since).try_collect().await.unwrap();
+        assert_eq!(exported.len(), 1);
+        assert_eq!(exported[0].execution_status.as_deref(), Some("executed"));
+    }
+
+    #[tokio::test]
+    async fn test_upserts_dex_health() {
+        let store = SqliteStore::new("sqlite::memory:", Duration::from_secs(30), false).await.unwrap();
+        store.init().await.unwrap();
+
+        let mut health = DexHealth {
+            dex_name: "Uniswap".to_string(),
+            last_price: dec!(2000.5),
+            last_updated: Utc.with_ymd_and_hms(2024, 3, 1, 9, 5, 0).unwrap(),
+            consecutive_errors: 0,
+        };
+        store.upsert_dex_health(&health).await.unwrap();
+        health.consecutive_errors = 3;
+        store.upsert_dex_health(&health).await.unwrap();
+
+        assert_eq!(store.get_dex_health().await.unwrap(), vec![health]);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_store_refuses_writes() {
+        let store = SqliteStore::new("sqlite::memory:", Duration::from_secs(30), false).await.unwrap();
+        store.init().await.unwrap();
+        let mut saved = opportunity(5);
+        store.save_opportunity(&saved).await.unwrap();
+
+        let read_only = ReadOnlyStore::new(Box::new(store));
+        read_only.init().await.unwrap();
+        assert!(read_only.save_opportunity(&opportunity(10)).await.is_err());
+        saved.execution_status = Some("executed".to_string());
+        assert!(read_only.update_execution(&saved).await.is_err());
+        let health = DexHealth {
+            dex_name: "Uniswap".to_string(),
+            last_price: dec!(2000.5),
+            last_updated: Utc.with_ymd_and_hms(2024, 3, 1, 9, 5, 0).unwrap(),
+            consecutive_errors: 0,
+        };
+        assert!(read_only.upsert_dex_health(&health).await.is_err());
+
+        // Reads still work, and see nothing written
+        let recent = read_only.get_recent_opportunities(10).await.unwrap();
+        assert_eq!((recent.len(), recent[0].execution_status.clone()), (1, None));
+        assert!(read_only.get_dex_health().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_opens_read_only_without_creating_or_writing() {
+        let path = std::env::temp_dir().join(format!("read-only-{}.db", uuid::Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        assert!(SqliteStore::new(&url, Duration::from_secs(30), true).await.is_err());
+        assert!(!path.exists());
+
+        let store = SqliteStore::new(&url, Duration::from_secs(30), false).await.unwrap();
+        store.init().await.unwrap();
+        store.save_opportunity(&opportunity(5)).await.unwrap();
+        store.pool.close().await;
+
+        let store = SqliteStore::new(&url, Duration::from_secs(30), true).await.unwrap();
+        assert_eq!(store.get_recent_opportunities(10).await.unwrap().len(), 1);
+        assert!(store.save_opportunity(&opportunity(10)).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::OpportunityStore;
+use crate::models::{ArbitrageOpportunity, DexHealth};
+
+/// How often opportunities past the hot retention are moved to the cold store
+const ARCHIVE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A fast primary ("hot") store for new and recent opportunities in front of
+/// a cheaper archive ("cold") for older ones. Writes go to the hot store;
+/// reads merge both, so callers see one history. Cloning shares the stores.
+#[derive(Clone)]
+pub struct TieredStore {
+    hot: Arc<dyn OpportunityStore>,
+    cold: Arc<dyn OpportunityStore>,
+}
+
+impl TieredStore {
+    pub fn new(hot: Box<dyn OpportunityStore>, cold: Box<dyn OpportunityStore>) -> Self {
+        Self { hot: Arc::from(hot), cold: Arc::from(cold) }
+    }
+
+    /// Copy every hot opportunity recorded before `cutoff` to the cold store,
+    /// then delete them from the hot one, returning how many moved. Rows are
+    /// copied before anything is deleted, so an interrupted run loses nothing
+    /// and the next one finishes it (the cold store ignores ids it has).
+    pub async fn archive_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let mut copied = 0;
+        {
+            let mut rows = self.hot.opportunities_since(DateTime::UNIX_EPOCH);
+            while let Some(opportunity) = rows.try_next().await.context("Failed to read hot opportunities")? {
+                if opportunity.timestamp >= cutoff {
+                    break;
+                }
+                self.cold.save_opportunity(&opportunity).await
+                    .with_context(|| format!("Failed to archive opportunity {}", opportunity.id))?;
+                copied += 1;
+            }
+        }
+        if copied == 0 {
+            return Ok(0);
+        }
+        self.hot.delete_opportunities_before(cutoff).await.context("Failed to prune archived opportunities")
+    }
+
+    /// Every `ARCHIVE_INTERVAL`, archive opportunities older than `retention`
+    pub fn spawn_archiver(&self, retention: chrono::Duration) -> tokio::task::JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ARCHIVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match store.archive_before(Utc::now() - retention).await {
+                    Ok(0) => {}
+                    Ok(moved) => log::info!("Archived {} opportunities to the cold store", moved),
+                    Err(e) => log::warn!("Archiving opportunities failed, retrying later: {:#}", e),
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl OpportunityStore for TieredStore {
+    async fn init(&self) -> Result<()> {
+        self.hot.init().await?;
+        self.cold.init().await.context("Failed to initialize the cold store")
+    }
+
+    async fn check_schema(&self) -> Result<()> {
+        self.hot.check_schema().await?;
+        self.cold.check_schema().await.context("Cold store schema check failed")
+    }
+
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        self.hot.save_opportunity(opportunity).await
+    }
+
+    /// Updates whichever store holds the opportunity by now
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.hot.update_execution(opportunity).await?;
+        self.cold.update_execution(opportunity).await
+    }
+
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.hot.update_execution_status(opportunity).await?;
+        self.cold.update_execution_status(opportunity).await
+    }
+
+    /// The hot store's most recent, topped up from the cold store
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>> {
+        let mut recent = self.hot.get_recent_opportunities(limit).await?;
+        let missing = limit - recent.len() as i32;
+        if missing > 0 {
+            // Rows mid-archive are in both stores; fetch enough to skip them
+            let seen: HashSet<_> = recent.iter().map(|opportunity| opportunity.id).collect();
+            let older = self.cold.get_recent_opportunities(missing + seen.len() as i32).await?;
+            recent.extend(older.into_iter().filter(|opportunity| !seen.contains(&opportunity.id)));
+            recent.truncate(limit.max(0) as usize);
+        }
+        Ok(recent)
+    }
+
+    /// Cold rows, then the hot rows newer than the last of them. Archived
+    /// rows are all older than those left hot, so this is still oldest
+    /// first, and rows copied but not yet pruned are only returned once.
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>> {
+        let last_cold = Arc::new(Mutex::new(None));
+        let seen = last_cold.clone();
+        let cold = self.cold.opportunities_since(since).inspect_ok(move |opportunity| {
+            *seen.lock() = Some(opportunity.timestamp);
+        });
+        let hot = stream::once(async {}).flat_map(move |()| {
+            let after = *last_cold.lock();
+            self.hot.opportunities_since(since).try_filter(move |opportunity| {
+                std::future::ready(after.is_none_or(|after| opportunity.timestamp > after))
+            })
+        });
+        cold.chain(hot).boxed()
+    }
+
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        Ok(self.hot.delete_opportunities_before(cutoff).await? + self.cold.delete_opportunities_before(cutoff).await?)
+    }
+
+    /// Venue health is operational state, kept hot only
+    async fn upsert_dex_health(&self, health: &DexHealth) -> Result<()> {
+        self.hot.upsert_dex_health(health).await
+    }
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>> {
+        self.hot.get_dex_health().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::sqlite::SqliteStore;
+    use chrono::TimeZone;
+    use rust_decimal_macros::dec;
+
+    async fn store() -> Box<dyn OpportunityStore> {
+        let store = SqliteStore::new("sqlite::memory:", Duration::from_secs(30), false).await.unwrap();
+        store.init().await.unwrap();
+        Box::new(store)
+    }
+
+    fn opportunity(day: u32) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            timestamp: Utc.with_ymd_and_hms(2024, 3, day, 9, 0, 0).unwrap(),
+            buy_dex: "Uniswap".to_string(),
+            sell_dex: "SushiSwap".to_string(),
+            sell_price: dec!(2030),
+            price_difference_pct: dec!(0.015),
+            estimated_profit: dec!(10),
+            execution_status: Some("executed".to_string()),
+            realized_profit: Some(dec!(9.5)),
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_archives_old_rows_and_reads_across_stores() {
+        let tiered = TieredStore::new(store().await, store().await);
+        let (old, new) = (opportunity(1), opportunity(20));
+        tiered.save_opportunity(&old).await.unwrap();
+        tiered.save_opportunity(&new).await.unwrap();
+
+        let cutoff = Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0).unwrap();
+        assert_eq!(tiered.archive_before(cutoff).await.unwrap(), 1);
+        assert_eq!(tiered.archive_before(cutoff).await.unwrap(), 0);
+
+        // Moved: only the new row is hot, and the old one kept its outcome in the cold store
+        let hot = tiered.hot.get_recent_opportunities(10).await.unwrap();
+        assert_eq!(hot.iter().map(|row| row.id).collect::<Vec<_>>(), vec![new.id]);
+        let cold = tiered.cold.get_recent_opportunities(10).await.unwrap();
+        assert_eq!(cold.iter().map(|row| row.id).collect::<Vec<_>>(), vec![old.id]);
+        assert_eq!(cold[0].realized_profit, Some(dec!(9.5)));
+
+        // Still one history through the merged reads
+        let recent = tiered.get_recent_opportunities(10).await.unwrap();
+        assert_eq!(recent.iter().map(|row| row.id).collect::<Vec<_>>(), vec![new.id, old.id]);
+        assert_eq!(tiered.get_recent_opportunities(1).await.unwrap()[0].id, new.id);
+        let all: Vec<_> = tiered.opportunities_since(DateTime::UNIX_EPOCH).try_collect().await.unwrap();
+        assert_eq!(all.iter().map(|row| row.id).collect::<Vec<_>>(), vec![old.id, new.id]);
+    }
+
+    #[tokio::test]
+    async fn test_rows_mid_archive_are_read_once() {
+        let tiered = TieredStore::new(store().await, store().await);
+        let (old, new) = (opportunity(1), opportunity(20));
+        tiered.save_opportunity(&old).await.unwrap();
+        tiered.save_opportunity(&new).await.unwrap();
+        // Copied to the cold store but not yet pruned from the hot one
+        tiered.cold.save_opportunity(&old).await.unwrap();
+
+        let all: Vec<_> = tiered.opportunities_since(DateTime::UNIX_EPOCH).try_collect().await.unwrap();
+        assert_eq!(all.iter().map(|row| row.id).collect::<Vec<_>>(), vec![old.id, new.id]);
+        let recent = tiered.get_recent_opportunities(10).await.unwrap();
+        assert_eq!(recent.iter().map(|row| row.id).collect::<Vec<_>>(), vec![new.id, old.id]);
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// Events queued for the writer before new ones are dropped, so a stalled
+/// disk never backs up into the scan loop
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// One branch the bot took while checking a pair, with enough context to
+/// tell afterwards why it did or did not act
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Decision {
+    PriceFetched { venue: String, price: Decimal },
+    PriceFetchFailed { venue: String, error: String },
+    /// The validator turned the price away (bounds, change, CEX deviation,
+    /// circuit breaker)
+    PriceRejected { venue: String, reason: String },
+    /// Venue prices at least `ALERT_SPREAD_PCT` apart, alerted on whether
+    /// or not trading them would pay
+    SpreadAlert { buy_dex: String, sell_dex: String, spread_pct: Decimal },
+    /// No route was compared at all this cycle
+    ComparisonSkipped { reason: String },
+    /// A route at one trade size was dropped before becoming an opportunity
+    RouteRejected { buy_dex: String, sell_dex: String, size: Decimal, reason: String },
+    OpportunityDetected {
+        opportunity_id: Uuid,
+        buy_dex: String,
+        sell_dex: String,
+        size: Decimal,
+        estimated_profit: Decimal,
+    },
+    /// A detected opportunity that was not recorded or acted on
+    OpportunityRejected { opportunity_id: Uuid, reason: String },
+    OpportunityRecorded { opportunity_id: Uuid },
+    /// Execution outcome (`execution_status`) of a recorded opportunity,
+    /// including those the executor deferred
+    TradeOutcome { opportunity_id: Uuid, status: String },
+}
+
+/// A decision stamped with when, in which cycle and for which pair it was made
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecisionEvent {
+    pub timestamp: DateTime<Utc>,
+    /// Cycles completed before this one, counting from startup
+    pub cycle: u64,
+    pub pair: String,
+    #[serde(flatten)]
+    pub decision: Decision,
+}
+
+/// Destination for decision events. Called inline on the scan path, so
+/// implementations must hand the event off rather than do I/O.
+pub trait DecisionRecorder: Send + Sync {
+    fn record(&self, event: DecisionEvent);
+}
+
+/// Appends one JSON object per event to a file from a background task
+pub struct JsonLinesRecorder {
+    events: mpsc::Sender<DecisionEvent>,
+}
+
+impl JsonLinesRecorder {
+    /// Start the writer task; needs a Tokio runtime
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let (events, queue) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(write_events(path.into(), queue));
+        Self { events }
+    }
+}
+
+impl DecisionRecorder for JsonLinesRecorder {
+    fn record(&self, event: DecisionEvent) {
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.events.try_send(event) {
+            log::warn!("Decision log queue full, dropped {:?} event of cycle {}", event.decision, event.cycle);
+        }
+    }
+}
+
+/// Write queued events until every recorder handle is dropped, flushing
+/// whenever the queue drains
+async fn write_events(path: PathBuf, mut queue: mpsc::Receiver<DecisionEvent>) {
+    let mut file = match open_append(&path).await {
+        Ok(file) => tokio::io::BufWriter::new(file),
+        Err(e) => {
+            log::error!("Decision log disabled: {:#}", e);
+            return;
+        }
+    };
+
+    while let Some(event) = queue.recv().await {
+        if let Err(e) = append(&mut file, &event).await {
+            log::warn!("Failed to write decision log {}: {:#}", path.display(), e);
+        }
+        if queue.is_empty()
+            && let Err(e) = file.flush().await
+        {
+            log::warn!("Failed to flush decision log {}: {:#}", path.display(), e);
+        }
+    }
+}
+
+async fn open_append(path: &PathBuf) -> Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+async fn append(file: &mut tokio::io::BufWriter<tokio::fs::File>, event: &DecisionEvent) -> Result<()> {
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    file.write_all(&line).await?;
+    Ok(())
+}
+
+/// The `DECISION_LOG_FILE` recorder, None when the log is off
+pub fn from_config(config: &Config) -> Option<Box<dyn DecisionRecorder>> {
+    let path = config.decision_log_file.as_deref()?;
+    if config.read_only {
+        log::warn!("Read-only mode, not recording decisions to {}", path);
+        return None;
+    }
+    log::info!("Recording decisions to {}", path);
+    Some(Box::new(JsonLinesRecorder::new(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_writes_flat_json_lines() {
+        let path = std::env::temp_dir().join(format!("decisions-{}.jsonl", Uuid::new_v4()));
+        let recorder = JsonLinesRecorder::new(&path);
+        recorder.record(DecisionEvent {
+            timestamp: Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap(),
+            cycle: 4,
+            pair: "WETH/USDC".to_string(),
+            decision: Decision::PriceRejected {
+                venue: "SushiSwap".to_string(),
+                reason: "price change 20% exceeds 15%".to_string(),
+            },
+        });
+        drop(recorder);
+
+        // The writer finishes once the last handle is gone
+        let mut lines = String::new();
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path).unwrap_or_default();
+            if !lines.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        std::fs::remove_file(&path).ok();
+
+        let event: serde_json::Value = serde_json::from_str(lines.trim()).unwrap();
+        assert_eq!(event["event"], "price_rejected");
+        assert_eq!(event["cycle"], 4);
+        assert_eq!(event["venue"], "SushiSwap");
+        assert_eq!(event["timestamp"], "2024-03-01T09:00:00Z");
+    }
+}
@@ -0,0 +1,306 @@
+use anyhow::{Context, Result, anyhow};
+use dashmap::{DashMap, DashSet};
+use ethers::{
+    abi::Abi,
+    contract::Contract,
+    providers::{Http, Provider},
+    types::Address,
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use super::abi::ERC20_ABI;
+use super::tokens::{DecimalsOverride, known_decimals};
+use crate::models::TokenPair;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{MalformedResponse, contract_error, metered_call};
+
+/// Most decimals a token's `decimals()` may plausibly report
+const MAX_TOKEN_DECIMALS: u8 = 36;
+
+/// Cache key for venue-specific entries such as pool addresses
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub venue: &'static str,
+    pub pair: String,
+    pub fee_tier: Option<u32>, // venues with one pool per fee tier key each tier separately
+}
+
+impl PoolKey {
+    pub fn new(venue: &'static str, pair: &TokenPair) -> Self {
+        Self {
+            venue,
+            pair: pair.symbol.clone(),
+            fee_tier: None,
+        }
+    }
+
+    pub fn with_fee_tier(venue: &'static str, pair: &TokenPair, fee_tier: u32) -> Self {
+        Self {
+            fee_tier: Some(fee_tier),
+            ..Self::new(venue, pair)
+        }
+    }
+}
+
+/// Shared cache of resolved pool addresses, ERC20 contract handles and token
+/// decimals, handed to every DEX client as an `Arc` so they reuse each other's work.
+///
+/// Without the cache every quote parsed the ERC20 ABI and built two token
+/// contracts, then made two `decimals()` calls before the quote itself: three RPC
+/// calls and two contract constructions per venue per cycle. With it, only the
+/// first cycle pays for decimals and the one-off pool lookup; every later cycle
+/// makes exactly one RPC call (the quote) per venue and builds no contracts, so a
+/// two-venue cycle drops from 6 RPC calls to 2.
+pub struct PoolCache {
+    pools: DashMap<PoolKey, Address>,
+    /// Pools `pool_exists` found missing
+    missing: DashSet<PoolKey>,
+    token_contracts: DashMap<Address, Contract<Provider<Http>>>,
+    token_decimals: DashMap<Address, u8>,
+    /// Configured decimals, consulted after the token registry
+    decimals_overrides: HashMap<Address, u8>,
+    erc20_abi: Abi,
+}
+
+impl PoolCache {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            pools: DashMap::new(),
+            missing: DashSet::new(),
+            token_contracts: DashMap::new(),
+            token_decimals: DashMap::new(),
+            decimals_overrides: HashMap::new(),
+            erc20_abi: serde_json::from_str(ERC20_ABI).context("Failed to parse ERC20 ABI")?,
+        })
+    }
+
+    /// Use configured decimals for tokens the registry does not know. An
+    /// override of a registry token is ignored, with a warning.
+    pub fn with_decimals_overrides(mut self, overrides: &[DecimalsOverride]) -> Self {
+        for entry in overrides {
+            if let Some(known) = known_decimals(entry.token)
+                && known != entry.decimals
+            {
+                log::warn!(
+                    "Ignoring decimals override {} for {:?}: the token registry has {}",
+                    entry.decimals, entry.token, known
+                );
+            }
+            self.decimals_overrides.insert(entry.token, entry.decimals);
+        }
+        self
+    }
+
+    /// Pool address for a venue/pair, resolving and remembering it on first use.
+    /// A zero address from the resolver means the pool does not exist, as does
+    /// a pool `pool_exists` already found missing (without asking again).
+    pub async fn pool_address<F, Fut>(&self, key: PoolKey, resolve: F) -> Result<Address>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Address>>,
+    {
+        if let Some(address) = self.pools.get(&key) {
+            return Ok(*address);
+        }
+
+        let address = if self.missing.contains(&key) { Address::zero() } else { resolve().await? };
+        if address.is_zero() {
+            return Err(match key.fee_tier {
+                Some(fee_tier) => anyhow!("No {} pool exists for {} at fee tier {}", key.venue, key.pair, fee_tier),
+                None => anyhow!("No {} pool exists for {}", key.venue, key.pair),
+            });
+        }
+
+        log::debug!("Resolved {} pool for {}: {:?}", key.venue, key.pair, address);
+        self.pools.insert(key, address);
+        Ok(address)
+    }
+
+    /// Whether the venue has the pool, resolved once and remembered either
+    /// way. Unlike `pool_address`, a missing pool is cached too: this is the
+    /// startup capability check, after which the venue is not asked again.
+    pub async fn pool_exists<F, Fut>(&self, key: PoolKey, resolve: F) -> Result<bool>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Address>>,
+    {
+        if self.pools.contains_key(&key) {
+            return Ok(true);
+        }
+        if self.missing.contains(&key) {
+            return Ok(false);
+        }
+
+        let address = resolve().await?;
+        if address.is_zero() {
+            log::debug!("No {} pool for {}", key.venue, key.pair);
+            self.missing.insert(key);
+            return Ok(false);
+        }
+        self.pools.insert(key, address);
+        Ok(true)
+    }
+
+    /// Record an address already known to exist (e.g. from pool discovery),
+    /// so the first quote skips the factory lookup
+    pub fn seed(&self, key: PoolKey, address: Address) {
+        log::debug!("Seeded {} pool for {}: {:?}", key.venue, key.pair, address);
+        self.pools.insert(key, address);
+    }
+
+    /// Reusable ERC20 contract handle for a token
+    pub fn token_contract(&self, token: Address, provider: &Arc<Provider<Http>>) -> Contract<Provider<Http>> {
+        self.token_contracts
+            .entry(token)
+            .or_insert_with(|| Contract::new(token, self.erc20_abi.clone(), provider.clone()))
+            .clone()
+    }
+
+    /// Token decimals, resolved once per token and shared across venues:
+    /// from the token registry, else the configured overrides, else the
+    /// token's `decimals()`. No real token has more than
+    /// `MAX_TOKEN_DECIMALS`; a node claiming one does is answering wrongly.
+    pub async fn token_decimals(&self, token: Address, provider: &Arc<Provider<Http>>) -> Result<u8> {
+        if let Some(decimals) = self.token_decimals.get(&token) {
+            return Ok(*decimals);
+        }
+
+        if let Some(decimals) = known_decimals(token) {
+            log::debug!("Decimals of {:?} from the token registry: {}", token, decimals);
+            self.token_decimals.insert(token, decimals);
+            return Ok(decimals);
+        }
+        if let Some(&decimals) = self.decimals_overrides.get(&token) {
+            log::debug!("Decimals of {:?} from the configured override: {}", token, decimals);
+            self.token_decimals.insert(token, decimals);
+            return Ok(decimals);
+        }
+
+        let call = self.token_contract(token, provider).method::<_, u8>("decimals", ())?;
+        let decimals: u8 = metered_call(RpcCallKind::Decimals, || async {
+            call.call().await.map_err(contract_error).context("Failed to get token decimals")
+        })
+        .await?;
+        if decimals > MAX_TOKEN_DECIMALS {
+            return Err(anyhow::Error::new(MalformedResponse(format!(
+                "decimals() of {:?} returned {}, above {}",
+                token, decimals, MAX_TOKEN_DECIMALS
+            ))));
+        }
+
+        log::debug!("Decimals of {:?} from decimals(): {}", token, decimals);
+        self.token_decimals.insert(token, decimals);
+        Ok(decimals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::mock_rpc::MockRpc;
+    use ethers::abi::{Token, encode};
+    use ethers::types::U256;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+            token1: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: crate::models::PairBase::Token0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_address_resolved_once_per_venue() {
+        let cache = PoolCache::new().unwrap();
+        let lookups = AtomicUsize::new(0);
+        let resolve = || async {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(Address::repeat_byte(0x42))
+        };
+
+        for _ in 0..3 {
+            let address = cache.pool_address(PoolKey::new("Uniswap", &pair()), resolve).await.unwrap();
+            assert_eq!(address, Address::repeat_byte(0x42));
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        cache.pool_address(PoolKey::new("SushiSwap", &pair()), resolve).await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pool_existence_cached_both_ways() {
+        let cache = PoolCache::new().unwrap();
+        let lookups = AtomicUsize::new(0);
+        // A factory with a SushiSwap pair but no Uniswap pool
+        let factory = |venue: &'static str| {
+            let lookups = &lookups;
+            move || async move {
+                lookups.fetch_add(1, Ordering::SeqCst);
+                Ok(if venue == "SushiSwap" { Address::repeat_byte(0x07) } else { Address::zero() })
+            }
+        };
+
+        for _ in 0..3 {
+            assert!(cache.pool_exists(PoolKey::new("SushiSwap", &pair()), factory("SushiSwap")).await.unwrap());
+            assert!(!cache.pool_exists(PoolKey::new("Uniswap", &pair()), factory("Uniswap")).await.unwrap());
+        }
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // The found pool also serves quotes without another lookup
+        let address = cache.pool_address(PoolKey::new("SushiSwap", &pair()), factory("SushiSwap")).await.unwrap();
+        assert_eq!(address, Address::repeat_byte(0x07));
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // Lookup failures are not answers and are not cached
+        let failed = cache.pool_exists(PoolKey::new("QuickSwap", &pair()), || async { anyhow::bail!("rate limited") });
+        assert!(failed.await.is_err());
+        assert!(cache.pool_exists(PoolKey::new("QuickSwap", &pair()), factory("SushiSwap")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_token_decimals_resolved_in_precedence_order() {
+        let (weth, overridden, unknown) = (
+            Address::from_str(&pair().token0).unwrap(),
+            Address::repeat_byte(0x0d),
+            Address::repeat_byte(0x0e),
+        );
+        let rpc = MockRpc::serve(|_, _| encode(&[Token::Uint(U256::from(9))])).await;
+        let provider = Arc::new(Provider::<Http>::try_from(rpc.url.as_str()).unwrap());
+        let overrides = [
+            DecimalsOverride { token: weth, decimals: 6 },
+            DecimalsOverride { token: overridden, decimals: 12 },
+        ];
+        let cache = PoolCache::new().unwrap().with_decimals_overrides(&overrides);
+
+        // The registry beats an override, which beats decimals()
+        assert_eq!(cache.token_decimals(weth, &provider).await.unwrap(), 18);
+        assert_eq!(cache.token_decimals(overridden, &provider).await.unwrap(), 12);
+        assert_eq!(rpc.requests(), 0);
+        assert_eq!(cache.token_decimals(unknown, &provider).await.unwrap(), 9);
+        assert_eq!(rpc.requests(), 1);
+
+        // Each is resolved once
+        for token in [weth, overridden, unknown] {
+            cache.token_decimals(token, &provider).await.unwrap();
+        }
+        assert_eq!(rpc.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_pool_is_not_cached() {
+        let cache = PoolCache::new().unwrap();
+        let key = PoolKey::new("SushiSwap", &pair());
+
+        let result = cache.pool_address(key.clone(), || async { Ok(Address::zero()) }).await;
+        assert!(result.unwrap_err().to_string().contains("No SushiSwap pool exists for WETH/USDC"));
+
+        let address = cache.pool_address(key, || async { Ok(Address::repeat_byte(0x07)) }).await.unwrap();
+        assert_eq!(address, Address::repeat_byte(0x07));
+    }
+}
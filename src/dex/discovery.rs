@@ -0,0 +1,344 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::{
+    abi::Abi,
+    contract::Contract,
+    providers::{Http, Provider},
+    types::{Address, U256},
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::abi::{
+    SUSHISWAP_ROUTER_ABI, UNISWAP_V2_FACTORY_ABI, UNISWAP_V2_PAIR_ABI, UNISWAP_V3_FACTORY_ABI,
+    UNISWAP_V3_POOL_ABI, UNISWAP_V3_QUOTER_ABI,
+};
+use super::uniswap::STANDARD_FEE_TIERS;
+use super::{sushiswap, uniswap};
+use crate::config::Config;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{metered_call, rpc_call};
+
+/// A pool that exists and holds liquidity
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveredPool {
+    pub venue: String,
+    /// The pool's tokens, lower address first
+    pub tokens: (Address, Address),
+    /// Uniswap V3 fee tier; V2 pairs have one pool per token pair
+    pub fee_tier: Option<u32>,
+    pub address: Address,
+}
+
+/// Discovery results for a token list, saved so discovery only reruns when
+/// the list changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolRegistry {
+    /// Tokens of interest, sorted
+    pub tokens: Vec<Address>,
+    pub pools: Vec<DiscoveredPool>,
+}
+
+impl PoolRegistry {
+    /// The registry saved at `path`, if there is one
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .with_context(|| format!("Invalid pool registry {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The pool `venue` has for `token_a`/`token_b` (in either order) at `fee_tier`
+    pub fn find(&self, venue: &str, token_a: Address, token_b: Address, fee_tier: Option<u32>) -> Option<Address> {
+        let tokens = sorted(token_a, token_b);
+        self.pools
+            .iter()
+            .find(|pool| pool.venue == venue && pool.tokens == tokens && pool.fee_tier == fee_tier)
+            .map(|pool| pool.address)
+    }
+}
+
+fn sorted(a: Address, b: Address) -> (Address, Address) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+/// Factory and pool reads discovery needs. A zero address means the factory
+/// has no such pool.
+#[async_trait]
+pub trait FactoryLookup: Send + Sync {
+    async fn v2_pair(&self, token_a: Address, token_b: Address) -> Result<Address>;
+
+    async fn v3_pool(&self, token_a: Address, token_b: Address, fee_tier: u32) -> Result<Address>;
+
+    /// Whether both reserves of a V2 pair are non-zero
+    async fn v2_has_liquidity(&self, pair: Address) -> Result<bool>;
+
+    /// Whether a V3 pool has non-zero in-range liquidity
+    async fn v3_has_liquidity(&self, pool: Address) -> Result<bool>;
+}
+
+/// Every pool the venues have for each pair of `tokens` that exists and
+/// holds liquidity. Failed lookups are logged and treated as no pool.
+pub async fn discover(lookup: &dyn FactoryLookup, tokens: &[Address]) -> PoolRegistry {
+    let mut tokens = tokens.to_vec();
+    tokens.sort();
+    tokens.dedup();
+
+    let mut pools = Vec::new();
+    for (i, &token_a) in tokens.iter().enumerate() {
+        for &token_b in &tokens[i + 1..] {
+            let pair = lookup.v2_pair(token_a, token_b).await;
+            if let Some(address) = active(pair, |address| lookup.v2_has_liquidity(address)).await {
+                pools.push(DiscoveredPool {
+                    venue: sushiswap::VENUE.to_string(),
+                    tokens: (token_a, token_b),
+                    fee_tier: None,
+                    address,
+                });
+            }
+
+            for fee_tier in STANDARD_FEE_TIERS {
+                let pool = lookup.v3_pool(token_a, token_b, fee_tier).await;
+                if let Some(address) = active(pool, |address| lookup.v3_has_liquidity(address)).await {
+                    pools.push(DiscoveredPool {
+                        venue: uniswap::VENUE.to_string(),
+                        tokens: (token_a, token_b),
+                        fee_tier: Some(fee_tier),
+                        address,
+                    });
+                }
+            }
+        }
+    }
+
+    PoolRegistry { tokens, pools }
+}
+
+/// The looked-up address, if the pool exists and `has_liquidity` says so
+async fn active<F, Fut>(address: Result<Address>, has_liquidity: F) -> Option<Address>
+where
+    F: FnOnce(Address) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let address = match address {
+        Ok(address) if address.is_zero() => return None,
+        Ok(address) => address,
+        Err(e) => {
+            log::warn!("Pool lookup failed, skipping: {:#}", e);
+            return None;
+        }
+    };
+    match has_liquidity(address).await {
+        Ok(true) => Some(address),
+        Ok(false) => {
+            log::debug!("Pool {:?} has no liquidity, skipping", address);
+            None
+        }
+        Err(e) => {
+            log::warn!("Failed to read liquidity of pool {:?}, skipping: {:#}", address, e);
+            None
+        }
+    }
+}
+
+/// The venues' factories, found through the configured quoter and router
+pub struct OnChainFactories {
+    provider: Arc<Provider<Http>>,
+    v2_factory: Contract<Provider<Http>>,
+    v3_factory: Contract<Provider<Http>>,
+    v2_pair_abi: Abi,
+    v3_pool_abi: Abi,
+}
+
+impl OnChainFactories {
+    pub async fn new(config: &Config, provider: Arc<Provider<Http>>) -> Result<Self> {
+        let router = Contract::new(
+            Address::from_str(&config.sushiswap_router_address).context("Invalid router address")?,
+            serde_json::from_str::<Abi>(SUSHISWAP_ROUTER_ABI)?,
+            provider.clone(),
+        );
+        let quoter = Contract::new(
+            Address::from_str(&config.uniswap_v3_quoter_address).context("Invalid quoter address")?,
+            serde_json::from_str::<Abi>(UNISWAP_V3_QUOTER_ABI)?,
+            provider.clone(),
+        );
+
+        let router_factory = router.method::<_, Address>("factory", ())?;
+        let v2_factory: Address = rpc_call(|| async {
+            router_factory.call().await.context("Failed to get SushiSwap factory address")
+        })
+        .await?;
+        let quoter_factory = quoter.method::<_, Address>("factory", ())?;
+        let v3_factory: Address = rpc_call(|| async {
+            quoter_factory.call().await.context("Failed to get Uniswap V3 factory address")
+        })
+        .await?;
+
+        Ok(Self {
+            v2_factory: Contract::new(v2_factory, serde_json::from_str::<Abi>(UNISWAP_V2_FACTORY_ABI)?, provider.clone()),
+            v3_factory: Contract::new(v3_factory, serde_json::from_str::<Abi>(UNISWAP_V3_FACTORY_ABI)?, provider.clone()),
+            v2_pair_abi: serde_json::from_str(UNISWAP_V2_PAIR_ABI)?,
+            v3_pool_abi: serde_json::from_str(UNISWAP_V3_POOL_ABI)?,
+            provider,
+        })
+    }
+}
+
+#[async_trait]
+impl FactoryLookup for OnChainFactories {
+    async fn v2_pair(&self, token_a: Address, token_b: Address) -> Result<Address> {
+        let call = self.v2_factory.method::<_, Address>("getPair", (token_a, token_b))?;
+        rpc_call(|| async { call.call().await.context("Failed to look up SushiSwap pair") }).await
+    }
+
+    async fn v3_pool(&self, token_a: Address, token_b: Address, fee_tier: u32) -> Result<Address> {
+        let call = self.v3_factory.method::<_, Address>("getPool", (token_a, token_b, fee_tier))?;
+        rpc_call(|| async { call.call().await.context("Failed to look up Uniswap V3 pool") }).await
+    }
+
+    async fn v2_has_liquidity(&self, pair: Address) -> Result<bool> {
+        let contract = Contract::new(pair, self.v2_pair_abi.clone(), self.provider.clone());
+        let call = contract.method::<_, (u128, u128, u32)>("getReserves", ())?;
+        let (reserve0, reserve1, _) = metered_call(RpcCallKind::Reserves, || async {
+            call.call().await.context("Failed to get pair reserves")
+        })
+        .await?;
+        Ok(reserve0 > 0 && reserve1 > 0)
+    }
+
+    async fn v3_has_liquidity(&self, pool: Address) -> Result<bool> {
+        let contract = Contract::new(pool, self.v3_pool_abi.clone(), self.provider.clone());
+        let call = contract.method::<_, U256>("liquidity", ())?;
+        let liquidity = rpc_call(|| async {
+            call.call().await.context("Failed to get pool liquidity")
+        })
+        .await?;
+        Ok(!liquidity.is_zero())
+    }
+}
+
+/// The registry for `DISCOVERY_TOKENS`, from `POOL_REGISTRY_FILE` when it was
+/// built for the same tokens, otherwise discovered on chain and saved there
+pub async fn load_or_discover(config: &Config, provider: Arc<Provider<Http>>) -> Result<PoolRegistry> {
+    let mut tokens = config
+        .discovery_tokens
+        .iter()
+        .map(|token| Address::from_str(token.trim()).with_context(|| format!("Invalid DISCOVERY_TOKENS entry {}", token)))
+        .collect::<Result<Vec<_>>>()?;
+    tokens.sort();
+    tokens.dedup();
+
+    let path = Path::new(&config.pool_registry_file);
+    if let Some(registry) = PoolRegistry::load(path)?
+        && registry.tokens == tokens
+    {
+        log::info!("Loaded {} discovered pools from {}", registry.pools.len(), path.display());
+        return Ok(registry);
+    }
+
+    log::info!("Discovering pools for {} tokens", tokens.len());
+    let factories = OnChainFactories::new(config, provider).await?;
+    let registry = discover(&factories, &tokens).await;
+    for pool in &registry.pools {
+        log::info!(
+            "Found {} pool {:?} for {:?}/{:?}{}",
+            pool.venue,
+            pool.address,
+            pool.tokens.0,
+            pool.tokens.1,
+            pool.fee_tier.map(|fee| format!(" at fee tier {}", fee)).unwrap_or_default()
+        );
+    }
+    if config.read_only {
+        log::info!("Read-only mode, not saving {} discovered pools", registry.pools.len());
+    } else {
+        registry.save(path)?;
+        log::info!("Saved {} discovered pools to {}", registry.pools.len(), path.display());
+    }
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use std::collections::HashMap;
+
+    /// Factories with a fixed set of pools, keyed by sorted tokens and fee tier
+    #[derive(Default)]
+    struct MockFactories {
+        pools: HashMap<((Address, Address), Option<u32>), Address>,
+        empty: Vec<Address>,
+        broken: Vec<Address>,
+    }
+
+    impl MockFactories {
+        fn lookup(&self, token_a: Address, token_b: Address, fee_tier: Option<u32>) -> Address {
+            self.pools.get(&(sorted(token_a, token_b), fee_tier)).copied().unwrap_or_default()
+        }
+
+        fn liquidity(&self, pool: Address) -> Result<bool> {
+            if self.broken.contains(&pool) {
+                bail!("execution reverted");
+            }
+            Ok(!self.empty.contains(&pool))
+        }
+    }
+
+    #[async_trait]
+    impl FactoryLookup for MockFactories {
+        async fn v2_pair(&self, token_a: Address, token_b: Address) -> Result<Address> {
+            Ok(self.lookup(token_a, token_b, None))
+        }
+
+        async fn v3_pool(&self, token_a: Address, token_b: Address, fee_tier: u32) -> Result<Address> {
+            Ok(self.lookup(token_a, token_b, Some(fee_tier)))
+        }
+
+        async fn v2_has_liquidity(&self, pair: Address) -> Result<bool> {
+            self.liquidity(pair)
+        }
+
+        async fn v3_has_liquidity(&self, pool: Address) -> Result<bool> {
+            self.liquidity(pool)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discovers_only_live_pools() {
+        let (weth, usdc, dai) = (Address::repeat_byte(0x7c), Address::repeat_byte(0x27), Address::repeat_byte(0x8f));
+        let pool = Address::repeat_byte;
+        let factories = MockFactories {
+            pools: HashMap::from([
+                ((sorted(weth, usdc), None), pool(0x01)),
+                ((sorted(weth, usdc), Some(500)), pool(0x02)),
+                ((sorted(weth, usdc), Some(3000)), pool(0x03)), // drained
+                ((sorted(usdc, dai), Some(100)), pool(0x04)),
+                ((sorted(weth, dai), Some(3000)), pool(0x05)), // liquidity read fails
+            ]),
+            empty: vec![pool(0x03)],
+            broken: vec![pool(0x05)],
+        };
+
+        let registry = discover(&factories, &[weth, usdc, dai, weth]).await;
+
+        assert_eq!(registry.tokens, [usdc, weth, dai]);
+        assert_eq!(registry.pools.len(), 3);
+        assert_eq!(registry.find("SushiSwap", weth, usdc, None), Some(pool(0x01)));
+        assert_eq!(registry.find("Uniswap", usdc, weth, Some(500)), Some(pool(0x02)));
+        assert_eq!(registry.find("Uniswap", weth, usdc, Some(3000)), None);
+        assert_eq!(registry.find("Uniswap", dai, usdc, Some(100)), Some(pool(0x04)));
+        assert_eq!(registry.find("Uniswap", weth, dai, Some(3000)), None);
+    }
+}
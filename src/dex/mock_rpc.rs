@@ -0,0 +1,77 @@
+use ethers::types::Address;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Local JSON-RPC endpoint answering every `eth_call` with
+/// `respond(to, calldata)`, counting the requests it serves
+pub struct MockRpc {
+    pub url: String,
+    requests: Arc<AtomicUsize>,
+}
+
+impl MockRpc {
+    pub async fn serve<F>(respond: F) -> Self
+    where
+        F: Fn(Address, &[u8]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let respond = Arc::new(respond);
+        let served = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                served.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(answer(socket, respond.clone()));
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Requests served so far
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+/// Answer one request, then close the connection
+async fn answer<F>(mut socket: TcpStream, respond: Arc<F>)
+where
+    F: Fn(Address, &[u8]) -> Vec<u8>,
+{
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let body = loop {
+        let read = socket.read(&mut chunk).await.unwrap();
+        request.extend_from_slice(&chunk[..read]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse::<usize>()))
+                .unwrap()
+                .unwrap();
+            if request.len() >= end + 4 + length {
+                break serde_json::from_slice::<serde_json::Value>(&request[end + 4..end + 4 + length]).unwrap();
+            }
+        }
+    };
+
+    assert_eq!(body["method"], "eth_call", "unexpected request {}", body);
+    let call = &body["params"][0];
+    let to: Address = call["to"].as_str().unwrap().parse().unwrap();
+    let data = call["input"].as_str().or(call["data"].as_str()).unwrap();
+    let output = respond(to, &hex::decode(data.trim_start_matches("0x")).unwrap());
+
+    let reply = serde_json::json!({ "jsonrpc": "2.0", "id": body["id"], "result": format!("0x{}", hex::encode(output)) })
+        .to_string();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        reply.len(),
+        reply
+    );
+    socket.write_all(response.as_bytes()).await.unwrap();
+}
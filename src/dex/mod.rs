@@ -1,13 +1,601 @@
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow, bail};
 use async_trait::async_trait;
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
 use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::config::Config;
+use crate::util::verify_chain_id;
 use crate::models::TokenPair;
+use cache::{PoolCache, PoolKey};
+use sushiswap::SushiswapClient;
+use uniswap::UniswapV3Client;
 
+pub mod abi;
+pub mod cache;
+pub mod discovery;
+#[cfg(test)]
+mod mock_rpc;
+pub mod static_prices;
 pub mod uniswap;
 pub mod sushiswap;
+pub mod tokens;
 
+/// Quote-token amount reference prices are quoted for (1000 USDC), unless
+/// `VENUE_QUOTE_SIZES` sets a venue's own
+pub const REFERENCE_QUOTE_SIZE: Decimal = Decimal::ONE_THOUSAND;
+
+/// A venue's reference quote size, configured as `Venue:size`.
+///
+/// Reference prices answer "what is the price": they pick the route and feed
+/// the validator, so each venue should be quoted small enough that its price
+/// impact is negligible, which for a thin venue means smaller than for a deep
+/// one. What a trade would actually earn is quoted separately at the trade
+/// size (`TRADE_AMOUNT` or the size ladder), where depth rightly counts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueQuoteSize {
+    pub venue: String,
+    pub size: Decimal,
+}
+
+impl FromStr for VenueQuoteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (venue, size) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected Venue:size, got {:?}", s))?;
+        let size: Decimal = size.trim().parse().map_err(|e| format!("invalid size in {:?}: {}", s, e))?;
+        if venue.trim().is_empty() || size <= Decimal::ZERO {
+            return Err(format!("expected Venue:size with a positive size, got {:?}", s));
+        }
+        Ok(Self {
+            venue: venue.trim().to_string(),
+            size,
+        })
+    }
+}
+
+/// Swap fee of a constant-product (V2-style) pair in basis points. Forks
+/// that charge another rate are configured through `VENUE_FEE_BPS`.
+pub const V2_FEE_BPS: u32 = 30;
+
+/// A venue's swap fee, configured as `Venue:bps`. Quoter and router quotes
+/// already deduct the real fee; this rate is only used where the bot does
+/// its own AMM math (price impact caps, static pricing).
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueFee {
+    pub venue: String,
+    pub fee_bps: u32,
+}
+
+impl FromStr for VenueFee {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (venue, fee_bps) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected Venue:bps, got {:?}", s))?;
+        let fee_bps: u32 = fee_bps.trim().parse().map_err(|e| format!("invalid fee in {:?}: {}", s, e))?;
+        if venue.trim().is_empty() || fee_bps >= 10_000 {
+            return Err(format!("expected Venue:bps with a fee below 10000 bps, got {:?}", s));
+        }
+        Ok(Self {
+            venue: venue.trim().to_string(),
+            fee_bps,
+        })
+    }
+}
+
+/// Share of a swap's input left after a `fee_bps` fee, e.g. 0.997 for 30 bps
+pub fn fee_kept(fee_bps: u32) -> Decimal {
+    Decimal::ONE - Decimal::new(fee_bps.into(), 4)
+}
+
+/// Where venue prices come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DexBackend {
+    /// On-chain quotes through `POLYGON_RPC_URL`
+    #[default]
+    Rpc,
+    /// Scheduled prices from `STATIC_VENUES`, no network needed
+    Static,
+}
+
+impl FromStr for DexBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rpc" => Ok(DexBackend::Rpc),
+            "static" => Ok(DexBackend::Static),
+            other => Err(format!("unknown DEX backend {:?} (expected rpc or static)", other)),
+        }
+    }
+}
+
+/// Protocol family of a venue, for generic code (liquidity checks, TWAP,
+/// price impact) that has to branch on V2 vs V3 semantics
+#[allow(dead_code)] // Balancer and Curve clients are not implemented yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VenueKind {
+    /// Constant-product pairs behind a V2-style router (SushiSwap, QuickSwap)
+    UniswapV2Like,
+    /// Concentrated-liquidity pools with fee tiers, priced through a quoter
+    UniswapV3,
+    /// Weighted pools behind the Balancer V2 vault
+    BalancerV2,
+    /// Curve stableswap pools
+    CurveStable,
+}
+
+/// Every client returns prices as quote tokens per base token of the pair
+/// (see `TokenPair::base`), whichever direction it actually quoted in.
 #[async_trait]
-pub trait DexClient {
-    async fn get_price(&self, pair: &TokenPair) -> Result<Decimal>;
+pub trait DexClient: Send + Sync {
+    /// Venue name used in logs, validation state and stored opportunities
+    fn name(&self) -> &'static str;
+
+    fn venue_kind(&self) -> VenueKind;
+
+    /// Effective price when spending `size` quote tokens, so depth shows up as
+    /// a worse price. Reference prices use the venue's configured reference
+    /// size (see `Config::reference_quote_size`).
+    async fn get_price_for_size(&self, pair: &TokenPair, size: Decimal) -> Result<Decimal>;
+
+    /// Price for `size` quote tokens as of a past block. Needs an archive node
+    /// for anything older than the node's pruning window (typically ~128 blocks).
+    async fn get_price_at_block(&self, pair: &TokenPair, size: Decimal, block: u64) -> Result<Decimal>;
+
+    /// Output of swapping exactly `amount_in` on this venue: base tokens out for
+    /// quote tokens in when buying, quote tokens out for base in when selling.
+    /// The venue's swap fee is already deducted by the on-chain quote.
+    async fn quote_exact_input(&self, pair: &TokenPair, side: TradeSide, amount_in: Decimal) -> Result<SwapQuote>;
+
+    /// Time-weighted average price over the last `window_seconds`, from the
+    /// venue's on-chain oracle, with the current mid price of the same pool.
+    /// `None` for venues without one (e.g. V2 pairs).
+    async fn get_twap(&self, _pair: &TokenPair, _window_seconds: u32) -> Result<Option<TwapPrice>> {
+        Ok(None)
+    }
+
+    /// Whether the venue has a pool for `pair` at all, checked with its
+    /// factory and cached, so venues that cannot quote a pair are dropped
+    /// before the scan loop instead of failing every cycle
+    async fn supports_pair(&self, _pair: &TokenPair) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Fetch and cache everything quoting `pair` needs (token decimals, pool
+    /// addresses) once before the scan loop, so the first cycle is as fast as
+    /// the rest and bad configuration fails at startup. A missing pool is not
+    /// an error here: `supports_pair` reports it and the venue is dropped.
+    async fn warm_up(&self, _pair: &TokenPair) -> Result<()> {
+        Ok(())
+    }
+
+    /// Quote tokens held by the venue's pool(s) for `pair`, a depth measure
+    /// comparable across venues. `None` for venues that cannot report one.
+    async fn get_liquidity(&self, _pair: &TokenPair) -> Result<Option<Decimal>> {
+        Ok(None)
+    }
+
+    /// Largest quote-token input whose price impact on the venue's pool
+    /// stays within `max_bps`. `None` for venues that cannot work it out.
+    async fn max_input_within_slippage(&self, _pair: &TokenPair, _max_bps: u16) -> Result<Option<Decimal>> {
+        Ok(None)
+    }
+}
+
+/// A pool's time-weighted average price next to its current mid price. Both
+/// are fee-free and size-free, so they compare directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwapPrice {
+    pub twap: Decimal,
+    pub mid: Decimal,
+}
+
+/// A venue's quote for one swap
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapQuote {
+    /// Tokens out, the venue's swap fee already deducted
+    pub amount_out: Decimal,
+    /// Uniswap V3 fee tier of the pool that was quoted, which a trade on the
+    /// quote has to go through
+    pub fee_tier: Option<u32>,
+    /// Gas the swap itself would use, for venues whose quoter simulates it
+    pub gas_estimate: Option<u64>,
+    /// How far the swap moves the pool price, in basis points, for venues
+    /// whose quoter reports the price it leaves behind
+    pub price_impact_bps: Option<Decimal>,
+}
+
+impl SwapQuote {
+    /// Quote of a venue with a single pool per pair
+    pub fn new(amount_out: Decimal) -> Self {
+        Self { amount_out, fee_tier: None, gas_estimate: None, price_impact_bps: None }
+    }
+}
+
+/// Direction of a swap relative to the pair's base token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeSide {
+    /// Spend quote tokens for base tokens
+    Buy,
+    /// Spend base tokens for quote tokens
+    Sell,
+}
+
+impl TradeSide {
+    /// `(token_in, token_out)` for this side of `pair`
+    pub fn tokens(self, pair: &TokenPair) -> Result<(Address, Address)> {
+        let base = Address::from_str(pair.base_token()).context("Invalid base token address")?;
+        let quote = Address::from_str(pair.quote_token()).context("Invalid quote token address")?;
+        Ok(match self {
+            TradeSide::Buy => (quote, base),
+            TradeSide::Sell => (base, quote),
+        })
+    }
+}
+
+/// Provider on a tuned HTTP client: idle connections are kept warm (and
+/// TCP keep-alive probed) so back-to-back quotes skip the TCP/TLS handshake
+pub fn rpc_provider(config: &Config) -> Result<Provider<Http>> {
+    let client = reqwest::Client::builder()
+        .pool_idle_timeout(Duration::from_secs(config.rpc_pool_idle_timeout_seconds))
+        .pool_max_idle_per_host(config.rpc_pool_max_idle_per_host)
+        .tcp_keepalive(Duration::from_secs(config.rpc_pool_idle_timeout_seconds.max(1)))
+        .connect_timeout(Duration::from_secs(config.rpc_connect_timeout_seconds))
+        .build()
+        .context("Failed to build RPC HTTP client")?;
+    let url: reqwest::Url = config.polygon_rpc_url.parse().context("Invalid POLYGON_RPC_URL")?;
+    Ok(Provider::new(Http::new_with_client(url, client)))
+}
+
+/// Build a client for every configured venue, sharing one pool cache and
+/// one RPC connection pool. The static backend needs no RPC at all.
+pub async fn create_clients(config: &Config) -> Result<Vec<Box<dyn DexClient>>> {
+    if config.dex_backend == DexBackend::Static {
+        return static_prices::create_clients(config);
+    }
+
+    let provider = Arc::new(rpc_provider(config)?);
+    verify_chain_id(provider.as_ref(), config.chain_id).await?;
+
+    // Pool addresses, token contracts and decimals are shared by every venue
+    let pool_cache = Arc::new(PoolCache::new()?.with_decimals_overrides(&config.token_decimals));
+    let quote_from = config
+        .quote_from_address
+        .as_deref()
+        .map(|address| Address::from_str(address.trim()))
+        .transpose()
+        .context("Invalid QUOTE_FROM_ADDRESS")?;
+
+    if !config.discovery_tokens.is_empty() {
+        let registry = discovery::load_or_discover(config, provider.clone()).await?;
+        seed_pair_pools(&pool_cache, &registry, config)?;
+    }
+
+    let uniswap_client = UniswapV3Client::new(
+        provider.clone(),
+        &config.uniswap_v3_quoter_address,
+        &config.weth_address,
+        &config.usdc_address,
+        pool_cache.clone(),
+        quote_from,
+        &config.uniswap_fee_tiers,
+    ).await.context("Failed to create Uniswap client")?
+    .with_depth_preference(config.uniswap_depth_tolerance)
+    .with_quoter_version(config.uniswap_quoter_version)?
+    .with_raw_quote_logging(config.log_raw_quotes)
+    .with_price_limit_bps(if config.uniswap_quote_price_limit { config.max_slippage_bps } else { 0 });
+
+    let sushiswap_client = SushiswapClient::new(
+        provider,
+        &config.sushiswap_router_address,
+        &config.weth_address,
+        &config.usdc_address,
+        pool_cache,
+        quote_from,
+    ).await.context("Failed to create SushiSwap client")?
+    .with_raw_quote_logging(config.log_raw_quotes)
+    .with_fee_bps(config.venue_fee_bps(sushiswap::VENUE, V2_FEE_BPS));
+
+    Ok(vec![Box::new(uniswap_client), Box::new(sushiswap_client)])
+}
+
+/// Hand the traded pair's discovered pools to the cache, so the clients
+/// start from the registry instead of asking the factories again
+fn seed_pair_pools(cache: &PoolCache, registry: &discovery::PoolRegistry, config: &Config) -> Result<()> {
+    let pair = config.token_pair();
+    let weth = Address::from_str(&config.weth_address).context("Invalid WETH_ADDRESS")?;
+    let usdc = Address::from_str(&config.usdc_address).context("Invalid USDC_ADDRESS")?;
+
+    if let Some(address) = registry.find(sushiswap::VENUE, weth, usdc, None) {
+        cache.seed(PoolKey::new(sushiswap::VENUE, &pair), address);
+    }
+    for &fee_tier in &config.uniswap_fee_tiers {
+        if let Some(address) = registry.find(uniswap::VENUE, weth, usdc, Some(fee_tier)) {
+            cache.seed(PoolKey::with_fee_tier(uniswap::VENUE, &pair, fee_tier), address);
+        }
+    }
+    Ok(())
+}
+
+/// Convert a human-readable token amount into raw integer units, truncating dust
+pub(crate) fn to_units(amount: Decimal, decimals: u32) -> Result<U256> {
+    let scale = 10u64.checked_pow(decimals)
+        .ok_or_else(|| anyhow!("Unsupported token decimals {}", decimals))?;
+    let scaled = (amount * Decimal::from(scale)).trunc();
+    if scaled.is_sign_negative() {
+        bail!("Cannot convert negative amount {} to token units", amount);
+    }
+    U256::from_dec_str(&scaled.to_string())
+        .map_err(|e| anyhow!("Amount {} out of range: {}", amount, e))
+}
+
+/// Convert raw integer token units into a human-readable amount
+pub(crate) fn from_units(amount: U256, decimals: u32) -> Result<Decimal> {
+    if amount > U256::from(i128::MAX as u128) {
+        bail!("Token amount {} too large to represent", amount);
+    }
+    Decimal::try_from_i128_with_scale(amount.as_u128() as i128, decimals)
+        .map_err(|e| anyhow!("Token amount {} too large to represent: {}", amount, e))
+}
+
+/// USDC paid per WETH received when `size` USDC quotes `amount_out` raw
+/// WETH units. A ratio that does not fit a `Decimal` (e.g. a near-zero
+/// output) fails with every input to the calculation.
+pub(crate) fn quoted_price(venue: &str, size: Decimal, amount_out: U256, weth_decimals: u8) -> Result<Decimal> {
+    let weth_out = amount_out.as_u128() as f64 / 10_f64.powi(weth_decimals as i32);
+    let usdc_in = size.to_f64().unwrap_or_default();
+    let ratio = usdc_in / weth_out;
+
+    Decimal::try_from(ratio).with_context(|| {
+        format!(
+            "Failed to convert {} price to Decimal: {} USDC in for {} raw WETH out ({} decimals, {} WETH) gives ratio {}",
+            venue, size, amount_out, weth_decimals, weth_out, ratio
+        )
+    })
+}
+
+/// Orient a quoted exchange rate (units of `token_in` paid per unit of
+/// `token_out` received, each already scaled by its own decimals) as quote per
+/// base for `pair`, inverting when the venue quoted in the other direction.
+pub(crate) fn orient_price(pair: &TokenPair, token_in: Address, token_out: Address, in_per_out: Decimal) -> Result<Decimal> {
+    let base = Address::from_str(pair.base_token()).context("Invalid base token address")?;
+    let quote = Address::from_str(pair.quote_token()).context("Invalid quote token address")?;
+
+    if (token_in, token_out) == (quote, base) {
+        Ok(in_per_out)
+    } else if (token_in, token_out) == (base, quote) {
+        if in_per_out.is_zero() {
+            bail!("Cannot invert a zero {} price", pair.symbol);
+        }
+        Ok(Decimal::ONE / in_per_out)
+    } else {
+        bail!("Quote tokens {:?} -> {:?} do not belong to pair {}", token_in, token_out, pair.symbol)
+    }
+}
+
+/// `token1` per `token0` (each scaled by its own decimals) for a Uniswap V3
+/// pool whose square-root price is `sqrt_price_x96` (Q64.96 fixed point)
+pub(crate) fn sqrt_price_x96_price(sqrt_price_x96: U256, token0_decimals: u8, token1_decimals: u8) -> Result<Decimal> {
+    let sqrt_price = sqrt_price_x96.to_string().parse::<f64>()? / 2_f64.powi(96);
+    let scaled = sqrt_price * sqrt_price * 10_f64.powi(i32::from(token0_decimals) - i32::from(token1_decimals));
+    Decimal::try_from(scaled).with_context(|| format!("Square-root price {} out of range", sqrt_price_x96))
+}
+
+/// `token1` per `token0` (each scaled by its own decimals) for a Uniswap V3
+/// pool at `tick`, which may be a fractional average tick
+pub(crate) fn tick_price(tick: f64, token0_decimals: u8, token1_decimals: u8) -> Result<Decimal> {
+    let raw = 1.0001_f64.powf(tick);
+    let scaled = raw * 10_f64.powi(i32::from(token0_decimals) - i32::from(token1_decimals));
+    Decimal::try_from(scaled).with_context(|| format!("Tick {} price out of range", tick))
+}
+
+/// Parameters of one quoter or router call exactly as sent, for
+/// `LOG_RAW_QUOTES`. Callers check the flag before building one, so the
+/// logging costs nothing when it is off.
+#[derive(Debug, Clone, Copy)]
+pub struct RawQuoteCall<'a> {
+    pub venue: &'a str,
+    pub method: &'a str,
+    pub token_in: Address,
+    pub token_out: Address,
+    pub fee_tier: Option<u32>,
+    pub amount_in: U256,
+    pub block: Option<u64>,
+}
+
+impl RawQuoteCall<'_> {
+    /// Log the call with its undecoded `response` (raw units, before any
+    /// decimal conversion) at debug level
+    pub fn log<T: fmt::Debug>(&self, response: &Result<T>) {
+        let fee_tier = self.fee_tier.map_or_else(|| "-".to_string(), |fee| fee.to_string());
+        let block = self.block.map_or_else(|| "latest".to_string(), |block| block.to_string());
+        match response {
+            Ok(raw) => log::debug!(
+                "Raw {} {}: token_in={:?} token_out={:?} fee_tier={} amount_in={} block={} -> {:?}",
+                self.venue, self.method, self.token_in, self.token_out, fee_tier, self.amount_in, block, raw
+            ),
+            Err(e) => log::debug!(
+                "Raw {} {}: token_in={:?} token_out={:?} fee_tier={} amount_in={} block={} -> error: {:#}",
+                self.venue, self.method, self.token_in, self.token_out, fee_tier, self.amount_in, block, e
+            ),
+        }
+    }
+}
+
+/// Replace the node's cryptic "missing state" errors for block-pinned calls
+/// with an explanation that an archive node is required
+pub(crate) fn explain_historical_error(error: anyhow::Error, block: Option<u64>) -> anyhow::Error {
+    let Some(block) = block else {
+        return error;
+    };
+
+    let message = format!("{:#}", error).to_lowercase();
+    let missing_state = [
+        "missing trie node",
+        "header not found",
+        "state is not available",
+        "state not available",
+        "historical state",
+        "pruned",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern));
+
+    if missing_state {
+        anyhow!(
+            "RPC node has no state for block {}; historical quotes require an archive node ({})",
+            block, error
+        )
+    } else {
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PairBase;
+    use rust_decimal_macros::dec;
+
+    const WETH: &str = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619";
+    const USDC: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+
+    fn pair(token0: &str, token1: &str, base: PairBase) -> TokenPair {
+        TokenPair {
+            token0: token0.to_string(),
+            token1: token1.to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base,
+        }
+    }
+
+    #[test]
+    fn test_unit_conversion_round_trip() {
+        let raw = to_units(dec!(1000.123456789), 6).unwrap();
+        assert_eq!(raw, U256::from(1_000_123_456u64)); // Dust below 6 decimals is truncated
+        assert_eq!(from_units(raw, 6).unwrap(), dec!(1000.123456));
+
+        let weth = U256::exp10(18) / 2;
+        assert_eq!(from_units(weth, 18).unwrap(), dec!(0.5));
+        assert!(to_units(dec!(-1), 6).is_err());
+    }
+
+    #[test]
+    fn test_quoted_price_error_names_the_numbers() {
+        let half_weth = U256::exp10(18) / 2;
+        assert_eq!(quoted_price("SushiSwap", dec!(1000), half_weth, 18).unwrap(), dec!(2000));
+
+        // A drained pool quoting nothing out gives an infinite ratio
+        let err = format!("{:#}", quoted_price("SushiSwap", dec!(1000), U256::zero(), 18).unwrap_err());
+        for expected in ["SushiSwap", "1000 USDC", "0 raw WETH", "18 decimals", "0 WETH", "ratio inf"] {
+            assert!(err.contains(expected), "{:?} missing from {:?}", expected, err);
+        }
+    }
+
+    thread_local! {
+        static LOGGED: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    /// Keeps this thread's log lines, so parallel tests don't see each other's
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGGED.with(|lines| lines.borrow_mut().push(record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_raw_quote_log_shows_raw_amounts() {
+        static LOGGER: CaptureLogger = CaptureLogger;
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let call = RawQuoteCall {
+            venue: "Uniswap",
+            method: "quoteExactInputSingle",
+            token_in: Address::from_str(USDC).unwrap(),
+            token_out: Address::from_str(WETH).unwrap(),
+            fee_tier: Some(500),
+            amount_in: U256::from(1_000_000_000u64),
+            block: None,
+        };
+        call.log(&Ok(U256::exp10(18) / 2));
+        call.log::<U256>(&Err(anyhow!("execution reverted")));
+
+        let lines = LOGGED.with(|lines| lines.take());
+        assert_eq!(lines.len(), 2);
+        for expected in ["Raw Uniswap quoteExactInputSingle", "fee_tier=500", "amount_in=1000000000", "block=latest"] {
+            assert!(lines[0].contains(expected), "{:?} missing from {:?}", expected, lines[0]);
+        }
+        assert!(lines[0].to_lowercase().contains(&USDC.to_lowercase()), "{}", lines[0]);
+        assert!(lines[0].ends_with("-> 500000000000000000"), "{}", lines[0]);
+        assert!(lines[1].ends_with("-> error: execution reverted"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_price_orientation_ignores_token_order() {
+        let weth = Address::from_str(WETH).unwrap();
+        let usdc = Address::from_str(USDC).unwrap();
+        let weth_first = pair(WETH, USDC, PairBase::Token0);
+        let usdc_first = pair(USDC, WETH, PairBase::Token1);
+
+        for pair in [&weth_first, &usdc_first] {
+            // Paying 2000 USDC per WETH
+            assert_eq!(orient_price(pair, usdc, weth, dec!(2000)).unwrap(), dec!(2000));
+            // Paying 0.0005 WETH per USDC is the same 2000 USDC per WETH
+            assert_eq!(orient_price(pair, weth, usdc, dec!(0.0005)).unwrap(), dec!(2000));
+        }
+
+        // Declaring USDC as the base flips the frame
+        let usdc_base = pair(WETH, USDC, PairBase::Token1);
+        assert_eq!(orient_price(&usdc_base, usdc, weth, dec!(2000)).unwrap(), dec!(0.0005));
+
+        assert!(orient_price(&weth_first, usdc, Address::zero(), dec!(2000)).is_err());
+    }
+
+    #[test]
+    fn test_explains_missing_archive_state() {
+        let error = explain_historical_error(anyhow!("(code: -32000, message: missing trie node 1a2b, data: None)"), Some(40_000_000));
+        assert!(error.to_string().contains("no state for block 40000000"));
+        assert!(error.to_string().contains("archive node"));
+
+        // Other failures and latest-block calls pass through untouched
+        let error = explain_historical_error(anyhow!("execution reverted"), Some(40_000_000));
+        assert_eq!(error.to_string(), "execution reverted");
+        let error = explain_historical_error(anyhow!("missing trie node"), None);
+        assert_eq!(error.to_string(), "missing trie node");
+    }
+
+    #[test]
+    fn test_tick_price_scales_by_decimals() {
+        assert_eq!(tick_price(0.0, 18, 18).unwrap(), Decimal::ONE);
+
+        // USDC (6 decimals) is token0 of the Polygon WETH/USDC pool, so a
+        // 2000 USDC/WETH market sits at tick ln(0.0005 * 1e12) / ln(1.0001)
+        let tick = (0.0005e12_f64).ln() / 1.0001_f64.ln();
+        let weth_per_usdc = tick_price(tick, 6, 18).unwrap();
+        assert!((weth_per_usdc - Decimal::new(5, 4)).abs() < Decimal::new(1, 12));
+
+        // Same market as a QuoterV2 sqrtPriceX96After: sqrt(0.0005 * 1e12) * 2^96
+        let sqrt_price_x96 = U256::from(22_360_679_774_997_u64) * (U256::one() << 96) / U256::exp10(9);
+        let weth_per_usdc = sqrt_price_x96_price(sqrt_price_x96, 6, 18).unwrap();
+        assert!((weth_per_usdc - Decimal::new(5, 4)).abs() < Decimal::new(1, 12));
+    }
 }
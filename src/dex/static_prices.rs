@@ -0,0 +1,211 @@
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::{DexClient, SwapQuote, TradeSide, V2_FEE_BPS, VenueKind, fee_kept};
+use crate::config::Config;
+use crate::models::TokenPair;
+
+/// Price series a static venue plays back, one step per check interval
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceSchedule {
+    /// These prices in turn, starting over after the last
+    Series(Vec<Decimal>),
+    /// `base + amplitude * sin(2π step / period)`
+    Sine { base: Decimal, amplitude: Decimal, period: u32 },
+}
+
+impl PriceSchedule {
+    /// Price at `step` (0 = startup)
+    pub fn price_at(&self, step: u64) -> Result<Decimal> {
+        match self {
+            PriceSchedule::Series(prices) => Ok(prices[(step % prices.len() as u64) as usize]),
+            PriceSchedule::Sine { base, amplitude, period } => {
+                let phase = TAU * (step % u64::from(*period)) as f64 / f64::from(*period);
+                let offset = Decimal::try_from(phase.sin())
+                    .map_err(|e| anyhow!("Invalid sine offset at step {}: {}", step, e))?;
+                Ok((base + amplitude * offset).round_dp(6))
+            }
+        }
+    }
+}
+
+/// A static venue, configured as `Name:series:p1/p2/...` or
+/// `Name:sine:base/amplitude/period` (period in steps)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticVenue {
+    pub name: String,
+    pub schedule: PriceSchedule,
+}
+
+impl FromStr for StaticVenue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("{} in {:?} (expected Name:series:p1/p2/... or Name:sine:base/amplitude/period)", reason, s);
+        let mut parts = s.trim().splitn(3, ':');
+        let (Some(name), Some(kind), Some(values)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("missing fields"));
+        };
+        if name.trim().is_empty() {
+            return Err(invalid("empty venue name"));
+        }
+
+        let numbers = values
+            .split('/')
+            .map(|value| value.trim().parse::<Decimal>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| invalid(&e.to_string()))?;
+        if numbers.iter().any(|&number| number < Decimal::ZERO) {
+            return Err(invalid("negative value"));
+        }
+
+        let schedule = match (kind.trim(), numbers.as_slice()) {
+            ("series", prices) if !prices.is_empty() && prices.iter().all(|&price| price > Decimal::ZERO) => {
+                PriceSchedule::Series(prices.to_vec())
+            }
+            ("sine", &[base, amplitude, period]) if amplitude < base && period >= Decimal::ONE && period.fract().is_zero() => {
+                PriceSchedule::Sine {
+                    base,
+                    amplitude,
+                    period: period.try_into().map_err(|_| invalid("period out of range"))?,
+                }
+            }
+            _ => return Err(invalid("invalid schedule")),
+        };
+
+        Ok(Self {
+            name: name.trim().to_string(),
+            schedule,
+        })
+    }
+}
+
+/// Offline venue pricing from a fixed schedule, for running the bot (and
+/// tests) with no RPC endpoint. The schedule advances one step per
+/// `step_interval` of (Tokio) time since startup, so every quote within a
+/// cycle sees the same price, and historical quotes read step `block`.
+pub struct StaticDexClient {
+    name: &'static str,
+    schedule: PriceSchedule,
+    step_interval: Duration,
+    started: Instant,
+    /// Share of each swap input kept after the venue fee
+    fee_kept: Decimal,
+}
+
+impl StaticDexClient {
+    pub fn new(venue: StaticVenue, step_interval: Duration) -> Self {
+        Self {
+            // Venue names are `&'static str` everywhere; the handful of
+            // static venues live for the whole run anyway
+            name: Box::leak(venue.name.into_boxed_str()),
+            schedule: venue.schedule,
+            step_interval: step_interval.max(Duration::from_millis(1)),
+            started: Instant::now(),
+            fee_kept: fee_kept(V2_FEE_BPS),
+        }
+    }
+
+    /// Charge `fee_bps` on swap quotes instead of a V2 pair's 0.3%
+    pub fn with_fee_bps(mut self, fee_bps: u32) -> Self {
+        self.fee_kept = fee_kept(fee_bps);
+        self
+    }
+
+    fn current_price(&self) -> Result<Decimal> {
+        let step = self.started.elapsed().as_millis() / self.step_interval.as_millis();
+        self.schedule.price_at(step as u64)
+    }
+}
+
+#[async_trait]
+impl DexClient for StaticDexClient {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn venue_kind(&self) -> VenueKind {
+        VenueKind::UniswapV2Like
+    }
+
+    /// The scheduled price; static venues have infinite depth
+    async fn get_price_for_size(&self, _pair: &TokenPair, _size: Decimal) -> Result<Decimal> {
+        self.current_price()
+    }
+
+    async fn get_price_at_block(&self, _pair: &TokenPair, _size: Decimal, block: u64) -> Result<Decimal> {
+        self.schedule.price_at(block)
+    }
+
+    async fn quote_exact_input(&self, _pair: &TokenPair, side: TradeSide, amount_in: Decimal) -> Result<SwapQuote> {
+        let price = self.current_price()?;
+        let amount_out = match side {
+            TradeSide::Buy => amount_in / price,
+            TradeSide::Sell => amount_in * price,
+        };
+        Ok(SwapQuote::new(amount_out * self.fee_kept))
+    }
+}
+
+/// A client for every `STATIC_VENUES` entry, stepping every check interval
+pub fn create_clients(config: &Config) -> Result<Vec<Box<dyn DexClient>>> {
+    if config.static_venues.is_empty() {
+        bail!("DEX_BACKEND=static needs at least one STATIC_VENUES entry");
+    }
+
+    let step_interval = Duration::from_secs(config.check_interval_seconds);
+    Ok(config
+        .static_venues
+        .iter()
+        .map(|venue| {
+            log::info!("Static venue {}: {:?}", venue.name, venue.schedule);
+            let fee_bps = config.venue_fee_bps(&venue.name, V2_FEE_BPS);
+            Box::new(StaticDexClient::new(venue.clone(), step_interval).with_fee_bps(fee_bps)) as Box<dyn DexClient>
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn test_swap_quotes_charge_configured_fee() {
+        let venue: StaticVenue = "QuickSwap:series:2000".parse().unwrap();
+        let pair = TokenPair {
+            token0: "WETH".to_string(),
+            token1: "USDC".to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: crate::models::PairBase::Token0,
+        };
+
+        let standard = StaticDexClient::new(venue.clone(), Duration::from_secs(30));
+        assert_eq!(standard.quote_exact_input(&pair, TradeSide::Buy, dec!(1000)).await.unwrap().amount_out, dec!(0.4985));
+
+        let low_fee = StaticDexClient::new(venue, Duration::from_secs(30)).with_fee_bps(10);
+        assert_eq!(low_fee.quote_exact_input(&pair, TradeSide::Buy, dec!(1000)).await.unwrap().amount_out, dec!(0.4995));
+        assert_eq!(low_fee.quote_exact_input(&pair, TradeSide::Sell, dec!(1)).await.unwrap().amount_out, dec!(1998));
+    }
+
+    #[test]
+    fn test_parses_and_plays_schedules() {
+        let series: StaticVenue = "Uniswap:series:2000/2010/2030".parse().unwrap();
+        assert_eq!(series.name, "Uniswap");
+        let prices: Vec<_> = (0..4).map(|step| series.schedule.price_at(step).unwrap()).collect();
+        assert_eq!(prices, [dec!(2000), dec!(2010), dec!(2030), dec!(2000)]);
+
+        let sine: StaticVenue = "SushiSwap:sine:2000/40/4".parse().unwrap();
+        let prices: Vec<_> = (0..5).map(|step| sine.schedule.price_at(step).unwrap()).collect();
+        assert_eq!(prices, [dec!(2000), dec!(2040), dec!(2000), dec!(1960), dec!(2000)]);
+
+        for bad in ["Uniswap", "Uniswap:series:", "Uniswap:sine:2000/40", "Uniswap:sine:2000/40/0", "Uniswap:walk:1/2/3"] {
+            assert!(bad.parse::<StaticVenue>().is_err(), "{} should not parse", bad);
+        }
+    }
+}
@@ -1,97 +1,398 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use rust_decimal::Decimal;
 use ethers::{
-    providers::{Provider, Http, Middleware},
-    types::{Address, U256},
+    providers::{Provider, Http},
+    abi::Abi,
+    types::{Address, BlockNumber, U256},
     contract::Contract,
 };
 use std::str::FromStr;
 use std::sync::Arc;
 
+use super::abi::{SUSHISWAP_ROUTER_ABI, UNISWAP_V2_FACTORY_ABI, UNISWAP_V2_PAIR_ABI};
+use super::cache::{PoolCache, PoolKey};
+use super::{
+    DexClient, RawQuoteCall, SwapQuote, TradeSide, V2_FEE_BPS, VenueKind, explain_historical_error, fee_kept,
+    from_units, orient_price, quoted_price, to_units,
+};
+use crate::models::TokenPair;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{MalformedResponse, contract_error, metered_call, rpc_call};
+
+pub(crate) const VENUE: &str = "SushiSwap";
 
 pub struct SushiswapClient {
     provider: Arc<Provider<Http>>,
     router_contract: Contract<Provider<Http>>,
+    cache: Arc<PoolCache>,
     weth_address: Address,
     usdc_address: Address,
+    /// Sender for quote calls, so caller-dependent pricing matches execution
+    quote_from: Option<Address>,
+    /// Log every router call's parameters and raw output
+    log_raw_quotes: bool,
+    /// Share of each input kept after the pair fee, for our own curve math
+    fee_kept: Decimal,
 }
 
 impl SushiswapClient {
     pub async fn new(
-        rpc_url: &str,
+        provider: Arc<Provider<Http>>,
         router_address: &str,
         weth_address: &str,
         usdc_address: &str,
+        cache: Arc<PoolCache>,
+        quote_from: Option<Address>,
     ) -> Result<Self> {
-        let provider = Arc::new(
-            Provider::<Http>::try_from(rpc_url)
-                .context("Failed to create HTTP provider")?
-        );
-        
         let router_addr = Address::from_str(router_address)
             .context("Invalid router address")?;
         
-        let router_contract = Contract::from_json(
-            provider.clone(),
+        let router_contract = Contract::new(
             router_addr,
-            SUSHISWAP_ROUTER_ABI.as_bytes(),
-        ).context("Failed to create router contract")?;
+            serde_json::from_str::<Abi>(SUSHISWAP_ROUTER_ABI).context("Failed to create router contract")?,
+            provider.clone(),
+        );
         
         Ok(Self {
             provider,
             router_contract,
+            cache,
             weth_address: Address::from_str(weth_address)?,
             usdc_address: Address::from_str(usdc_address)?,
+            quote_from,
+            log_raw_quotes: false,
+            fee_kept: fee_kept(V2_FEE_BPS),
         })
     }
+
+    /// Use a `fee_bps` pair fee in analytical sizing, for forks that do not
+    /// charge the standard 0.3%
+    pub fn with_fee_bps(mut self, fee_bps: u32) -> Self {
+        self.fee_kept = fee_kept(fee_bps);
+        self
+    }
+
+    /// Log each router call's parameters and undecoded output at debug level
+    pub fn with_raw_quote_logging(mut self, enabled: bool) -> Self {
+        self.log_raw_quotes = enabled;
+        self
+    }
     
-    async fn get_token_decimals(&self, token_address: Address) -> Result<u8> {
-        let token_contract = Contract::from_json(
+    /// Resolve (once, via the shared cache) the pool this client quotes against,
+    /// so a missing pool fails fast instead of reverting on every quote
+    async fn pool_address(&self, pair: &TokenPair) -> Result<Address> {
+        self.cache.pool_address(PoolKey::new(VENUE, pair), || self.lookup_pair()).await
+    }
+
+    /// The factory's pair, zero if it has none
+    async fn lookup_pair(&self) -> Result<Address> {
+        let factory_call = self.router_contract.method::<_, Address>("factory", ())?;
+        let factory_address: Address = rpc_call(|| async {
+            factory_call.call().await.map_err(contract_error).context("Failed to get factory address")
+        })
+        .await?;
+
+        let factory = Contract::new(
+            factory_address,
+            serde_json::from_str::<Abi>(UNISWAP_V2_FACTORY_ABI)?,
+            self.provider.clone(),
+        );
+
+        let lookup = factory.method::<_, Address>("getPair", (self.usdc_address, self.weth_address))?;
+        rpc_call(|| async { lookup.call().await.map_err(contract_error).context("Failed to look up SushiSwap pair") }).await
+    }
+
+    /// The pair's current reserve of the quote token
+    async fn quote_reserve(&self, pair: &TokenPair) -> Result<Decimal> {
+        let pair_address = self.pool_address(pair).await?;
+        let pair_contract = Contract::new(
+            pair_address,
+            serde_json::from_str::<Abi>(UNISWAP_V2_PAIR_ABI)?,
             self.provider.clone(),
-            token_address,
-            ERC20_ABI.as_bytes(),
-        )?;
+        );
+
+        let token0_call = pair_contract.method::<_, Address>("token0", ())?;
+        let token0 = rpc_call(|| async { token0_call.call().await.context("Failed to get pair token0") }).await?;
+        let reserves_call = pair_contract.method::<_, (u128, u128, u32)>("getReserves", ())?;
+        let (reserve0, reserve1, _) =
+            metered_call(RpcCallKind::Reserves, || async { reserves_call.call().await.context("Failed to get SushiSwap reserves") }).await?;
+
+        let quote = Address::from_str(pair.quote_token()).context("Invalid quote token address")?;
+        let reserve = if token0 == quote { reserve0 } else { reserve1 };
+        let decimals = self.cache.token_decimals(quote, &self.provider).await?;
+
+        from_units(U256::from(reserve), decimals as u32)
+    }
+
+    /// Quote `size` USDC -> WETH, optionally pinned to a historical block
+    async fn quote(&self, pair: &TokenPair, size: Decimal, block: Option<u64>) -> Result<Decimal> {
+        self.pool_address(pair).await?;
+
+        let usdc_decimals = self.cache.token_decimals(self.usdc_address, &self.provider).await?;
+        let weth_decimals = self.cache.token_decimals(self.weth_address, &self.provider).await?;
+        
+        let amount_in = to_units(size, usdc_decimals as u32)?;
+        let amount_out = self
+            .quote_exact_in(self.usdc_address, self.weth_address, amount_in, block)
+            .await?;
         
-        let decimals: u8 = token_contract
-            .method::<_, u8>("decimals", ())?
-            .call()
-            .await
-            .context("Failed to get token decimals")?;
+        // Convert back to human readable price
+        let usdc_per_weth = quoted_price(VENUE, size, amount_out, weth_decimals)?;
+        let price = orient_price(pair, self.usdc_address, self.weth_address, usdc_per_weth)?;
+        
+        log::debug!("SushiSwap price for {}: {}", pair.symbol, price);
+        Ok(price)
+    }
+
+    /// Router output in raw units for swapping exactly `amount_in` directly
+    /// from `token_in` to `token_out`, net of the pair fee
+    async fn quote_exact_in(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        block: Option<u64>,
+    ) -> Result<U256> {
+        let path = vec![token_in, token_out];
+        let hops = path.len();
         
-        Ok(decimals)
+        let mut call = self.router_contract
+            .method::<_, Vec<U256>>("getAmountsOut", (amount_in, path))?;
+        if let Some(block) = block {
+            call = call.block(BlockNumber::Number(block.into()));
+        }
+        if let Some(from) = self.quote_from {
+            call = call.from(from);
+        }
+
+        let response: Result<Vec<U256>> = metered_call(RpcCallKind::Quote, || async {
+            call.call()
+                .await
+                .map_err(|e| explain_historical_error(contract_error(e), block))
+                .context("Failed to get SushiSwap quote")
+        })
+        .await;
+        if self.log_raw_quotes {
+            RawQuoteCall {
+                venue: VENUE,
+                method: "getAmountsOut",
+                token_in,
+                token_out,
+                fee_tier: None,
+                amount_in,
+                block,
+            }
+            .log(&response);
+        }
+        let amounts_out = response?;
+
+        // One amount per token in the path, the input first
+        if amounts_out.len() != hops {
+            return Err(anyhow::Error::new(MalformedResponse(format!(
+                "getAmountsOut returned {} amounts for a {}-token path",
+                amounts_out.len(),
+                hops
+            ))))
+            .context("Failed to get SushiSwap quote");
+        }
+        Ok(amounts_out[hops - 1])
     }
 }
 
 #[async_trait]
 impl DexClient for SushiswapClient {
-    async fn get_price(&self, pair: &TokenPair) -> Result<Decimal> {
-        let usdc_decimals = self.get_token_decimals(self.usdc_address).await?;
-        let weth_decimals = self.get_token_decimals(self.weth_address).await?;
-        
-        let amount_in = U256::from(1000) * U256::exp10(usdc_decimals as usize); // 1000 USDC
-        
-        // Create the path: USDC -> WETH
-        let path = vec![self.usdc_address, self.weth_address];
-        
-        let amounts_out: Vec<U256> = self.router_contract
-            .method::<_, Vec<U256>>("getAmountsOut", (amount_in, path))?
-            .call()
-            .await
-            .context("Failed to get SushiSwap quote")?;
-        
-        if amounts_out.len() != 2 {
-            return Err(anyhow!("Unexpected getAmountsOut response length"));
+    fn name(&self) -> &'static str {
+        VENUE
+    }
+
+    fn venue_kind(&self) -> VenueKind {
+        VenueKind::UniswapV2Like
+    }
+
+    async fn get_price_for_size(&self, pair: &TokenPair, size: Decimal) -> Result<Decimal> {
+        self.quote(pair, size, None).await
+    }
+
+    async fn get_price_at_block(&self, pair: &TokenPair, size: Decimal, block: u64) -> Result<Decimal> {
+        self.quote(pair, size, Some(block)).await
+    }
+
+    async fn quote_exact_input(&self, pair: &TokenPair, side: TradeSide, amount_in: Decimal) -> Result<SwapQuote> {
+        self.pool_address(pair).await?;
+
+        let (token_in, token_out) = side.tokens(pair)?;
+        let decimals_in = self.cache.token_decimals(token_in, &self.provider).await?;
+        let decimals_out = self.cache.token_decimals(token_out, &self.provider).await?;
+
+        let amount_out = self
+            .quote_exact_in(token_in, token_out, to_units(amount_in, decimals_in as u32)?, None)
+            .await?;
+        from_units(amount_out, decimals_out as u32).map(SwapQuote::new)
+    }
+
+    async fn supports_pair(&self, pair: &TokenPair) -> Result<bool> {
+        self.cache.pool_exists(PoolKey::new(VENUE, pair), || self.lookup_pair()).await
+    }
+
+    /// Both tokens' decimals and the pair's address
+    async fn warm_up(&self, pair: &TokenPair) -> Result<()> {
+        for token in [self.usdc_address, self.weth_address] {
+            self.cache.token_decimals(token, &self.provider).await?;
         }
-        
-        // Convert back to human readable price
-        let weth_out = amounts_out[1].as_u128() as f64 / 10_f64.powi(weth_decimals as i32);
-        let usdc_in = 1000.0; // We quoted for 1000 USDC
-        
-        let price = Decimal::try_from(usdc_in / weth_out)
-            .context("Failed to convert price to Decimal")?;
-        
-        log::debug!("SushiSwap price for {}: {} USDC per WETH", pair.symbol, price);
-        Ok(price)
+        self.supports_pair(pair).await?;
+        Ok(())
+    }
+
+    /// The pair's quote-token reserve
+    async fn get_liquidity(&self, pair: &TokenPair) -> Result<Option<Decimal>> {
+        self.quote_reserve(pair).await.map(Some)
+    }
+
+    /// From the pair's current reserves. Impact here is the curve movement
+    /// only; the pair fee is already part of every quote.
+    async fn max_input_within_slippage(&self, pair: &TokenPair, max_bps: u16) -> Result<Option<Decimal>> {
+        Ok(Some(max_input_for_impact(self.quote_reserve(pair).await?, max_bps, self.fee_kept)))
+    }
+}
+
+/// Constant-product size cap. Swapping `x` against input reserve `R` moves
+/// the execution price `x*f / (R + x*f)` away from spot (`f` = share kept
+/// after the fee), so impact `s` is reached at `x = s*R / (f * (1 - s))`.
+fn max_input_for_impact(reserve_in: Decimal, max_bps: u16, fee_kept: Decimal) -> Decimal {
+    let max_impact = Decimal::from(max_bps) / Decimal::from(10_000);
+    if max_impact >= Decimal::ONE {
+        return Decimal::MAX;
+    }
+    max_impact * reserve_in / (fee_kept * (Decimal::ONE - max_impact))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::mock_rpc::MockRpc;
+    use crate::util::is_malformed;
+    use ethers::abi::{Token, encode};
+    use ethers::utils::id;
+    use rust_decimal_macros::dec;
+
+    const WETH: &str = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619";
+    const USDC: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+
+    /// Price impact of swapping `amount_in` against a pair with these
+    /// reserves, from the router's getAmountOut formula
+    fn impact(reserve_in: Decimal, reserve_out: Decimal, amount_in: Decimal, fee_kept: Decimal) -> Decimal {
+        let amount_out = amount_in * fee_kept * reserve_out / (reserve_in + amount_in * fee_kept);
+        let spot = reserve_out / reserve_in;
+        Decimal::ONE - amount_out / (amount_in * fee_kept * spot)
+    }
+
+    #[test]
+    fn test_max_input_hits_impact_exactly() {
+        // 1M USDC / 500 WETH pair with a 1% cap: 0.01 * 1M / (0.997 * 0.99)
+        let standard = fee_kept(V2_FEE_BPS);
+        let cap = max_input_for_impact(dec!(1000000), 100, standard);
+        assert_eq!(cap.round_dp(6), dec!(10131.404314));
+        assert!((impact(dec!(1000000), dec!(500), cap, standard) - dec!(0.01)).abs() < dec!(0.000000001));
+
+        // Thinner 50k USDC / 25 WETH pair with a 0.5% cap
+        let cap = max_input_for_impact(dec!(50000), 50, standard);
+        assert_eq!(cap.round_dp(6), dec!(252.012318));
+        assert!((impact(dec!(50000), dec!(25), cap, standard) - dec!(0.005)).abs() < dec!(0.000000001));
+
+        assert_eq!(max_input_for_impact(dec!(50000), 0, standard), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_max_input_follows_configured_fee() {
+        // A 0.1% fork keeps more of each input, so less of it reaches the same impact
+        let low_fee = fee_kept(10);
+        assert_eq!(low_fee, dec!(0.999));
+        let cap = max_input_for_impact(dec!(1000000), 100, low_fee);
+        assert_eq!(cap.round_dp(6), dec!(10111.121222));
+        assert!(cap < max_input_for_impact(dec!(1000000), 100, fee_kept(V2_FEE_BPS)));
+        assert!((impact(dec!(1000000), dec!(500), cap, low_fee) - dec!(0.01)).abs() < dec!(0.000000001));
+    }
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: WETH.to_string(),
+            token1: USDC.to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: crate::models::PairBase::Token0,
+        }
+    }
+
+    /// Client for a router at 0x0101.. on `rpc`, with its own cache
+    async fn client(rpc: &MockRpc) -> SushiswapClient {
+        let provider = Arc::new(Provider::<Http>::try_from(rpc.url.as_str()).unwrap());
+        let router = format!("{:?}", Address::repeat_byte(0x01));
+        SushiswapClient::new(provider, &router, WETH, USDC, Arc::new(PoolCache::new().unwrap()), None).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_primes_decimals_and_pool() {
+        let (factory, pool) = (Address::repeat_byte(0xfa), Address::repeat_byte(0x02));
+        let usdc = Address::from_str(USDC).unwrap();
+        let rpc = MockRpc::serve(move |to, data| {
+            let selector = &data[..4];
+            encode(&[if selector == id("factory()") {
+                Token::Address(factory)
+            } else if selector == id("getPair(address,address)") {
+                Token::Address(pool)
+            } else if selector == id("decimals()") {
+                Token::Uint(U256::from(if to == usdc { 6 } else { 18 }))
+            } else {
+                panic!("unexpected call to {:?}", to)
+            }])
+        })
+        .await;
+        let client = client(&rpc).await;
+
+        // Both tokens' decimals are in the registry: only the factory and its pair
+        client.warm_up(&pair()).await.unwrap();
+        assert_eq!(rpc.requests(), 2);
+
+        assert_eq!(client.pool_address(&pair()).await.unwrap(), pool);
+        assert!(client.supports_pair(&pair()).await.unwrap());
+        assert_eq!(client.cache.token_decimals(usdc, &client.provider).await.unwrap(), 6);
+        assert_eq!(client.cache.token_decimals(Address::from_str(WETH).unwrap(), &client.provider).await.unwrap(), 18);
+        client.warm_up(&pair()).await.unwrap();
+        assert_eq!(rpc.requests(), 2);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_malformed_responses_are_named() {
+        // A node that answers getAmountsOut with one amount too many, claims
+        // 77 decimals for a token and returns nothing for the factory lookup
+        let weth = Address::from_str(WETH).unwrap();
+        let token = Address::repeat_byte(0x77);
+        let rpc = MockRpc::serve(move |to, data| {
+            let selector = &data[..4];
+            if selector == id("getAmountsOut(uint256,address[])") {
+                encode(&[Token::Array(vec![Token::Uint(U256::from(1)); 3])])
+            } else if selector == id("decimals()") {
+                encode(&[Token::Uint(U256::from(if to == token { 77 } else { 6 }))])
+            } else if selector == id("factory()") {
+                Vec::new()
+            } else {
+                panic!("unexpected call to {:?}", to)
+            }
+        })
+        .await;
+        let client = client(&rpc).await;
+
+        let quote = client.quote_exact_in(Address::from_str(USDC).unwrap(), weth, U256::from(1_000_000), None);
+        let decimals = client.cache.token_decimals(token, &client.provider);
+        let (quote, decimals, lookup) = tokio::join!(quote, decimals, client.lookup_pair());
+
+        let quote = quote.unwrap_err();
+        assert!(is_malformed(&quote));
+        assert!(format!("{:#}", quote).contains("getAmountsOut returned 3 amounts for a 2-token path"));
+        let decimals = decimals.unwrap_err();
+        assert!(is_malformed(&decimals));
+        assert!(decimals.to_string().contains("returned 77, above 36"));
+        // Undecodable output rather than a revert or transport error
+        assert!(is_malformed(&lookup.unwrap_err()));
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context, Result, bail};
+use ethers::types::Address;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::TokenPair;
+
+/// Decimals of well-known Polygon tokens, which never change, so they need
+/// no `decimals()` call
+const KNOWN_DECIMALS: [(&str, u8); 7] = [
+    ("0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619", 18), // WETH
+    ("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270", 18), // WPOL (WMATIC)
+    ("0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174", 6),  // USDC.e
+    ("0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359", 6),  // USDC
+    ("0xc2132D05D31c914a87C6611C10748AEb04B58e8F", 6),  // USDT
+    ("0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063", 18), // DAI
+    ("0x1BFD67037B42Cf73acF2047067bd4F2C47D9BfD6", 8),  // WBTC
+];
+
+/// Registry decimals of `token`, if it is a well-known one
+pub fn known_decimals(token: Address) -> Option<u8> {
+    KNOWN_DECIMALS
+        .iter()
+        .find(|(address, _)| Address::from_str(address).is_ok_and(|address| address == token))
+        .map(|&(_, decimals)| decimals)
+}
+
+/// Configured decimals for a token the registry does not know, as
+/// `address:decimals`, sparing its `decimals()` call or standing in for a
+/// token that does not implement it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalsOverride {
+    pub token: Address,
+    pub decimals: u8,
+}
+
+impl FromStr for DecimalsOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, decimals) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected address:decimals, got {:?}", s))?;
+        let token = Address::from_str(address.trim()).map_err(|e| format!("invalid token address {:?}: {}", address, e))?;
+        let decimals = decimals.trim().parse().map_err(|e| format!("invalid decimals {:?}: {}", decimals, e))?;
+        Ok(Self { token, decimals })
+    }
+}
+
+/// How a token behaves when transferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferBehavior {
+    Standard,
+    /// Takes a cut of every transfer (or rebases), so quoted outputs overstate
+    /// what arrives. `None` when the fee is not known.
+    FeeOnTransfer { fee_bps: Option<u32> },
+}
+
+impl fmt::Display for TransferBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferBehavior::Standard => write!(f, "standard"),
+            TransferBehavior::FeeOnTransfer { fee_bps: Some(bps) } => write!(f, "fee-on-transfer ({} bps)", bps),
+            TransferBehavior::FeeOnTransfer { fee_bps: None } => write!(f, "fee-on-transfer (unknown fee)"),
+        }
+    }
+}
+
+/// What to do with pairs that contain a fee-on-transfer token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FeeOnTransferPolicy {
+    /// Never compare or trade the pair
+    #[default]
+    Skip,
+    /// Trade it, discounting each swap leg by the known transfer fees
+    Haircut,
+}
+
+impl FromStr for FeeOnTransferPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "skip" => Ok(FeeOnTransferPolicy::Skip),
+            "haircut" => Ok(FeeOnTransferPolicy::Haircut),
+            other => Err(format!("unknown policy {:?} (expected skip or haircut)", other)),
+        }
+    }
+}
+
+/// How a pair may be traded given its tokens' transfer behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairTransferFee {
+    /// Neither token is taxed
+    None,
+    /// Fraction lost to transfer fees on each swap leg
+    Haircut(Decimal),
+    /// The pair must not be traded
+    Skip,
+}
+
+/// Known transfer behavior per token. Tokens are classified from a configured
+/// deny-list (`FEE_ON_TRANSFER_TOKENS`); anything not listed is standard.
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<Address, TransferBehavior>,
+}
+
+impl TokenRegistry {
+    /// Build from `address` or `address:fee_bps` entries
+    pub fn from_entries(entries: &[String]) -> Result<Self> {
+        let mut tokens = HashMap::new();
+        for entry in entries {
+            let (address, fee_bps) = match entry.split_once(':') {
+                Some((address, bps)) => {
+                    let bps: u32 = bps.trim().parse()
+                        .with_context(|| format!("Invalid fee for fee-on-transfer token {}", entry))?;
+                    if bps >= 10_000 {
+                        bail!("Fee for fee-on-transfer token {} must be below 10000 bps", entry);
+                    }
+                    (address, Some(bps))
+                }
+                None => (entry.as_str(), None),
+            };
+            let address = Address::from_str(address.trim())
+                .with_context(|| format!("Invalid fee-on-transfer token address {}", entry))?;
+            tokens.insert(address, TransferBehavior::FeeOnTransfer { fee_bps });
+        }
+        Ok(Self { tokens })
+    }
+
+    pub fn classify(&self, token: Address) -> TransferBehavior {
+        self.tokens.get(&token).copied().unwrap_or(TransferBehavior::Standard)
+    }
+
+    /// Transfer-fee treatment for a pair under `policy`. A leg moves one token
+    /// of each side, so the per-leg loss compounds both tokens' fees.
+    pub fn pair_transfer_fee(&self, pair: &TokenPair, policy: FeeOnTransferPolicy) -> Result<PairTransferFee> {
+        let mut kept = Decimal::ONE;
+        let mut taxed = false;
+
+        for token in [&pair.token0, &pair.token1] {
+            let address = Address::from_str(token).context("Invalid pair token address")?;
+            match self.classify(address) {
+                TransferBehavior::Standard => {}
+                TransferBehavior::FeeOnTransfer { fee_bps } => {
+                    taxed = true;
+                    match (policy, fee_bps) {
+                        (FeeOnTransferPolicy::Haircut, Some(bps)) => {
+                            kept *= Decimal::ONE - Decimal::from(bps) / Decimal::from(10_000);
+                        }
+                        _ => return Ok(PairTransferFee::Skip),
+                    }
+                }
+            }
+        }
+
+        Ok(if taxed {
+            PairTransferFee::Haircut(Decimal::ONE - kept)
+        } else {
+            PairTransferFee::None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PairBase;
+    use rust_decimal_macros::dec;
+
+    const WETH: &str = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619";
+    const USDC: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: WETH.to_string(),
+            token1: USDC.to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: PairBase::Token0,
+        }
+    }
+
+    #[test]
+    fn test_classifies_listed_tokens() {
+        let registry = TokenRegistry::from_entries(&[format!("{}:200", WETH)]).unwrap();
+        let weth = Address::from_str(WETH).unwrap();
+        let usdc = Address::from_str(USDC).unwrap();
+
+        assert_eq!(registry.classify(weth), TransferBehavior::FeeOnTransfer { fee_bps: Some(200) });
+        assert_eq!(registry.classify(usdc), TransferBehavior::Standard);
+        assert!(TokenRegistry::from_entries(&[format!("{}:10000", WETH)]).is_err());
+    }
+
+    #[test]
+    fn test_pair_transfer_fee_by_policy() {
+        let standard = TokenRegistry::default();
+        assert_eq!(standard.pair_transfer_fee(&pair(), FeeOnTransferPolicy::Skip).unwrap(), PairTransferFee::None);
+
+        let known_fee = TokenRegistry::from_entries(&[format!("{}:200", WETH)]).unwrap();
+        assert_eq!(known_fee.pair_transfer_fee(&pair(), FeeOnTransferPolicy::Skip).unwrap(), PairTransferFee::Skip);
+        assert_eq!(
+            known_fee.pair_transfer_fee(&pair(), FeeOnTransferPolicy::Haircut).unwrap(),
+            PairTransferFee::Haircut(dec!(0.02))
+        );
+
+        // A haircut is impossible without knowing the fee
+        let unknown_fee = TokenRegistry::from_entries(&[WETH.to_string()]).unwrap();
+        assert_eq!(unknown_fee.pair_transfer_fee(&pair(), FeeOnTransferPolicy::Haircut).unwrap(), PairTransferFee::Skip);
+    }
+}
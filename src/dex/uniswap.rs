@@ -1,104 +1,969 @@
 use anyhow::{Context, Result, anyhow};
 use async_trait::async_trait;
+use futures::future::join_all;
 use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use ethers::{
-    providers::{Provider, Http, Middleware},
-    types::{Address, U256},
-    contract::Contract,
+    providers::{Provider, Http},
+    abi::{Abi, Detokenize},
+    types::{Address, BlockNumber, I256, U256, U512},
+    contract::{Contract, ContractCall},
 };
+use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use super::abi::{ERC20_ABI, UNISWAP_V3_FACTORY_ABI, UNISWAP_V3_POOL_ABI, UNISWAP_V3_QUOTER_ABI, UNISWAP_V3_QUOTER_V2_ABI};
+use super::cache::{PoolCache, PoolKey};
+use super::{
+    DexClient, RawQuoteCall, SwapQuote, TradeSide, TwapPrice, VenueKind, explain_historical_error, from_units, orient_price,
+    quoted_price, sqrt_price_x96_price, tick_price, to_units,
+};
+use crate::models::TokenPair;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{contract_error, is_revert, metered_call, rpc_call};
+
+pub(crate) const VENUE: &str = "Uniswap";
+
+/// Fee tiers (hundredths of a basis point) Uniswap V3 deploys pools for
+pub(crate) const STANDARD_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+/// Bounds (exclusive) a V3 pool accepts for `sqrtPriceLimitX96`, from TickMath
+const MIN_SQRT_RATIO: u64 = 4_295_128_739;
+const MAX_SQRT_RATIO: &str = "1461446703485210103287273052203988822378723970342";
+
+/// `sqrtPriceLimitX96` for a swap starting at `sqrt_price_x96` that may move
+/// the pool price (token1 per token0) at most `slippage_bps` against the
+/// swapper: down for token0 in (`zero_for_one`), up for token1 in. Computed
+/// as the integer square root of the scaled squared price, in 512 bits since
+/// the square of a 160-bit price overflows 256, and kept inside the ratios
+/// the pool accepts.
+fn sqrt_price_limit_x96(sqrt_price_x96: U256, zero_for_one: bool, slippage_bps: u16) -> U256 {
+    let squared = sqrt_price_x96.full_mul(sqrt_price_x96);
+    let (kept, whole) = (U512::from(10_000 - u32::from(slippage_bps.min(9_999))), U512::from(10_000));
+    let limit = if zero_for_one { squared * kept / whole } else { squared * whole / kept }.integer_sqrt();
+    let min = U512::from(MIN_SQRT_RATIO) + 1;
+    let max = U512::from(U256::from_dec_str(MAX_SQRT_RATIO).expect("valid constant")) - 1;
+    U256::try_from(limit.clamp(min, max)).expect("clamped below MAX_SQRT_RATIO")
+}
+
+/// Check the configured tiers against the standard set, dropping duplicates
+fn validate_fee_tiers(fee_tiers: &[u32]) -> Result<Vec<u32>> {
+    if fee_tiers.is_empty() {
+        return Err(anyhow!("At least one Uniswap V3 fee tier must be configured"));
+    }
+
+    let mut validated = Vec::with_capacity(fee_tiers.len());
+    for &fee_tier in fee_tiers {
+        if !STANDARD_FEE_TIERS.contains(&fee_tier) {
+            return Err(anyhow!(
+                "Unsupported Uniswap V3 fee tier {} (expected one of {:?})",
+                fee_tier,
+                STANDARD_FEE_TIERS
+            ));
+        }
+        if !validated.contains(&fee_tier) {
+            validated.push(fee_tier);
+        }
+    }
+    Ok(validated)
+}
+
+/// Uniswap quoter contract interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuoterVersion {
+    /// The original Quoter: output amount only
+    #[default]
+    V1,
+    /// QuoterV2: also the post-swap pool price and a gas estimate
+    V2,
+}
+
+impl QuoterVersion {
+    /// Polygon deployment of this quoter
+    pub fn default_address(self) -> &'static str {
+        match self {
+            QuoterVersion::V1 => "0xb27308f9F90D607463bb33eA1BeBb41C27CE5AB6",
+            QuoterVersion::V2 => "0x61fFE014bA17989E743c5F6cB21bF9697530B21e",
+        }
+    }
+
+    fn abi(self) -> &'static str {
+        match self {
+            QuoterVersion::V1 => UNISWAP_V3_QUOTER_ABI,
+            QuoterVersion::V2 => UNISWAP_V3_QUOTER_V2_ABI,
+        }
+    }
+}
+
+impl FromStr for QuoterVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "v1" | "1" => Ok(QuoterVersion::V1),
+            "v2" | "2" => Ok(QuoterVersion::V2),
+            other => Err(format!("unknown quoter version {:?} (expected v1 or v2)", other)),
+        }
+    }
+}
+
+/// What a quoter reports for one exact-input swap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuoteOutput {
+    /// Output in raw units, net of the pool fee
+    pub amount_out: U256,
+    /// Pool square-root price (Q64.96) after the swap; QuoterV2 only
+    pub sqrt_price_x96_after: Option<U256>,
+    /// Gas the swap itself would use; QuoterV2 only
+    pub gas_estimate: Option<u64>,
+}
+
+impl QuoteOutput {
+    /// From QuoterV2's `(amountOut, sqrtPriceX96After, initializedTicksCrossed, gasEstimate)`
+    fn from_v2((amount_out, sqrt_price_x96_after, _ticks_crossed, gas_estimate): (U256, U256, u32, U256)) -> Self {
+        Self {
+            amount_out,
+            sqrt_price_x96_after: Some(sqrt_price_x96_after),
+            gas_estimate: (gas_estimate <= U256::from(u64::MAX)).then(|| gas_estimate.as_u64()),
+        }
+    }
+}
+
+/// One fee tier's answer to an exact-input quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierQuote {
+    pub fee_tier: u32,
+    pub quote: QuoteOutput,
+    /// The pool's in-range liquidity, fetched only while depth is preferred
+    pub liquidity: Option<u128>,
+}
+
+/// The pair's tokens in pool order. Pools sort their tokens by address, and
+/// their prices are token1 per token0.
+fn pool_tokens(pair: &TokenPair) -> Result<(Address, Address)> {
+    let token0 = Address::from_str(&pair.token0).context("Invalid token0 address")?;
+    let token1 = Address::from_str(&pair.token1).context("Invalid token1 address")?;
+    Ok(if token0 < token1 { (token0, token1) } else { (token1, token0) })
+}
+
+/// How far a pool's price moves between two square-root prices, in basis
+/// points of the first
+fn price_move_bps(sqrt_price_x96_before: U256, sqrt_price_x96_after: U256) -> Option<Decimal> {
+    if sqrt_price_x96_before.is_zero() {
+        return None;
+    }
+    let ratio = sqrt_price_x96_after.to_string().parse::<f64>().ok()?
+        / sqrt_price_x96_before.to_string().parse::<f64>().ok()?;
+    Decimal::try_from((ratio * ratio - 1.0).abs() * 10_000.0).ok()
+}
+
+/// The tier to route through: the deepest pool among those whose output is
+/// within `depth_tolerance` (a fraction) of the best, or simply the best
+/// output when the tolerance is zero or no liquidity is known
+fn select_tier(quotes: &[TierQuote], depth_tolerance: Decimal) -> Option<&TierQuote> {
+    let best = quotes.iter().max_by_key(|tier| tier.quote.amount_out)?;
+    let tolerance_ppm = (depth_tolerance * Decimal::from(1_000_000)).to_u64().unwrap_or_default();
+    if tolerance_ppm == 0 {
+        return Some(best);
+    }
+
+    let best_out = best.quote.amount_out;
+    let floor = best_out - best_out * U256::from(tolerance_ppm.min(1_000_000)) / U256::from(1_000_000);
+    quotes
+        .iter()
+        .filter(|tier| tier.quote.amount_out >= floor && tier.liquidity.is_some())
+        .max_by_key(|tier| (tier.liquidity, tier.quote.amount_out))
+        .or(Some(best))
+}
 
 pub struct UniswapV3Client {
     provider: Arc<Provider<Http>>,
     quoter_contract: Contract<Provider<Http>>,
+    cache: Arc<PoolCache>,
     weth_address: Address,
     usdc_address: Address,
+    /// Sender for quote calls, so caller-dependent pricing matches execution
+    quote_from: Option<Address>,
+    /// Fee tiers probed for every quote, in configured order
+    fee_tiers: Vec<u32>,
+    /// Output (as a fraction) given up to route through a deeper pool
+    depth_tolerance: Decimal,
+    quoter_version: QuoterVersion,
+    /// Log every quoter call's parameters and raw output
+    log_raw_quotes: bool,
+    /// Stop each quote once the pool price has moved this far (0 = sweep
+    /// the pool for the whole amount)
+    price_limit_bps: u16,
+    /// Fee tier each pair's latest live price was quoted on, by symbol,
+    /// whose pool the TWAP check reads
+    quoted_tiers: Mutex<HashMap<String, u32>>,
 }
 
 impl UniswapV3Client {
     pub async fn new(
-        rpc_url: &str,
+        provider: Arc<Provider<Http>>,
         quoter_address: &str,
         weth_address: &str,
         usdc_address: &str,
+        cache: Arc<PoolCache>,
+        quote_from: Option<Address>,
+        fee_tiers: &[u32],
     ) -> Result<Self> {
-        let provider = Arc::new(
-            Provider::<Http>::try_from(rpc_url)
-                .context("Failed to create HTTP provider")?
-        );
-        
+        let fee_tiers = validate_fee_tiers(fee_tiers)?;
         let quoter_addr = Address::from_str(quoter_address)
             .context("Invalid quoter address")?;
         
-        let quoter_contract = Contract::from_json(
-            provider.clone(),
+        let quoter_contract = Contract::new(
             quoter_addr,
-            UNISWAP_V3_QUOTER_ABI.as_bytes(),
-        ).context("Failed to create quoter contract")?;
+            serde_json::from_str::<Abi>(UNISWAP_V3_QUOTER_ABI).context("Failed to create quoter contract")?,
+            provider.clone(),
+        );
         
         Ok(Self {
             provider,
             quoter_contract,
+            cache,
             weth_address: Address::from_str(weth_address)?,
             usdc_address: Address::from_str(usdc_address)?,
+            quote_from,
+            fee_tiers,
+            depth_tolerance: Decimal::ZERO,
+            quoter_version: QuoterVersion::V1,
+            log_raw_quotes: false,
+            price_limit_bps: 0,
+            quoted_tiers: Mutex::new(HashMap::new()),
         })
     }
-    
-    async fn get_token_decimals(&self, token_address: Address) -> Result<u8> {
-        let token_contract = Contract::from_json(
+
+    /// Quote through `version`'s interface; the quoter address given to `new`
+    /// must be that version's deployment
+    pub fn with_quoter_version(mut self, version: QuoterVersion) -> Result<Self> {
+        self.quoter_contract = Contract::new(
+            self.quoter_contract.address(),
+            serde_json::from_str::<Abi>(version.abi()).context("Failed to create quoter contract")?,
             self.provider.clone(),
-            token_address,
-            ERC20_ABI.as_bytes(),
-        )?;
-        
-        let decimals: u8 = token_contract
-            .method::<_, u8>("decimals", ())?
-            .call()
+        );
+        self.quoter_version = version;
+        Ok(self)
+    }
+
+    /// Prefer a deeper pool when its output is within `tolerance` (a
+    /// fraction, e.g. 0.001) of the best tier's, so large trades avoid thin pools
+    pub fn with_depth_preference(mut self, tolerance: Decimal) -> Self {
+        self.depth_tolerance = tolerance;
+        self
+    }
+    
+    /// Log each quoter call's parameters and undecoded output at debug level
+    pub fn with_raw_quote_logging(mut self, enabled: bool) -> Self {
+        self.log_raw_quotes = enabled;
+        self
+    }
+
+    /// Quote only as far as the pool price can move within `bps` (e.g. the
+    /// slippage tolerance), so a quote never assumes more depth than the
+    /// executor would accept. A pool too thin to fill the whole amount
+    /// within it quotes what it fills, pricing the amount conservatively.
+    /// Costs a `slot0` read per tier and quote.
+    pub fn with_price_limit_bps(mut self, bps: u16) -> Self {
+        self.price_limit_bps = bps;
+        self
+    }
+
+    /// Resolve (once, via the shared cache) the pool for one fee tier,
+    /// so a missing pool fails fast instead of reverting on every quote
+    async fn pool_address(&self, pair: &TokenPair, fee_tier: u32) -> Result<Address> {
+        self.cache
+            .pool_address(PoolKey::with_fee_tier(VENUE, pair, fee_tier), || self.lookup_pool(fee_tier))
             .await
-            .context("Failed to get token decimals")?;
-        
-        Ok(decimals)
     }
-}
 
-#[async_trait]
-impl DexClient for UniswapV3Client {
-    async fn get_price(&self, pair: &TokenPair) -> Result<Decimal> {
+    /// The factory's pool for one fee tier, zero if it has none
+    async fn lookup_pool(&self, fee_tier: u32) -> Result<Address> {
+        let factory_call = self.quoter_contract.method::<_, Address>("factory", ())?;
+        let factory_address: Address = rpc_call(|| async {
+            factory_call.call().await.map_err(contract_error).context("Failed to get factory address")
+        })
+        .await?;
+
+        let factory = Contract::new(
+            factory_address,
+            serde_json::from_str::<Abi>(UNISWAP_V3_FACTORY_ABI)?,
+            self.provider.clone(),
+        );
+
+        let lookup = factory.method::<_, Address>("getPool", (self.usdc_address, self.weth_address, fee_tier))?;
+        rpc_call(|| async { lookup.call().await.map_err(contract_error).context("Failed to look up Uniswap V3 pool") }).await
+    }
+
+    /// Quote `size` USDC -> WETH, optionally pinned to a historical block
+    async fn quote(&self, pair: &TokenPair, size: Decimal, block: Option<u64>) -> Result<Decimal> {
         // Convert trade amount to token units (assuming USDC input)
-        let usdc_decimals = self.get_token_decimals(self.usdc_address).await?;
-        let weth_decimals = self.get_token_decimals(self.weth_address).await?;
-        
-        let amount_in = U256::from(1000) * U256::exp10(usdc_decimals as usize); // 1000 USDC
+        let usdc_decimals = self.cache.token_decimals(self.usdc_address, &self.provider).await?;
+        let weth_decimals = self.cache.token_decimals(self.weth_address, &self.provider).await?;
         
-        // Uniswap V3 fee tiers: 500 (0.05%), 3000 (0.3%), 10000 (1%)
-        // Try the most common 0.3% fee tier first
-        let fee_tier = 3000u32;
-        
-        let quote_result: U256 = self.quoter_contract
-            .method::<_, U256>(
-                "quoteExactInputSingle",
-                (
-                    self.usdc_address,
-                    self.weth_address,
-                    fee_tier,
-                    amount_in,
-                    U256::zero(), // No price limit
-                ),
-            )?
-            .call()
-            .await
-            .context("Failed to get Uniswap quote")?;
+        let amount_in = to_units(size, usdc_decimals as u32)?;
+        let selected = self
+            .best_quote_exact_in(pair, self.usdc_address, self.weth_address, amount_in, block)
+            .await?;
+        if block.is_none() {
+            self.quoted_tiers.lock().insert(pair.symbol.clone(), selected.fee_tier);
+        }
         
         // Convert back to human readable price
-        let weth_out = quote_result.as_u128() as f64 / 10_f64.powi(weth_decimals as i32);
-        let usdc_in = 1000.0; // We quoted for 1000 USDC
-        
-        let price = Decimal::try_from(usdc_in / weth_out)
-            .context("Failed to convert price to Decimal")?;
+        let usdc_per_weth = quoted_price(VENUE, size, selected.quote.amount_out, weth_decimals)?;
+        let price = orient_price(pair, self.usdc_address, self.weth_address, usdc_per_weth)?;
         
-        log::debug!("Uniswap V3 price for {}: {} USDC per WETH", pair.symbol, price);
+        log::debug!("Uniswap V3 price for {}: {}", pair.symbol, price);
         Ok(price)
     }
+
+    /// Quote of the selected fee tier (see `select_tier`)
+    async fn best_quote_exact_in(
+        &self,
+        pair: &TokenPair,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        block: Option<u64>,
+    ) -> Result<TierQuote> {
+        let quotes = self
+            .try_all_fee_tiers_and_aggregate_liquidity(pair, token_in, token_out, amount_in, block)
+            .await?;
+        let selected = select_tier(&quotes, self.depth_tolerance)
+            .ok_or_else(|| anyhow!("No Uniswap V3 fee tiers configured"))?;
+
+        log::debug!("Selected Uniswap V3 fee tier for {}: {:?}", pair.symbol, selected);
+        Ok(*selected)
+    }
+
+    /// Quote every configured fee tier, with each pool's liquidity while a
+    /// depth preference is set. Tiers without a pool (the quoter reverts) or
+    /// whose quote fails are skipped; it is an error only if every tier fails.
+    pub async fn try_all_fee_tiers_and_aggregate_liquidity(
+        &self,
+        pair: &TokenPair,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        block: Option<u64>,
+    ) -> Result<Vec<TierQuote>> {
+        let results = join_all(self.fee_tiers.iter().map(|&fee_tier| async move {
+            let pool_address = self.pool_address(pair, fee_tier).await?;
+            let price_limit = self.price_limit(pool_address, token_in < token_out, block).await?;
+            let (quote, liquidity) = if self.depth_tolerance.is_zero() {
+                (self.quote_exact_in(token_in, token_out, amount_in, fee_tier, price_limit, block).await?, None)
+            } else {
+                let (quote, liquidity) = futures::try_join!(
+                    self.quote_exact_in(token_in, token_out, amount_in, fee_tier, price_limit, block),
+                    self.pool_liquidity(pool_address, block),
+                )?;
+                (quote, Some(liquidity))
+            };
+            Ok::<_, anyhow::Error>(TierQuote { fee_tier, quote, liquidity })
+        }))
+        .await;
+
+        let mut quotes = Vec::with_capacity(results.len());
+        let mut last_error = None;
+        for (&fee_tier, result) in self.fee_tiers.iter().zip(results) {
+            match result {
+                Ok(quote) => quotes.push(quote),
+                Err(e) if is_revert(&e) => {
+                    log::debug!("Skipping Uniswap V3 fee tier {} for {}: no pool or quote reverted", fee_tier, pair.symbol);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    log::debug!("Skipping Uniswap V3 fee tier {} for {}: {:#}", fee_tier, pair.symbol, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) if quotes.is_empty() => Err(e),
+            _ => Ok(quotes),
+        }
+    }
+
+    /// In-range liquidity of a pool
+    async fn pool_liquidity(&self, pool_address: Address, block: Option<u64>) -> Result<u128> {
+        let pool = Contract::new(
+            pool_address,
+            serde_json::from_str::<Abi>(UNISWAP_V3_POOL_ABI)?,
+            self.provider.clone(),
+        );
+        let mut call = pool.method::<_, u128>("liquidity", ())?;
+        if let Some(block) = block {
+            call = call.block(BlockNumber::Number(block.into()));
+        }
+
+        rpc_call(|| async {
+            call.call()
+                .await
+                .map_err(|e| explain_historical_error(e.into(), block))
+                .context("Failed to get Uniswap V3 pool liquidity")
+        })
+        .await
+    }
+
+    /// `sqrtPriceLimitX96` for a swap through the pool, zero (no limit)
+    /// unless a price limit is set
+    async fn price_limit(&self, pool_address: Address, zero_for_one: bool, block: Option<u64>) -> Result<U256> {
+        if self.price_limit_bps == 0 {
+            return Ok(U256::zero());
+        }
+        let sqrt_price_x96 = self.pool_sqrt_price_x96(pool_address, block).await?;
+        Ok(sqrt_price_limit_x96(sqrt_price_x96, zero_for_one, self.price_limit_bps))
+    }
+
+    /// The pool's square-root price (Q64.96) from `slot0`
+    async fn pool_sqrt_price_x96(&self, pool_address: Address, block: Option<u64>) -> Result<U256> {
+        let pool = Contract::new(
+            pool_address,
+            serde_json::from_str::<Abi>(UNISWAP_V3_POOL_ABI)?,
+            self.provider.clone(),
+        );
+        let mut call = pool.method::<_, (U256, i32, u16, u16, u16, u8, bool)>("slot0", ())?;
+        if let Some(block) = block {
+            call = call.block(BlockNumber::Number(block.into()));
+        }
+
+        let (sqrt_price_x96, ..) = rpc_call(|| async {
+            call.call()
+                .await
+                .map_err(|e| explain_historical_error(contract_error(e), block))
+                .context("Failed to get Uniswap V3 pool price")
+        })
+        .await?;
+        Ok(sqrt_price_x96)
+    }
+
+    /// Quoter output for swapping exactly `amount_in` through one fee tier,
+    /// stopping at `price_limit` unless it is zero
+    async fn quote_exact_in(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        fee_tier: u32,
+        price_limit: U256,
+        block: Option<u64>,
+    ) -> Result<QuoteOutput> {
+        let raw_call = RawQuoteCall {
+            venue: VENUE,
+            method: "quoteExactInputSingle",
+            token_in,
+            token_out,
+            fee_tier: Some(fee_tier),
+            amount_in,
+            block,
+        };
+        match self.quoter_version {
+            QuoterVersion::V1 => {
+                let call = self.quoter_contract.method::<_, U256>(
+                    "quoteExactInputSingle",
+                    (token_in, token_out, fee_tier, amount_in, price_limit),
+                )?;
+                let response = self.call_quoter(call, block).await;
+                if self.log_raw_quotes {
+                    raw_call.log(&response);
+                }
+                Ok(QuoteOutput { amount_out: response?, sqrt_price_x96_after: None, gas_estimate: None })
+            }
+            QuoterVersion::V2 => {
+                let call = self.quoter_contract.method::<_, (U256, U256, u32, U256)>(
+                    "quoteExactInputSingle",
+                    ((token_in, token_out, amount_in, fee_tier, price_limit),),
+                )?;
+                let response = self.call_quoter(call, block).await;
+                if self.log_raw_quotes {
+                    raw_call.log(&response);
+                }
+                response.map(QuoteOutput::from_v2)
+            }
+        }
+    }
+
+    async fn call_quoter<D: Detokenize>(&self, mut call: ContractCall<Provider<Http>, D>, block: Option<u64>) -> Result<D> {
+        if let Some(block) = block {
+            call = call.block(BlockNumber::Number(block.into()));
+        }
+        if let Some(from) = self.quote_from {
+            call = call.from(from);
+        }
+
+        metered_call(RpcCallKind::Quote, || async {
+            call.call()
+                .await
+                .map_err(|e| explain_historical_error(contract_error(e), block))
+                .context("Failed to get Uniswap quote")
+        })
+        .await
+    }
+
+    /// Pool price (quote per base) the swap would leave behind, if the quoter reported it
+    async fn price_after(&self, pair: &TokenPair, quote: &QuoteOutput) -> Result<Option<Decimal>> {
+        let Some(sqrt_price_x96) = quote.sqrt_price_x96_after else {
+            return Ok(None);
+        };
+        let (token0, token1) = pool_tokens(pair)?;
+        let token0_decimals = self.cache.token_decimals(token0, &self.provider).await?;
+        let token1_decimals = self.cache.token_decimals(token1, &self.provider).await?;
+
+        let token1_per_token0 = sqrt_price_x96_price(sqrt_price_x96, token0_decimals, token1_decimals)?;
+        orient_price(pair, token1, token0, token1_per_token0).map(Some)
+    }
+}
+
+#[async_trait]
+impl DexClient for UniswapV3Client {
+    fn name(&self) -> &'static str {
+        VENUE
+    }
+
+    fn venue_kind(&self) -> VenueKind {
+        VenueKind::UniswapV3
+    }
+
+    async fn get_price_for_size(&self, pair: &TokenPair, size: Decimal) -> Result<Decimal> {
+        self.quote(pair, size, None).await
+    }
+
+    async fn get_price_at_block(&self, pair: &TokenPair, size: Decimal, block: u64) -> Result<Decimal> {
+        self.quote(pair, size, Some(block)).await
+    }
+
+    async fn quote_exact_input(&self, pair: &TokenPair, side: TradeSide, amount_in: Decimal) -> Result<SwapQuote> {
+        let (token_in, token_out) = side.tokens(pair)?;
+        let decimals_in = self.cache.token_decimals(token_in, &self.provider).await?;
+        let decimals_out = self.cache.token_decimals(token_out, &self.provider).await?;
+
+        let selected = self
+            .best_quote_exact_in(pair, token_in, token_out, to_units(amount_in, decimals_in as u32)?, None)
+            .await?;
+        // QuoterV2 reports where the swap leaves the pool; its impact is the
+        // move from the pool's current price
+        let price_impact_bps = match selected.quote.sqrt_price_x96_after {
+            Some(sqrt_price_x96_after) => {
+                let pool_address = self.pool_address(pair, selected.fee_tier).await?;
+                let sqrt_price_x96 = self.pool_sqrt_price_x96(pool_address, None).await?;
+                price_move_bps(sqrt_price_x96, sqrt_price_x96_after)
+            }
+            None => None,
+        };
+        if let Some(price_after) = self.price_after(pair, &selected.quote).await? {
+            log::debug!(
+                "Uniswap V3 {:?} {} on tier {} leaves {} at {} ({:?} bps impact, swap gas estimate {:?})",
+                side, amount_in, selected.fee_tier, pair.symbol, price_after, price_impact_bps, selected.quote.gas_estimate
+            );
+        }
+        Ok(SwapQuote {
+            amount_out: from_units(selected.quote.amount_out, decimals_out as u32)?,
+            fee_tier: Some(selected.fee_tier),
+            gas_estimate: selected.quote.gas_estimate,
+            price_impact_bps,
+        })
+    }
+
+    /// TWAP and mid price of the pool the pair's latest price was quoted on,
+    /// or of the first configured fee tier that has a pool before any quote
+    async fn get_twap(&self, pair: &TokenPair, window_seconds: u32) -> Result<Option<TwapPrice>> {
+        let quoted_tier = self.quoted_tiers.lock().get(&pair.symbol).copied();
+        let pool_address = match quoted_tier {
+            Some(fee_tier) => self.pool_address(pair, fee_tier).await?,
+            None => {
+                let mut pool_address = None;
+                let mut last_error = None;
+                for &fee_tier in &self.fee_tiers {
+                    match self.pool_address(pair, fee_tier).await {
+                        Ok(address) => {
+                            pool_address = Some(address);
+                            break;
+                        }
+                        Err(e) => last_error = Some(e),
+                    }
+                }
+                pool_address.ok_or_else(|| last_error.unwrap_or_else(|| anyhow!("No Uniswap V3 fee tiers configured")))?
+            }
+        };
+        let pool = Contract::new(
+            pool_address,
+            serde_json::from_str::<Abi>(UNISWAP_V3_POOL_ABI)?,
+            self.provider.clone(),
+        );
+
+        let observe = pool.method::<_, (Vec<I256>, Vec<U256>)>("observe", vec![window_seconds, 0u32])?;
+        let (tick_cumulatives, _) = rpc_call(|| async {
+            observe.call().await.context("Failed to observe Uniswap V3 pool")
+        })
+        .await?;
+        let [then, now] = tick_cumulatives[..] else {
+            return Err(anyhow!("Unexpected observe response length"));
+        };
+        let average_tick = (now - then).as_i128() as f64 / f64::from(window_seconds);
+
+        let (token0, token1) = pool_tokens(pair)?;
+        let token0_decimals = self.cache.token_decimals(token0, &self.provider).await?;
+        let token1_decimals = self.cache.token_decimals(token1, &self.provider).await?;
+
+        let twap = tick_price(average_tick, token0_decimals, token1_decimals)?;
+        let sqrt_price_x96 = self.pool_sqrt_price_x96(pool_address, None).await?;
+        let mid = sqrt_price_x96_price(sqrt_price_x96, token0_decimals, token1_decimals)?;
+        Ok(Some(TwapPrice {
+            twap: orient_price(pair, token1, token0, twap)?,
+            mid: orient_price(pair, token1, token0, mid)?,
+        }))
+    }
+
+    /// Whether any configured fee tier has a pool for the pair
+    async fn supports_pair(&self, pair: &TokenPair) -> Result<bool> {
+        for &fee_tier in &self.fee_tiers {
+            let key = PoolKey::with_fee_tier(VENUE, pair, fee_tier);
+            if self.cache.pool_exists(key, || self.lookup_pool(fee_tier)).await? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Both tokens' decimals and every configured fee tier's pool, remembering
+    /// missing tiers so quotes skip them without a lookup
+    async fn warm_up(&self, pair: &TokenPair) -> Result<()> {
+        for token in [self.usdc_address, self.weth_address] {
+            self.cache.token_decimals(token, &self.provider).await?;
+        }
+        for &fee_tier in &self.fee_tiers {
+            let key = PoolKey::with_fee_tier(VENUE, pair, fee_tier);
+            self.cache.pool_exists(key, || self.lookup_pool(fee_tier)).await?;
+        }
+        Ok(())
+    }
+
+    /// Quote tokens held across the pools of every configured fee tier,
+    /// skipping tiers without one
+    async fn get_liquidity(&self, pair: &TokenPair) -> Result<Option<Decimal>> {
+        let quote = Address::from_str(pair.quote_token()).context("Invalid quote token address")?;
+        let token = Contract::new(quote, serde_json::from_str::<Abi>(ERC20_ABI)?, self.provider.clone());
+
+        let mut balance = U256::zero();
+        for &fee_tier in &self.fee_tiers {
+            let key = PoolKey::with_fee_tier(VENUE, pair, fee_tier);
+            if !self.cache.pool_exists(key, || self.lookup_pool(fee_tier)).await? {
+                continue;
+            }
+            let pool_address = self.pool_address(pair, fee_tier).await?;
+            let call = token.method::<_, U256>("balanceOf", pool_address)?;
+            balance += rpc_call(|| async { call.call().await.context("Failed to get Uniswap V3 pool balance") }).await?;
+        }
+
+        let decimals = self.cache.token_decimals(quote, &self.provider).await?;
+        from_units(balance, decimals as u32).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::mock_rpc::MockRpc;
+    use ethers::abi::{Token, encode};
+    use ethers::utils::id;
+    use rust_decimal_macros::dec;
+
+    const WETH: &str = "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619";
+    const USDC: &str = "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174";
+    /// ~2000 USDC per WETH, with USDC the pool's token0
+    const SQRT_PRICE_X96: u128 = 1_771_595_571_142_957_166_518_320_255_467_520;
+
+    fn tier(fee_tier: u32, amount_out: u64, liquidity: u128) -> TierQuote {
+        let quote = QuoteOutput { amount_out: U256::from(amount_out), sqrt_price_x96_after: None, gas_estimate: None };
+        TierQuote { fee_tier, quote, liquidity: Some(liquidity) }
+    }
+
+    #[test]
+    fn test_selects_deeper_tier_within_tolerance() {
+        // The 0.05% pool quotes 0.06% better but is a tenth as deep
+        let quotes = [tier(500, 1_000_000, 1_000), tier(3000, 999_400, 10_000), tier(10000, 990_000, 50_000)];
+
+        assert_eq!(select_tier(&quotes, Decimal::ZERO).unwrap().fee_tier, 500);
+        assert_eq!(select_tier(&quotes, dec!(0.001)).unwrap().fee_tier, 3000);
+        assert_eq!(select_tier(&quotes, dec!(0.01)).unwrap().fee_tier, 10000);
+        assert_eq!(select_tier(&quotes, dec!(0.0001)).unwrap().fee_tier, 500);
+
+        // Without liquidity data only the price counts
+        let unknown = [TierQuote { liquidity: None, ..quotes[0] }, TierQuote { liquidity: None, ..quotes[1] }];
+        assert_eq!(select_tier(&unknown, dec!(0.01)).unwrap().fee_tier, 500);
+        assert!(select_tier(&[], dec!(0.01)).is_none());
+    }
+
+    #[test]
+    fn test_decodes_quoter_v2_struct_call() {
+        let abi: Abi = serde_json::from_str(UNISWAP_V3_QUOTER_V2_ABI).unwrap();
+        let function = abi.function("quoteExactInputSingle").unwrap();
+        assert_eq!(
+            function.short_signature(),
+            ethers::utils::id("quoteExactInputSingle((address,address,uint256,uint24,uint160))")
+        );
+
+        // The params struct is one static tuple argument of five words
+        let params = (Address::repeat_byte(0x11), Address::repeat_byte(0x22), U256::exp10(9), 500u32, U256::zero());
+        let calldata = function.encode_input(&ethers::abi::Tokenize::into_tokens((params,))).unwrap();
+        assert_eq!(calldata.len(), 4 + 5 * 32);
+
+        let sqrt_price_after = U256::from(1_771_595_571_142_957_166_518_320_255_467_520_u128);
+        let returned = ethers::abi::encode(&[
+            Token::Uint(U256::from(499_000_000_000_000_000_u64)),
+            Token::Uint(sqrt_price_after),
+            Token::Uint(2.into()),
+            Token::Uint(96_500.into()),
+        ]);
+        let decoded = Detokenize::from_tokens(function.decode_output(&returned).unwrap()).unwrap();
+
+        assert_eq!(
+            QuoteOutput::from_v2(decoded),
+            QuoteOutput {
+                amount_out: U256::from(499_000_000_000_000_000_u64),
+                sqrt_price_x96_after: Some(sqrt_price_after),
+                gas_estimate: Some(96_500),
+            }
+        );
+    }
+
+    #[test]
+    fn test_sqrt_price_limit_from_slippage() {
+        // Price 1: a 1% move is sqrt(0.99) or sqrt(1 / 0.99) of the square root
+        let one = U256::one() << 96;
+        assert_eq!(sqrt_price_limit_x96(one, true, 100), U256::from_dec_str("78831026366734652303669917531").unwrap());
+        assert_eq!(sqrt_price_limit_x96(one, false, 100), U256::from_dec_str("79627299360338032629969613668").unwrap());
+
+        // ~500 USDC per WETH (USDC token0): squaring overflows 256 bits
+        let sqrt_price = U256::from(1_771_595_571_142_957_166_518_320_255_467_520_u128);
+        assert_eq!(
+            sqrt_price_limit_x96(sqrt_price, true, 50),
+            U256::from_dec_str("1767161032094945755930686888435936").unwrap()
+        );
+        assert_eq!(
+            sqrt_price_limit_x96(sqrt_price, false, 50),
+            U256::from_dec_str("1776041238286377644151444108980841").unwrap()
+        );
+
+        // Never past the ratios the pool accepts
+        let max = U256::from_dec_str(MAX_SQRT_RATIO).unwrap();
+        assert_eq!(sqrt_price_limit_x96(U256::from(MIN_SQRT_RATIO), true, 100), U256::from(MIN_SQRT_RATIO + 1));
+        assert_eq!(sqrt_price_limit_x96(max - 1, false, 100), max - 1);
+    }
+
+    #[test]
+    fn test_validates_fee_tiers() {
+        assert_eq!(validate_fee_tiers(&[500, 3000, 10000]).unwrap(), vec![500, 3000, 10000]);
+        assert_eq!(validate_fee_tiers(&[100, 3000, 100]).unwrap(), vec![100, 3000]);
+        assert!(validate_fee_tiers(&[]).is_err());
+
+        let err = validate_fee_tiers(&[500, 2500]).unwrap_err();
+        assert!(err.to_string().contains("Unsupported Uniswap V3 fee tier 2500"));
+    }
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: WETH.to_string(),
+            token1: USDC.to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: crate::models::PairBase::Token0,
+        }
+    }
+
+    fn slot0(sqrt_price: U256) -> Vec<u8> {
+        let mut slot0 = vec![Token::Uint(sqrt_price)];
+        slot0.extend([Token::Int(U256::zero()), Token::Uint(0.into()), Token::Uint(1.into())]);
+        slot0.extend([Token::Uint(1.into()), Token::Uint(0.into()), Token::Bool(true)]);
+        encode(&slot0)
+    }
+
+    /// Node with a factory at 0xfafa.. whose pool for each fee tier is
+    /// `pools(fee_tier)` (zero for none), the tokens' decimals, and every
+    /// pool's slot0 at `SQRT_PRICE_X96`. `respond` answers anything else,
+    /// and is asked first.
+    async fn rpc<P, R>(pools: P, respond: R) -> MockRpc
+    where
+        P: Fn(u32) -> Address + Send + Sync + 'static,
+        R: Fn(Address, &[u8]) -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        let usdc = Address::from_str(USDC).unwrap();
+        MockRpc::serve(move |to, data| {
+            if let Some(response) = respond(to, data) {
+                return response;
+            }
+            let selector = &data[..4];
+            if selector == id("slot0()") {
+                return slot0(U256::from(SQRT_PRICE_X96));
+            }
+            encode(&[if selector == id("factory()") {
+                Token::Address(Address::repeat_byte(0xfa))
+            } else if selector == id("getPool(address,address,uint24)") {
+                Token::Address(pools(U256::from_big_endian(&data[68..100]).as_u32()))
+            } else if selector == id("decimals()") {
+                Token::Uint(U256::from(if to == usdc { 6 } else { 18 }))
+            } else {
+                panic!("unexpected call to {:?}", to)
+            }])
+        })
+        .await
+    }
+
+    /// Client for a quoter at 0x0101.. on `rpc`, over these fee tiers
+    async fn client(rpc: &MockRpc, cache: Arc<PoolCache>, fee_tiers: &[u32]) -> UniswapV3Client {
+        let provider = Arc::new(Provider::<Http>::try_from(rpc.url.as_str()).unwrap());
+        let quoter = format!("{:?}", Address::repeat_byte(0x01));
+        UniswapV3Client::new(provider, &quoter, WETH, USDC, cache, None, fee_tiers).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_primes_decimals_and_fee_tier_pools() {
+        // Only the 0.05% tier has a pool
+        let pool = Address::repeat_byte(0x05);
+        let rpc = rpc(move |fee_tier| if fee_tier == 500 { pool } else { Address::zero() }, |_, _| None).await;
+        let cache = Arc::new(PoolCache::new().unwrap());
+        let client = client(&rpc, cache.clone(), &[500, 3000]).await;
+        let pair = pair();
+
+        // Both tokens' decimals are in the registry: only the factory and its pool for each tier
+        client.warm_up(&pair).await.unwrap();
+        assert_eq!(rpc.requests(), 4);
+
+        assert_eq!(client.pool_address(&pair, 500).await.unwrap(), pool);
+        let missing = client.pool_address(&pair, 3000).await.unwrap_err();
+        assert!(missing.to_string().contains("No Uniswap pool exists for WETH/USDC at fee tier 3000"));
+        assert!(client.supports_pair(&pair).await.unwrap());
+        let usdc = Address::from_str(USDC).unwrap();
+        assert_eq!(cache.token_decimals(usdc, &client.provider).await.unwrap(), 6);
+        client.warm_up(&pair).await.unwrap();
+        assert_eq!(rpc.requests(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_skips_fee_tiers_without_a_pool() {
+        // Only the 0.05% tier has a pool, holding 1.5M USDC
+        let pool = Address::repeat_byte(0x05);
+        let usdc = Address::from_str(USDC).unwrap();
+        let rpc = rpc(
+            move |fee_tier| if fee_tier == 500 { pool } else { Address::zero() },
+            move |to, data| {
+                (data[..4] == id("balanceOf(address)")).then(|| {
+                    assert_eq!((to, Address::from_slice(&data[16..36])), (usdc, pool));
+                    encode(&[Token::Uint(U256::from(1_500_000_000_000_u64))])
+                })
+            },
+        )
+        .await;
+        let client = client(&rpc, Arc::new(PoolCache::new().unwrap()), &[500, 3000, 10000]).await;
+
+        assert_eq!(client.get_liquidity(&pair()).await.unwrap(), Some(dec!(1500000)));
+    }
+
+    #[tokio::test]
+    async fn test_quotes_stop_at_the_slippage_price_limit() {
+        let rpc = rpc(
+            |_| Address::repeat_byte(0x05),
+            |_, data| {
+                (data[..4] == id("quoteExactInputSingle(address,address,uint24,uint256,uint160)")).then(|| {
+                    // USDC (token0) in pushes the price down, to at most 0.5% below
+                    assert_eq!(
+                        U256::from_big_endian(&data[132..164]),
+                        U256::from_dec_str("1767161032094945755930686888435936").unwrap()
+                    );
+                    encode(&[Token::Uint(U256::from(499_000_000_000_000_000_u64))])
+                })
+            },
+        )
+        .await;
+        let client = client(&rpc, Arc::new(PoolCache::new().unwrap()), &[500]).await.with_price_limit_bps(50);
+
+        let price = client.get_price_for_size(&pair(), dec!(1000)).await.unwrap();
+        assert_eq!(price.round_dp(6), dec!(2004.008016));
+    }
+
+    #[test]
+    fn test_price_move_from_square_root_prices() {
+        let before = U256::from(1_771_595_571_142_957_166_518_320_255_467_520_u128);
+        // The square root 0.5% lower is the price 0.9975% lower
+        let after = U256::from_dec_str("1762737593287242380685728654190182").unwrap();
+        assert_eq!(price_move_bps(before, after).unwrap().round_dp(6), dec!(99.75));
+        assert_eq!(price_move_bps(after, after).unwrap(), Decimal::ZERO);
+        assert_eq!(price_move_bps(U256::zero(), after), None);
+    }
+
+    #[tokio::test]
+    async fn test_quoter_v2_reports_gas_and_price_impact() {
+        let sqrt_price_after = U256::from_dec_str("1762737593287242380685728654190182").unwrap();
+        let rpc = rpc(
+            |_| Address::repeat_byte(0x05),
+            move |_, data| {
+                (data[..4] == id("quoteExactInputSingle((address,address,uint256,uint24,uint160))")).then(|| {
+                    encode(&[
+                        Token::Uint(U256::from(499_000_000_000_000_000_u64)),
+                        Token::Uint(sqrt_price_after),
+                        Token::Uint(1.into()),
+                        Token::Uint(96_500.into()),
+                    ])
+                })
+            },
+        )
+        .await;
+        let client = client(&rpc, Arc::new(PoolCache::new().unwrap()), &[500])
+            .await
+            .with_quoter_version(QuoterVersion::V2)
+            .unwrap();
+        // WETH listed first, though USDC is the pool's token0
+        let pair = pair();
+
+        let quote = client.quote_exact_input(&pair, TradeSide::Buy, dec!(1000)).await.unwrap();
+        assert_eq!(quote.amount_out, dec!(0.499));
+        assert_eq!(quote.fee_tier, Some(500));
+        assert_eq!(quote.gas_estimate, Some(96_500));
+        assert_eq!(quote.price_impact_bps.unwrap().round_dp(6), dec!(99.75));
+
+        let sqrt_price = U256::from(SQRT_PRICE_X96);
+        let output = QuoteOutput { amount_out: U256::zero(), sqrt_price_x96_after: Some(sqrt_price), gas_estimate: None };
+        let price = client.price_after(&pair, &output).await.unwrap().unwrap();
+        assert_eq!(price.round_dp(2), dec!(2000.00));
+    }
+
+    #[tokio::test]
+    async fn test_twap_reads_the_quoted_tier_next_to_its_mid() {
+        // The 0.3% tier quotes better, so it is the one priced
+        let (low_fee_pool, quoted_pool) = (Address::repeat_byte(0x05), Address::repeat_byte(0x30));
+        let rpc = rpc(
+            move |fee_tier| if fee_tier == 500 { low_fee_pool } else { quoted_pool },
+            move |to, data| {
+                let selector = &data[..4];
+                if selector == id("slot0()") {
+                    assert_eq!(to, quoted_pool);
+                    Some(slot0(U256::from(SQRT_PRICE_X96)))
+                } else if selector == id("observe(uint32[])") {
+                    assert_eq!(to, quoted_pool);
+                    // An average tick of 200311 over the 600s window
+                    let cumulatives = vec![Token::Int(U256::zero()), Token::Int(U256::from(200_311u64 * 600))];
+                    let liquidity = vec![Token::Uint(U256::zero()), Token::Uint(U256::zero())];
+                    Some(encode(&[Token::Array(cumulatives), Token::Array(liquidity)]))
+                } else if selector == id("quoteExactInputSingle(address,address,uint24,uint256,uint160)") {
+                    let fee_tier = U256::from_big_endian(&data[68..100]);
+                    let amount_out: u64 =
+                        if fee_tier == U256::from(500) { 498_000_000_000_000_000 } else { 499_000_000_000_000_000 };
+                    Some(encode(&[Token::Uint(U256::from(amount_out))]))
+                } else {
+                    None
+                }
+            },
+        )
+        .await;
+        let client = client(&rpc, Arc::new(PoolCache::new().unwrap()), &[500, 3000]).await;
+        let pair = pair();
+
+        client.get_price_for_size(&pair, dec!(1000)).await.unwrap();
+        let TwapPrice { twap, mid } = client.get_twap(&pair, 600).await.unwrap().unwrap();
+        assert_eq!(mid.round_dp(2), dec!(2000.00));
+        assert_eq!(twap.round_dp(2), dec!(2000.04));
+    }
 }
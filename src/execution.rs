@@ -0,0 +1,1486 @@
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use ethers::{
+    abi::{Abi, Token, Tokenize},
+    contract::{Contract, ContractCall, ContractError},
+    providers::{Http, Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, H256, TransactionReceipt, U256, transaction::eip2718::TypedTransaction},
+};
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::arbitrage::{PairThresholds, Thresholds};
+use crate::arming::{ExecutorArmingPolicy, ShadowTrade};
+use crate::config::Config;
+use crate::database::OpportunityStore;
+use crate::dex::{from_units, to_units};
+use crate::fills;
+use crate::dex::abi::{ARBITRAGE_EXECUTOR_ABI, SUSHISWAP_ROUTER_ABI, UNISWAP_V3_ROUTER_ABI};
+use crate::dex::cache::PoolCache;
+use crate::models::{ArbitrageOpportunity, BaseCurrency, ExecutionStatus, RouteKey};
+use crate::nonce::{NonceManager, bump_fees, is_nonce_error};
+use crate::ranker::{self, Balances, RankStrategy, Ranker};
+use crate::relay::TxSubmitter;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{metered_call, rpc_call, settlement_call};
+
+/// Uniswap V3 fee tier for opportunities that do not say which tier they
+/// were quoted on
+const DEFAULT_UNISWAP_FEE_TIER: u32 = 3000;
+
+/// The fee tier a Uniswap leg quoted on `fee_tier` trades through
+fn uniswap_fee_tier(fee_tier: Option<i32>) -> u32 {
+    fee_tier.and_then(|fee| u32::try_from(fee).ok()).unwrap_or(DEFAULT_UNISWAP_FEE_TIER)
+}
+
+/// How long a submitted swap stays valid before the router rejects it
+const SWAP_DEADLINE_SECS: i64 = 120;
+
+/// How long to wait for a transaction to be mined before bumping its fee
+const STUCK_TX_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Fee increase applied to each replacement of a stuck transaction
+const FEE_BUMP_PCT: u64 = 20;
+
+/// Number of same-nonce replacements to try before giving up
+const MAX_FEE_BUMPS: usize = 3;
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Standard deviations of recent price moves the adaptive slippage tolerates
+const SLIPPAGE_SIGMAS: u32 = 3;
+
+/// Why a mined transaction did not execute, for the trade's status
+#[derive(Debug, thiserror::Error)]
+pub enum TxFailure {
+    #[error("transaction {0:?} reverted")]
+    Reverted(H256),
+    #[error("transaction {0:?} was reorged out and dropped")]
+    Dropped(H256),
+}
+
+/// Whether a mined transaction reached its confirmations in time, with the
+/// receipt of the block it is buried in (which a reorg may have changed)
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Confirmation {
+    Confirmed(Box<TransactionReceipt>),
+    Pending,
+}
+
+/// A mined trade: its final transaction, and for sequential trades the buy
+/// leg's fills its sell leg is settled against
+struct SentTrade {
+    receipt: TransactionReceipt,
+    buy_fills: Option<Vec<fills::SwapFill>>,
+}
+
+/// A trade whose final transaction is mined but short of its confirmations,
+/// left for `Executor::finalize_pending`
+struct PendingTrade {
+    opportunity: ArbitrageOpportunity,
+    hash: H256,
+    buy_fills: Option<Vec<fills::SwapFill>>,
+}
+
+/// Router families the executor knows how to swap through. The discriminant is
+/// the `venueKind` passed to the arbitrage contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouterKind {
+    UniswapV2Like = 0,
+    UniswapV3 = 1,
+}
+
+/// One swap of an atomic route, mirroring the contract's `SwapLeg` struct
+#[derive(Debug, Clone)]
+struct SwapLeg {
+    router: Address,
+    kind: RouterKind,
+    fee: u32,
+    token_in: Address,
+    token_out: Address,
+    amount_out_min: U256,
+}
+
+impl SwapLeg {
+    fn into_token(self) -> Token {
+        Token::Tuple(vec![
+            Token::Address(self.router),
+            Token::Uint((self.kind as u8).into()),
+            Token::Uint(self.fee.into()),
+            Token::Address(self.token_in),
+            Token::Address(self.token_out),
+            Token::Uint(self.amount_out_min),
+        ])
+    }
+}
+
+/// Arguments to `executeArbitrage(tokenIn, amountIn, legs, minProfit, useFlashLoan)`
+fn route_tokens(
+    token_in: Address,
+    amount_in: U256,
+    legs: Vec<SwapLeg>,
+    min_profit: U256,
+    use_flash_loan: bool,
+) -> Vec<Token> {
+    let legs = Token::Array(legs.into_iter().map(SwapLeg::into_token).collect());
+    (token_in, amount_in, legs, min_profit, use_flash_loan).into_tokens()
+}
+
+/// Per-route lockout after a trade attempt, so a route is not traded again
+/// before the previous attempt can have confirmed (or its inventory settled)
+#[derive(Debug)]
+struct RouteCooldown {
+    cooldown: chrono::Duration,
+    last_attempt: HashMap<RouteKey, DateTime<Utc>>,
+}
+
+impl RouteCooldown {
+    fn new(cooldown: chrono::Duration) -> Self {
+        Self {
+            cooldown,
+            last_attempt: HashMap::new(),
+        }
+    }
+
+    /// Start an attempt on `route` at `now`, or return how much longer it
+    /// is locked out
+    fn try_start(&mut self, route: RouteKey, now: DateTime<Utc>) -> Result<(), chrono::Duration> {
+        if let Some(last) = self.last_attempt.get(&route) {
+            let ready_at = *last + self.cooldown;
+            if now < ready_at {
+                return Err(ready_at - now);
+            }
+        }
+        self.last_attempt.insert(route, now);
+        Ok(())
+    }
+}
+
+/// Slippage tolerance sized to a pair's recent volatility: `SLIPPAGE_SIGMAS`
+/// standard deviations of its cycle-to-cycle price moves, clamped to
+/// `[floor_bps, ceiling_bps]`. Tight in calm markets, where a loose
+/// tolerance only invites sandwiching; wide in volatile ones, where a tight
+/// one makes fills fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveSlippage {
+    pub floor_bps: u16,
+    pub ceiling_bps: u16,
+}
+
+impl AdaptiveSlippage {
+    /// Tolerance for a pair whose price moves `volatility_bps` per cycle
+    pub fn slippage_bps(&self, volatility_bps: Decimal) -> u16 {
+        let ceiling = self.ceiling_bps.max(self.floor_bps);
+        let tolerance = (volatility_bps * Decimal::from(SLIPPAGE_SIGMAS)).ceil();
+        u16::try_from(tolerance).unwrap_or(ceiling).clamp(self.floor_bps, ceiling)
+    }
+}
+
+/// Why an opportunity was held back from this cycle's executions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deferral {
+    /// A more profitable size on the same route is being traded
+    SameRoute,
+    /// `MAX_CONCURRENT_TRADES` more profitable trades are already planned
+    ConcurrencyLimit,
+    /// Its gas would take the cycle past `MAX_GAS_SPEND_PER_CYCLE`
+    GasBudget,
+    /// Higher-ranked trades already committed the capital its size needs
+    Capital,
+}
+
+impl fmt::Display for Deferral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Deferral::SameRoute => "a more profitable size on the same route is being traded",
+            Deferral::ConcurrencyLimit => "concurrent trade limit reached",
+            Deferral::GasBudget => "cycle gas budget exhausted",
+            Deferral::Capital => "not enough capital left for its size",
+        })
+    }
+}
+
+/// Pick which `candidates` (indices into `opportunities`) to execute, taking
+/// them in `ranker` order: one per route, at most `max_trades`, with their gas
+/// costs summing to no more than `gas_budget` and their trade sizes to no more
+/// than `capital` in each funding currency (each if set; currencies missing
+/// from `capital` are not limited). Returns the picked indices and every other
+/// candidate with the reason it was held back.
+fn plan_executions(
+    opportunities: &[ArbitrageOpportunity],
+    candidates: &[usize],
+    ranker: &dyn Ranker,
+    max_trades: usize,
+    gas_budget: Option<Decimal>,
+    capital: Option<&Balances>,
+) -> (Vec<usize>, Vec<(usize, Deferral)>) {
+    let mut picked: Vec<usize> = Vec::new();
+    let mut deferred = Vec::new();
+    let mut gas_spent = Decimal::ZERO;
+    let mut remaining = capital.cloned().unwrap_or_default();
+    for index in ranker.rank(opportunities, candidates) {
+        let opportunity = &opportunities[index];
+        let deferral = if picked.iter().any(|&other| opportunities[other].route_key() == opportunity.route_key()) {
+            Some(Deferral::SameRoute)
+        } else if picked.len() >= max_trades {
+            Some(Deferral::ConcurrencyLimit)
+        } else if gas_budget.is_some_and(|budget| gas_spent + opportunity.gas_cost > budget) {
+            Some(Deferral::GasBudget)
+        } else if remaining
+            .get(&opportunity.profit_currency)
+            .is_some_and(|available| opportunity.trade_amount > *available)
+        {
+            Some(Deferral::Capital)
+        } else {
+            None
+        };
+
+        match deferral {
+            Some(deferral) => deferred.push((index, deferral)),
+            None => {
+                gas_spent += opportunity.gas_cost;
+                if let Some(available) = remaining.get_mut(&opportunity.profit_currency) {
+                    *available -= opportunity.trade_amount;
+                }
+                picked.push(index);
+            }
+        }
+    }
+    (picked, deferred)
+}
+
+/// Executes arbitrage opportunities.
+///
+/// With `ARB_CONTRACT_ADDRESS` set, both swaps run inside a single call to the
+/// operator's arbitrage contract, which reverts unless the round trip is profitable.
+///
+/// Otherwise execution falls back to two sequential swaps, which is NOT atomic:
+/// the buy leg is mined before the sell leg is sent. If the sell leg reverts, is
+/// dropped, or the price moves in between, the wallet is left holding the WETH
+/// bought on the buy venue and the trade can realize a loss. Keep `TRADE_AMOUNT`
+/// to a size you are willing to hold as inventory.
+pub struct Executor {
+    provider: Arc<Provider<Http>>,
+    wallet: LocalWallet,
+    nonces: NonceManager,
+    submitter: TxSubmitter,
+    uniswap_router: Contract<Provider<Http>>,
+    sushiswap_router: Contract<Provider<Http>>,
+    arb_contract: Option<Contract<Provider<Http>>>,
+    use_flash_loan: bool,
+    weth_address: Address,
+    usdc_address: Address,
+    /// Token contracts and decimals
+    tokens: PoolCache,
+    /// Global net profit and slippage gates
+    thresholds: Thresholds,
+    /// Per-pair overrides of `thresholds`
+    pair_thresholds: Vec<PairThresholds>,
+    /// Replaces the fixed slippage tolerance for pairs with enough price history
+    adaptive_slippage: Option<AdaptiveSlippage>,
+    max_opportunity_age: Option<chrono::Duration>,
+    max_block_staleness: Option<u64>,
+    cooldown: Mutex<RouteCooldown>,
+    /// Blocks the final transaction must be buried under to count as executed
+    confirmations: u64,
+    max_concurrent_trades: usize,
+    max_gas_spend_per_cycle: Option<Decimal>,
+    ranker: Box<dyn Ranker>,
+    /// Whether trades are capped by the wallet's balances each cycle
+    capital_limited: bool,
+    /// Bounds trades in flight, across cycles too
+    in_flight: Semaphore,
+    /// Cleared when a warm-standby executor arms
+    dry_run: AtomicBool,
+    /// Trades mined but not yet `confirmations` deep, oldest first
+    pending: Mutex<Vec<PendingTrade>>,
+    /// Arms a dry-run executor once its dry-run trades have held up
+    arming: Option<Mutex<ExecutorArmingPolicy>>,
+    /// In manual arming mode, the operator's confirmation
+    arm_confirm_file: Option<PathBuf>,
+}
+
+impl Executor {
+    pub async fn new(config: &Config, private_key: &str) -> Result<Self> {
+        if config.read_only {
+            log::error!("Read-only mode: refusing to create a trade executor");
+            bail!("read-only mode forbids trade execution");
+        }
+        let provider = Arc::new(
+            Provider::<Http>::try_from(config.polygon_rpc_url.as_str())
+                .context("Failed to create HTTP provider")?
+        );
+
+        let chain_id = rpc_call(|| async {
+            provider.get_chainid().await.context("Failed to fetch chain id")
+        })
+        .await?;
+
+        let wallet = LocalWallet::from_str(private_key.trim_start_matches("0x"))
+            .context("Invalid WALLET_PRIVATE_KEY")?
+            .with_chain_id(chain_id.as_u64());
+
+        let uniswap_router = Contract::new(
+            Address::from_str(&config.uniswap_v3_router_address)
+                .context("Invalid Uniswap V3 router address")?,
+            serde_json::from_str::<Abi>(UNISWAP_V3_ROUTER_ABI).context("Failed to create Uniswap router contract")?,
+            provider.clone(),
+        );
+
+        let sushiswap_router = Contract::new(
+            Address::from_str(&config.sushiswap_router_address)
+                .context("Invalid SushiSwap router address")?,
+            serde_json::from_str::<Abi>(SUSHISWAP_ROUTER_ABI).context("Failed to create SushiSwap router contract")?,
+            provider.clone(),
+        );
+
+        let arb_contract = match &config.arb_contract_address {
+            Some(address) => Some(Contract::new(
+                Address::from_str(address).context("Invalid ARB_CONTRACT_ADDRESS")?,
+                serde_json::from_str::<Abi>(ARBITRAGE_EXECUTOR_ABI).context("Failed to create arbitrage contract")?,
+                provider.clone(),
+            )),
+            None => None,
+        };
+
+        log::info!(
+            "Executor ready for wallet {:?} ({}, {})",
+            wallet.address(),
+            if arb_contract.is_some() { "atomic" } else { "sequential" },
+            if config.dry_run { "dry run" } else { "LIVE" }
+        );
+        if config.dry_run && config.auto_arm_after_cycles > 0 {
+            log::info!(
+                "Warm standby: arming after {} cycles of confirmed dry-run trades ({:?})",
+                config.auto_arm_after_cycles, config.arm_mode
+            );
+        }
+
+        Ok(Self {
+            submitter: TxSubmitter::new(provider.clone(), config.private_relay_url.as_deref()),
+            provider,
+            nonces: NonceManager::new(wallet.address()),
+            wallet,
+            uniswap_router,
+            sushiswap_router,
+            arb_contract,
+            use_flash_loan: config.arb_contract_flash_loan,
+            weth_address: Address::from_str(&config.weth_address)?,
+            usdc_address: Address::from_str(&config.usdc_address)?,
+            tokens: PoolCache::new()?.with_decimals_overrides(&config.token_decimals),
+            thresholds: config.global_thresholds(),
+            pair_thresholds: config.pair_thresholds.clone(),
+            adaptive_slippage: config.adaptive_slippage.then_some(AdaptiveSlippage {
+                floor_bps: config.adaptive_slippage_floor_bps,
+                ceiling_bps: config.adaptive_slippage_ceiling_bps,
+            }),
+            max_opportunity_age: (config.max_opportunity_age_ms > 0)
+                .then(|| chrono::Duration::milliseconds(config.max_opportunity_age_ms as i64)),
+            max_block_staleness: (config.max_block_staleness > 0).then_some(config.max_block_staleness),
+            cooldown: Mutex::new(RouteCooldown::new(chrono::Duration::seconds(config.trade_cooldown_seconds as i64))),
+            confirmations: config.execution_confirmations,
+            max_concurrent_trades: config.max_concurrent_trades,
+            max_gas_spend_per_cycle: (config.max_gas_spend_per_cycle > Decimal::ZERO)
+                .then_some(config.max_gas_spend_per_cycle),
+            ranker: ranker::from_strategy(config.opportunity_ranker),
+            // Flash-loaned trades borrow their size instead of spending the wallet's
+            capital_limited: config.opportunity_ranker == RankStrategy::Capital && !config.arb_contract_flash_loan,
+            in_flight: Semaphore::new(config.max_concurrent_trades),
+            dry_run: AtomicBool::new(config.dry_run),
+            pending: Mutex::new(Vec::new()),
+            arming: (config.dry_run && config.auto_arm_after_cycles > 0).then(|| {
+                Mutex::new(ExecutorArmingPolicy::new(
+                    config.arm_mode,
+                    config.auto_arm_after_cycles,
+                    config.arm_max_quote_deviation_pct,
+                ))
+            }),
+            arm_confirm_file: config.arm_confirm_file.as_ref().map(PathBuf::from),
+        })
+    }
+
+    /// Choose which of this cycle's `candidates` to execute (see
+    /// `plan_executions`), recording the rest as skipped with the reason logged.
+    /// With the capital ranker the wallet's current balances cap the cycle's trades.
+    pub async fn plan_cycle(&self, opportunities: &mut [ArbitrageOpportunity], candidates: &[usize]) -> Vec<usize> {
+        let capital = if self.capital_limited {
+            match self.wallet_balances().await {
+                Ok(balances) => Some(balances),
+                Err(e) => {
+                    log::warn!("Failed to read wallet balances, planning without a capital limit: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        log::debug!("Ranking {} execution candidates by {}", candidates.len(), self.ranker.name());
+        let (picked, deferred) = plan_executions(
+            opportunities,
+            candidates,
+            self.ranker.as_ref(),
+            self.max_concurrent_trades,
+            self.max_gas_spend_per_cycle,
+            capital.as_ref(),
+        );
+        for (index, deferral) in deferred {
+            let opportunity = &mut opportunities[index];
+            log::info!(
+                "Not executing {} ({} -> {}, est. profit {} {}): {}",
+                opportunity.id,
+                opportunity.buy_dex,
+                opportunity.sell_dex,
+                opportunity.estimated_profit,
+                opportunity.profit_currency,
+                deferral
+            );
+            record(opportunity, ExecutionStatus::Skipped, None);
+        }
+        picked
+    }
+
+    /// Execute an opportunity, recording the outcome on its `execution_status`
+    /// and `realized_profit` fields. Waits while `MAX_CONCURRENT_TRADES` are
+    /// already in flight. `volatility_bps` is the pair's recent volatility,
+    /// for the adaptive slippage tolerance.
+    pub async fn execute(&self, opportunity: &mut ArbitrageOpportunity, volatility_bps: Option<Decimal>) -> Result<()> {
+        let _permit = self.in_flight.acquire().await.context("Executor is shut down")?;
+
+        // Both execution paths swap USDC -> WETH -> USDC
+        if opportunity.profit_currency != BaseCurrency::Usdc.as_str() {
+            log::warn!(
+                "Not executing {}: {}-funded round trips are quote-only",
+                opportunity.id, opportunity.profit_currency
+            );
+            record(opportunity, ExecutionStatus::Skipped, None);
+            return Ok(());
+        }
+
+        let thresholds = self.thresholds(&opportunity.token_pair);
+        if opportunity.estimated_profit <= Decimal::ZERO
+            || opportunity.estimated_profit < thresholds.min_net_profit
+        {
+            log::info!(
+                "Not executing {}: net profit {} USDC below minimum {} USDC",
+                opportunity.id, opportunity.estimated_profit, thresholds.min_net_profit
+            );
+            record(opportunity, ExecutionStatus::Skipped, None);
+            return Ok(());
+        }
+
+        if let Some(max_age) = self.max_opportunity_age
+            && is_expired(opportunity.timestamp, Utc::now(), max_age)
+        {
+            log::warn!(
+                "Abandoning {}: detected {} ms ago, older than the {} ms limit",
+                opportunity.id,
+                (Utc::now() - opportunity.timestamp).num_milliseconds(),
+                max_age.num_milliseconds()
+            );
+            record(opportunity, ExecutionStatus::Expired, None);
+            return Ok(());
+        }
+
+        if let Some(max_blocks) = self.max_block_staleness
+            && let Some(block) = opportunity.block_number
+        {
+            match stale_by(self.provider.as_ref(), block as u64, max_blocks).await {
+                Ok(None) => {}
+                Ok(Some(behind)) => {
+                    log::warn!(
+                        "Abandoning {}: detected at block {}, {} blocks behind the chain (limit {})",
+                        opportunity.id, block, behind, max_blocks
+                    );
+                    record(opportunity, ExecutionStatus::StaleBlock, None);
+                    return Ok(());
+                }
+                Err(e) => {
+                    record(opportunity, ExecutionStatus::Failed, None);
+                    return Err(e.context("Failed to check block staleness"));
+                }
+            }
+        }
+
+        if let Err(remaining) = self.cooldown.lock().try_start(opportunity.route_key(), Utc::now()) {
+            log::info!(
+                "Not executing {}: route {} -> {} is cooling down for another {} s",
+                opportunity.id,
+                opportunity.buy_dex,
+                opportunity.sell_dex,
+                remaining.num_seconds().max(1)
+            );
+            record(opportunity, ExecutionStatus::CoolingDown, None);
+            return Ok(());
+        }
+
+        if self.is_dry_run() {
+            log::info!(
+                "[dry run] Would buy WETH with {} USDC on {} at {} and sell on {} at {}",
+                opportunity.trade_amount,
+                opportunity.buy_dex,
+                opportunity.buy_price,
+                opportunity.sell_dex,
+                opportunity.sell_price
+            );
+            record(opportunity, ExecutionStatus::DryRun, None);
+            return Ok(());
+        }
+
+        let slippage_bps = self.slippage_bps(thresholds.max_slippage_bps, volatility_bps);
+        log::debug!("Executing {} with {} bps slippage tolerance", opportunity.id, slippage_bps);
+        let result = match &self.arb_contract {
+            Some(contract) => self.execute_atomic(contract, opportunity, slippage_bps).await,
+            None => self.execute_sequential(opportunity, slippage_bps).await,
+        };
+
+        let sent = match result {
+            Ok(sent) => sent,
+            Err(e) => {
+                record(opportunity, failure_status(&e), None);
+                return Err(e);
+            }
+        };
+
+        // Its own block is the first confirmation; deeper ones are awaited in
+        // the background so the scan loop is not held up
+        if self.confirmations > 1 {
+            log::info!(
+                "Trade {} mined in {:?}, pending until {} blocks deep",
+                opportunity.id, sent.receipt.transaction_hash, self.confirmations
+            );
+            record(opportunity, ExecutionStatus::Pending, None);
+            self.pending.lock().push(PendingTrade {
+                opportunity: opportunity.clone(),
+                hash: sent.receipt.transaction_hash,
+                buy_fills: sent.buy_fills,
+            });
+            return Ok(());
+        }
+
+        match self.settle(opportunity, &sent.receipt, sent.buy_fills.as_deref()).await {
+            Ok(realized_profit) => {
+                log::info!(
+                    "Executed {}: realized {} USDC (estimated {} USDC)",
+                    opportunity.id, realized_profit, opportunity.estimated_profit
+                );
+                record(opportunity, ExecutionStatus::Executed, Some(realized_profit));
+                Ok(())
+            }
+            Err(e) => {
+                record(opportunity, ExecutionStatus::Failed, None);
+                Err(e)
+            }
+        }
+    }
+
+    /// Settle every pending trade whose final transaction is now
+    /// `confirmations` deep, or was reverted or dropped by a reorg, writing
+    /// its outcome to `store`. Returns how many were settled; the rest stay
+    /// queued for the next call.
+    pub async fn finalize_pending(&self, store: &dyn OpportunityStore) -> Result<usize> {
+        let queued = std::mem::take(&mut *self.pending.lock());
+        let mut finalized = 0;
+
+        for trade in queued {
+            let PendingTrade { mut opportunity, hash, buy_fills } = trade;
+            let confirmation =
+                await_confirmations(self.provider.as_ref(), hash, self.confirmations, Duration::ZERO).await;
+            match confirmation {
+                Ok(Confirmation::Pending) => {
+                    self.pending.lock().push(PendingTrade { opportunity, hash, buy_fills });
+                    continue;
+                }
+                Ok(Confirmation::Confirmed(receipt)) => {
+                    match self.settle(&opportunity, &receipt, buy_fills.as_deref()).await {
+                        Ok(realized_profit) => {
+                            log::info!(
+                                "Executed {}: realized {} USDC (estimated {} USDC) after {} confirmations",
+                                opportunity.id, realized_profit, opportunity.estimated_profit, self.confirmations
+                            );
+                            record(&mut opportunity, ExecutionStatus::Executed, Some(realized_profit));
+                        }
+                        Err(e) => {
+                            log::error!("Failed to settle confirmed trade {}: {:#}", opportunity.id, e);
+                            record(&mut opportunity, ExecutionStatus::Failed, None);
+                        }
+                    }
+                }
+                Err(e) if e.downcast_ref::<TxFailure>().is_some() => {
+                    log::warn!("Pending trade {} did not confirm: {:#}", opportunity.id, e);
+                    record(&mut opportunity, failure_status(&e), None);
+                }
+                Err(e) => {
+                    // Retry on the next call rather than guessing
+                    log::warn!("Failed to check pending trade {}: {:#}", opportunity.id, e);
+                    self.pending.lock().push(PendingTrade { opportunity, hash, buy_fills });
+                    continue;
+                }
+            }
+
+            store.update_execution(&opportunity).await
+                .context("Failed to record finalized trade")?;
+            finalized += 1;
+        }
+
+        Ok(finalized)
+    }
+
+    /// Run `finalize_pending` every `interval` until the task is aborted
+    pub async fn watch_pending(self: Arc<Self>, store: Arc<dyn OpportunityStore>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = self.finalize_pending(store.as_ref()).await {
+                log::warn!("Finalizing pending trades failed: {:#}", e);
+            }
+        }
+    }
+
+    /// Realized profit in USDC of a mined trade, from what its final
+    /// transaction's `receipt` (and a sequential trade's buy leg) actually
+    /// filled. Gas is paid in MATIC; the same USDC estimate the opportunity
+    /// used is subtracted so realized and estimated profit are directly
+    /// comparable.
+    async fn settle(
+        &self,
+        opportunity: &ArbitrageOpportunity,
+        receipt: &TransactionReceipt,
+        buy_fills: Option<&[fills::SwapFill]>,
+    ) -> Result<Decimal> {
+        let owner = self.wallet.address();
+        let usdc_decimals = self.token_decimals(self.usdc_address).await?;
+
+        let Some(buy_fills) = buy_fills else {
+            // The pools pay the contract; what reached the wallet is what it made
+            let received = fills::net_transfers(receipt, self.usdc_address, owner, usdc_decimals)?;
+            return Ok(received - opportunity.gas_cost);
+        };
+
+        let mut fills = buy_fills.to_vec();
+        fills.extend(fills::decode_swaps(receipt, owner, (self.usdc_address, self.weth_address))?);
+        if fills.len() != 2 {
+            bail!("Expected a swap event per leg, decoded {} from the receipts", fills.len());
+        }
+        fills::realized_profit(&fills, self.usdc_address, usdc_decimals, opportunity.gas_cost)
+    }
+
+    /// Buy on the cheap venue, then sell what was received on the expensive one
+    async fn execute_sequential(&self, opportunity: &ArbitrageOpportunity, slippage_bps: u16) -> Result<SentTrade> {
+        let owner = self.wallet.address();
+        let usdc_decimals = self.token_decimals(self.usdc_address).await?;
+        let weth_decimals = self.token_decimals(self.weth_address).await?;
+
+        // Buy leg: USDC -> WETH on the cheaper venue
+        let usdc_in = to_units(opportunity.trade_amount, usdc_decimals)?;
+        let expected_weth = opportunity.trade_amount / opportunity.buy_price;
+        let min_weth = to_units(apply_slippage(expected_weth, slippage_bps)?, weth_decimals)?;
+
+        let bought = self
+            .swap(&opportunity.buy_dex, opportunity.buy_fee_tier, self.usdc_address, self.weth_address, usdc_in, min_weth)
+            .await
+            .with_context(|| format!("Buy leg on {} failed", opportunity.buy_dex))?;
+
+        // Read from the buy leg's own swap event rather than a balance
+        // difference, which trades running concurrently from this wallet
+        // would skew
+        let tokens = (self.usdc_address, self.weth_address);
+        let buy_fills = fills::decode_swaps(&bought, owner, tokens)?;
+        let [buy_fill] = buy_fills.as_slice() else {
+            bail!("Expected one swap event in the buy leg's receipt, decoded {}", buy_fills.len());
+        };
+        let weth_received = buy_fill.amount_out;
+        let weth_received_human = from_units(weth_received, weth_decimals)?;
+
+        // Sell leg: WETH -> USDC on the more expensive venue
+        let expected_usdc = weth_received_human * opportunity.sell_price;
+        let min_usdc = to_units(apply_slippage(expected_usdc, slippage_bps)?, usdc_decimals)?;
+
+        let sold = self
+            .swap(&opportunity.sell_dex, opportunity.sell_fee_tier, self.weth_address, self.usdc_address, weth_received, min_usdc)
+            .await
+            .with_context(|| format!(
+                "Sell leg on {} failed, wallet still holds {} WETH from the buy leg",
+                opportunity.sell_dex, weth_received_human
+            ))?;
+        Ok(SentTrade { receipt: sold, buy_fills: Some(buy_fills) })
+    }
+
+    /// Run both legs in one transaction through the arbitrage contract. The call is
+    /// simulated first, after any approval it needs, so an unprofitable route
+    /// reverts for free instead of on-chain.
+    async fn execute_atomic(
+        &self,
+        contract: &Contract<Provider<Http>>,
+        opportunity: &ArbitrageOpportunity,
+        slippage_bps: u16,
+    ) -> Result<SentTrade> {
+        let (call, amount_in) = self.atomic_call(contract, opportunity, slippage_bps).await?;
+
+        // The contract pulls the input with transferFrom, so the simulation
+        // reverts until it is approved
+        if !self.use_flash_loan {
+            self.ensure_allowance(self.usdc_address, contract.address(), amount_in).await?;
+        }
+
+        if let Err(e) = call.call().await {
+            return Err(describe_revert(e));
+        }
+
+        let receipt = self.send(call.tx)
+            .await
+            .context("Atomic arbitrage transaction failed")?;
+        Ok(SentTrade { receipt, buy_fills: None })
+    }
+
+    /// The `executeArbitrage` call for `opportunity` from the wallet, and
+    /// the USDC it puts in (raw units)
+    async fn atomic_call(
+        &self,
+        contract: &Contract<Provider<Http>>,
+        opportunity: &ArbitrageOpportunity,
+        slippage_bps: u16,
+    ) -> Result<(ContractCall<Provider<Http>, U256>, U256)> {
+        let usdc_decimals = self.token_decimals(self.usdc_address).await?;
+        let weth_decimals = self.token_decimals(self.weth_address).await?;
+
+        let amount_in = to_units(opportunity.trade_amount, usdc_decimals)?;
+        let expected_weth = opportunity.trade_amount / opportunity.buy_price;
+        let min_weth = to_units(apply_slippage(expected_weth, slippage_bps)?, weth_decimals)?;
+        let min_usdc = to_units(apply_slippage(expected_weth * opportunity.sell_price, slippage_bps)?, usdc_decimals)?;
+
+        // Gas is paid in MATIC outside the contract, so require the on-chain gross
+        // profit to cover the gas estimate plus the configured minimum net profit
+        let min_profit = to_units(
+            opportunity.gas_cost + self.thresholds(&opportunity.token_pair).min_net_profit.max(Decimal::ZERO),
+            usdc_decimals,
+        )?;
+
+        let legs = vec![
+            self.swap_leg(&opportunity.buy_dex, opportunity.buy_fee_tier, self.usdc_address, self.weth_address, min_weth)?,
+            self.swap_leg(&opportunity.sell_dex, opportunity.sell_fee_tier, self.weth_address, self.usdc_address, min_usdc)?,
+        ];
+        let args = route_tokens(self.usdc_address, amount_in, legs, min_profit, self.use_flash_loan);
+
+        let call = contract
+            .method::<_, U256>("executeArbitrage", args.as_slice())?
+            .from(self.wallet.address());
+        Ok((call, amount_in))
+    }
+
+    /// Net profit in USDC the trade would make at the latest block, found by
+    /// simulating it with `eth_call`: the arbitrage contract's
+    /// `executeArbitrage`, or each leg's router swap from the wallet in turn.
+    /// Nothing is sent, so a wallet that cannot fund or has not approved the
+    /// trade fails the simulation.
+    pub async fn simulate_profit(&self, opportunity: &ArbitrageOpportunity, volatility_bps: Option<Decimal>) -> Result<Decimal> {
+        let usdc_decimals = self.token_decimals(self.usdc_address).await?;
+        if let Some(contract) = &self.arb_contract {
+            let slippage_bps = self.slippage_bps(self.thresholds(&opportunity.token_pair).max_slippage_bps, volatility_bps);
+            let (call, _) = self.atomic_call(contract, opportunity, slippage_bps).await?;
+            let profit = match call.call().await {
+                Ok(profit) => profit,
+                Err(e) => return Err(describe_revert(e)),
+            };
+            return Ok(from_units(profit, usdc_decimals)? - opportunity.gas_cost);
+        }
+
+        let usdc_in = to_units(opportunity.trade_amount, usdc_decimals)?;
+        let weth_out = self
+            .simulate_swap(&opportunity.buy_dex, opportunity.buy_fee_tier, self.usdc_address, self.weth_address, usdc_in)
+            .await
+            .with_context(|| format!("Simulated buy leg on {} failed", opportunity.buy_dex))?;
+        let usdc_out = self
+            .simulate_swap(&opportunity.sell_dex, opportunity.sell_fee_tier, self.weth_address, self.usdc_address, weth_out)
+            .await
+            .with_context(|| format!("Simulated sell leg on {} failed", opportunity.sell_dex))?;
+        Ok(from_units(usdc_out, usdc_decimals)? - opportunity.trade_amount - opportunity.gas_cost)
+    }
+
+    fn router_for(&self, dex: &str) -> Result<(Address, RouterKind)> {
+        match dex {
+            "Uniswap" => Ok((self.uniswap_router.address(), RouterKind::UniswapV3)),
+            "SushiSwap" => Ok((self.sushiswap_router.address(), RouterKind::UniswapV2Like)),
+            other => bail!("No router configured for venue {}", other),
+        }
+    }
+
+    /// A leg through `dex`, on the Uniswap V3 pool of `fee_tier` when the
+    /// venue is Uniswap, the tier the leg was quoted on
+    fn swap_leg(
+        &self,
+        dex: &str,
+        fee_tier: Option<i32>,
+        token_in: Address,
+        token_out: Address,
+        amount_out_min: U256,
+    ) -> Result<SwapLeg> {
+        let (router, kind) = self.router_for(dex)?;
+        Ok(SwapLeg {
+            router,
+            kind,
+            fee: if kind == RouterKind::UniswapV3 { uniswap_fee_tier(fee_tier) } else { 0 },
+            token_in,
+            token_out,
+            amount_out_min,
+        })
+    }
+
+    async fn swap(
+        &self,
+        dex: &str,
+        fee_tier: Option<i32>,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+    ) -> Result<TransactionReceipt> {
+        let (router, _, tx) = self.swap_tx(dex, fee_tier, token_in, token_out, amount_in, min_amount_out)?;
+        self.ensure_allowance(token_in, router, amount_in).await?;
+        self.send(tx).await
+    }
+
+    /// What swapping exactly `amount_in` on `dex` from the wallet returns at
+    /// the latest block, by `eth_call`
+    async fn simulate_swap(
+        &self,
+        dex: &str,
+        fee_tier: Option<i32>,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+    ) -> Result<U256> {
+        let (_, kind, mut tx) = self.swap_tx(dex, fee_tier, token_in, token_out, amount_in, U256::zero())?;
+        tx.set_from(self.wallet.address());
+        let output = rpc_call(|| async { self.provider.call(&tx, None).await.context("Swap simulation reverted") }).await?;
+
+        match kind {
+            RouterKind::UniswapV3 => Ok(self.uniswap_router.decode_output::<U256, _>("exactInputSingle", output)?),
+            RouterKind::UniswapV2Like => {
+                let amounts = self.sushiswap_router.decode_output::<Vec<U256>, _>("swapExactTokensForTokens", output)?;
+                amounts.last().copied().context("Swap simulation returned no amounts")
+            }
+        }
+    }
+
+    /// The router, its kind and the transaction swapping exactly `amount_in`
+    /// on `dex` into the wallet
+    fn swap_tx(
+        &self,
+        dex: &str,
+        fee_tier: Option<i32>,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        min_amount_out: U256,
+    ) -> Result<(Address, RouterKind, TypedTransaction)> {
+        let recipient = self.wallet.address();
+        let deadline = U256::from((Utc::now().timestamp() + SWAP_DEADLINE_SECS) as u64);
+
+        match self.router_for(dex)? {
+            (router, RouterKind::UniswapV3) => {
+                let params = (
+                    token_in,
+                    token_out,
+                    uniswap_fee_tier(fee_tier),
+                    recipient,
+                    deadline,
+                    amount_in,
+                    min_amount_out,
+                    U256::zero(), // No price limit, amountOutMinimum bounds the fill
+                );
+                let call = self.uniswap_router
+                    .method::<_, U256>("exactInputSingle", (params,))?;
+                Ok((router, RouterKind::UniswapV3, call.tx))
+            }
+            (router, RouterKind::UniswapV2Like) => {
+                let path = vec![token_in, token_out];
+                let call = self.sushiswap_router
+                    .method::<_, Vec<U256>>(
+                        "swapExactTokensForTokens",
+                        (amount_in, min_amount_out, path, recipient, deadline),
+                    )?;
+                Ok((router, RouterKind::UniswapV2Like, call.tx))
+            }
+        }
+    }
+
+    async fn ensure_allowance(&self, token: Address, spender: Address, amount: U256) -> Result<()> {
+        let contract = self.erc20(token);
+        let call = contract.method::<_, U256>("allowance", (self.wallet.address(), spender))?;
+        let allowance: U256 = rpc_call(|| async {
+            call.call().await.context("Failed to read allowance")
+        })
+        .await?;
+
+        if allowance >= amount {
+            return Ok(());
+        }
+
+        log::info!("Approving {:?} to spend {:?}", spender, token);
+        let approve = contract.method::<_, bool>("approve", (spender, U256::MAX))?;
+        self.send(approve.tx).await.context("Approval failed")?;
+        Ok(())
+    }
+
+    /// Fill, sign locally and submit a transaction with a managed nonce, then wait
+    /// for it to be mined. A transaction that isn't mined within `STUCK_TX_TIMEOUT`
+    /// is replaced with the same nonce and a higher fee, up to `MAX_FEE_BUMPS` times.
+    /// The nonce is only reserved once the transaction is filled, and handed back
+    /// if the first submission fails, so a failed send leaves no gap.
+    async fn send(&self, mut tx: TypedTransaction) -> Result<TransactionReceipt> {
+        tx.set_from(self.wallet.address());
+        tx.set_chain_id(self.wallet.chain_id());
+
+        self.provider.fill_transaction(&mut tx, None).await
+            .context("Failed to fill transaction")?;
+        tx.set_nonce(self.nonces.next(self.provider.as_ref()).await?);
+
+        let mut submitted: Vec<H256> = Vec::new();
+
+        for attempt in 0..=MAX_FEE_BUMPS {
+            if attempt > 0 {
+                bump_fees(&mut tx, FEE_BUMP_PCT);
+                log::warn!(
+                    "Transaction {:?} not mined after {:?}, resubmitting with higher fee (attempt {}/{})",
+                    submitted.last(), STUCK_TX_TIMEOUT, attempt, MAX_FEE_BUMPS
+                );
+            }
+
+            let signature = match self.wallet.sign_transaction(&tx).await {
+                Ok(signature) => signature,
+                Err(e) => {
+                    if submitted.is_empty() {
+                        self.release_nonce().await;
+                    }
+                    return Err(anyhow::Error::new(e).context("Failed to sign transaction"));
+                }
+            };
+
+            match self.submitter.submit(tx.rlp_signed(&signature)).await {
+                Ok(tx_hash) => {
+                    log::info!("Submitted transaction {:?} (nonce {:?})", tx_hash, tx.nonce());
+                    submitted.push(tx_hash);
+                }
+                Err(e) => {
+                    // A replacement is rejected if an earlier submission was mined meanwhile
+                    if let Some(receipt) = self.find_receipt(&submitted).await? {
+                        return check_receipt(receipt);
+                    }
+                    if submitted.is_empty() || is_nonce_error(&e) {
+                        self.release_nonce().await;
+                    }
+                    return Err(e);
+                }
+            }
+
+            if let Some(receipt) = self.wait_for_receipt(&submitted, STUCK_TX_TIMEOUT).await? {
+                return check_receipt(receipt);
+            }
+        }
+
+        bail!("Transaction with nonce {:?} still not mined after {} fee bumps", tx.nonce(), MAX_FEE_BUMPS)
+    }
+
+    /// Resync the nonce counter after a nonce was reserved but never used, or
+    /// the node disagreed with it
+    async fn release_nonce(&self) {
+        if let Err(e) = self.nonces.reset(self.provider.as_ref()).await {
+            log::warn!("Failed to resync the nonce after a failed send: {:#}", e);
+        }
+    }
+
+    /// Poll until any of the submitted hashes (all sharing one nonce) is mined
+    async fn wait_for_receipt(&self, hashes: &[H256], timeout: Duration) -> Result<Option<TransactionReceipt>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        while tokio::time::Instant::now() < deadline {
+            if let Some(receipt) = self.find_receipt(hashes).await? {
+                return Ok(Some(receipt));
+            }
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+
+        Ok(None)
+    }
+
+    async fn find_receipt(&self, hashes: &[H256]) -> Result<Option<TransactionReceipt>> {
+        for hash in hashes {
+            let receipt = settlement_call(RpcCallKind::Call, || async {
+                self.provider.get_transaction_receipt(*hash).await
+                    .context("Failed to fetch transaction receipt")
+            })
+            .await?;
+            if let Some(receipt) = receipt {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decimals of `token`, resolved once the way the venues resolve them:
+    /// the token registry, then `TOKEN_DECIMALS`, then `decimals()`
+    async fn token_decimals(&self, token: Address) -> Result<u32> {
+        Ok(self.tokens.token_decimals(token, &self.provider).await? as u32)
+    }
+
+    /// The wallet's USDC and WETH, keyed by currency name
+    async fn wallet_balances(&self) -> Result<Balances> {
+        let owner = self.wallet.address();
+        let mut balances = Balances::new();
+        for (currency, token) in [(BaseCurrency::Usdc, self.usdc_address), (BaseCurrency::Weth, self.weth_address)] {
+            let raw = self.balance_of(token, owner).await?;
+            let decimals = self.token_decimals(token).await?;
+            balances.insert(currency.as_str().to_string(), from_units(raw, decimals)?);
+        }
+        Ok(balances)
+    }
+
+    async fn balance_of(&self, token: Address, owner: Address) -> Result<U256> {
+        let call = self.erc20(token).method::<_, U256>("balanceOf", owner)?;
+        settlement_call(RpcCallKind::Call, || async { call.call().await.context("Failed to read token balance") }).await
+    }
+
+    fn erc20(&self, token: Address) -> Contract<Provider<Http>> {
+        self.tokens.token_contract(token, &self.provider)
+    }
+
+    /// Net profit and slippage gates for `pair`
+    fn thresholds(&self, pair: &str) -> Thresholds {
+        self.thresholds.for_pair(pair, &self.pair_thresholds)
+    }
+
+    /// Whether trades are only logged, not sent
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.load(Ordering::Relaxed)
+    }
+
+    /// Whether this is a dry run waiting to arm, which needs each cycle's
+    /// dry-run trades simulated (see `record_shadow_cycle`)
+    pub fn is_on_standby(&self) -> bool {
+        self.arming.is_some() && self.is_dry_run()
+    }
+
+    /// Feed one cycle's simulated dry-run trades to the arming policy, going
+    /// live once it is satisfied (and in manual mode, confirmed by the
+    /// operator creating `ARM_CONFIRM_FILE`)
+    pub fn record_shadow_cycle(&self, trades: &[ShadowTrade]) {
+        let Some(arming) = &self.arming else {
+            return;
+        };
+        if !self.is_dry_run() {
+            return;
+        }
+
+        let mut policy = arming.lock();
+        let was_satisfied = policy.is_satisfied();
+        policy.record_cycle(trades);
+        let confirmed = self.arm_confirm_file.as_ref().is_some_and(|file| file.exists());
+        if policy.arm(confirmed) {
+            self.dry_run.store(false, Ordering::Relaxed);
+            log::warn!(
+                "Executor armed after {} cycles of confirmed dry-run trades: trading LIVE from now on",
+                policy.streak()
+            );
+        } else if policy.is_satisfied() && !was_satisfied {
+            log::warn!(
+                "Dry-run trades confirmed for {} cycles; waiting for the operator to create {} to arm live trading",
+                policy.streak(),
+                self.arm_confirm_file.as_ref().map_or("ARM_CONFIRM_FILE".to_string(), |file| file.display().to_string())
+            );
+        } else if !trades.is_empty() {
+            log::info!("Warm standby: {}/{} confirmed dry-run cycles", policy.streak(), policy.after_cycles());
+        }
+    }
+
+    /// The adaptive tolerance for the pair's volatility when enabled and
+    /// known, otherwise the pair's fixed `max_slippage_bps`
+    fn slippage_bps(&self, max_slippage_bps: u16, volatility_bps: Option<Decimal>) -> u16 {
+        match (self.adaptive_slippage, volatility_bps) {
+            (Some(adaptive), Some(volatility_bps)) => adaptive.slippage_bps(volatility_bps),
+            _ => max_slippage_bps,
+        }
+    }
+}
+
+/// Minimum acceptable output for an expected amount under `slippage_bps`.
+/// Config rejects tolerances of 100% or more, which would make the minimum
+/// zero or negative; this refuses them in case one slips through anyway.
+fn apply_slippage(expected: Decimal, slippage_bps: u16) -> Result<Decimal> {
+    let factor = Decimal::ONE - Decimal::from(slippage_bps) / Decimal::from(10000);
+    if factor <= Decimal::ZERO || factor > Decimal::ONE {
+        bail!("Slippage of {} bps leaves a minimum output factor of {}, outside (0, 1]", slippage_bps, factor);
+    }
+    Ok(expected * factor)
+}
+
+fn check_receipt(receipt: TransactionReceipt) -> Result<TransactionReceipt> {
+    if receipt.status != Some(1u64.into()) {
+        return Err(TxFailure::Reverted(receipt.transaction_hash).into());
+    }
+    Ok(receipt)
+}
+
+/// Poll a mined transaction until it is `confirmations` blocks deep (its own
+/// block counting as the first), or `timeout` passes. A transaction reorged
+/// out is waited for while the node still knows it, since it may be mined
+/// again, and reported dropped once it is gone; re-mined, it may revert.
+async fn await_confirmations<M: Middleware>(
+    provider: &M,
+    hash: H256,
+    confirmations: u64,
+    timeout: Duration,
+) -> Result<Confirmation> {
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    loop {
+        let receipt = settlement_call(RpcCallKind::Call, || async {
+            provider.get_transaction_receipt(hash).await
+                .map_err(|e| anyhow!("{}", e))
+                .context("Failed to fetch transaction receipt")
+        })
+        .await?;
+
+        match receipt.map(check_receipt).transpose()? {
+            Some(receipt @ TransactionReceipt { block_number: Some(mined), .. }) => {
+                let current = settlement_call(RpcCallKind::Block, || async {
+                    provider.get_block_number().await.map_err(|e| anyhow!("{}", e)).context("Failed to get block number")
+                })
+                .await?;
+                let depth = current.as_u64().saturating_sub(mined.as_u64()) + 1;
+                if depth >= confirmations {
+                    return Ok(Confirmation::Confirmed(Box::new(receipt)));
+                }
+                log::debug!("Transaction {:?} is {}/{} blocks deep", hash, depth, confirmations);
+            }
+            Some(_) => {}
+            None => {
+                let known = settlement_call(RpcCallKind::Call, || async {
+                    provider.get_transaction(hash).await
+                        .map_err(|e| anyhow!("{}", e))
+                        .context("Failed to fetch transaction")
+                })
+                .await?;
+                if known.is_none() {
+                    return Err(TxFailure::Dropped(hash).into());
+                }
+                log::warn!("Transaction {:?} was reorged out, waiting for it to be mined again", hash);
+            }
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(Confirmation::Pending);
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+}
+
+/// Turn a failed simulation into an error carrying the contract's revert reason
+fn describe_revert(error: ContractError<Provider<Http>>) -> anyhow::Error {
+    if let Some(reason) = error.decode_revert::<String>() {
+        anyhow!("Arbitrage contract reverted: {}", reason)
+    } else if let Some(data) = error.as_revert() {
+        anyhow!("Arbitrage contract reverted without a reason (data: {})", data)
+    } else {
+        anyhow::Error::new(error).context("Failed to simulate arbitrage transaction")
+    }
+}
+
+/// Blocks the chain has moved past `block`, if more than `max_blocks`
+async fn stale_by<M: Middleware>(provider: &M, block: u64, max_blocks: u64) -> Result<Option<u64>> {
+    let current = metered_call(RpcCallKind::Block, || async {
+        provider.get_block_number().await.map_err(|e| anyhow!("{}", e)).context("Failed to get block number")
+    })
+    .await?;
+    let behind = current.as_u64().saturating_sub(block);
+    Ok((behind > max_blocks).then_some(behind))
+}
+
+/// Whether an opportunity detected at `detected` is older than `max_age` at `now`
+fn is_expired(detected: DateTime<Utc>, now: DateTime<Utc>, max_age: chrono::Duration) -> bool {
+    now - detected > max_age
+}
+
+/// Status of a trade that failed with `error`
+fn failure_status(error: &anyhow::Error) -> ExecutionStatus {
+    match error.downcast_ref::<TxFailure>() {
+        Some(TxFailure::Reverted(_)) => ExecutionStatus::Reverted,
+        Some(TxFailure::Dropped(_)) => ExecutionStatus::Dropped,
+        None => ExecutionStatus::Failed,
+    }
+}
+
+fn record(opportunity: &mut ArbitrageOpportunity, status: ExecutionStatus, realized_profit: Option<Decimal>) {
+    opportunity.execution_status = Some(status.as_str().to_string());
+    opportunity.realized_profit = realized_profit;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranker::{CapitalRanker, ProfitRanker};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_route_encoding_matches_contract_abi() {
+        let usdc = Address::repeat_byte(0x11);
+        let weth = Address::repeat_byte(0x22);
+        let v3_router = Address::repeat_byte(0x33);
+        let v2_router = Address::repeat_byte(0x44);
+
+        let legs = vec![
+            SwapLeg {
+                router: v3_router,
+                kind: RouterKind::UniswapV3,
+                fee: 3000,
+                token_in: usdc,
+                token_out: weth,
+                amount_out_min: U256::from(400_000_000_000_000_000u64),
+            },
+            SwapLeg {
+                router: v2_router,
+                kind: RouterKind::UniswapV2Like,
+                fee: 0,
+                token_in: weth,
+                token_out: usdc,
+                amount_out_min: U256::from(1_005_000_000u64),
+            },
+        ];
+        let tokens = route_tokens(usdc, U256::from(1_000_000_000u64), legs, U256::from(5_000_000u64), true);
+
+        let abi: Abi = serde_json::from_str(ARBITRAGE_EXECUTOR_ABI).unwrap();
+        let function = abi.function("executeArbitrage").unwrap();
+        let calldata = function.encode_input(&tokens).unwrap();
+        let decoded = function.decode_input(&calldata[4..]).unwrap();
+
+        assert_eq!(decoded, tokens);
+        match &decoded[2] {
+            Token::Array(legs) => {
+                assert_eq!(legs.len(), 2);
+                assert_eq!(
+                    legs[1],
+                    Token::Tuple(vec![
+                        Token::Address(v2_router),
+                        Token::Uint(0.into()),
+                        Token::Uint(0.into()),
+                        Token::Address(weth),
+                        Token::Address(usdc),
+                        Token::Uint(1_005_000_000u64.into()),
+                    ])
+                );
+            }
+            other => panic!("expected legs array, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_opportunity_expiry_boundary() {
+        let detected = Utc::now();
+        let max_age = chrono::Duration::milliseconds(5000);
+
+        assert!(!is_expired(detected, detected, max_age));
+        assert!(!is_expired(detected, detected + max_age, max_age));
+        assert!(is_expired(detected, detected + max_age + chrono::Duration::milliseconds(1), max_age));
+    }
+
+    fn opportunity(buy_dex: &str, sell_dex: &str, profit: Decimal, gas_cost: Decimal) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            buy_dex: buy_dex.to_string(),
+            sell_dex: sell_dex.to_string(),
+            estimated_profit: profit,
+            gas_cost,
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[test]
+    fn test_plans_most_profitable_trades_within_limits() {
+        let gas = Decimal::from(2);
+        let opportunities = [
+            opportunity("SushiSwap", "Uniswap", Decimal::from(4), gas),
+            opportunity("QuickSwap", "Uniswap", Decimal::from(9), gas),
+            opportunity("SushiSwap", "Uniswap", Decimal::from(6), gas), // larger size, same route
+            opportunity("Uniswap", "SushiSwap", Decimal::from(3), gas),
+            opportunity("QuickSwap", "SushiSwap", Decimal::from(5), gas),
+        ];
+        let all = [0, 1, 2, 3, 4];
+
+        let (picked, deferred) = plan_executions(&opportunities, &all, &ProfitRanker, 2, None, None);
+        assert_eq!(picked, [1, 2]);
+        assert_eq!(
+            deferred,
+            [(4, Deferral::ConcurrencyLimit), (0, Deferral::SameRoute), (3, Deferral::ConcurrencyLimit)]
+        );
+
+        // Only two trades' gas fits the budget
+        let (picked, deferred) = plan_executions(&opportunities, &all, &ProfitRanker, 10, Some(Decimal::from(5)), None);
+        assert_eq!(picked, [1, 2]);
+        assert_eq!(deferred[0], (4, Deferral::GasBudget));
+
+        // Non-candidates are never considered
+        let (picked, deferred) = plan_executions(&opportunities, &[0, 3], &ProfitRanker, 1, None, None);
+        assert_eq!(picked, [0]);
+        assert_eq!(deferred, [(3, Deferral::ConcurrencyLimit)]);
+    }
+
+    #[test]
+    fn test_capital_ranker_earns_more_from_limited_capital() {
+        let sized = |buy_dex: &str, size: i64, profit: i64| ArbitrageOpportunity {
+            trade_amount: Decimal::from(size),
+            ..opportunity(buy_dex, "Uniswap", Decimal::from(profit), Decimal::ONE)
+        };
+        let opportunities = [
+            sized("SushiSwap", 3000, 12),
+            sized("QuickSwap", 1000, 6),
+            sized("Balancer", 2000, 9),
+        ];
+        let all = [0, 1, 2];
+        let capital = Balances::from([("USDC".to_string(), Decimal::from(3000))]);
+        let total = |picked: &[usize]| picked.iter().map(|&i| opportunities[i].estimated_profit).sum::<Decimal>();
+
+        // The single most profitable trade ties up all the capital
+        let (picked, deferred) = plan_executions(&opportunities, &all, &ProfitRanker, 10, None, Some(&capital));
+        assert_eq!(picked, [0]);
+        assert_eq!(deferred, [(2, Deferral::Capital), (1, Deferral::Capital)]);
+        assert_eq!(total(&picked), Decimal::from(12));
+
+        // Two smaller trades earn more from the same 3000 USDC
+        let (picked, deferred) = plan_executions(&opportunities, &all, &CapitalRanker, 10, None, Some(&capital));
+        assert_eq!(picked, [1, 2]);
+        assert_eq!(deferred, [(0, Deferral::Capital)]);
+        assert_eq!(total(&picked), Decimal::from(15));
+
+        // Without a limit every route trades
+        let (picked, _) = plan_executions(&opportunities, &all, &CapitalRanker, 10, None, None);
+        assert_eq!(picked, [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_adaptive_slippage_follows_volatility() {
+        use crate::price_validator::PriceValidator;
+
+        let adaptive = AdaptiveSlippage { floor_bps: 10, ceiling_bps: 200 };
+        let slippage_after = |prices: &[Decimal]| {
+            let mut validator = PriceValidator::new();
+            for &price in prices {
+                validator.record_pair_price("WETH/USDC", price);
+            }
+            adaptive.slippage_bps(validator.volatility_bps("WETH/USDC").unwrap())
+        };
+
+        // Calm: a few bps per cycle stays near the floor
+        let stable = slippage_after(&[dec!(2000), dec!(2000.4), dec!(2000.1), dec!(2000.6), dec!(2000.2)]);
+        // Volatile: ~1% swings widen it
+        let volatile = slippage_after(&[dec!(2000), dec!(2020), dec!(1995), dec!(2025), dec!(1990)]);
+        assert_eq!(stable, 10);
+        assert!(volatile > 100 && volatile <= 200, "volatile slippage {}", volatile);
+
+        // Bounded on both sides
+        assert_eq!(adaptive.slippage_bps(Decimal::ZERO), 10);
+        assert_eq!(adaptive.slippage_bps(dec!(1000)), 200);
+        assert_eq!(apply_slippage(dec!(1000), 50).unwrap(), dec!(995));
+    }
+
+    #[test]
+    fn test_refuses_slippage_of_100_pct_or_more() {
+        assert_eq!(apply_slippage(dec!(1000), 0).unwrap(), dec!(1000));
+        assert_eq!(apply_slippage(dec!(1000), 9999).unwrap(), dec!(0.1));
+        assert!(apply_slippage(dec!(1000), 10000).is_err());
+        assert!(apply_slippage(dec!(1000), 15000).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_detects_stale_block() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(ethers::types::U64::from(54_000_010)).unwrap();
+        mock.push(ethers::types::U64::from(54_000_002)).unwrap();
+
+        assert_eq!(stale_by(&provider, 54_000_000, 3).await.unwrap(), None);
+        assert_eq!(stale_by(&provider, 54_000_000, 3).await.unwrap(), Some(10));
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(180);
+
+    fn mined_receipt(hash: H256, block: u64, status: u64) -> Option<TransactionReceipt> {
+        Some(TransactionReceipt {
+            transaction_hash: hash,
+            block_number: Some(block.into()),
+            status: Some(status.into()),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waits_for_confirmations() {
+        let hash = H256::repeat_byte(1);
+        let (provider, mock) = Provider::mocked();
+        // Responses pop last-pushed first: the third poll sees block 102, by
+        // when a reorg has moved the transaction to block 100 from 99
+        for (block, mined) in [(102u64, 100u64), (101, 100), (100, 99)] {
+            mock.push(ethers::types::U64::from(block)).unwrap();
+            mock.push(mined_receipt(hash, mined, 1)).unwrap();
+        }
+        let confirmation = await_confirmations(&provider, hash, 3, TIMEOUT).await.unwrap();
+        assert_eq!(confirmation, Confirmation::Confirmed(Box::new(mined_receipt(hash, 100, 1).unwrap())));
+
+        // Stuck short of its confirmations until the timeout
+        for _ in 0..3 {
+            mock.push(ethers::types::U64::from(100u64)).unwrap();
+            mock.push(mined_receipt(hash, 100, 1)).unwrap();
+        }
+        let confirmation = await_confirmations(&provider, hash, 3, RECEIPT_POLL_INTERVAL * 2).await.unwrap();
+        assert_eq!(confirmation, Confirmation::Pending);
+
+        // A zero timeout checks once, as the pending-trade finalizer does
+        mock.push(ethers::types::U64::from(100u64)).unwrap();
+        mock.push(mined_receipt(hash, 100, 1)).unwrap();
+        let confirmation = await_confirmations(&provider, hash, 3, Duration::ZERO).await.unwrap();
+        assert_eq!(confirmation, Confirmation::Pending);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reorged_transaction_is_dropped_or_reverts() {
+        let hash = H256::repeat_byte(2);
+        let (provider, mock) = Provider::mocked();
+        // Mined, then reorged out and forgotten by the node
+        mock.push(Option::<ethers::types::Transaction>::None).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        mock.push(ethers::types::U64::from(100u64)).unwrap();
+        mock.push(mined_receipt(hash, 100, 1)).unwrap();
+        let error = await_confirmations(&provider, hash, 3, TIMEOUT).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<TxFailure>(), Some(TxFailure::Dropped(_))));
+
+        // Reorged out, then re-mined in a block where it reverts
+        mock.push(mined_receipt(hash, 101, 0)).unwrap();
+        mock.push(Some(ethers::types::Transaction { hash, ..Default::default() })).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        let error = await_confirmations(&provider, hash, 3, TIMEOUT).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<TxFailure>(), Some(TxFailure::Reverted(_))));
+    }
+
+    #[test]
+    fn test_route_cooldown_locks_out_and_releases() {
+        let route = |buy: &str, sell: &str| (buy.to_string(), sell.to_string(), "WETH/USDC".to_string());
+        let start = Utc::now();
+        let seconds = chrono::Duration::seconds;
+        let mut cooldown = RouteCooldown::new(seconds(60));
+
+        assert!(cooldown.try_start(route("SushiSwap", "Uniswap"), start).is_ok());
+        assert_eq!(cooldown.try_start(route("SushiSwap", "Uniswap"), start + seconds(20)), Err(seconds(40)));
+        // Other routes, including the reverse direction, are independent
+        assert!(cooldown.try_start(route("Uniswap", "SushiSwap"), start + seconds(20)).is_ok());
+
+        // A locked-out opportunity does not extend the lockout
+        assert!(cooldown.try_start(route("SushiSwap", "Uniswap"), start + seconds(60)).is_ok());
+        assert!(cooldown.try_start(route("SushiSwap", "Uniswap"), start + seconds(119)).is_err());
+        assert!(cooldown.try_start(route("SushiSwap", "Uniswap"), start + seconds(120)).is_ok());
+    }
+}
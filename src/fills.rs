@@ -0,0 +1,259 @@
+use anyhow::{Context, Result, anyhow, bail};
+use ethers::{
+    abi::{ParamType, Token, decode},
+    types::{Address, H256, I256, Log, TransactionReceipt, U256},
+    utils::keccak256,
+};
+use rust_decimal::Decimal;
+
+use crate::dex::{VenueKind, from_units};
+
+const V2_SWAP_EVENT: &str = "Swap(address,uint256,uint256,uint256,uint256,address)";
+const V3_SWAP_EVENT: &str = "Swap(address,address,int256,int256,uint160,uint128,int24)";
+const TRANSFER_EVENT: &str = "Transfer(address,address,uint256)";
+
+/// Token amounts one swap actually moved, decoded from its pool's `Swap` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapFill {
+    pub pool: Address,
+    pub kind: VenueKind,
+    pub token_in: Address,
+    pub amount_in: U256,
+    pub token_out: Address,
+    pub amount_out: U256,
+}
+
+/// Venue kind whose pools emit `Swap` events with this signature topic
+fn swap_event_kind(topic: H256) -> Option<VenueKind> {
+    if topic == H256::from(keccak256(V2_SWAP_EVENT)) {
+        Some(VenueKind::UniswapV2Like)
+    } else if topic == H256::from(keccak256(V3_SWAP_EVENT)) {
+        Some(VenueKind::UniswapV3)
+    } else {
+        None
+    }
+}
+
+/// Every swap in `receipt` between `tokens` whose output went to `recipient`.
+/// Both V2 and V3 pools order their tokens by address, which says which of a
+/// pool's amounts belongs to which token; events of other kinds are skipped.
+pub fn decode_swaps(receipt: &TransactionReceipt, recipient: Address, tokens: (Address, Address)) -> Result<Vec<SwapFill>> {
+    let (token0, token1) = if tokens.0 < tokens.1 { tokens } else { (tokens.1, tokens.0) };
+    let mut fills = Vec::new();
+    for log in &receipt.logs {
+        let Some(kind) = log.topics.first().copied().and_then(swap_event_kind) else {
+            continue;
+        };
+        // Both events index the sender, then the recipient
+        if log.topics.get(2) != Some(&H256::from(recipient)) {
+            continue;
+        }
+        let (amount0_in, amount1_in, amount0_out, amount1_out) = pool_amounts(kind, log)
+            .with_context(|| format!("Invalid {:?} swap event from pool {:?}", kind, log.address))?;
+        let fill = |token_in, amount_in, token_out, amount_out| SwapFill {
+            pool: log.address,
+            kind,
+            token_in,
+            amount_in,
+            token_out,
+            amount_out,
+        };
+        fills.push(if amount0_in > U256::zero() {
+            fill(token0, amount0_in, token1, amount1_out)
+        } else {
+            fill(token1, amount1_in, token0, amount0_out)
+        });
+    }
+    Ok(fills)
+}
+
+/// `(amount0In, amount1In, amount0Out, amount1Out)` of a swap event
+fn pool_amounts(kind: VenueKind, log: &Log) -> Result<(U256, U256, U256, U256)> {
+    match kind {
+        VenueKind::UniswapV2Like => {
+            let amounts = decode(&vec![ParamType::Uint(256); 4], &log.data)?;
+            let [in0, in1, out0, out1] = amounts
+                .into_iter()
+                .map(|token| token.into_uint().ok_or_else(|| anyhow!("amount is not a uint")))
+                .collect::<Result<Vec<_>>>()?
+                .try_into()
+                .map_err(|_| anyhow!("expected four amounts"))?;
+            Ok((in0, in1, out0, out1))
+        }
+        VenueKind::UniswapV3 => {
+            // Signed pool deltas: positive flowed into the pool, negative out of it
+            let params = [
+                ParamType::Int(256),
+                ParamType::Int(256),
+                ParamType::Uint(160),
+                ParamType::Uint(128),
+                ParamType::Int(24),
+            ];
+            let amounts = decode(&params, &log.data)?;
+            let delta = |token: &Token| {
+                token.clone().into_int().map(I256::from_raw).ok_or_else(|| anyhow!("amount is not an int"))
+            };
+            let (amount0, amount1) = (delta(&amounts[0])?, delta(&amounts[1])?);
+            let split = |delta: I256| {
+                if delta.is_positive() {
+                    (delta.into_raw(), U256::zero())
+                } else {
+                    (U256::zero(), delta.unsigned_abs())
+                }
+            };
+            let ((in0, out0), (in1, out1)) = (split(amount0), split(amount1));
+            Ok((in0, in1, out0, out1))
+        }
+        VenueKind::BalancerV2 | VenueKind::CurveStable => bail!("no swap event decoding for {:?} pools", kind),
+    }
+}
+
+/// Quote tokens received minus quote tokens spent across `fills`, net of
+/// `gas_cost` (already in the quote token)
+pub fn realized_profit(fills: &[SwapFill], quote: Address, quote_decimals: u32, gas_cost: Decimal) -> Result<Decimal> {
+    let mut net = Decimal::ZERO;
+    for fill in fills {
+        if fill.token_out == quote {
+            net += from_units(fill.amount_out, quote_decimals)?;
+        }
+        if fill.token_in == quote {
+            net -= from_units(fill.amount_in, quote_decimals)?;
+        }
+    }
+    Ok(net - gas_cost)
+}
+
+/// `token` received by `owner` minus `token` sent by it in `receipt`, from
+/// the token's `Transfer` events. Sees what a contract route actually paid
+/// out, flash-loan fees included, where the pools' swaps only go to the contract.
+pub fn net_transfers(receipt: &TransactionReceipt, token: Address, owner: Address, decimals: u32) -> Result<Decimal> {
+    let transfer = H256::from(keccak256(TRANSFER_EVENT));
+    let owner = H256::from(owner);
+    let mut net = Decimal::ZERO;
+    for log in receipt.logs.iter().filter(|log| log.address == token && log.topics.first() == Some(&transfer)) {
+        let value = decode(&[ParamType::Uint(256)], &log.data)
+            .ok()
+            .and_then(|values| values.into_iter().next()?.into_uint())
+            .ok_or_else(|| anyhow!("Invalid transfer event from {:?}", token))?;
+        if log.topics.get(1) == Some(&owner) {
+            net -= from_units(value, decimals)?;
+        }
+        if log.topics.get(2) == Some(&owner) {
+            net += from_units(value, decimals)?;
+        }
+    }
+    Ok(net)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+    use rust_decimal_macros::dec;
+    use std::str::FromStr;
+
+    fn weth() -> Address {
+        Address::from_str("0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619").unwrap()
+    }
+
+    fn usdc() -> Address {
+        Address::from_str("0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174").unwrap()
+    }
+
+    fn swap_log(pool: Address, event: &str, recipient: Address, data: Vec<Token>) -> Log {
+        Log {
+            address: pool,
+            topics: vec![H256::from(keccak256(event)), H256::from(Address::repeat_byte(0xee)), H256::from(recipient)],
+            data: encode(&data).into(),
+            ..Default::default()
+        }
+    }
+
+    fn receipt(logs: Vec<Log>) -> TransactionReceipt {
+        TransactionReceipt { logs, ..Default::default() }
+    }
+
+    fn int(value: i64) -> Token {
+        Token::Int(I256::from(value).into_raw())
+    }
+
+    #[test]
+    fn test_decodes_v2_swap() {
+        // USDC (token0 by address) in, WETH out, plus someone else's swap and an unrelated event
+        let bot = Address::repeat_byte(0xb0);
+        let pool = Address::repeat_byte(0x02);
+        let uint = |value: u64| Token::Uint(U256::from(value));
+        let receipt = receipt(vec![
+            swap_log(pool, V2_SWAP_EVENT, bot, vec![uint(1_000_000_000), uint(0), uint(0), uint(499_750_000_000_000_000)]),
+            swap_log(pool, V2_SWAP_EVENT, Address::repeat_byte(0x01), vec![uint(5), uint(0), uint(0), uint(1)]),
+            Log { topics: vec![H256::from(keccak256("Sync(uint112,uint112)"))], ..Default::default() },
+        ]);
+
+        let fills = decode_swaps(&receipt, bot, (weth(), usdc())).unwrap();
+        assert_eq!(fills, vec![SwapFill {
+            pool,
+            kind: VenueKind::UniswapV2Like,
+            token_in: usdc(),
+            amount_in: U256::from(1_000_000_000u64),
+            token_out: weth(),
+            amount_out: U256::from(499_750_000_000_000_000u64),
+        }]);
+    }
+
+    #[test]
+    fn test_decodes_v3_swap_and_realized_profit() {
+        // Selling WETH (token1) for USDC on V3: USDC leaves the pool, so amount0 is negative
+        let bot = Address::repeat_byte(0xb0);
+        let pool = Address::repeat_byte(0x03);
+        let v3_data = vec![
+            int(-1_011_449_022),
+            int(499_750_000_000_000_000),
+            Token::Uint(U256::one() << 96),
+            Token::Uint(U256::from(10u64).pow(18.into())),
+            int(-200_000),
+        ];
+        let sell = receipt(vec![swap_log(pool, V3_SWAP_EVENT, bot, v3_data)]);
+
+        let sold = decode_swaps(&sell, bot, (usdc(), weth())).unwrap();
+        assert_eq!(sold, vec![SwapFill {
+            pool,
+            kind: VenueKind::UniswapV3,
+            token_in: weth(),
+            amount_in: U256::from(499_750_000_000_000_000u64),
+            token_out: usdc(),
+            amount_out: U256::from(1_011_449_022u64),
+        }]);
+
+        // Bought for 1000 USDC on a V2 pool, sold on V3, less 5 USDC of gas
+        let bought = SwapFill {
+            pool: Address::repeat_byte(0x02),
+            kind: VenueKind::UniswapV2Like,
+            token_in: usdc(),
+            amount_in: U256::from(1_000_000_000u64),
+            token_out: weth(),
+            amount_out: U256::from(499_750_000_000_000_000u64),
+        };
+        let fills = [bought, sold[0]];
+        assert_eq!(realized_profit(&fills, usdc(), 6, dec!(5)).unwrap(), dec!(6.449022));
+    }
+
+    #[test]
+    fn test_nets_transfers_of_one_token() {
+        let bot = Address::repeat_byte(0xb0);
+        let contract = Address::repeat_byte(0xc0);
+        let transfer = |token: Address, from: Address, to: Address, value: u64| Log {
+            address: token,
+            topics: vec![H256::from(keccak256(TRANSFER_EVENT)), H256::from(from), H256::from(to)],
+            data: encode(&[Token::Uint(U256::from(value))]).into(),
+            ..Default::default()
+        };
+        // The bot funds the contract route and is paid back the proceeds
+        let receipt = receipt(vec![
+            transfer(usdc(), bot, contract, 1_000_000_000),
+            transfer(weth(), contract, bot, 1),
+            transfer(usdc(), contract, bot, 1_011_449_022),
+        ]);
+        assert_eq!(net_transfers(&receipt, usdc(), bot, 6).unwrap(), dec!(11.449022));
+        assert_eq!(net_transfers(&receipt, usdc(), contract, 6).unwrap(), dec!(-11.449022));
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::{Context, Result, anyhow, bail};
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::U256,
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::dex::{VenueKind, from_units, to_units};
+use crate::util::{http_call, rpc_call};
+
+/// Where gas prices come from, in the order the fallback chain tries them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasSource {
+    /// `ESTIMATED_GAS_COST`, whatever the network is doing
+    Fixed,
+    /// The RPC's legacy `eth_gasPrice`
+    Rpc,
+    /// The RPC's EIP-1559 max fee estimate
+    Eip1559,
+    /// The Polygon gas station API's "standard" max fee
+    GasStation,
+}
+
+impl FromStr for GasSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fixed" => Ok(GasSource::Fixed),
+            "rpc" => Ok(GasSource::Rpc),
+            "eip1559" => Ok(GasSource::Eip1559),
+            "gas_station" => Ok(GasSource::GasStation),
+            other => Err(format!("unknown gas source {:?} (expected fixed, rpc, eip1559 or gas_station)", other)),
+        }
+    }
+}
+
+/// Gas a swap transaction spends outside the pool (its intrinsic cost),
+/// added to a quoter's estimate of the swap itself
+pub const SWAP_OVERHEAD_GAS_UNITS: u64 = 21_000;
+
+/// Gas used by one swap on each kind of venue, including its share of the
+/// transaction overhead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapGasUnits {
+    pub uniswap_v2: u64,
+    pub uniswap_v3: u64,
+    pub balancer_v2: u64,
+    pub curve_stable: u64,
+}
+
+impl SwapGasUnits {
+    pub fn swap(&self, kind: VenueKind) -> u64 {
+        match kind {
+            VenueKind::UniswapV2Like => self.uniswap_v2,
+            VenueKind::UniswapV3 => self.uniswap_v3,
+            VenueKind::BalancerV2 => self.balancer_v2,
+            VenueKind::CurveStable => self.curve_stable,
+        }
+    }
+
+    /// Gas used by one swap on a `kind` venue, from the quoter's own
+    /// estimate of the swap when it reported one
+    pub fn swap_or_estimate(&self, kind: VenueKind, estimate: Option<u64>) -> u64 {
+        estimate.map_or_else(|| self.swap(kind), |gas| gas + SWAP_OVERHEAD_GAS_UNITS)
+    }
+
+    /// Gas used by a round trip buying on a `buy` venue and selling on a `sell` one
+    pub fn route(&self, buy: VenueKind, sell: VenueKind) -> u64 {
+        self.swap(buy) + self.swap(sell)
+    }
+}
+
+/// Current USDC cost of a transaction
+#[async_trait]
+pub trait GasOracle: Send + Sync {
+    /// Source name used in logs
+    fn name(&self) -> &'static str;
+
+    async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal>;
+}
+
+/// USDC cost of `gas_units` at `gas_price` wei per unit, with the native
+/// token (POL) at `native_price_usdc`
+fn native_cost_usdc(gas_units: u64, gas_price: U256, native_price_usdc: Decimal) -> Result<Decimal> {
+    let wei = gas_price.checked_mul(U256::from(gas_units))
+        .ok_or_else(|| anyhow!("Gas cost overflows at {} wei per unit", gas_price))?;
+    Ok(from_units(wei, 18)? * native_price_usdc)
+}
+
+/// The configured flat estimate; never fails, so it ends every chain
+pub struct FixedGasOracle {
+    pub cost_usdc: Decimal,
+}
+
+#[async_trait]
+impl GasOracle for FixedGasOracle {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    async fn current_cost_usdc(&self, _gas_units: u64) -> Result<Decimal> {
+        Ok(self.cost_usdc)
+    }
+}
+
+pub struct RpcGasOracle {
+    provider: Arc<Provider<Http>>,
+    native_price_usdc: Decimal,
+}
+
+#[async_trait]
+impl GasOracle for RpcGasOracle {
+    fn name(&self) -> &'static str {
+        "rpc"
+    }
+
+    async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal> {
+        let gas_price = rpc_call(|| async {
+            self.provider.get_gas_price().await.context("Failed to get gas price")
+        })
+        .await?;
+        native_cost_usdc(gas_units, gas_price, self.native_price_usdc)
+    }
+}
+
+pub struct Eip1559GasOracle {
+    provider: Arc<Provider<Http>>,
+    native_price_usdc: Decimal,
+}
+
+#[async_trait]
+impl GasOracle for Eip1559GasOracle {
+    fn name(&self) -> &'static str {
+        "eip1559"
+    }
+
+    async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal> {
+        let (max_fee, _priority_fee) = rpc_call(|| async {
+            self.provider.estimate_eip1559_fees(None).await.context("Failed to estimate EIP-1559 fees")
+        })
+        .await?;
+        native_cost_usdc(gas_units, max_fee, self.native_price_usdc)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GasStationResponse {
+    standard: GasStationTier,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GasStationTier {
+    /// Gwei
+    max_fee: f64,
+}
+
+pub struct GasStationOracle {
+    client: reqwest::Client,
+    url: String,
+    native_price_usdc: Decimal,
+}
+
+#[async_trait]
+impl GasOracle for GasStationOracle {
+    fn name(&self) -> &'static str {
+        "gas_station"
+    }
+
+    async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal> {
+        let response: GasStationResponse = http_call(|| async {
+            self.client
+                .get(&self.url)
+                .send()
+                .await
+                .context("Gas station request failed")?
+                .error_for_status()
+                .context("Gas station rejected request")?
+                .json()
+                .await
+                .context("Invalid gas station response")
+        })
+        .await?;
+
+        let max_fee_gwei = Decimal::try_from(response.standard.max_fee)
+            .context("Invalid gas station max fee")?;
+        let gas_price = to_units(max_fee_gwei, 9)?;
+        native_cost_usdc(gas_units, gas_price, self.native_price_usdc)
+    }
+}
+
+/// Tries each oracle in order, returning the first cost one produces
+pub struct FallbackGasOracle {
+    oracles: Vec<Box<dyn GasOracle>>,
+}
+
+impl FallbackGasOracle {
+    pub fn new(oracles: Vec<Box<dyn GasOracle>>) -> Self {
+        Self { oracles }
+    }
+}
+
+#[async_trait]
+impl GasOracle for FallbackGasOracle {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal> {
+        for oracle in &self.oracles {
+            match oracle.current_cost_usdc(gas_units).await {
+                Ok(cost) => {
+                    log::debug!("Gas for {} units from {}: {} USDC", gas_units, oracle.name(), cost);
+                    return Ok(cost);
+                }
+                Err(e) => log::warn!("Gas source {} failed, falling back: {:#}", oracle.name(), e),
+            }
+        }
+        bail!("Every gas source failed")
+    }
+}
+
+/// The configured sources as a fallback chain, always ending with the fixed
+/// estimate so a cost is available even when every live source is down
+pub fn from_config(config: &Config) -> Result<Box<dyn GasOracle>> {
+    let mut sources = config.gas_source.clone();
+    if !sources.contains(&GasSource::Fixed) {
+        sources.push(GasSource::Fixed);
+    }
+
+    let provider = Arc::new(
+        Provider::<Http>::try_from(config.polygon_rpc_url.as_str())
+            .context("Failed to create HTTP provider")?
+    );
+    let native_price_usdc = config.native_token_price_usdc;
+
+    let oracles = sources
+        .into_iter()
+        .map(|source| -> Box<dyn GasOracle> {
+            match source {
+                GasSource::Fixed => Box::new(FixedGasOracle { cost_usdc: config.estimated_gas_cost }),
+                GasSource::Rpc => Box::new(RpcGasOracle { provider: provider.clone(), native_price_usdc }),
+                GasSource::Eip1559 => Box::new(Eip1559GasOracle { provider: provider.clone(), native_price_usdc }),
+                GasSource::GasStation => Box::new(GasStationOracle {
+                    client: reqwest::Client::new(),
+                    url: config.gas_station_url.clone(),
+                    native_price_usdc,
+                }),
+            }
+        })
+        .collect();
+
+    Ok(Box::new(FallbackGasOracle::new(oracles)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    struct MockOracle {
+        cost: Option<Decimal>,
+    }
+
+    #[async_trait]
+    impl GasOracle for MockOracle {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        async fn current_cost_usdc(&self, gas_units: u64) -> Result<Decimal> {
+            self.cost
+                .map(|cost| cost * Decimal::from(gas_units))
+                .ok_or_else(|| anyhow!("source down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_source() {
+        let oracle = FallbackGasOracle::new(vec![
+            Box::new(MockOracle { cost: None }),
+            Box::new(MockOracle { cost: Some(dec!(0.00001)) }),
+            Box::new(FixedGasOracle { cost_usdc: dec!(5) }),
+        ]);
+        assert_eq!(oracle.current_cost_usdc(300_000).await.unwrap(), dec!(3));
+
+        let oracle = FallbackGasOracle::new(vec![
+            Box::new(MockOracle { cost: None }),
+            Box::new(FixedGasOracle { cost_usdc: dec!(5) }),
+        ]);
+        assert_eq!(oracle.current_cost_usdc(300_000).await.unwrap(), dec!(5));
+
+        let oracle = FallbackGasOracle::new(vec![Box::new(MockOracle { cost: None })]);
+        assert!(oracle.current_cost_usdc(300_000).await.is_err());
+    }
+
+    #[test]
+    fn test_route_units_sum_both_venues() {
+        let units = SwapGasUnits { uniswap_v2: 130_000, uniswap_v3: 170_000, balancer_v2: 200_000, curve_stable: 220_000 };
+        assert_eq!(units.route(VenueKind::UniswapV3, VenueKind::UniswapV2Like), 300_000);
+        assert_eq!(units.route(VenueKind::UniswapV2Like, VenueKind::UniswapV3), 300_000);
+        assert_eq!(units.route(VenueKind::UniswapV2Like, VenueKind::UniswapV2Like), 260_000);
+        assert_eq!(units.route(VenueKind::BalancerV2, VenueKind::CurveStable), 420_000);
+
+        // A quoter's estimate covers the swap only
+        assert_eq!(units.swap_or_estimate(VenueKind::UniswapV3, Some(96_500)), 117_500);
+        assert_eq!(units.swap_or_estimate(VenueKind::UniswapV3, None), 170_000);
+    }
+
+    #[test]
+    fn test_native_cost_in_usdc() {
+        // 300k gas at 50 gwei is 0.015 POL
+        let gas_price = U256::from(50_000_000_000u64);
+        assert_eq!(native_cost_usdc(300_000, gas_price, dec!(0.5)).unwrap(), dec!(0.0075));
+    }
+}
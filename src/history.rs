@@ -0,0 +1,228 @@
+use anyhow::{Context, Result, anyhow, bail};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+
+use crate::cli::flag_value;
+use crate::config::Config;
+use crate::dex::DexClient;
+use crate::models::TokenPair;
+use crate::util::Pct;
+
+/// Prices from every venue at one historical block
+#[derive(Debug, Clone, Serialize)]
+pub struct SpreadSample {
+    pub block: u64,
+    pub prices: Vec<VenuePrice>,
+    pub spread_pct: Decimal,
+    pub clears_threshold: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VenuePrice {
+    pub venue: String,
+    pub price: Decimal,
+}
+
+/// Options for the `history` subcommand:
+/// `history --from <block> --to <block> [--stride <blocks>] [--format csv|json] [--out <file>]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryArgs {
+    pub from_block: u64,
+    pub to_block: u64,
+    pub stride: u64,
+    pub json: bool,
+    pub out: Option<String>,
+}
+
+impl HistoryArgs {
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let value = |flag: &str| flag_value(args, flag);
+
+        let from_block = value("--from")
+            .ok_or_else(|| anyhow!("history requires --from <block>"))?
+            .parse()
+            .context("Invalid --from block")?;
+        let to_block = value("--to")
+            .ok_or_else(|| anyhow!("history requires --to <block>"))?
+            .parse()
+            .context("Invalid --to block")?;
+        let stride = value("--stride").unwrap_or("100").parse().context("Invalid --stride")?;
+
+        if to_block < from_block {
+            bail!("--to ({}) must not be before --from ({})", to_block, from_block);
+        }
+        if stride == 0 {
+            bail!("--stride must be at least 1");
+        }
+
+        let json = match value("--format").unwrap_or("csv") {
+            "csv" => false,
+            "json" => true,
+            other => bail!("Unknown --format {} (expected csv or json)", other),
+        };
+
+        Ok(Self {
+            from_block,
+            to_block,
+            stride,
+            json,
+            out: value("--out").map(str::to_string),
+        })
+    }
+}
+
+/// Quote every venue, at its reference quote size, at each `stride`-th block
+/// in the range and record the spread.
+///
+/// Fails immediately if the node lacks historical state (non-archive node);
+/// blocks where a venue cannot be quoted for other reasons (e.g. the pool did
+/// not exist yet) are logged and skipped.
+pub async fn scan_spreads(
+    venues: &[Box<dyn DexClient>],
+    pair: &TokenPair,
+    args: &HistoryArgs,
+    config: &Config,
+) -> Result<Vec<SpreadSample>> {
+    let mut samples = Vec::new();
+
+    for block in (args.from_block..=args.to_block).step_by(args.stride as usize) {
+        let mut prices = Vec::with_capacity(venues.len());
+
+        for client in venues {
+            let venue = client.name();
+            match client.get_price_at_block(pair, config.reference_quote_size(venue), block).await {
+                Ok(price) => prices.push(VenuePrice {
+                    venue: venue.to_string(),
+                    price,
+                }),
+                Err(e) if format!("{:#}", e).contains("archive node") => return Err(e),
+                Err(e) => log::warn!("Skipping {} at block {}: {:#}", venue, block, e),
+            }
+        }
+
+        if prices.len() != venues.len() {
+            continue;
+        }
+
+        if let Some(sample) = spread_sample(block, prices, config.min_profit_threshold) {
+            log::debug!("Block {}: spread {}", block, Pct(sample.spread_pct));
+            samples.push(sample);
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Spread between the cheapest and most expensive venue, relative to the cheapest
+fn spread_sample(block: u64, prices: Vec<VenuePrice>, min_profit_threshold: Decimal) -> Option<SpreadSample> {
+    let min = prices.iter().map(|p| p.price).min()?;
+    let max = prices.iter().map(|p| p.price).max()?;
+    if min <= Decimal::ZERO {
+        return None;
+    }
+
+    let spread_pct = (max - min) / min;
+    Some(SpreadSample {
+        block,
+        prices,
+        spread_pct,
+        clears_threshold: spread_pct >= min_profit_threshold,
+    })
+}
+
+pub fn write_csv(samples: &[SpreadSample], venues: &[&str], mut writer: impl Write) -> Result<()> {
+    let price_columns: Vec<String> = venues.iter().map(|venue| format!("{}_price", venue.to_lowercase())).collect();
+    writeln!(writer, "block,{},spread_pct,clears_threshold", price_columns.join(","))?;
+
+    for sample in samples {
+        let prices: Vec<String> = sample.prices.iter().map(|p| p.price.to_string()).collect();
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            sample.block,
+            prices.join(","),
+            sample.spread_pct,
+            sample.clears_threshold
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Run the `history` subcommand end to end, writing the report to a file or stdout
+pub async fn run(args: &HistoryArgs, venues: &[Box<dyn DexClient>], config: &Config) -> Result<()> {
+    let pair = &config.token_pair();
+    let min_profit_threshold = config.min_profit_threshold;
+    log::info!(
+        "Scanning {} from block {} to {} every {} blocks",
+        pair.symbol, args.from_block, args.to_block, args.stride
+    );
+
+    let samples = scan_spreads(venues, pair, args, config).await?;
+
+    let writer: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(File::create(path).with_context(|| format!("Failed to create {}", path))?),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    if args.json {
+        serde_json::to_writer_pretty(writer, &samples)?;
+    } else {
+        let names: Vec<&str> = venues.iter().map(|client| client.name()).collect();
+        write_csv(&samples, &names, writer)?;
+    }
+
+    let clearing = samples.iter().filter(|s| s.clears_threshold).count();
+    log::info!(
+        "{} of {} sampled blocks had a spread at or above {}",
+        clearing,
+        samples.len(),
+        Pct(min_profit_threshold)
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_history_args() {
+        let parsed = HistoryArgs::parse(&args(&["--from", "100", "--to", "500", "--format", "json"])).unwrap();
+        assert_eq!(parsed.stride, 100);
+        assert!(parsed.json);
+
+        assert!(HistoryArgs::parse(&args(&["--from", "500", "--to", "100"])).is_err());
+        assert!(HistoryArgs::parse(&args(&["--from", "1", "--to", "2", "--stride", "0"])).is_err());
+    }
+
+    #[test]
+    fn test_spread_csv_report() {
+        let prices = |uni, sushi| vec![
+            VenuePrice { venue: "Uniswap".to_string(), price: uni },
+            VenuePrice { venue: "SushiSwap".to_string(), price: sushi },
+        ];
+        let samples = vec![
+            spread_sample(100, prices(dec!(2000), dec!(2020)), dec!(0.005)).unwrap(),
+            spread_sample(200, prices(dec!(2000), dec!(2001)), dec!(0.005)).unwrap(),
+        ];
+
+        let mut out = Vec::new();
+        write_csv(&samples, &["Uniswap", "SushiSwap"], &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "block,uniswap_price,sushiswap_price,spread_pct,clears_threshold\n\
+             100,2000,2020,0.01,true\n\
+             200,2000,2001,0.0005,false\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Emergency stop for trading. While halted the bot keeps fetching,
+/// validating and logging prices but acts on no opportunity. Halted while
+/// `KILL_SWITCH_FILE` exists or after `halt()` (SIGUSR1) until `resume()`
+/// (SIGUSR2); resuming never overrides the file. Cloning shares the state,
+/// so another task can flip and read it.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    file: Option<PathBuf>,
+    manual: Arc<AtomicBool>,
+    /// State at the last check, to log each transition once
+    observed: Arc<AtomicBool>,
+}
+
+impl KillSwitch {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Self {
+            file,
+            ..Self::default()
+        }
+    }
+
+    pub fn halt(&self) {
+        self.manual.store(true, Ordering::SeqCst);
+        self.log_transition();
+    }
+
+    /// Clear a manual halt; a present kill switch file still halts
+    pub fn resume(&self) {
+        self.manual.store(false, Ordering::SeqCst);
+        self.log_transition();
+    }
+
+    /// Why trading is halted right now, None while it is allowed
+    pub fn halted_by(&self) -> Option<String> {
+        if let Some(file) = &self.file
+            && file.exists()
+        {
+            return Some(format!("kill switch file {}", file.display()));
+        }
+        self.manual.load(Ordering::SeqCst).then(|| "manual stop".to_string())
+    }
+
+    /// Check the switch, logging when it flips since the last check
+    pub fn is_halted(&self) -> bool {
+        self.log_transition()
+    }
+
+    /// Log a flip of the switch since the last check, returning whether it
+    /// is halted now
+    fn log_transition(&self) -> bool {
+        let reason = self.halted_by();
+        let halted = reason.is_some();
+        if self.observed.swap(halted, Ordering::SeqCst) != halted {
+            match reason {
+                Some(reason) => log::warn!("Trading halted by kill switch ({})", reason),
+                None => log::warn!("Kill switch cleared, trading resumed"),
+            }
+        }
+        halted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halts_and_resumes() {
+        let file = std::env::temp_dir().join(format!("kill-switch-{}", uuid::Uuid::new_v4()));
+        let switch = KillSwitch::new(Some(file.clone()));
+        let handle = switch.clone();
+        assert!(!switch.is_halted());
+
+        // A manual stop from a shared handle
+        handle.halt();
+        assert!(switch.is_halted());
+        assert_eq!(switch.halted_by().as_deref(), Some("manual stop"));
+        handle.resume();
+        assert!(!switch.is_halted());
+
+        // The file halts until removed, whatever resume() says
+        std::fs::write(&file, "").unwrap();
+        assert!(switch.is_halted());
+        switch.resume();
+        assert!(switch.is_halted());
+        std::fs::remove_file(&file).unwrap();
+        assert!(!switch.is_halted());
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// When each pair last had a profitable opportunity, to tell a strategy that
+/// has gone cold (a config or data problem, or just a quiet market) from one
+/// that is still finding trades. Cloning shares the state, so readers outside
+/// the scan loop see what the bot records.
+#[derive(Debug, Clone)]
+pub struct LastProfitable {
+    state: Arc<RwLock<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    started: DateTime<Utc>,
+    last: HashMap<String, DateTime<Utc>>,
+    /// Pairs already alerted on during their current quiet spell
+    alerted: HashSet<String>,
+}
+
+impl LastProfitable {
+    pub fn new(started: DateTime<Utc>) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(State {
+                started,
+                last: HashMap::new(),
+                alerted: HashSet::new(),
+            })),
+        }
+    }
+
+    /// Note a profitable opportunity on `pair`, ending any quiet spell
+    pub fn record(&self, pair: &str, at: DateTime<Utc>) {
+        let mut state = self.state.write();
+        state.last.insert(pair.to_string(), at);
+        state.alerted.remove(pair);
+    }
+
+    /// Time since `pair`'s last profitable opportunity, or since startup
+    /// while it has had none
+    pub fn since(&self, pair: &str, now: DateTime<Utc>) -> Duration {
+        let state = self.state.read();
+        now - state.last.get(pair).copied().unwrap_or(state.started)
+    }
+
+    /// Whether `pair` has been quiet for at least `threshold` and not yet
+    /// alerted on this spell. Returns true once per spell.
+    pub fn quiet_alert_due(&self, pair: &str, now: DateTime<Utc>, threshold: Duration) -> bool {
+        if self.since(pair, now) < threshold {
+            return false;
+        }
+        self.state.write().alerted.insert(pair.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_alerts_once_per_quiet_spell() {
+        let clock = MockClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap());
+        let last = LastProfitable::new(clock.now());
+        let threshold = Duration::minutes(30);
+
+        // Nothing found since startup
+        clock.advance(Duration::minutes(29));
+        assert_eq!(last.since("WETH/USDC", clock.now()), Duration::minutes(29));
+        assert!(!last.quiet_alert_due("WETH/USDC", clock.now(), threshold));
+        clock.advance(Duration::minutes(1));
+        assert!(last.quiet_alert_due("WETH/USDC", clock.now(), threshold));
+        clock.advance(Duration::minutes(10));
+        assert!(!last.quiet_alert_due("WETH/USDC", clock.now(), threshold));
+
+        // A find resets the gauge and the alert
+        last.record("WETH/USDC", clock.now());
+        clock.advance(Duration::minutes(30));
+        assert_eq!(last.since("WETH/USDC", clock.now()), threshold);
+        assert!(last.quiet_alert_due("WETH/USDC", clock.now(), threshold));
+    }
+}
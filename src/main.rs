@@ -1,88 +1,353 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
+mod arbitrage;
+mod arming;
+mod cex;
+mod cli;
+mod clock;
 mod config;
 mod database;
+mod decisions;
 mod dex;
+mod execution;
+mod fills;
+mod gas;
+mod history;
+mod kill_switch;
+mod last_profitable;
 mod models;
+mod nonce;
+mod notifier;
+mod preflight;
 mod price_validator; // Add the new module
+mod ranker;
+mod recent;
+mod relay;
+mod reorg;
+mod rpc_budget;
+mod report;
+mod signals;
+mod sink;
+#[cfg(test)]
+mod sim;
+mod sampling;
+mod spreads;
+mod strategy;
+mod util;
 
+use arming::ShadowTrade;
+use arbitrage::{LegQuotes, ProfitModel, RoutePolicy, VenuePrice, best_route, round_trip_bps, twap_deviation_bps};
+use clock::{Clock, SystemClock};
+use cex::CexReference;
 use config::Config;
-use database::Database;
-use dex::{uniswap::UniswapV3Client, sushiswap::SushiswapClient, DexClient};
-use models::{ArbitrageOpportunity, TokenPair};
-use price_validator::{PriceValidator, ValidationResult}; 
+use database::{OpportunityStore, ReadOnlyStore};
+use decisions::{Decision, DecisionEvent, DecisionRecorder};
+use dex::{DexClient, TradeSide, TwapPrice, VenueKind};
+use dex::tokens::{PairTransferFee, TokenRegistry};
+use futures::future::join_all;
+use gas::GasOracle;
+use execution::Executor;
+use history::HistoryArgs;
+use kill_switch::KillSwitch;
+use last_profitable::LastProfitable;
+use models::{ArbitrageOpportunity, BaseCurrency, ExecutionStatus, OpportunityIdStrategy, TokenPair};
+use notifier::{Notifier, SpreadAlert};
+use price_validator::{CycleStatus, PriceValidator, ValidationResult, VenueStatus};
+use ranker::{ProfitRanker, Ranker};
+use recent::RecentOpportunities;
+use reorg::{ChainView, ReorgChecker};
+use signals::{OperatorSignal, OperatorSignals};
+use sink::OpportunitySink;
+use sampling::AdaptiveSampler;
+use spreads::SpreadStats;
+use strategy::{MarketSnapshot, RouteChoice, RouteQuote, SizeQuotes, Strategy};
+use util::{Pct, VENUE_FETCH_TIMEOUT, is_malformed, is_timeout, with_deadline};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    dotenv::dotenv().ok();
+    config::load_dotenv();
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("preflight") {
+        return preflight::run().await;
+    }
+
     let config = Config::from_env()?;
-    let db = Database::new(&config.database_url).await?;
-    
-    
-    db.init().await?;
+    util::set_pct_max_decimals(config.pct_display_max_decimals);
+    if config.rpc_cu_per_second > 0 || config.rpc_cu_per_day > 0 {
+        rpc_budget::install(rpc_budget::RpcBudget::new(
+            config.rpc_cu_per_second,
+            config.rpc_cu_per_day,
+            &config.rpc_cu_costs,
+            Arc::new(clock::SystemClock),
+        ));
+    }
+
+    if args.first().map(String::as_str) == Some("history") {
+        let history_args = HistoryArgs::parse(&args[1..])?;
+        let venues = dex::create_clients(&config).await?;
+        return history::run(&history_args, &venues, &config).await;
+    }
+
+    if args.first().map(String::as_str) == Some("export") {
+        return export_opportunities(&config, &args[1..]).await;
+    }
+
+    if args.first().map(String::as_str) == Some("report") {
+        return report::run(&config, &args[1..]).await;
+    }
+
+    let tiered = database::open_tiered(&config).await?;
+    let db: Box<dyn OpportunityStore> = match &tiered {
+        Some(tiered) => Box::new(tiered.clone()),
+        None => database::connect(&config.database_url, &config.pool_settings()).await?,
+    };
+    if config.read_only {
+        log::info!("Read-only mode: no database writes, transactions or state files");
+    } else {
+        db.init().await?;
+        if let Some(tiered) = tiered {
+            tiered.spawn_archiver(chrono::Duration::days(config.hot_retention_days as i64));
+        }
+    }
 
     let mut bot = ArbitrageBot::new(config, db).await?;
     bot.run().await
 }
 
+/// `export --since <date> [--out <file.csv>]`: write recorded opportunities as CSV
+async fn export_opportunities(config: &Config, args: &[String]) -> Result<()> {
+    let since = cli::flag_value(args, "--since")
+        .ok_or_else(|| anyhow::anyhow!("export requires --since <YYYY-MM-DD or RFC 3339>"))
+        .and_then(cli::parse_datetime)?;
+
+    let db = database::open(config).await?;
+    match cli::flag_value(args, "--out") {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path))?;
+            database::export_csv(db.as_ref(), since, std::io::BufWriter::new(file)).await
+        }
+        None => database::export_csv(db.as_ref(), since, std::io::stdout().lock()).await,
+    }
+}
+
 pub struct ArbitrageBot {
     config: Config,
-    db: Database,
-    uniswap_client: UniswapV3Client,
-    sushiswap_client: SushiswapClient,
+    db: Arc<dyn OpportunityStore>,
+    sink: Box<dyn OpportunitySink>,
+    /// Source of the block opportunities are recorded against, when a
+    /// feature needs it
+    chain: Option<Arc<dyn ChainView>>,
+    reorg_checker: Option<Arc<ReorgChecker>>,
+    venues: Vec<Box<dyn DexClient>>,
     price_validator: PriceValidator, // Use the separate module
+    cex_reference: Option<CexReference>,
+    executor: Option<Arc<Executor>>,
+    gas_oracle: Box<dyn GasOracle>,
+    notifier: Option<Notifier>,
+    token_registry: TokenRegistry,
+    recent: RecentOpportunities,
+    spread_stats: SpreadStats,
+    sampler: Option<AdaptiveSampler>,
+    /// Turns each cycle's market snapshot into opportunities
+    strategy: Box<dyn Strategy>,
+    last_profitable: LastProfitable,
+    decisions: Option<Box<dyn DecisionRecorder>>,
+    kill_switch: KillSwitch,
+    cycles_completed: u64,
+    /// Cycles that validated a price, counted toward the warmup until it is done
+    warmup_progress: u64,
+    clock: Arc<dyn Clock>,
 }
 
 impl ArbitrageBot {
-    pub async fn new(config: Config, db: Database) -> Result<Self> {
-        let uniswap_client = UniswapV3Client::new(
-            &config.polygon_rpc_url,
-            &config.uniswap_v3_quoter_address,
-            &config.weth_address,
-            &config.usdc_address,
-        ).await.context("Failed to create Uniswap client")?;
-        
-        let sushiswap_client = SushiswapClient::new(
-            &config.polygon_rpc_url,
-            &config.sushiswap_router_address,
-            &config.weth_address,
-            &config.usdc_address,
-        ).await.context("Failed to create SushiSwap client")?;
-        
+    pub async fn new(config: Config, db: Box<dyn OpportunityStore>) -> Result<Self> {
+        let venues = dex::create_clients(&config).await?;
+        let pair = config.token_pair();
+        let warm_ups = join_all(venues.iter().map(|venue| async {
+            venue.warm_up(&pair).await.with_context(|| format!("Failed to warm up {}", venue.name()))
+        }))
+        .await;
+        warm_ups.into_iter().collect::<Result<Vec<_>>>()?;
+
+        let executor = match &config.private_key {
+            Some(_) if config.read_only => {
+                log::info!("Read-only mode, trade execution disabled");
+                None
+            }
+            Some(_) if config.monitor_only => {
+                log::info!("Monitor-only mode, trade execution disabled");
+                None
+            }
+            Some(key) => Some(
+                Executor::new(&config, key).await.context("Failed to create executor")?
+            ),
+            None => {
+                log::info!("No WALLET_PRIVATE_KEY configured, trade execution disabled");
+                None
+            }
+        };
+
+        Self::from_parts(config, db, venues, executor, Arc::new(SystemClock)).await
+    }
+
+    /// Assemble the bot from already-built venues, store, executor and clock,
+    /// e.g. scripted ones in simulations
+    pub async fn from_parts(
+        config: Config,
+        db: Box<dyn OpportunityStore>,
+        venues: Vec<Box<dyn DexClient>>,
+        executor: Option<Executor>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
         // Create price validator with custom bounds based on config
-        let price_validator = PriceValidator::with_bounds(
-            Decimal::from(500),   // Min price
-            Decimal::from(10000), // Max price
-            Decimal::try_from(0.15).unwrap(), // 15% max change
-            5, // 5 minutes max age
-        );
+        let mut price_validator = PriceValidator::with_bounds(
+            config.min_valid_price,
+            config.max_valid_price,
+            config.max_price_change_pct,
+            config.max_price_age_minutes,
+        )
+        .with_max_reference_deviation(config.cex_max_deviation_pct)
+        .with_max_consensus_deviation(config.max_consensus_deviation_pct)
+        .with_volatility_window(config.volatility_window)
+        .with_clock(clock.clone());
+        if config.persist_dex_health {
+            let health = db.get_dex_health().await.context("Failed to load DEX health")?;
+            log::info!("Restored circuit-breaker state for {} venue(s)", health.len());
+            price_validator.restore(health);
+        }
+
+        let healthy = venues
+            .iter()
+            .filter(|venue| !price_validator.is_circuit_breaker_tripped(venue.name()))
+            .count();
+        if healthy < 2 && !config.monitor_only && !config.read_only {
+            anyhow::bail!(
+                "arbitrage requires at least 2 venues, found {} (set MONITOR_ONLY=true to only record prices)",
+                healthy
+            );
+        }
+
+        let gas_oracle = gas::from_config(&config)?;
+        let cex_reference = config.cex_ticker_url.as_deref().map(CexReference::new);
+        let notifier = Notifier::from_config(&config);
+        let db: Arc<dyn OpportunityStore> = if config.read_only {
+            Arc::new(ReadOnlyStore::new(db))
+        } else {
+            Arc::from(db)
+        };
+        let sink = sink::from_config(&config, db.clone())?;
+        // Executing bots always record the block, which makes opportunity ids
+        // idempotent so a restart cannot trade the same opportunity twice;
+        // deterministic ids need it for every opportunity
+        let chain = if config.reorg_confirmations > 0
+            || config.max_block_staleness > 0
+            || config.private_key.is_some()
+            || config.opportunity_id_strategy == OpportunityIdStrategy::Deterministic
+        {
+            Some(reorg::rpc_chain(&config.polygon_rpc_url)?)
+        } else {
+            None
+        };
+        let reorg_checker = match (&chain, config.reorg_confirmations) {
+            (Some(chain), confirmations) if confirmations > 0 => {
+                Some(Arc::new(ReorgChecker::new(chain.clone(), db.clone(), confirmations)))
+            }
+            _ => None,
+        };
+
+        let token_registry = TokenRegistry::from_entries(&config.fee_on_transfer_tokens)
+            .context("Invalid FEE_ON_TRANSFER_TOKENS")?;
+
+        let recent = RecentOpportunities::new(config.memory_buffer_size);
+        let decisions = decisions::from_config(&config);
+        let kill_switch = KillSwitch::new(config.kill_switch_file.as_ref().map(Into::into));
+
+        let strategy = strategy::from_kind(config.strategy);
+        let sampler = config.adaptive_sampling.then(|| {
+            AdaptiveSampler::new(
+                Duration::from_secs(config.max_check_interval_seconds),
+                config.sampling_backoff_factor,
+                config.sampling_speedup_factor,
+                config.sampling_near_threshold,
+            )
+        });
 
         Ok(Self {
             config,
             db,
-            uniswap_client,
-            sushiswap_client,
+            sink,
+            chain,
+            reorg_checker,
+            venues,
             price_validator,
+            cex_reference,
+            executor: executor.map(Arc::new),
+            gas_oracle,
+            notifier,
+            token_registry,
+            recent,
+            spread_stats: SpreadStats::default(),
+            sampler,
+            strategy,
+            last_profitable: LastProfitable::new(clock.now()),
+            decisions,
+            kill_switch,
+            cycles_completed: 0,
+            warmup_progress: 0,
+            clock,
         })
     }
 
+    /// Record decisions with `recorder` instead of the configured log
+    #[cfg(test)]
+    pub fn with_decision_recorder(mut self, recorder: Box<dyn DecisionRecorder>) -> Self {
+        self.decisions = Some(recorder);
+        self
+    }
+
     pub async fn run(&mut self) -> Result<()> {
+        self.run_until(tokio::signal::ctrl_c()).await
+    }
+
+    /// Scan until `shutdown` completes, then log the spread summary
+    pub async fn run_until(&mut self, shutdown: impl Future) -> Result<()> {
         log::info!("Starting Production Polygon Arbitrage Bot");
+        log::info!("Effective configuration:");
+        for line in self.config.redacted_summary().lines() {
+            log::info!("  {}", line);
+        }
+        log::info!("Detecting opportunities with the {} strategy", self.strategy.name());
 
-        let token_pair = TokenPair {
-            token0: self.config.weth_address.clone(),
-            token1: self.config.usdc_address.clone(),
-            symbol: "WETH/USDC".to_string(),
-        };
+        let token_pair = self.config.token_pair();
+        for token in [&token_pair.token0, &token_pair.token1] {
+            if let Ok(address) = token.parse() {
+                log::info!("Token {}: {}", token, self.token_registry.classify(address));
+            }
+        }
+
+        self.prune_unsupported_venues(&token_pair).await;
+
+        let mut signals = OperatorSignals::install()?;
+        tokio::pin!(shutdown);
+
+        let reorg_watch = self.reorg_checker.clone().map(|checker| {
+            tokio::spawn(checker.watch(Duration::from_secs(self.config.check_interval_seconds)))
+        });
+        let pending_watch = self.executor.clone().map(|executor| {
+            tokio::spawn(executor.watch_pending(self.db.clone(), Duration::from_secs(self.config.check_interval_seconds)))
+        });
 
         loop {
-            if let Err(e) = self.check_arbitrage_opportunity(&token_pair).await {
+            let pause = if let Err(e) = self.check_arbitrage_opportunity(&token_pair).await {
                 log::error!("Error checking arbitrage opportunity: {}", e);
                 
                 // Print validation stats on errors
@@ -90,167 +355,1040 @@ impl ArbitrageBot {
                 log::info!("Validation stats: {:?}", stats);
                 
                 // Exponential backoff on errors
-                sleep(Duration::from_secs(60)).await;
+                Duration::from_secs(60)
             } else {
-                sleep(Duration::from_secs(self.config.check_interval_seconds)).await;
+                let interval = Duration::from_secs(self.config.check_interval_seconds);
+                match &mut self.sampler {
+                    Some(sampler) => {
+                        let pause = sampler.interval(interval);
+                        if pause > interval {
+                            log::debug!("Quiet market, next check in {:?}", pause);
+                        }
+                        pause
+                    }
+                    None => interval,
+                }
+            };
+
+            tokio::select! {
+                _ = sleep(pause) => {}
+                _ = &mut shutdown => {
+                    log::info!("Shutting down");
+                    for watch in [reorg_watch, pending_watch].into_iter().flatten() {
+                        watch.abort();
+                    }
+                    self.spread_stats.log_summary();
+                    self.log_recent_opportunities();
+                    return Ok(());
+                }
+                signal = signals.recv() => match signal {
+                    OperatorSignal::Reload => self.reload_config(),
+                    OperatorSignal::Halt => self.kill_switch.halt(),
+                    OperatorSignal::Resume => self.kill_switch.resume(),
+                },
+            }
+
+            self.cycles_completed += 1;
+
+            if let Some(notifier) = &mut self.notifier {
+                notifier.flush().await;
+            }
+        }
+    }
+
+    /// Drop venues without a pool for `pair` before scanning, instead of
+    /// quoting them into a revert every cycle. A venue whose check fails is
+    /// kept; its quotes will report the problem.
+    async fn prune_unsupported_venues(&mut self, pair: &TokenPair) {
+        let checks = join_all(self.venues.iter().map(|venue| venue.supports_pair(pair))).await;
+        let supported: Vec<bool> = self
+            .venues
+            .iter()
+            .zip(checks)
+            .map(|(venue, check)| match check {
+                Ok(true) => true,
+                Ok(false) => {
+                    log::warn!("{} has no pool for {}, not quoting it", venue.name(), pair.symbol);
+                    false
+                }
+                Err(e) => {
+                    log::warn!("Failed to check {} support for {}, keeping it: {:#}", venue.name(), pair.symbol, e);
+                    true
+                }
+            })
+            .collect();
+
+        let mut supported = supported.into_iter();
+        self.venues.retain(|_| supported.next().unwrap_or(true));
+    }
+
+    /// Apply edits to the hot-reloadable settings (SIGHUP), keeping the
+    /// validator's price history and the rest of the running state
+    fn reload_config(&mut self) {
+        let new = match Config::reload() {
+            Ok(new) => new,
+            Err(e) => {
+                log::error!("Config reload rejected, keeping current settings: {}", e);
+                return;
             }
+        };
+
+        let changed = self.config.apply_reloadable(&new);
+        if changed.is_empty() {
+            log::info!("Config reloaded, no hot-reloadable settings changed");
+            return;
+        }
+
+        self.price_validator.set_bounds(
+            self.config.min_valid_price,
+            self.config.max_valid_price,
+            self.config.max_price_change_pct,
+            self.config.max_price_age_minutes,
+        );
+        log::info!("Config reloaded, updated: {}", changed.join(", "));
+    }
+
+    /// Keep only the `max_opportunities_per_cycle` most profitable of a
+    /// cycle's opportunities, in their original order, so a wide ladder on a
+    /// volatile cycle cannot flood the store
+    fn cap_opportunities(&self, pair: &TokenPair, opportunities: &mut Vec<ArbitrageOpportunity>) {
+        let cap = self.config.max_opportunities_per_cycle;
+        if cap == 0 || opportunities.len() <= cap {
+            return;
         }
+        let ranked = ProfitRanker.rank(opportunities, &(0..opportunities.len()).collect::<Vec<_>>());
+        let cutoff = &opportunities[ranked[cap - 1]];
+        log::warn!(
+            "{} opportunities this cycle exceed the cap of {}, dropping {} below {} {} profit",
+            opportunities.len(),
+            cap,
+            opportunities.len() - cap,
+            cutoff.estimated_profit,
+            cutoff.profit_currency
+        );
+        let kept: HashSet<usize> = ranked[..cap].iter().copied().collect();
+        let mut index = 0;
+        opportunities.retain(|opportunity| {
+            let keep = kept.contains(&index);
+            index += 1;
+            if !keep {
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "over the per-cycle cap".to_string(),
+                });
+            }
+            keep
+        });
     }
 
     async fn check_arbitrage_opportunity(&mut self, pair: &TokenPair) -> Result<()> {
         log::debug!("Checking arbitrage opportunity for {}", pair.symbol);
 
-        // Get prices from both DEXes with timeout
-        let timeout_duration = Duration::from_secs(30);
+        let cycle_start = self.clock.now();
 
-        let (uniswap_result, sushiswap_result) = tokio::join!(
-            tokio::time::timeout(timeout_duration, self.uniswap_client.get_price(pair)),
-            tokio::time::timeout(timeout_duration, self.sushiswap_client.get_price(pair))
+        // Get prices from every venue concurrently, each with its own timeout
+        // on top of the per-call timeouts and retries inside
+        let fetch_start = tokio::time::Instant::now();
+        let (results, reference_price, block) = tokio::join!(
+            join_all(self.venues.iter().map(|venue| {
+                let size = self.config.reference_quote_size(venue.name());
+                with_deadline(VENUE_FETCH_TIMEOUT, venue.get_price_for_size(pair, size))
+            })),
+            self.reference_price(),
+            self.current_block(),
         );
- 
-        // Handle Uniswap price
-        let uniswap_price = match uniswap_result {
-            Ok(Ok(price)) => price,
-            Ok(Err(e)) => {
-                log::error!("Failed to get Uniswap price: {}", e);
-                return Ok(());
-            },
-            Err(_) => {
-                log::error!("Uniswap price fetch timeout");
-                return Ok(());
+        log::debug!("Fetched {} venue prices in {:?}", results.len(), fetch_start.elapsed());
+        self.price_validator.set_reference_price(reference_price);
+
+        // Validate and record whatever came back, so one failing venue neither
+        // hides the others' prices nor escapes circuit-breaker accounting
+        let mut valid_prices = Vec::with_capacity(self.venues.len());
+        let mut statuses = Vec::with_capacity(self.venues.len());
+        for (venue, result) in self.venues.iter().zip(results) {
+            let name = venue.name();
+            let price = match result {
+                Ok(price) => price,
+                Err(e) => {
+                    log::error!("Failed to get {} price: {:#}", name, e);
+                    self.decide(pair, || Decision::PriceFetchFailed { venue: name.to_string(), error: format!("{:#}", e) });
+                    self.price_validator.record_fetch_error(name);
+                    let status = if is_timeout(&e) {
+                        VenueStatus::TimedOut
+                    } else if is_malformed(&e) {
+                        VenueStatus::Malformed
+                    } else {
+                        VenueStatus::FetchFailed
+                    };
+                    statuses.push((name, status));
+                    continue;
+                }
+            };
+
+            self.decide(pair, || Decision::PriceFetched { venue: name.to_string(), price });
+            let validation = self.price_validator.validate_price(name, price)?;
+            if !validation.is_valid() {
+                log::warn!("Invalid {} price: {}",
+                    name, validation.error_message().unwrap_or("Unknown error"));
+                self.decide(pair, || Decision::PriceRejected {
+                    venue: name.to_string(),
+                    reason: validation.error_message().unwrap_or("Unknown error").to_string(),
+                });
+                let status = match validation {
+                    ValidationResult::CircuitBreakerTripped(_) => VenueStatus::CircuitBreakerTripped,
+                    _ => VenueStatus::Rejected,
+                };
+                statuses.push((name, status));
+                continue;
             }
-        };
 
-        // Handle SushiSwap price
-        let sushiswap_price = match sushiswap_result {
-            Ok(Ok(price)) => price,
-            Ok(Err(e)) => {
-                log::error!("Failed to get SushiSwap price: {}", e);
-                return Ok(());
-            },
-            Err(_) => {
-                log::error!("SushiSwap price fetch timeout");
-                return Ok(());
+            valid_prices.push((name, price));
+            statuses.push((name, VenueStatus::Valid(price)));
+        }
+
+        if self.config.consensus_check {
+            for (name, reason) in self.consensus_outliers(pair, &valid_prices).await {
+                log::warn!("Invalid {} price: {}", name, reason);
+                self.decide(pair, || Decision::PriceRejected { venue: name.to_string(), reason });
+                valid_prices.retain(|(venue, _)| *venue != name);
+                if let Some((_, status)) = statuses.iter_mut().find(|(venue, _)| *venue == name) {
+                    *status = VenueStatus::Rejected;
+                }
             }
-        };
+        }
+
+        let now = self.clock.now();
+        let quiet = self.last_profitable.since(&pair.symbol, now);
+        let rpc_usage = rpc_budget::global()
+            .map(|budget| {
+                let usage = budget.usage();
+                match usage.per_day {
+                    0 => format!(" rpc_cu_today={}", usage.today),
+                    per_day => format!(" rpc_cu_today={}/{}", usage.today, per_day),
+                }
+            })
+            .unwrap_or_default();
+        log::info!(
+            "{} since_profitable={}s{}",
+            CycleStatus { venues: statuses, stats: self.price_validator.get_stats() },
+            quiet.num_seconds(),
+            rpc_usage
+        );
+        if self.config.quiet_alert_minutes > 0
+            && self.last_profitable.quiet_alert_due(
+                &pair.symbol,
+                now,
+                chrono::Duration::minutes(self.config.quiet_alert_minutes as i64),
+            )
+        {
+            log::warn!("No profitable {} opportunity for {} minutes", pair.symbol, quiet.num_minutes());
+            if let Some(notifier) = &mut self.notifier {
+                notifier.notify_quiet(&pair.symbol, quiet).await;
+            }
+        }
+
+        if !valid_prices.is_empty() {
+            let mean = valid_prices.iter().map(|(_, price)| *price).sum::<Decimal>() / Decimal::from(valid_prices.len());
+            self.price_validator.record_pair_price(&pair.symbol, mean);
+        }
+
+        // Only cycles that validated a price build the history the warmup waits for
+        let warming_up = self.warmup_progress < self.config.warmup_cycles;
+        if warming_up && !valid_prices.is_empty() {
+            self.warmup_progress += 1;
+            if self.warmup_progress == self.config.warmup_cycles {
+                log::info!(
+                    "Warmup complete after {} cycles with valid prices, acting on opportunities from the next one",
+                    self.warmup_progress
+                );
+            }
+        }
+
+        if self.config.persist_dex_health && !self.config.read_only {
+            for venue in &self.venues {
+                let Some(health) = self.price_validator.health(venue.name()) else {
+                    continue;
+                };
+                if let Err(e) = self.db.upsert_dex_health(&health).await {
+                    log::warn!("Failed to persist {} health: {:#}", venue.name(), e);
+                }
+            }
+        }
 
-        // Validate prices using the separate validator
-        let uniswap_validation = self.price_validator.validate_price("Uniswap", uniswap_price)?;
-        let sushiswap_validation = self.price_validator.validate_price("SushiSwap", sushiswap_price)?;
+        let summary: Vec<String> = valid_prices
+            .iter()
+            .map(|(name, price)| format!("{}: {} USDC", name, price))
+            .collect();
 
-        // Check if both prices are valid
-        if !uniswap_validation.is_valid() {
-            log::warn!("Invalid Uniswap price: {}", 
-                uniswap_validation.error_message().unwrap_or("Unknown error"));
+        if self.config.monitor_only || self.config.read_only {
+            log::info!("Prices - {}", summary.join(", "));
             return Ok(());
         }
 
-        if !sushiswap_validation.is_valid() {
-            log::warn!("Invalid SushiSwap price: {}", 
-                sushiswap_validation.error_message().unwrap_or("Unknown error"));
+        let Some((buy, sell, price_diff)) = best_route(&valid_prices) else {
+            log::warn!(
+                "Only {} of {} venues returned a valid price ({}), skipping comparison",
+                valid_prices.len(),
+                self.venues.len(),
+                summary.join(", ")
+            );
+            self.decide(pair, || Decision::ComparisonSkipped {
+                reason: format!("{} of {} venues returned a valid price", valid_prices.len(), self.venues.len()),
+            });
             return Ok(());
+        };
+
+        log::info!("Valid prices - {}", summary.join(", "));
+
+        log::debug!("Price difference: {}", Pct(price_diff));
+        self.spread_stats.record(&pair.symbol, price_diff);
+        if let Some(sampler) = &mut self.sampler {
+            sampler.observe(price_diff, self.config.thresholds(&pair.symbol).min_profit_threshold);
         }
 
-        log::info!(
-            "Valid prices - Uniswap: {} USDC, SushiSwap: {} USDC",
-            uniswap_price,
-            sushiswap_price
-        );
+        // Monitoring only: the profit gates below still decide what is traded
+        if !self.config.alert_spread_pct.is_zero() && price_diff >= self.config.alert_spread_pct {
+            log::warn!(
+                "⚠️ Large spread {}: {} at {}, {} at {} (alert threshold {})",
+                Pct(price_diff),
+                buy.0,
+                buy.1,
+                sell.0,
+                sell.1,
+                Pct(self.config.alert_spread_pct)
+            );
+            self.decide(pair, || Decision::SpreadAlert {
+                buy_dex: buy.0.to_string(),
+                sell_dex: sell.0.to_string(),
+                spread_pct: price_diff,
+            });
+            if let Some(notifier) = &mut self.notifier {
+                let alert = SpreadAlert { pair: pair.symbol.clone(), buy, sell, spread_pct: price_diff };
+                notifier.notify_spread(&alert).await;
+            }
+        }
+
+        let transfer_fee = match self.token_registry.pair_transfer_fee(pair, self.config.fee_on_transfer_policy)? {
+            PairTransferFee::None => Decimal::ZERO,
+            PairTransferFee::Haircut(fee) => fee,
+            PairTransferFee::Skip => {
+                log::debug!("{} contains a fee-on-transfer token, skipping comparison", pair.symbol);
+                self.decide(pair, || Decision::ComparisonSkipped { reason: "fee-on-transfer token".to_string() });
+                return Ok(());
+            }
+        };
+
+        let model = ProfitModel {
+            min_profit_threshold: self.config.thresholds(&pair.symbol).min_profit_threshold,
+            gas_cost: self.config.estimated_gas_cost,
+            route_gas_costs: self.route_gas_costs(&valid_prices).await?,
+            base_currency: self.config.base_currency,
+            transfer_fee,
+            price_decimals: self.config.price_decimals,
+            usdc_decimals: self.config.usdc_decimals_display,
+            rounding: self.config.rounding_mode,
+            routes: RoutePolicy {
+                allow: self.config.route_allowlist.clone(),
+                deny: self.config.route_denylist.clone(),
+            },
+            max_plausible_profit_pct: self.config.max_plausible_profit_pct,
+        };
 
-        // Calculate price difference and potential profit
-        let price_diff = if uniswap_price > sushiswap_price {
-            (uniswap_price - sushiswap_price) / sushiswap_price
+        let candidates = if self.config.trade_size_ladder.is_empty() {
+            let caps = self.slippage_caps(pair, &valid_prices).await;
+            let size = self.config.trade_amount;
+            let prices = valid_prices.iter().copied().filter(|&price| self.within_slippage_cap(&caps, price, size)).collect();
+            vec![(size, prices)]
         } else {
-            (sushiswap_price - uniswap_price) / uniswap_price
+            self.quote_ladder(pair, &valid_prices).await
         };
 
-        log::debug!("Price difference: {:.4}%", price_diff * Decimal::from(100));
+        let (manipulated, inconsistent) = tokio::join!(
+            self.twap_outliers(pair, &valid_prices),
+            self.inconsistent_venues(pair, &valid_prices),
+        );
+        let flagged = manipulated
+            .into_iter()
+            .map(|venue| (venue, "spot price strays from TWAP"))
+            .chain(inconsistent.into_iter().map(|venue| (venue, "inconsistent venue round trip")))
+            .collect();
+
+        // The market data is gathered; what it offers is the strategy's call
+        let mut snapshot = MarketSnapshot {
+            pair,
+            model: &model,
+            max_slippage_bps: self.config.thresholds(&pair.symbol).max_slippage_bps,
+            sizes: candidates.into_iter().map(|(size, prices)| SizeQuotes { size, prices }).collect(),
+            flagged,
+            quoted: Vec::new(),
+        };
+
+        // Prices only pick the route; profit comes from quoting both legs at size
+        for choice in self.strategy.routes(&snapshot) {
+            match choice {
+                Ok(route) => {
+                    if let Some(legs) = self.quote_route(pair, &model, &route, cycle_start).await {
+                        snapshot.quoted.push(RouteQuote { route, legs });
+                    }
+                }
+                Err(rejection) => self.decide(pair, || rejection.into()),
+            }
+        }
+        let mut opportunities = Vec::new();
+        for outcome in self.strategy.evaluate(&snapshot) {
+            match outcome {
+                Ok(opportunity) => opportunities.push(opportunity),
+                Err(rejection) => self.decide(pair, || rejection.into()),
+            }
+        }
 
-        // Check if price difference exceeds minimum threshold
-        if price_diff >= self.config.min_profit_threshold {
-            let opportunity = self.calculate_arbitrage_profit(
-                pair,
-                uniswap_price,
-                sushiswap_price,
-                price_diff,
-            ).await?;
+        if let Some((number, hash)) = block {
+            for opportunity in &mut opportunities {
+                opportunity.block_number = Some(number as i64);
+                opportunity.block_hash = Some(reorg::hash_text(hash));
+                if let Some(key) = opportunity.idempotency_key() {
+                    opportunity.id = key;
+                }
+            }
+        }
+        if !opportunities.is_empty() {
+            self.last_profitable.record(&pair.symbol, self.clock.now());
+            if let Some(sampler) = &mut self.sampler {
+                sampler.reset();
+            }
+        }
+        for opportunity in &opportunities {
+            self.decide(pair, || Decision::OpportunityDetected {
+                opportunity_id: opportunity.id,
+                buy_dex: opportunity.buy_dex.clone(),
+                sell_dex: opportunity.sell_dex.clone(),
+                size: opportunity.trade_amount,
+                estimated_profit: opportunity.estimated_profit,
+            });
+        }
 
-            // Additional profitability check after gas costs
-            if opportunity.estimated_profit > Decimal::ZERO {
+        // Until the validator has price history, its change checks are inactive,
+        // so opportunities are only logged
+        if warming_up {
+            for opportunity in &opportunities {
                 log::info!(
-                    "🚀 Profitable arbitrage opportunity found! Profit: {} USDC ({:.2}%)",
+                    "Warmup {}/{}, not acting: {} -> {} at {} {}, est. profit {} {}",
+                    self.warmup_progress,
+                    self.config.warmup_cycles,
+                    opportunity.buy_dex,
+                    opportunity.sell_dex,
+                    opportunity.trade_amount,
+                    opportunity.profit_currency,
                     opportunity.estimated_profit,
-                    (price_diff * Decimal::from(100))
+                    opportunity.profit_currency
                 );
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "warmup".to_string(),
+                });
+            }
+            return Ok(());
+        }
 
-                // Save to database
-                self.db.save_opportunity(&opportunity).await
-                    .context("Failed to save opportunity to database")?;
+        if self.kill_switch.is_halted() {
+            for opportunity in &opportunities {
+                log::warn!(
+                    "Trading halted by kill switch, not acting: {} -> {} at {} {}, est. profit {} {}",
+                    opportunity.buy_dex,
+                    opportunity.sell_dex,
+                    opportunity.trade_amount,
+                    opportunity.profit_currency,
+                    opportunity.estimated_profit,
+                    opportunity.profit_currency
+                );
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "trading halted by kill switch".to_string(),
+                });
+            }
+            return Ok(());
+        }
 
-                // Here you would implement the actual trading logic
-                // self.execute_arbitrage(&opportunity).await?;
-            } else {
-                log::debug!("Opportunity found but not profitable after gas costs");
+        // Catch logic bugs here rather than persisting nonsense
+        opportunities.retain(|opportunity| match opportunity.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("Discarding invalid opportunity {} -> {}: {:#}", opportunity.buy_dex, opportunity.sell_dex, e);
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: format!("invalid: {:#}", e),
+                });
+                false
+            }
+        });
+        self.cap_opportunities(pair, &mut opportunities);
+
+        // Every profitable size is recorded; the executor picks which to trade
+        let mut recorded = Vec::new();
+        for (index, opportunity) in opportunities.iter_mut().enumerate() {
+            if !model.is_plausible(opportunity) {
+                log::error!(
+                    "🚨 Suspicious opportunity {} -> {}: {} spread, {} {} profit on {} {} exceeds the {} plausibility cap; not acting (pricing bug?)",
+                    opportunity.buy_dex,
+                    opportunity.sell_dex,
+                    Pct(opportunity.price_difference_pct),
+                    opportunity.estimated_profit,
+                    opportunity.profit_currency,
+                    opportunity.trade_amount,
+                    opportunity.profit_currency,
+                    Pct(self.config.max_plausible_profit_pct)
+                );
+                opportunity.execution_status = Some(ExecutionStatus::Suspicious.as_str().to_string());
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "implausibly profitable".to_string(),
+                });
+                self.sink.emit(opportunity).await?;
+                if let Some(checker) = &self.reorg_checker {
+                    checker.track(opportunity.clone());
+                }
+                continue;
+            }
+
+            if self.config.reconfirm_before_save && !self.reconfirm(pair, &model, opportunity).await {
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "evaporated on reconfirmation".to_string(),
+                });
+                continue;
+            }
+
+            log::info!(
+                "🚀 Profitable arbitrage opportunity found! Size: {} {}, Profit: {} {} ({})",
+                opportunity.trade_amount,
+                opportunity.profit_currency,
+                opportunity.estimated_profit,
+                opportunity.profit_currency,
+                Pct(opportunity.price_difference_pct)
+            );
+
+            if !self.sink.emit(opportunity).await? {
+                log::info!(
+                    "Opportunity {} was already recorded (seen before a restart?), not acting on it again",
+                    opportunity.id
+                );
+                self.decide(pair, || Decision::OpportunityRejected {
+                    opportunity_id: opportunity.id,
+                    reason: "already recorded".to_string(),
+                });
+                continue;
+            }
+            self.decide(pair, || Decision::OpportunityRecorded { opportunity_id: opportunity.id });
+
+            if let Some(notifier) = &mut self.notifier {
+                notifier.notify(opportunity).await;
+            }
+            recorded.push(index);
+        }
+
+        // Without the head this cycle's ids are random, so a restart could not
+        // tell these opportunities from new ones and might trade them twice
+        let executor = match (&self.executor, block) {
+            (Some(_), None) if !recorded.is_empty() => {
+                log::warn!("Not executing this cycle's opportunities: the chain head is unknown");
+                for &index in &recorded {
+                    self.decide(pair, || Decision::OpportunityRejected {
+                        opportunity_id: opportunities[index].id,
+                        reason: "chain head unknown".to_string(),
+                    });
+                }
+                None
+            }
+            (executor, _) => executor.as_ref(),
+        };
+        if let Some(executor) = executor
+            && !recorded.is_empty()
+        {
+            let picked = executor.plan_cycle(&mut opportunities, &recorded).await;
+            let volatility_bps = self.price_validator.volatility_bps(&pair.symbol);
+            let executions = opportunities
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| picked.contains(index))
+                .map(|(_, opportunity)| async move {
+                    if let Err(e) = executor.execute(opportunity, volatility_bps).await {
+                        log::error!("Execution of {} failed: {:#}", opportunity.id, e);
+                    }
+                });
+            join_all(executions).await;
+
+            // A warm-standby executor arms on dry-run trades whose simulation at a
+            // later block makes what was estimated
+            if executor.is_on_standby() {
+                let mut shadow = Vec::new();
+                for opportunity in &opportunities {
+                    if opportunity.execution_status.as_deref() != Some(ExecutionStatus::DryRun.as_str()) {
+                        continue;
+                    }
+                    let simulated = executor.simulate_profit(opportunity, volatility_bps).await;
+                    if let Err(e) = &simulated {
+                        log::warn!("Simulation of dry-run trade {} failed: {:#}", opportunity.id, e);
+                    }
+                    shadow.push(ShadowTrade {
+                        estimated_profit: opportunity.estimated_profit,
+                        shadow_profit: simulated.ok(),
+                    });
+                }
+                executor.record_shadow_cycle(&shadow);
+            }
+
+            for &index in &recorded {
+                let opportunity = &opportunities[index];
+                self.decide(pair, || Decision::TradeOutcome {
+                    opportunity_id: opportunity.id,
+                    status: opportunity.execution_status.clone().unwrap_or_default(),
+                });
+                self.db.update_execution(opportunity).await
+                    .context("Failed to record execution result")?;
+            }
+        }
+
+        for index in recorded {
+            if let Some(checker) = &self.reorg_checker {
+                checker.track(opportunities[index].clone());
             }
+            self.recent.push(opportunities[index].clone());
         }
 
         Ok(())
     }
 
-    async fn calculate_arbitrage_profit(
+    /// The opportunities still in the in-memory buffer, newest first: the
+    /// only record of a run without a database, e.g. in read-only mode
+    fn log_recent_opportunities(&self) {
+        let recent = self.recent.latest(self.config.memory_buffer_size);
+        if recent.is_empty() {
+            return;
+        }
+        log::info!("Last {} opportunities, newest first:", recent.len());
+        for opportunity in recent {
+            log::info!(
+                "  {} {} {} -> {} at {} {}: est. profit {} {} ({})",
+                opportunity.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                opportunity.token_pair,
+                opportunity.buy_dex,
+                opportunity.sell_dex,
+                opportunity.trade_amount,
+                opportunity.profit_currency,
+                opportunity.estimated_profit,
+                opportunity.profit_currency,
+                opportunity.execution_status.as_deref().unwrap_or("not executed")
+            );
+        }
+    }
+
+    /// Pass a decision to the decision log. The event is only built when
+    /// the log is on.
+    fn decide(&self, pair: &TokenPair, decision: impl FnOnce() -> Decision) {
+        if let Some(recorder) = &self.decisions {
+            recorder.record(DecisionEvent {
+                timestamp: self.clock.now(),
+                cycle: self.cycles_completed,
+                pair: pair.symbol.clone(),
+                decision: decision(),
+            });
+        }
+    }
+
+    /// Current CEX mid, if configured. A failed fetch only skips the
+    /// reference check this cycle.
+    async fn reference_price(&self) -> Option<Decimal> {
+        let cex = self.cex_reference.as_ref()?;
+        match cex.mid_price().await {
+            Ok(mid) => Some(mid),
+            Err(e) => {
+                log::warn!("Failed to get CEX reference price, skipping the check: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Chain head opportunities found this cycle are recorded against, while
+    /// reorg or block-staleness checks or execution are on. Without it those
+    /// checks are skipped, ids are random for this cycle's opportunities and
+    /// none of them is executed.
+    async fn current_block(&self) -> Option<(u64, ethers::types::H256)> {
+        let chain = self.chain.as_ref()?;
+        match chain.head().await {
+            Ok(head) => Some(head),
+            Err(e) => {
+                log::warn!("Failed to get the current block, opportunities will not be block-checked: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Quote each ladder size on the venues that returned a valid reference
+    /// price this cycle. Sizes where fewer than two venues answer are dropped
+    /// later by the profit model. A venue sits out the sizes past its
+    /// slippage cap.
+    async fn quote_ladder(&self, pair: &TokenPair, healthy: &[VenuePrice]) -> Vec<(Decimal, Vec<VenuePrice>)> {
+        let venues: Vec<(&dyn DexClient, Decimal)> = self.venues
+            .iter()
+            .filter_map(|venue| {
+                let &(_, price) = healthy.iter().find(|(name, _)| *name == venue.name())?;
+                Some((venue.as_ref(), price))
+            })
+            .collect();
+        let caps = self.slippage_caps(pair, healthy).await;
+
+        let mut quotes = Vec::with_capacity(self.config.trade_size_ladder.len());
+        for &size in &self.config.trade_size_ladder {
+            let within_cap: Vec<&(&dyn DexClient, Decimal)> = venues
+                .iter()
+                .filter(|(venue, reference_price)| self.within_slippage_cap(&caps, (venue.name(), *reference_price), size))
+                .collect();
+            let results = join_all(within_cap.iter().map(|(venue, reference_price)| {
+                with_deadline(VENUE_FETCH_TIMEOUT, venue.get_price_for_size(pair, self.quote_size(size, *reference_price)))
+            }))
+            .await;
+
+            let mut prices = Vec::with_capacity(within_cap.len());
+            for ((venue, _), result) in within_cap.iter().zip(results) {
+                match result {
+                    Ok(price) => prices.push((venue.name(), price)),
+                    Err(e) => log::warn!(
+                        "Failed to quote {} {} on {}: {:#}",
+                        size, self.config.base_currency, venue.name(), e
+                    ),
+                }
+            }
+            quotes.push((size, prices));
+        }
+
+        quotes
+    }
+
+    /// Quote-token input of a `size` trade: WETH sizes at their USDC value
+    /// at the venue's reference price
+    fn quote_size(&self, size: Decimal, reference_price: Decimal) -> Decimal {
+        match self.config.base_currency {
+            BaseCurrency::Usdc => size,
+            BaseCurrency::Weth => size * reference_price,
+        }
+    }
+
+    /// Largest quote-token input each priced venue takes within the pair's
+    /// slippage limit, for the venues that can work one out
+    async fn slippage_caps(&self, pair: &TokenPair, prices: &[VenuePrice]) -> HashMap<&'static str, Decimal> {
+        let max_slippage_bps = self.config.thresholds(&pair.symbol).max_slippage_bps;
+        let venues: Vec<&dyn DexClient> = self.venues
+            .iter()
+            .filter(|venue| prices.iter().any(|(name, _)| *name == venue.name()))
+            .map(|venue| venue.as_ref())
+            .collect();
+        let caps = join_all(venues.iter().map(|venue| venue.max_input_within_slippage(pair, max_slippage_bps))).await;
+
+        let mut by_venue = HashMap::new();
+        for (venue, cap) in venues.iter().zip(caps) {
+            match cap {
+                Ok(Some(cap)) => {
+                    by_venue.insert(venue.name(), cap);
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to get {} slippage cap, quoting every size: {:#}", venue.name(), e),
+            }
+        }
+        by_venue
+    }
+
+    /// Whether a `size` trade stays within the venue's slippage cap
+    fn within_slippage_cap(
+        &self,
+        caps: &HashMap<&'static str, Decimal>,
+        (name, reference_price): VenuePrice,
+        size: Decimal,
+    ) -> bool {
+        let Some(&cap) = caps.get(name) else {
+            return true;
+        };
+        let within = self.quote_size(size, reference_price) <= cap;
+        if !within {
+            log::debug!("{} {} is past {}'s slippage cap of {}, skipping", size, self.config.base_currency, name, cap.round_dp(2));
+        }
+        within
+    }
+
+    /// Both legs of a route the strategy picked, unless quoting them fails
+    /// or a venue's reference price has aged out meanwhile
+    async fn quote_route(
         &self,
         pair: &TokenPair,
-        uniswap_price: Decimal,
-        sushiswap_price: Decimal,
-        price_diff_pct: Decimal,
-    ) -> Result<ArbitrageOpportunity> {
-        let trade_amount = self.config.trade_amount;
-
-        let (buy_dex, sell_dex, buy_price, sell_price) = if uniswap_price > sushiswap_price {
-            ("SushiSwap", "Uniswap", sushiswap_price, uniswap_price)
-        } else {
-            ("Uniswap", "SushiSwap", uniswap_price, sushiswap_price)
+        model: &ProfitModel,
+        route: &RouteChoice,
+        cycle_start: chrono::DateTime<chrono::Utc>,
+    ) -> Option<LegQuotes> {
+        let RouteChoice { size, buy, sell, .. } = *route;
+        let rejected = |reason: String| Decision::RouteRejected {
+            buy_dex: buy.0.to_string(),
+            sell_dex: sell.0.to_string(),
+            size,
+            reason,
+        };
+        let legs = match self.quote_legs(pair, buy.0, sell.0, size).await {
+            Ok(legs) => legs,
+            Err(e) => {
+                log::warn!("Failed to quote {} {} round trip {} -> {}: {:#}", size, model.base_currency, buy.0, sell.0, e);
+                self.decide(pair, || rejected(format!("leg quote failed: {:#}", e)));
+                return None;
+            }
         };
+        // Leg quotes can be slow; never compare against a price that has aged out meanwhile
+        let now = self.clock.now();
+        if self.config.enforce_freshness
+            && let Some(stale) = [buy.0, sell.0]
+                .into_iter()
+                .find(|name| !self.price_validator.is_fresh(name, cycle_start, now))
+        {
+            log::warn!("{} price is not fresh from this cycle, skipping {} {} comparison", stale, size, model.base_currency);
+            self.decide(pair, || rejected(format!("{} price is not fresh", stale)));
+            return None;
+        }
+        Some(legs)
+    }
+
+    /// Venues whose validated price strays from the liquidity-weighted
+    /// consensus of the others, with the reason. Venues whose liquidity
+    /// cannot be read still take part, at an average weight.
+    async fn consensus_outliers(&mut self, pair: &TokenPair, valid: &[VenuePrice]) -> Vec<(&'static str, String)> {
+        if valid.len() < 3 {
+            return Vec::new();
+        }
+
+        let venues: Vec<&dyn DexClient> = self.venues
+            .iter()
+            .filter(|venue| valid.iter().any(|(name, _)| *name == venue.name()))
+            .map(|venue| venue.as_ref())
+            .collect();
+        let liquidity = join_all(venues.iter().map(|venue| venue.get_liquidity(pair))).await;
+
+        let mut weighted = Vec::with_capacity(venues.len());
+        for (venue, liquidity) in venues.iter().zip(liquidity) {
+            let liquidity = liquidity.unwrap_or_else(|e| {
+                log::warn!("Failed to get {} liquidity, weighting it as average: {:#}", venue.name(), e);
+                None
+            });
+            if let Some(&(name, price)) = valid.iter().find(|(name, _)| *name == venue.name()) {
+                weighted.push((name, price, liquidity));
+            }
+        }
+
+        self.price_validator.check_consensus(&weighted)
+    }
+
+    /// Venues whose pool mid price strays from the same pool's TWAP by more
+    /// than the configured limit, a sign of in-block manipulation. Venues
+    /// without a TWAP are left to the price validator's checks.
+    async fn twap_outliers(&self, pair: &TokenPair, spots: &[VenuePrice]) -> Vec<&'static str> {
+        let max_deviation = Decimal::from(self.config.max_spot_twap_deviation_bps);
+        if max_deviation.is_zero() {
+            return Vec::new();
+        }
+
+        let venues: Vec<&dyn DexClient> = self.venues
+            .iter()
+            .filter(|venue| spots.iter().any(|(name, _)| *name == venue.name()))
+            .map(|venue| venue.as_ref())
+            .collect();
+        let twaps = join_all(venues.iter().map(|venue| venue.get_twap(pair, self.config.twap_window_seconds))).await;
+
+        let mut outliers = Vec::new();
+        for (venue, twap) in venues.iter().zip(twaps) {
+            let TwapPrice { twap, mid } = match twap {
+                Ok(Some(twap)) => twap,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("Failed to get {} TWAP, relying on price validation: {:#}", venue.name(), e);
+                    continue;
+                }
+            };
+
+            if let Some(deviation) = twap_deviation_bps(mid, twap)
+                && deviation > max_deviation
+            {
+                log::warn!(
+                    "Rejecting {} mid {}: {:.1} bps from its {}s TWAP {} (max {} bps)",
+                    venue.name(), mid, deviation, self.config.twap_window_seconds, twap, max_deviation
+                );
+                outliers.push(venue.name());
+            }
+        }
+
+        outliers
+    }
+
+    /// Venues whose round trip (buy the base token for their reference size,
+    /// sell it straight back) gains anything or loses more than
+    /// `MAX_ROUND_TRIP_LOSS_BPS`. Either points at bad venue data or a bug in
+    /// our own quoting, so the venue sits out the cycle.
+    async fn inconsistent_venues(&self, pair: &TokenPair, healthy: &[VenuePrice]) -> Vec<&'static str> {
+        let max_loss = Decimal::from(self.config.max_round_trip_loss_bps);
+        if max_loss.is_zero() {
+            return Vec::new();
+        }
+
+        let venues: Vec<&dyn DexClient> = self.venues
+            .iter()
+            .filter(|venue| healthy.iter().any(|(name, _)| *name == venue.name()))
+            .map(|venue| venue.as_ref())
+            .collect();
+        let round_trips = join_all(venues.iter().map(|venue| async move {
+            let size = self.config.reference_quote_size(venue.name());
+            let bought = venue.quote_exact_input(pair, TradeSide::Buy, size).await?.amount_out;
+            let back = venue.quote_exact_input(pair, TradeSide::Sell, bought).await?.amount_out;
+            Ok::<_, anyhow::Error>((size, back))
+        }))
+        .await;
+
+        let mut inconsistent = Vec::new();
+        for (venue, round_trip) in venues.iter().zip(round_trips) {
+            let (size, back) = match round_trip {
+                Ok(round_trip) => round_trip,
+                Err(e) => {
+                    log::warn!("Failed to quote {} round trip, skipping its self-check: {:#}", venue.name(), e);
+                    continue;
+                }
+            };
+            let Some(bps) = round_trip_bps(size, back) else {
+                continue;
+            };
+
+            if bps > Decimal::ZERO || -bps > max_loss {
+                log::warn!(
+                    "Rejecting {}: round trip of {} returns {} ({:+.1} bps, expected a loss of at most {} bps)",
+                    venue.name(), size, back, bps, max_loss
+                );
+                inconsistent.push(venue.name());
+            }
+        }
 
-        // Calculate tokens received when buying (accounting for slippage)
-        let slippage_factor = Decimal::from(1) - 
-            Decimal::from(self.config.max_slippage_bps) / Decimal::from(10000);
-
-        let tokens_bought = (trade_amount / buy_price) * slippage_factor;
-
-        // Calculate USDC received when selling (accounting for slippage)
-        let usdc_received = (tokens_bought * sell_price) * slippage_factor;
-
-        // Estimate gas costs based on current network conditions
-        let estimated_gas_cost = self.estimate_gas_cost().await?;
-
-        // Calculate net profit
-        let gross_profit = usdc_received - trade_amount;
-        let net_profit = gross_profit - estimated_gas_cost;
-
-        Ok(ArbitrageOpportunity {
-            id: uuid::Uuid::new_v4(),
-            timestamp: Utc::now(),
-            token_pair: pair.symbol.clone(),
-            buy_dex: buy_dex.to_string(),
-            sell_dex: sell_dex.to_string(),
-            buy_price,
-            sell_price,
-            price_difference_pct: price_diff_pct,
-            trade_amount,
-            estimated_profit: net_profit,
-            gas_cost: estimated_gas_cost,
+        inconsistent
+    }
+
+    /// Quote the first leg for `size` of the base currency, then the second
+    /// leg for exactly what it returns: buy then sell for USDC-funded trips,
+    /// sell then buy back for WETH-funded ones
+    async fn quote_legs(&self, pair: &TokenPair, buy_dex: &str, sell_dex: &str, size: Decimal) -> Result<LegQuotes> {
+        let venue = |name: &str| {
+            self.venues
+                .iter()
+                .find(|venue| venue.name() == name)
+                .with_context(|| format!("Unknown venue {}", name))
+        };
+
+        let (buy_venue, sell_venue) = (venue(buy_dex)?, venue(sell_dex)?);
+
+        let (bought, received) = match self.config.base_currency {
+            BaseCurrency::Usdc => {
+                let bought = buy_venue.quote_exact_input(pair, TradeSide::Buy, size).await?;
+                let received = sell_venue.quote_exact_input(pair, TradeSide::Sell, bought.amount_out).await?;
+                (bought, received)
+            }
+            BaseCurrency::Weth => {
+                let received = sell_venue.quote_exact_input(pair, TradeSide::Sell, size).await?;
+                let bought = buy_venue.quote_exact_input(pair, TradeSide::Buy, received.amount_out).await?;
+                (bought, received)
+            }
+        };
+
+        // A swap the quoter simulated is priced on its own gas estimate
+        let gas_cost = if bought.gas_estimate.is_some() || received.gas_estimate.is_some() {
+            let units = self.config.swap_gas_units;
+            let gas_units = units.swap_or_estimate(buy_venue.venue_kind(), bought.gas_estimate)
+                + units.swap_or_estimate(sell_venue.venue_kind(), received.gas_estimate);
+            Some(self.gas_oracle.current_cost_usdc(gas_units).await?)
+        } else {
+            None
+        };
+        Ok(LegQuotes {
+            tokens_bought: bought.amount_out,
+            usdc_received: received.amount_out,
+            buy_fee_tier: bought.fee_tier,
+            sell_fee_tier: received.fee_tier,
+            gas_cost,
+            price_impact_bps: bought.price_impact_bps.max(received.price_impact_bps),
         })
     }
 
-    async fn estimate_gas_cost(&self) -> Result<Decimal> {
-        // This is a simplified gas estimation
-        // In production, you'd want to:
-        // 1. Get current gas price from the network
-        // 2. Estimate gas usage for your specific transactions
-        // 3. Convert to USDC equivalent
+    /// Re-quote the opportunity's route at the current block and check it is
+    /// still profitable after gas, so spreads that existed only for an
+    /// instant are not persisted
+    async fn reconfirm(&self, pair: &TokenPair, model: &ProfitModel, opportunity: &ArbitrageOpportunity) -> bool {
+        let requoted = match self.requote(pair, model, opportunity).await {
+            Ok(requoted) => requoted,
+            Err(e) => {
+                log::warn!("Failed to reconfirm {} -> {}, skipping: {:#}", opportunity.buy_dex, opportunity.sell_dex, e);
+                return false;
+            }
+        };
 
-        // For now, use the configured estimate
-        Ok(self.config.estimated_gas_cost)
+        match requoted {
+            Some(reconfirmed) => {
+                log::debug!(
+                    "Reconfirmed {} -> {}: est. profit {} {} (was {})",
+                    opportunity.buy_dex, opportunity.sell_dex, reconfirmed.estimated_profit, opportunity.profit_currency,
+                    opportunity.estimated_profit
+                );
+                true
+            }
+            None => {
+                log::info!(
+                    "Opportunity {} -> {} at {} {} evaporated before saving (est. profit was {} {})",
+                    opportunity.buy_dex, opportunity.sell_dex, opportunity.trade_amount, opportunity.profit_currency,
+                    opportunity.estimated_profit, opportunity.profit_currency
+                );
+                false
+            }
+        }
+    }
+
+    /// The opportunity's route quoted again now, `None` once it no longer
+    /// pays after gas
+    async fn requote(
+        &self,
+        pair: &TokenPair,
+        model: &ProfitModel,
+        opportunity: &ArbitrageOpportunity,
+    ) -> Result<Option<ArbitrageOpportunity>> {
+        let legs = self.quote_legs(pair, &opportunity.buy_dex, &opportunity.sell_dex, opportunity.trade_amount).await?;
+        let route = (opportunity.buy_dex.as_str(), opportunity.sell_dex.as_str());
+        Ok(model.evaluate(pair, route, opportunity.price_difference_pct, opportunity.trade_amount, legs))
     }
-}
\ No newline at end of file
+
+    /// Gas cost of a round trip buying on a `buy` venue and selling on a
+    /// `sell` one, each swap costing its venue kind's gas units
+    async fn estimate_gas_cost(&self, buy: VenueKind, sell: VenueKind) -> Result<Decimal> {
+        self.gas_oracle.current_cost_usdc(self.config.swap_gas_units.route(buy, sell)).await
+    }
+
+    /// Gas cost of every route between the priced venues, pricing each
+    /// distinct pair of venue kinds once
+    async fn route_gas_costs(&self, prices: &[VenuePrice]) -> Result<HashMap<(String, String), Decimal>> {
+        let kinds: Vec<(&str, VenueKind)> = prices
+            .iter()
+            .filter_map(|(name, _)| self.venues.iter().find(|venue| venue.name() == *name))
+            .map(|venue| (venue.name(), venue.venue_kind()))
+            .collect();
+
+        let mut by_kinds = HashMap::new();
+        let mut costs = HashMap::new();
+        for &(buy, buy_kind) in &kinds {
+            for &(sell, sell_kind) in kinds.iter().filter(|(sell, _)| *sell != buy) {
+                let cost = match by_kinds.get(&(buy_kind, sell_kind)) {
+                    Some(&cost) => cost,
+                    None => {
+                        let cost = self.estimate_gas_cost(buy_kind, sell_kind).await?;
+                        by_kinds.insert((buy_kind, sell_kind), cost);
+                        cost
+                    }
+                };
+                costs.insert((buy.to_string(), sell.to_string()), cost);
+            }
+        }
+        Ok(costs)
+    }
+}
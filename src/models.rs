@@ -1,20 +1,90 @@
+use anyhow::{Result, bail};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// Which token of a pair is the base. Prices are always expressed as
+/// quote tokens per one base token (e.g. USDC per WETH).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PairBase {
+    Token0,
+    Token1,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenPair {
     pub token0: String,
     pub token1: String,
     pub symbol: String,
+    pub base: PairBase,
+}
+
+impl TokenPair {
+    pub fn base_token(&self) -> &str {
+        match self.base {
+            PairBase::Token0 => &self.token0,
+            PairBase::Token1 => &self.token1,
+        }
+    }
+
+    pub fn quote_token(&self) -> &str {
+        match self.base {
+            PairBase::Token0 => &self.token1,
+            PairBase::Token1 => &self.token0,
+        }
+    }
+}
+
+/// Asset an arbitrage round trip is funded in, and so the asset its trade
+/// amount, gas cost and profit are denominated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BaseCurrency {
+    /// Buy WETH with USDC, sell it back for more USDC
+    #[default]
+    Usdc,
+    /// Sell WETH for USDC, buy back more WETH
+    Weth,
+}
+
+impl BaseCurrency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BaseCurrency::Usdc => "USDC",
+            BaseCurrency::Weth => "WETH",
+        }
+    }
+}
+
+impl FromStr for BaseCurrency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "USDC" => Ok(BaseCurrency::Usdc),
+            "WETH" => Ok(BaseCurrency::Weth),
+            other => Err(format!("unknown currency {:?} (expected USDC or WETH)", other)),
+        }
+    }
+}
+
+impl fmt::Display for BaseCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct ArbitrageOpportunity {
     pub id: Uuid,
     pub timestamp: DateTime<Utc>,
+    /// Chain head when the opportunity was found, recorded while reorg or
+    /// block-staleness checks are on
+    pub block_number: Option<i64>,
+    pub block_hash: Option<String>,
     pub token_pair: String,
     pub buy_dex: String,
     pub sell_dex: String,
@@ -24,4 +94,253 @@ pub struct ArbitrageOpportunity {
     pub trade_amount: Decimal,
     pub estimated_profit: Decimal,
     pub gas_cost: Decimal,
-}
\ No newline at end of file
+    /// Asset the trade amount, estimated profit and gas cost are in, e.g. "USDC"
+    pub profit_currency: String,
+    pub execution_status: Option<String>,
+    pub realized_profit: Option<Decimal>,
+    /// Uniswap V3 fee tier of the pool each leg was priced on, None for
+    /// venues with one pool per pair
+    pub buy_fee_tier: Option<i32>,
+    pub sell_fee_tier: Option<i32>,
+}
+
+/// One route between two venues for a pair: (buy_dex, sell_dex, token_pair)
+pub type RouteKey = (String, String, String);
+
+/// How far the spread of an opportunity's own buy/sell prices may exceed its
+/// recorded price difference. Fees and price impact only narrow the spread,
+/// so anything beyond rounding and a little market drift is a bug.
+const PRICE_DIFF_TOLERANCE: Decimal = Decimal::from_parts(1, 0, 0, false, 3);
+
+/// UUIDv5 namespace of opportunity idempotency keys
+const OPPORTUNITY_NAMESPACE: Uuid = Uuid::from_u128(0x5c1e_4a0e_9b3f_4d2a_8f6e_2b7d_0c9a_1e47);
+
+/// How opportunities are identified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpportunityIdStrategy {
+    /// A random UUIDv4, replaced by the idempotency key only when the block
+    /// is recorded anyway (executing, reorg-checking or staleness-checking bots)
+    #[default]
+    Random,
+    /// Always the idempotency key: the block is recorded for every
+    /// opportunity, so the same content gets the same id in the log, the
+    /// database, notifications and execution receipts
+    Deterministic,
+}
+
+impl FromStr for OpportunityIdStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(OpportunityIdStrategy::Random),
+            "deterministic" => Ok(OpportunityIdStrategy::Deterministic),
+            other => Err(format!("unknown opportunity id strategy {:?} (expected random or deterministic)", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ArbitrageOpportunity {
+    /// A valid, unexecuted SushiSwap -> Uniswap WETH/USDC opportunity at 1000
+    /// USDC, for tests to adjust with struct update syntax
+    pub fn sample() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            block_number: None,
+            block_hash: None,
+            token_pair: "WETH/USDC".to_string(),
+            buy_dex: "SushiSwap".to_string(),
+            sell_dex: "Uniswap".to_string(),
+            buy_price: Decimal::from(2000),
+            sell_price: Decimal::from(2020),
+            price_difference_pct: Decimal::new(1, 2),
+            trade_amount: Decimal::from(1000),
+            estimated_profit: Decimal::from(5),
+            gas_cost: Decimal::from(5),
+            profit_currency: "USDC".to_string(),
+            execution_status: None,
+            realized_profit: None,
+            buy_fee_tier: None,
+            sell_fee_tier: None,
+        }
+    }
+}
+
+impl ArbitrageOpportunity {
+    pub fn route_key(&self) -> RouteKey {
+        (self.buy_dex.clone(), self.sell_dex.clone(), self.token_pair.clone())
+    }
+
+    /// Deterministic id of the logical opportunity: one route and size at one
+    /// block. Detecting it again (e.g. after a restart) yields the same id, so
+    /// it cannot be recorded or executed twice. None without a block number.
+    pub fn idempotency_key(&self) -> Option<Uuid> {
+        let block = self.block_number?;
+        let name = format!(
+            "{}|{}|{}|{}|{}",
+            block,
+            self.buy_dex,
+            self.sell_dex,
+            self.token_pair,
+            self.trade_amount.normalize()
+        );
+        Some(Uuid::new_v5(&OPPORTUNITY_NAMESPACE, name.as_bytes()))
+    }
+
+    /// Check the invariants every recorded opportunity must hold, naming the
+    /// first one violated
+    pub fn validate(&self) -> Result<()> {
+        if self.buy_dex.is_empty() || self.sell_dex.is_empty() {
+            bail!("buy_dex and sell_dex must be set");
+        }
+        if self.buy_dex == self.sell_dex {
+            bail!("buy_dex and sell_dex are both {}", self.buy_dex);
+        }
+        if self.trade_amount <= Decimal::ZERO {
+            bail!("trade_amount {} must be positive", self.trade_amount);
+        }
+        if self.buy_price <= Decimal::ZERO {
+            bail!("buy_price {} must be positive", self.buy_price);
+        }
+        if self.sell_price < self.buy_price {
+            bail!("sell_price {} is below buy_price {}", self.sell_price, self.buy_price);
+        }
+        if self.price_difference_pct < Decimal::ZERO {
+            bail!("price_difference_pct {} is negative", self.price_difference_pct);
+        }
+
+        let spread = (self.sell_price - self.buy_price) / self.buy_price;
+        if spread > self.price_difference_pct + PRICE_DIFF_TOLERANCE {
+            bail!(
+                "price_difference_pct {} is inconsistent with buy_price {} and sell_price {} (spread {})",
+                self.price_difference_pct, self.buy_price, self.sell_price, spread
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Circuit-breaker state of one venue, as persisted in `dex_health`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DexHealth {
+    pub dex_name: String,
+    /// Last price that passed validation, zero if none has yet
+    pub last_price: Decimal,
+    pub last_updated: DateTime<Utc>,
+    pub consecutive_errors: i32,
+}
+
+/// Lifecycle of an opportunity once it reaches the executor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Skipped,
+    DryRun,
+    Executed,
+    Failed,
+    /// Implausibly profitable, likely a pricing bug; never executed
+    Suspicious,
+    /// Too old by the time it reached the executor; abandoned
+    Expired,
+    /// Its block was reorged out after it was recorded
+    Reorged,
+    /// Its route traded too recently; not executed
+    CoolingDown,
+    /// Too many blocks old by the time it reached the executor; abandoned
+    StaleBlock,
+    /// Mined, but not yet buried under the required confirmations
+    Pending,
+    /// Its transaction was mined and reverted
+    Reverted,
+    /// Its transaction was reorged out and is no longer known to the node
+    Dropped,
+}
+
+impl ExecutionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionStatus::Skipped => "skipped",
+            ExecutionStatus::DryRun => "dry_run",
+            ExecutionStatus::Executed => "executed",
+            ExecutionStatus::Failed => "failed",
+            ExecutionStatus::Suspicious => "suspicious",
+            ExecutionStatus::Expired => "expired",
+            ExecutionStatus::Reorged => "reorged",
+            ExecutionStatus::CoolingDown => "cooling_down",
+            ExecutionStatus::StaleBlock => "stale_block",
+            ExecutionStatus::Pending => "pending",
+            ExecutionStatus::Reverted => "reverted",
+            ExecutionStatus::Dropped => "dropped",
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn opportunity() -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            id: Uuid::nil(),
+            sell_price: dec!(2015),
+            estimated_profit: dec!(2.5),
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    fn violation(change: impl FnOnce(&mut ArbitrageOpportunity)) -> String {
+        let mut opportunity = opportunity();
+        change(&mut opportunity);
+        opportunity.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_validate_names_each_broken_invariant() {
+        opportunity().validate().unwrap();
+
+        assert!(violation(|o| o.sell_dex = "SushiSwap".to_string()).contains("both SushiSwap"));
+        assert!(violation(|o| o.buy_dex.clear()).contains("must be set"));
+        assert!(violation(|o| o.trade_amount = Decimal::ZERO).contains("trade_amount 0 must be positive"));
+        assert!(violation(|o| o.buy_price = Decimal::ZERO).contains("buy_price 0 must be positive"));
+        assert!(violation(|o| o.sell_price = dec!(1990)).contains("sell_price 1990 is below buy_price 2000"));
+        assert!(violation(|o| o.price_difference_pct = dec!(-0.01)).contains("is negative"));
+
+        // A 5% effective spread cannot come from a 1% quoted difference
+        assert!(violation(|o| o.sell_price = dec!(2100)).contains("inconsistent"));
+    }
+
+    #[test]
+    fn test_idempotency_key_identifies_logical_opportunity() {
+        assert_eq!(opportunity().idempotency_key(), None);
+
+        let at_block = |block: i64| ArbitrageOpportunity { block_number: Some(block), ..opportunity() };
+        let key = at_block(54_000_000).idempotency_key().unwrap();
+
+        // Re-detection after a restart differs only in timestamp and prices
+        let again = ArbitrageOpportunity {
+            timestamp: Utc::now() + chrono::Duration::seconds(30),
+            sell_price: dec!(2016),
+            trade_amount: dec!(1000.00),
+            ..at_block(54_000_000)
+        };
+        assert_eq!(again.idempotency_key(), Some(key));
+
+        assert_ne!(at_block(54_000_001).idempotency_key(), Some(key));
+        let resized = ArbitrageOpportunity { trade_amount: dec!(5000), ..at_block(54_000_000) };
+        assert_ne!(resized.idempotency_key(), Some(key));
+        let reversed = ArbitrageOpportunity {
+            buy_dex: "Uniswap".to_string(),
+            sell_dex: "SushiSwap".to_string(),
+            ..at_block(54_000_000)
+        };
+        assert_ne!(reversed.idempotency_key(), Some(key));
+        let other_pair = ArbitrageOpportunity { token_pair: "WMATIC/USDC".to_string(), ..at_block(54_000_000) };
+        assert_ne!(other_pair.idempotency_key(), Some(key));
+
+        assert_eq!("Deterministic".parse(), Ok(OpportunityIdStrategy::Deterministic));
+        assert_eq!("random".parse(), Ok(OpportunityIdStrategy::Random));
+        assert!("content".parse::<OpportunityIdStrategy>().is_err());
+    }
+}
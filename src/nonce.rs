@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use ethers::{
+    providers::Middleware,
+    types::{Address, BlockNumber, U256, transaction::eip2718::TypedTransaction},
+};
+use parking_lot::Mutex;
+
+use crate::util::rpc_call;
+
+/// Minimum fee increase (percent) nodes accept for a same-nonce replacement
+pub const MIN_FEE_BUMP_PCT: u64 = 10;
+
+/// Hands out sequential nonces for a single sender so back-to-back transactions
+/// (e.g. the buy and sell legs) don't collide.
+///
+/// The first nonce is seeded from the pending transaction count on chain; after
+/// that nonces are allocated locally. Call `reset` whenever a submission fails
+/// with a nonce error so the local view is resynchronised with the node.
+pub struct NonceManager {
+    address: Address,
+    next_nonce: Mutex<Option<U256>>,
+}
+
+impl NonceManager {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            next_nonce: Mutex::new(None),
+        }
+    }
+
+    /// Reserve the next nonce, seeding from chain state on first use
+    pub async fn next<M: Middleware + 'static>(&self, provider: &M) -> Result<U256> {
+        if self.next_nonce.lock().is_none() {
+            let on_chain = self.pending_count(provider).await?;
+            let mut next_nonce = self.next_nonce.lock();
+            if next_nonce.is_none() {
+                *next_nonce = Some(on_chain);
+            }
+        }
+
+        let mut next_nonce = self.next_nonce.lock();
+        let nonce = next_nonce.expect("nonce seeded above");
+        *next_nonce = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Discard the local counter and re-read the pending nonce from the node
+    pub async fn reset<M: Middleware + 'static>(&self, provider: &M) -> Result<U256> {
+        let on_chain = self.pending_count(provider).await?;
+        *self.next_nonce.lock() = Some(on_chain);
+        log::warn!("Nonce for {:?} reset from chain state to {}", self.address, on_chain);
+        Ok(on_chain)
+    }
+
+    async fn pending_count<M: Middleware + 'static>(&self, provider: &M) -> Result<U256> {
+        rpc_call(|| async {
+            provider
+                .get_transaction_count(self.address, Some(BlockNumber::Pending.into()))
+                .await
+                .context("Failed to fetch pending transaction count")
+        })
+        .await
+    }
+}
+
+/// Raise a transaction's fees by `pct` percent (at least the replacement minimum)
+/// so it can replace a stuck transaction with the same nonce
+pub fn bump_fees(tx: &mut TypedTransaction, pct: u64) {
+    let pct = pct.max(MIN_FEE_BUMP_PCT);
+    let bump = |fee: U256| fee + fee * U256::from(pct) / U256::from(100) + 1;
+
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            inner.max_fee_per_gas = inner.max_fee_per_gas.map(bump);
+            inner.max_priority_fee_per_gas = inner.max_priority_fee_per_gas.map(bump);
+        }
+        TypedTransaction::Legacy(inner) => {
+            inner.gas_price = inner.gas_price.map(bump);
+        }
+        TypedTransaction::Eip2930(inner) => {
+            inner.tx.gas_price = inner.tx.gas_price.map(bump);
+        }
+    }
+}
+
+/// Whether a submission error means our local nonce is out of sync with the node
+pub fn is_nonce_error(error: &impl std::fmt::Display) -> bool {
+    let message = error.to_string().to_lowercase();
+    message.contains("nonce too low")
+        || message.contains("replacement transaction underpriced")
+        || message.contains("already known")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::Provider;
+    use ethers::types::{Eip1559TransactionRequest, TransactionRequest};
+
+    #[tokio::test]
+    async fn test_nonces_seed_from_chain_and_increment() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(7)).unwrap();
+
+        let manager = NonceManager::new(Address::repeat_byte(0x01));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(7));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(8));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(9));
+
+        // Only the first call hits the provider
+        mock.assert_request("eth_getTransactionCount", (Address::repeat_byte(0x01), "pending")).unwrap();
+        assert!(mock.assert_request("eth_getTransactionCount", ()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_resyncs_from_chain() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(12)).unwrap();
+        mock.push(U256::from(3)).unwrap();
+
+        let manager = NonceManager::new(Address::repeat_byte(0x01));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(3));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(4));
+
+        assert_eq!(manager.reset(&provider).await.unwrap(), U256::from(12));
+        assert_eq!(manager.next(&provider).await.unwrap(), U256::from(12));
+    }
+
+    #[test]
+    fn test_bump_fees() {
+        let mut legacy: TypedTransaction = TransactionRequest::new().gas_price(100).into();
+        bump_fees(&mut legacy, 5); // Clamped up to the replacement minimum
+        assert_eq!(legacy.gas_price(), Some(U256::from(111)));
+
+        let mut eip1559: TypedTransaction = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(200)
+            .max_priority_fee_per_gas(30)
+            .into();
+        bump_fees(&mut eip1559, 25);
+        match eip1559 {
+            TypedTransaction::Eip1559(inner) => {
+                assert_eq!(inner.max_fee_per_gas, Some(U256::from(251)));
+                assert_eq!(inner.max_priority_fee_per_gas, Some(U256::from(38)));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_detects_nonce_errors() {
+        assert!(is_nonce_error(&"(code: -32000, message: nonce too low, data: None)"));
+        assert!(is_nonce_error(&"replacement transaction underpriced"));
+        assert!(!is_nonce_error(&"execution reverted"));
+    }
+}
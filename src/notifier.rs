@@ -0,0 +1,318 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+use crate::arbitrage::VenuePrice;
+use crate::config::Config;
+use crate::models::{ArbitrageOpportunity, RouteKey};
+use crate::util::Pct;
+
+#[derive(Debug, Clone)]
+struct RouteSummary {
+    count: usize,
+    best_profit: Decimal,
+    best_spread: Decimal,
+    profit_currency: String,
+}
+
+/// Amount with its currency, to cents for USDC and finer for WETH
+fn format_amount(amount: Decimal, currency: &str) -> String {
+    match currency {
+        "USDC" => format!("{:.2} {}", amount, currency),
+        _ => format!("{:.6} {}", amount, currency),
+    }
+}
+
+/// Decides which alerts are sent and when, independent of the transport.
+///
+/// In per-event mode an alert for a route is suppressed while the previous one
+/// is younger than `min_interval`. In digest mode nothing is sent per event;
+/// opportunities are summarised per route and released by `flush` once every
+/// `digest_interval`.
+#[derive(Debug)]
+pub struct AlertPolicy {
+    min_interval: Duration,
+    digest_interval: Option<Duration>,
+    last_sent: HashMap<RouteKey, DateTime<Utc>>,
+    /// Spread alerts are throttled apart from opportunity alerts
+    last_spread_alert: HashMap<RouteKey, DateTime<Utc>>,
+    pending: HashMap<RouteKey, RouteSummary>,
+    last_flush: DateTime<Utc>,
+}
+
+impl AlertPolicy {
+    pub fn new(min_interval: Duration, digest_interval: Option<Duration>, now: DateTime<Utc>) -> Self {
+        Self {
+            min_interval,
+            digest_interval,
+            last_sent: HashMap::new(),
+            last_spread_alert: HashMap::new(),
+            pending: HashMap::new(),
+            last_flush: now,
+        }
+    }
+
+    /// Record an opportunity, returning the message to send right away (if any)
+    pub fn record(&mut self, opportunity: &ArbitrageOpportunity, now: DateTime<Utc>) -> Option<String> {
+        // One alert stream per route
+        let key = opportunity.route_key();
+
+        if self.digest_interval.is_some() {
+            let summary = self.pending.entry(key).or_insert(RouteSummary {
+                count: 0,
+                best_profit: opportunity.estimated_profit,
+                best_spread: opportunity.price_difference_pct,
+                profit_currency: opportunity.profit_currency.clone(),
+            });
+            summary.count += 1;
+            summary.best_profit = summary.best_profit.max(opportunity.estimated_profit);
+            summary.best_spread = summary.best_spread.max(opportunity.price_difference_pct);
+            return None;
+        }
+
+        if let Some(last) = self.last_sent.get(&key)
+            && now - *last < self.min_interval
+        {
+            log::debug!("Suppressing repeat alert for {} {} -> {}", key.2, key.0, key.1);
+            return None;
+        }
+
+        self.last_sent.insert(key, now);
+        Some(format_alert(opportunity))
+    }
+
+    /// Record a wide spread between venues, returning the alert to send right
+    /// away unless the route alerted within `min_interval`. Spread alerts
+    /// flag market events or bad data, so they bypass the digest.
+    pub fn record_spread(&mut self, spread: &SpreadAlert, now: DateTime<Utc>) -> Option<String> {
+        let key = (spread.buy.0.to_string(), spread.sell.0.to_string(), spread.pair.clone());
+        if let Some(last) = self.last_spread_alert.get(&key)
+            && now - *last < self.min_interval
+        {
+            log::debug!("Suppressing repeat spread alert for {} {} -> {}", key.2, key.0, key.1);
+            return None;
+        }
+
+        self.last_spread_alert.insert(key, now);
+        Some(format!(
+            "⚠️ {} spread {}: {} at {:.2}, {} at {:.2} (not necessarily profitable)",
+            spread.pair,
+            Pct(spread.spread_pct),
+            spread.buy.0,
+            spread.buy.1,
+            spread.sell.0,
+            spread.sell.1
+        ))
+    }
+
+    /// Digest message once the digest interval has elapsed and something is pending
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Option<String> {
+        let interval = self.digest_interval?;
+        if now - self.last_flush < interval {
+            return None;
+        }
+
+        self.last_flush = now;
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let mut routes: Vec<_> = self.pending.drain().collect();
+        routes.sort_by_key(|(_, summary)| std::cmp::Reverse(summary.best_profit));
+
+        let total: usize = routes.iter().map(|(_, summary)| summary.count).sum();
+        let mut message = format!(
+            "📊 {} arbitrage opportunities in the last {} min",
+            total,
+            interval.num_minutes()
+        );
+        for ((buy_dex, sell_dex, pair), summary) in routes {
+            message.push_str(&format!(
+                "\n• {} {} → {}: {}x, best {} ({})",
+                pair,
+                buy_dex,
+                sell_dex,
+                summary.count,
+                format_amount(summary.best_profit, &summary.profit_currency),
+                Pct(summary.best_spread)
+            ));
+        }
+        Some(message)
+    }
+}
+
+/// Venue prices far enough apart to alert on, whether or not trading them
+/// would pay
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpreadAlert {
+    pub pair: String,
+    /// Cheapest venue and its price
+    pub buy: VenuePrice,
+    /// Most expensive venue and its price
+    pub sell: VenuePrice,
+    pub spread_pct: Decimal,
+}
+
+fn format_alert(opportunity: &ArbitrageOpportunity) -> String {
+    format!(
+        "🚀 {} arbitrage: buy on {} at {:.2}, sell on {} at {:.2} ({}), est. profit {}",
+        opportunity.token_pair,
+        opportunity.buy_dex,
+        opportunity.buy_price,
+        opportunity.sell_dex,
+        opportunity.sell_price,
+        Pct(opportunity.price_difference_pct),
+        format_amount(opportunity.estimated_profit, &opportunity.profit_currency)
+    )
+}
+
+/// Sends opportunity alerts to a Discord webhook, throttled by `AlertPolicy`
+pub struct Notifier {
+    client: reqwest::Client,
+    webhook_url: String,
+    policy: AlertPolicy,
+}
+
+impl Notifier {
+    /// Notifier for the configured webhook, or `None` when notifications are off
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let webhook_url = config.discord_webhook_url.clone()?;
+        let digest_interval = match config.notify_digest_minutes {
+            0 => None,
+            minutes => Some(Duration::minutes(minutes as i64)),
+        };
+
+        Some(Self {
+            client: reqwest::Client::new(),
+            webhook_url,
+            policy: AlertPolicy::new(
+                Duration::seconds(config.notify_min_interval_seconds as i64),
+                digest_interval,
+                Utc::now(),
+            ),
+        })
+    }
+
+    pub async fn notify(&mut self, opportunity: &ArbitrageOpportunity) {
+        if let Some(message) = self.policy.record(opportunity, Utc::now()) {
+            self.send_logged(&message).await;
+        }
+    }
+
+    pub async fn notify_spread(&mut self, spread: &SpreadAlert) {
+        if let Some(message) = self.policy.record_spread(spread, Utc::now()) {
+            self.send_logged(&message).await;
+        }
+    }
+
+    /// Report a pair that has gone `quiet` without a profitable opportunity.
+    /// Sent right away: the caller raises it once per quiet spell.
+    pub async fn notify_quiet(&mut self, pair: &str, quiet: Duration) {
+        let message = format!(
+            "🔇 No profitable {} opportunity for {} minutes (quiet market, or a config or data problem?)",
+            pair,
+            quiet.num_minutes()
+        );
+        self.send_logged(&message).await;
+    }
+
+    /// Send the digest if one is due; call once per cycle
+    pub async fn flush(&mut self) {
+        if let Some(message) = self.policy.flush(Utc::now()) {
+            self.send_logged(&message).await;
+        }
+    }
+
+    // Alert delivery must never interrupt the trading loop
+    async fn send_logged(&self, message: &str) {
+        if let Err(e) = self.send(message).await {
+            log::warn!("Failed to send notification: {:#}", e);
+        }
+    }
+
+    async fn send(&self, message: &str) -> Result<()> {
+        self.client
+            .post(&self.webhook_url)
+            .json(&serde_json::json!({ "content": message }))
+            .send()
+            .await
+            .context("Webhook request failed")?
+            .error_for_status()
+            .context("Webhook rejected notification")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn opportunity(buy_dex: &str, sell_dex: &str, profit: Decimal) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            buy_dex: buy_dex.to_string(),
+            sell_dex: sell_dex.to_string(),
+            estimated_profit: profit,
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[test]
+    fn test_throttles_repeat_alerts_per_route() {
+        let start = Utc::now();
+        let mut policy = AlertPolicy::new(Duration::seconds(300), None, start);
+        let forward = opportunity("SushiSwap", "Uniswap", dec!(3));
+        let reverse = opportunity("Uniswap", "SushiSwap", dec!(3));
+
+        assert!(policy.record(&forward, start).is_some());
+        assert!(policy.record(&forward, start + Duration::seconds(60)).is_none());
+        // A different route has its own window
+        assert!(policy.record(&reverse, start + Duration::seconds(60)).is_some());
+        // The window is measured from the last alert actually sent
+        assert!(policy.record(&forward, start + Duration::seconds(299)).is_none());
+        assert!(policy.record(&forward, start + Duration::seconds(300)).is_some());
+    }
+
+    #[test]
+    fn test_spread_alerts_bypass_digest() {
+        let start = Utc::now();
+        let mut policy = AlertPolicy::new(Duration::seconds(300), Some(Duration::minutes(10)), start);
+        let spread = SpreadAlert {
+            pair: "WETH/USDC".to_string(),
+            buy: ("Uniswap", dec!(2000)),
+            sell: ("SushiSwap", dec!(2100)),
+            spread_pct: dec!(0.05),
+        };
+
+        let alert = policy.record_spread(&spread, start).unwrap();
+        assert_eq!(alert, "⚠️ WETH/USDC spread 5.00%: Uniswap at 2000.00, SushiSwap at 2100.00 (not necessarily profitable)");
+        assert!(policy.record_spread(&spread, start + Duration::seconds(60)).is_none());
+        // Opportunity alerts still wait for the digest
+        assert!(policy.record(&opportunity("Uniswap", "SushiSwap", dec!(3)), start).is_none());
+        assert!(policy.record_spread(&spread, start + Duration::seconds(300)).is_some());
+    }
+
+    #[test]
+    fn test_digest_flushes_on_interval() {
+        let start = Utc::now();
+        let mut policy = AlertPolicy::new(Duration::seconds(300), Some(Duration::minutes(10)), start);
+
+        assert!(policy.record(&opportunity("SushiSwap", "Uniswap", dec!(3)), start).is_none());
+        assert!(policy.record(&opportunity("SushiSwap", "Uniswap", dec!(7)), start + Duration::minutes(2)).is_none());
+        assert!(policy.record(&opportunity("Uniswap", "SushiSwap", dec!(1)), start + Duration::minutes(4)).is_none());
+
+        assert!(policy.flush(start + Duration::minutes(9)).is_none());
+
+        let digest = policy.flush(start + Duration::minutes(10)).unwrap();
+        assert!(digest.starts_with("📊 3 arbitrage opportunities in the last 10 min"));
+        assert!(digest.contains("SushiSwap → Uniswap: 2x, best 7.00 USDC"));
+        assert!(digest.contains("Uniswap → SushiSwap: 1x, best 1.00 USDC"));
+
+        // Nothing pending, and the next window starts from the last flush
+        assert!(policy.flush(start + Duration::minutes(20)).is_none());
+        policy.record(&opportunity("SushiSwap", "Uniswap", dec!(2)), start + Duration::minutes(25));
+        assert!(policy.flush(start + Duration::minutes(29)).is_none());
+        assert!(policy.flush(start + Duration::minutes(30)).is_some());
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Context, Result, anyhow, bail};
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::Address,
+    utils::format_ether,
+};
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::database;
+use crate::dex;
+use crate::dex::tokens::{PairTransferFee, TokenRegistry};
+use crate::rpc_budget::RpcCallKind;
+use crate::util::{Pct, metered_call, rpc_call, verify_chain_id};
+
+/// Pass/fail tally for the preflight checks, printed one line per check
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, outcome: Result<String>) {
+        match outcome {
+            Ok(detail) => println!("[PASS] {:<28} {}", name, detail),
+            Err(e) => {
+                self.failures += 1;
+                println!("[FAIL] {:<28} {:#}", name, e);
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        if self.failures > 0 {
+            bail!("{} preflight check(s) failed", self.failures);
+        }
+        println!("All preflight checks passed");
+        Ok(())
+    }
+}
+
+/// Exercise config, RPC, DEX quotes, database and wallet without trading.
+/// Returns an error (non-zero exit) if any check fails.
+pub async fn run() -> Result<()> {
+    let mut report = Report::default();
+
+    let config = match Config::from_env() {
+        Ok(config) => {
+            report.check("config", Ok("all variables parsed".to_string()));
+            config
+        }
+        Err(e) => {
+            report.check("config", Err(e.into()));
+            return report.finish();
+        }
+    };
+
+    report.check("addresses", check_addresses(&config));
+    report.check("rpc", check_rpc(&config).await);
+
+    match TokenRegistry::from_entries(&config.fee_on_transfer_tokens) {
+        Ok(registry) => {
+            let pair = config.token_pair();
+            for token in [&pair.token0, &pair.token1] {
+                let classification = Address::from_str(token)
+                    .map(|address| registry.classify(address).to_string())
+                    .context("Invalid token address");
+                report.check(&format!("token {}", token), classification);
+            }
+            let treatment = registry
+                .pair_transfer_fee(&pair, config.fee_on_transfer_policy)
+                .map(|fee| match fee {
+                    PairTransferFee::None => "no transfer fees".to_string(),
+                    PairTransferFee::Haircut(fee) => format!("quotes discounted {} per leg", Pct(fee)),
+                    PairTransferFee::Skip => "pair will be skipped (fee-on-transfer token)".to_string(),
+                });
+            report.check(&format!("transfers {}", pair.symbol), treatment);
+        }
+        Err(e) => report.check("fee-on-transfer list", Err(e)),
+    }
+
+    match dex::create_clients(&config).await {
+        Ok(venues) => {
+            let pair = config.token_pair();
+            for venue in &venues {
+                let quote = venue
+                    .get_price_for_size(&pair, config.reference_quote_size(venue.name()))
+                    .await
+                    .map(|price| format!("{} {}", pair.symbol, price));
+                report.check(&format!("quote {} ({:?})", venue.name(), venue.venue_kind()), quote);
+            }
+        }
+        Err(e) => report.check("dex clients", Err(e)),
+    }
+
+    report.check("database", check_database(&config).await);
+    report.check("wallet", check_wallet(&config).await);
+
+    report.finish()
+}
+
+fn check_addresses(config: &Config) -> Result<String> {
+    let mut addresses = vec![
+        ("WETH_ADDRESS", config.weth_address.as_str()),
+        ("USDC_ADDRESS", config.usdc_address.as_str()),
+        ("UNISWAP_V3_QUOTER", config.uniswap_v3_quoter_address.as_str()),
+        ("SUSHISWAP_ROUTER", config.sushiswap_router_address.as_str()),
+        ("UNISWAP_V3_ROUTER", config.uniswap_v3_router_address.as_str()),
+    ];
+    if let Some(contract) = &config.arb_contract_address {
+        addresses.push(("ARB_CONTRACT_ADDRESS", contract.as_str()));
+    }
+    if let Some(from) = &config.quote_from_address {
+        addresses.push(("QUOTE_FROM_ADDRESS", from.as_str()));
+    }
+
+    let invalid: Vec<&str> = addresses
+        .iter()
+        .filter(|(_, value)| Address::from_str(value.trim()).is_err())
+        .map(|(field, _)| *field)
+        .collect();
+
+    if invalid.is_empty() {
+        Ok(format!("{} addresses valid", addresses.len()))
+    } else {
+        Err(anyhow!("invalid address in {}", invalid.join(", ")))
+    }
+}
+
+async fn check_rpc(config: &Config) -> Result<String> {
+    let provider = Provider::<Http>::try_from(config.polygon_rpc_url.as_str())
+        .context("Failed to create HTTP provider")?;
+    verify_chain_id(&provider, config.chain_id).await?;
+    let block = metered_call(RpcCallKind::Block, || async { provider.get_block_number().await.context("Failed to get latest block") }).await?;
+
+    Ok(format!("chain id {}, latest block {}", config.chain_id, block))
+}
+
+async fn check_database(config: &Config) -> Result<String> {
+    let db = database::open(config)
+        .await
+        .context("Failed to connect")?;
+    db.check_schema().await.context("Schema migration failed")?;
+
+    Ok("connected, migrations apply cleanly (rolled back)".to_string())
+}
+
+async fn check_wallet(config: &Config) -> Result<String> {
+    let Some(key) = &config.private_key else {
+        return Ok("no WALLET_PRIVATE_KEY, execution disabled".to_string());
+    };
+
+    let wallet = LocalWallet::from_str(key.trim_start_matches("0x"))
+        .context("Invalid WALLET_PRIVATE_KEY")?;
+    let provider = Provider::<Http>::try_from(config.polygon_rpc_url.as_str())
+        .context("Failed to create HTTP provider")?;
+    let balance = rpc_call(|| async {
+        provider.get_balance(wallet.address(), None).await.context("Failed to get wallet balance")
+    })
+    .await?;
+
+    Ok(format!("{:?}, {} MATIC", wallet.address(), format_ether(balance)))
+}
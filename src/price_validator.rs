@@ -1,14 +1,21 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
 use chrono::{DateTime, Utc, Duration};
 
+use crate::clock::{Clock, SystemClock};
+use crate::models::DexHealth;
+use crate::util::Pct;
+
 /// Price validation and sanity checking for arbitrage opportunities
 pub struct PriceValidator {
-    /// Minimum reasonable price for WETH in USDC
+    /// Minimum reasonable price, in quote tokens per base token (USDC per WETH)
     min_price: Decimal,
     
-    /// Maximum reasonable price for WETH in USDC
+    /// Maximum reasonable price, in quote tokens per base token (USDC per WETH)
     max_price: Decimal,
     
     /// Maximum allowed price change percentage between checks
@@ -19,17 +26,40 @@ pub struct PriceValidator {
     
     /// Maximum age of price data before considering it stale
     max_price_age: Duration,
+    
+    /// Time source for validation timestamps and staleness
+    clock: Arc<dyn Clock>,
+    
+    /// How often each check has fired since startup
+    rejections: RejectionCounts,
+    
+    /// External (CEX) mid price for this cycle, if one is configured and answered
+    reference_price: Option<Decimal>,
+    
+    /// Maximum fractional deviation from the reference price
+    max_reference_deviation_pct: Decimal,
+    
+    /// Maximum fractional deviation from the other venues' consensus price
+    max_consensus_deviation_pct: Decimal,
+    
+    /// Latest validated cycle prices per pair, oldest first
+    pair_prices: HashMap<String, VecDeque<Decimal>>,
+    
+    /// Cycle prices kept per pair for volatility
+    volatility_window: usize,
 }
 
 #[derive(Debug, Clone)]
 struct PriceSnapshot {
-    price: Decimal,
+    /// Last validated price, None while the DEX has only ever failed
+    price: Option<Decimal>,
     timestamp: DateTime<Utc>,
     consecutive_errors: u32,
 }
 
 impl PriceValidator {
     /// Create a new price validator with sensible defaults
+    #[allow(dead_code)]
     pub fn new() -> Self {
         Self {
             // Reasonable bounds for WETH/USDC (adjust based on market conditions)
@@ -38,6 +68,13 @@ impl PriceValidator {
             max_price_change_pct: Decimal::try_from(0.15).unwrap(), // 15% max change
             last_prices: HashMap::new(),
             max_price_age: Duration::minutes(5), // 5 minutes max age
+            clock: Arc::new(SystemClock),
+            rejections: RejectionCounts::default(),
+            reference_price: None,
+            max_reference_deviation_pct: Decimal::try_from(0.02).unwrap(), // 2% from the CEX mid
+            max_consensus_deviation_pct: Decimal::try_from(0.01).unwrap(), // 1% from the other venues
+            pair_prices: HashMap::new(),
+            volatility_window: 20,
         }
     }
     
@@ -54,15 +91,67 @@ impl PriceValidator {
             max_price_change_pct,
             last_prices: HashMap::new(),
             max_price_age: Duration::minutes(max_price_age_minutes),
+            clock: Arc::new(SystemClock),
+            rejections: RejectionCounts::default(),
+            reference_price: None,
+            max_reference_deviation_pct: Decimal::try_from(0.02).unwrap(),
+            max_consensus_deviation_pct: Decimal::try_from(0.01).unwrap(),
+            pair_prices: HashMap::new(),
+            volatility_window: 20,
         }
     }
     
+    /// Take timestamps from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Replace the bounds in place, keeping price history and error counts
+    pub fn set_bounds(
+        &mut self,
+        min_price: Decimal,
+        max_price: Decimal,
+        max_price_change_pct: Decimal,
+        max_price_age_minutes: i64,
+    ) {
+        self.min_price = min_price;
+        self.max_price = max_price;
+        self.max_price_change_pct = max_price_change_pct;
+        self.max_price_age = Duration::minutes(max_price_age_minutes);
+    }
+    
+    /// Reject prices further than `max_deviation_pct` from the reference price
+    pub fn with_max_reference_deviation(mut self, max_deviation_pct: Decimal) -> Self {
+        self.max_reference_deviation_pct = max_deviation_pct;
+        self
+    }
+    
+    /// Reject venues further than `max_deviation_pct` from the consensus of
+    /// the others (see `check_consensus`)
+    pub fn with_max_consensus_deviation(mut self, max_deviation_pct: Decimal) -> Self {
+        self.max_consensus_deviation_pct = max_deviation_pct;
+        self
+    }
+    
+    /// Measure volatility over the last `cycles` cycle prices of each pair
+    pub fn with_volatility_window(mut self, cycles: usize) -> Self {
+        self.volatility_window = cycles.max(3);
+        self
+    }
+    
+    /// Set the external reference price for the coming validations, or clear
+    /// it (skipping the check) when none is available this cycle
+    pub fn set_reference_price(&mut self, reference_price: Option<Decimal>) {
+        self.reference_price = reference_price;
+    }
+    
     /// Validate a price from a specific DEX
     pub fn validate_price(&mut self, dex_name: &str, price: Decimal) -> Result<ValidationResult> {
-        let now = Utc::now();
-        
+        let now = self.clock.now();
         // Check 1: Absolute bounds
         if !self.check_absolute_bounds(price) {
+            self.rejections.bounds += 1;
             self.record_error(dex_name);
             return Ok(ValidationResult::Invalid(format!(
                 "Price {} outside reasonable bounds ({}-{})",
@@ -70,30 +159,40 @@ impl PriceValidator {
             )));
         }
         
+        // Check 1b: Deviation from the external reference (if one is set)
+        if let Some(validation_error) = self.check_reference_deviation(price) {
+            self.rejections.reference += 1;
+            self.record_error(dex_name);
+            return Ok(ValidationResult::Invalid(validation_error));
+        }
+        
         // Check 2: Relative change (if we have historical data)
         if let Some(validation_error) = self.check_price_change(dex_name, price) {
+            self.rejections.price_change += 1;
             self.record_error(dex_name);
             return Ok(ValidationResult::Invalid(validation_error));
         }
         
         // Check 3: Price staleness
-        if let Some(last_snapshot) = self.last_prices.get(dex_name) {
-            if now.signed_duration_since(last_snapshot.timestamp) > self.max_price_age {
-                log::warn!("Stale price data for {} (age: {:?})", 
-                    dex_name, 
-                    now.signed_duration_since(last_snapshot.timestamp)
-                );
-            }
+        if let Some(last_snapshot) = self.last_prices.get(dex_name)
+            && now.signed_duration_since(last_snapshot.timestamp) > self.max_price_age
+        {
+            self.rejections.stale += 1;
+            log::warn!("Stale price data for {} (age: {:?})", 
+                dex_name, 
+                now.signed_duration_since(last_snapshot.timestamp)
+            );
         }
         
         // Check 4: Circuit breaker for consecutive errors
-        if let Some(snapshot) = self.last_prices.get(dex_name) {
-            if snapshot.consecutive_errors > 5 {
-                return Ok(ValidationResult::CircuitBreakerTripped(format!(
-                    "Too many consecutive errors for {} ({})",
-                    dex_name, snapshot.consecutive_errors
-                )));
-            }
+        if let Some(snapshot) = self.last_prices.get(dex_name)
+            && snapshot.consecutive_errors > 5
+        {
+            self.rejections.circuit_breaker += 1;
+            return Ok(ValidationResult::CircuitBreakerTripped(format!(
+                "Too many consecutive errors for {} ({})",
+                dex_name, snapshot.consecutive_errors
+            )));
         }
         
         // All checks passed - record the valid price
@@ -107,28 +206,118 @@ impl PriceValidator {
         price >= self.min_price && price <= self.max_price
     }
     
+    /// Check the price against the external reference, when there is one
+    fn check_reference_deviation(&self, price: Decimal) -> Option<String> {
+        let reference = self.reference_price.filter(|reference| *reference > Decimal::ZERO)?;
+        let deviation = (price - reference).abs() / reference;
+        
+        (deviation > self.max_reference_deviation_pct).then(|| format!(
+            "Price {} deviates {} from reference {}",
+            price,
+            Pct(deviation),
+            reference
+        ))
+    }
+    
     /// Check if price change is reasonable compared to last price
     fn check_price_change(&self, dex_name: &str, price: Decimal) -> Option<String> {
-        if let Some(last_snapshot) = self.last_prices.get(dex_name) {
-            let price_change = (price - last_snapshot.price).abs() / last_snapshot.price;
-            
-            if price_change > self.max_price_change_pct {
-                return Some(format!(
-                    "Large price change detected for {} ({:.2}%): {} -> {}",
-                    dex_name,
-                    price_change * Decimal::from(100),
-                    last_snapshot.price,
-                    price
-                ));
+        let last_price = self.last_prices.get(dex_name)?.price?;
+        let price_change = (price - last_price).abs() / last_price;
+        
+        (price_change > self.max_price_change_pct).then(|| format!(
+            "Large price change detected for {} ({}): {} -> {}",
+            dex_name,
+            Pct(price_change),
+            last_price,
+            price
+        ))
+    }
+    
+    /// Cross-check a cycle's validated prices: each venue is compared with the
+    /// liquidity-weighted mean price of all the other venues, and rejected if
+    /// further from it than the max consensus deviation. Leaving the venue
+    /// itself out keeps a deep outlier from dragging the consensus towards
+    /// it, so this needs at least three venues. Venues that reported no
+    /// liquidity weigh as much as the average of those that did (equal weights
+    /// when none did). Rejections count towards the circuit breaker.
+    ///
+    /// Returns each rejected venue with the reason.
+    pub fn check_consensus<'a>(&mut self, prices: &[(&'a str, Decimal, Option<Decimal>)]) -> Vec<(&'a str, String)> {
+        if prices.len() < 3 {
+            return Vec::new();
+        }
+
+        let reported: Vec<Decimal> = prices.iter().filter_map(|&(_, _, liquidity)| liquidity).collect();
+        let default_weight = if reported.is_empty() {
+            Decimal::ONE
+        } else {
+            reported.iter().sum::<Decimal>() / Decimal::from(reported.len())
+        };
+        let weighted: Vec<(Decimal, Decimal)> = prices
+            .iter()
+            .map(|&(_, price, liquidity)| (price, liquidity.unwrap_or(default_weight).max(Decimal::ZERO)))
+            .collect();
+
+        let mut rejected = Vec::new();
+        for (index, &(dex_name, price, _)) in prices.iter().enumerate() {
+            let others = weighted
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, &price_and_weight)| price_and_weight);
+            let Some(consensus) = weighted_mean(others) else {
+                continue;
+            };
+
+            let deviation = (price - consensus).abs() / consensus;
+            if deviation > self.max_consensus_deviation_pct {
+                self.rejections.consensus += 1;
+                self.record_error(dex_name);
+                rejected.push((dex_name, format!(
+                    "Price {} deviates {} from the other venues' consensus {}",
+                    price,
+                    Pct(deviation),
+                    consensus.round_dp(6)
+                )));
             }
         }
-        None
+
+        rejected
+    }
+    
+    /// Add a pair's validated price for this cycle to its rolling window
+    pub fn record_pair_price(&mut self, pair: &str, price: Decimal) {
+        let window = self.pair_prices.entry(pair.to_string()).or_default();
+        window.push_back(price);
+        while window.len() > self.volatility_window {
+            window.pop_front();
+        }
+    }
+    
+    /// Standard deviation, in basis points, of the pair's cycle-to-cycle price
+    /// moves over the rolling window. None until three cycles are in.
+    pub fn volatility_bps(&self, pair: &str) -> Option<Decimal> {
+        let window = self.pair_prices.get(pair)?;
+        let moves: Vec<Decimal> = window
+            .iter()
+            .zip(window.iter().skip(1))
+            .filter(|(before, _)| !before.is_zero())
+            .map(|(before, after)| (after - before) / before * Decimal::from(10_000))
+            .collect();
+        if moves.len() < 2 {
+            return None;
+        }
+
+        let count = Decimal::from(moves.len());
+        let mean = moves.iter().sum::<Decimal>() / count;
+        let variance = moves.iter().map(|change| (change - mean) * (change - mean)).sum::<Decimal>() / count;
+        Decimal::try_from(variance.to_f64()?.sqrt()).ok().map(|bps| bps.round_dp(4))
     }
     
     /// Record a valid price
     fn record_valid_price(&mut self, dex_name: &str, price: Decimal, timestamp: DateTime<Utc>) {
         self.last_prices.insert(dex_name.to_string(), PriceSnapshot {
-            price,
+            price: Some(price),
             timestamp,
             consecutive_errors: 0,
         });
@@ -139,20 +328,58 @@ impl PriceValidator {
         if let Some(snapshot) = self.last_prices.get_mut(dex_name) {
             snapshot.consecutive_errors += 1;
         } else {
-            // First time seeing this DEX and it's an error
+            // First time seeing this DEX and it's an error: no price to compare against yet
             self.last_prices.insert(dex_name.to_string(), PriceSnapshot {
-                price: Decimal::ZERO,
-                timestamp: Utc::now(),
+                price: None,
+                timestamp: self.clock.now(),
                 consecutive_errors: 1,
             });
         }
     }
     
+    /// Count a failed or timed-out price fetch towards the DEX's circuit breaker
+    pub fn record_fetch_error(&mut self, dex_name: &str) {
+        self.record_error(dex_name);
+    }
+    
+    /// Whether the DEX's last price was validated at or after `fetched_after`
+    /// and is still within the max price age at `now`. A venue whose latest
+    /// fetch failed or was rejected is never fresh.
+    pub fn is_fresh(&self, dex_name: &str, fetched_after: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.last_prices.get(dex_name).is_some_and(|snapshot| {
+            snapshot.consecutive_errors == 0
+                && snapshot.timestamp >= fetched_after
+                && now.signed_duration_since(snapshot.timestamp) <= self.max_price_age
+        })
+    }
+    
+    /// Circuit-breaker state of a DEX, for persisting. A DEX without a valid
+    /// price yet is stored with a zero price.
+    pub fn health(&self, dex_name: &str) -> Option<DexHealth> {
+        self.last_prices.get(dex_name).map(|snapshot| DexHealth {
+            dex_name: dex_name.to_string(),
+            last_price: snapshot.price.unwrap_or(Decimal::ZERO),
+            last_updated: snapshot.timestamp,
+            consecutive_errors: snapshot.consecutive_errors.try_into().unwrap_or(i32::MAX),
+        })
+    }
+    
+    /// Reload persisted circuit-breaker state, e.g. after a restart
+    pub fn restore(&mut self, records: impl IntoIterator<Item = DexHealth>) {
+        for record in records {
+            self.last_prices.insert(record.dex_name, PriceSnapshot {
+                price: Some(record.last_price).filter(|price| *price > Decimal::ZERO),
+                timestamp: record.last_updated,
+                consecutive_errors: record.consecutive_errors.max(0) as u32,
+            });
+        }
+    }
+    
     /// Get the last valid price for a DEX
+    #[allow(dead_code)]
     pub fn get_last_price(&self, dex_name: &str) -> Option<(Decimal, DateTime<Utc>)> {
-        self.last_prices
-            .get(dex_name)
-            .map(|snapshot| (snapshot.price, snapshot.timestamp))
+        let snapshot = self.last_prices.get(dex_name)?;
+        snapshot.price.map(|price| (price, snapshot.timestamp))
     }
     
     /// Check if a DEX has too many consecutive errors
@@ -164,6 +391,7 @@ impl PriceValidator {
     }
     
     /// Reset error count for a DEX (call this when connection is restored)
+    #[allow(dead_code)]
     pub fn reset_error_count(&mut self, dex_name: &str) {
         if let Some(snapshot) = self.last_prices.get_mut(dex_name) {
             snapshot.consecutive_errors = 0;
@@ -177,11 +405,12 @@ impl PriceValidator {
             active_dexes: 0,
             circuit_breaker_tripped: 0,
             stale_prices: 0,
+            rejections: self.rejections,
         };
         
-        let now = Utc::now();
+        let now = self.clock.now();
         
-        for (_, snapshot) in &self.last_prices {
+        for snapshot in self.last_prices.values() {
             if snapshot.consecutive_errors == 0 {
                 stats.active_dexes += 1;
             }
@@ -199,6 +428,14 @@ impl PriceValidator {
     }
 }
 
+/// Mean of `(price, weight)` pairs by weight, None without positive weight
+fn weighted_mean(prices: impl Iterator<Item = (Decimal, Decimal)>) -> Option<Decimal> {
+    let (weighted_sum, total_weight) = prices.fold((Decimal::ZERO, Decimal::ZERO), |(sum, total), (price, weight)| {
+        (sum + price * weight, total + weight)
+    });
+    (total_weight > Decimal::ZERO).then(|| weighted_sum / total_weight)
+}
+
 /// Result of price validation
 #[derive(Debug, Clone)]
 pub enum ValidationResult {
@@ -210,10 +447,86 @@ pub enum ValidationResult {
 /// Statistics about validation state
 #[derive(Debug, Clone)]
 pub struct ValidationStats {
+    #[allow(dead_code)]
     pub total_dexes: usize,
     pub active_dexes: usize,
     pub circuit_breaker_tripped: usize,
     pub stale_prices: usize,
+    pub rejections: RejectionCounts,
+}
+
+/// Times each validation check has fired. Stale prices are only warned
+/// about, not rejected, but are counted alongside the rejections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RejectionCounts {
+    pub bounds: u64,
+    pub reference: u64,
+    pub price_change: u64,
+    pub stale: u64,
+    pub circuit_breaker: u64,
+    pub consensus: u64,
+}
+
+impl fmt::Display for RejectionCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bounds={} reference={} change={} stale={} breaker={} consensus={}",
+            self.bounds, self.reference, self.price_change, self.stale, self.circuit_breaker, self.consensus
+        )
+    }
+}
+
+/// Outcome of one venue's price fetch in a cycle
+#[derive(Debug, Clone, PartialEq)]
+pub enum VenueStatus {
+    Valid(Decimal),
+    TimedOut,
+    /// The node's answer did not have the expected shape
+    Malformed,
+    FetchFailed,
+    Rejected,
+    CircuitBreakerTripped,
+}
+
+impl fmt::Display for VenueStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VenueStatus::Valid(price) => write!(f, "ok@{}", price),
+            VenueStatus::TimedOut => f.write_str("timeout"),
+            VenueStatus::Malformed => f.write_str("malformed"),
+            VenueStatus::FetchFailed => f.write_str("error"),
+            VenueStatus::Rejected => f.write_str("rejected"),
+            VenueStatus::CircuitBreakerTripped => f.write_str("breaker"),
+        }
+    }
+}
+
+/// Per-venue status of a cycle plus the validator's running totals,
+/// logged as one `key=value` line every cycle
+pub struct CycleStatus<'a> {
+    pub venues: Vec<(&'a str, VenueStatus)>,
+    pub stats: ValidationStats,
+}
+
+impl fmt::Display for CycleStatus<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("venues")?;
+        for (name, status) in &self.venues {
+            write!(f, " {}={}", name, status)?;
+        }
+        let valid = self.venues.iter().filter(|(_, status)| matches!(status, VenueStatus::Valid(_))).count();
+        write!(
+            f,
+            " valid={}/{} active={} tripped={} stale={} rejections[{}]",
+            valid,
+            self.venues.len(),
+            self.stats.active_dexes,
+            self.stats.circuit_breaker_tripped,
+            self.stats.stale_prices,
+            self.stats.rejections
+        )
+    }
 }
 
 impl ValidationResult {
@@ -232,7 +545,13 @@ impl ValidationResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
+    use chrono::TimeZone;
     use rust_decimal_macros::dec;
+
+    fn mock_clock() -> Arc<MockClock> {
+        Arc::new(MockClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()))
+    }
     
     #[test]
     fn test_price_bounds() {
@@ -249,6 +568,8 @@ mod tests {
         // Too high
         let result = validator.validate_price("test_dex", dec!(15000)).unwrap();
         assert!(!result.is_valid());
+        
+        assert_eq!(validator.get_stats().rejections.bounds, 2);
     }
     
     #[test]
@@ -266,6 +587,10 @@ mod tests {
         // Large change - should be invalid
         let result = validator.validate_price("test_dex", dec!(3500)).unwrap();
         assert!(!result.is_valid());
+        
+        let rejections = validator.get_stats().rejections;
+        assert_eq!(rejections.price_change, 1);
+        assert_eq!(rejections.bounds, 0);
     }
     
     #[test]
@@ -284,4 +609,168 @@ mod tests {
         validator.reset_error_count("test_dex");
         assert!(!validator.is_circuit_breaker_tripped("test_dex"));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_freshness_boundary() {
+        let clock = mock_clock();
+        let mut validator = PriceValidator::new().with_clock(clock.clone());
+        let cycle_start = clock.now();
+        validator.validate_price("test_dex", dec!(2000)).unwrap();
+        
+        // Exactly at the max age is still fresh, a second later is not
+        assert!(validator.is_fresh("test_dex", cycle_start, cycle_start + Duration::minutes(5)));
+        assert!(!validator.is_fresh("test_dex", cycle_start, cycle_start + Duration::minutes(5) + Duration::seconds(1)));
+        
+        // A price from before this cycle does not count, however recent
+        assert!(!validator.is_fresh("test_dex", cycle_start + Duration::seconds(1), cycle_start + Duration::seconds(1)));
+        
+        // Nor does one whose latest fetch failed
+        validator.record_fetch_error("test_dex");
+        assert!(!validator.is_fresh("test_dex", cycle_start, cycle_start));
+        assert!(!validator.is_fresh("other_dex", cycle_start, cycle_start));
+    }
+    
+    #[test]
+    fn test_prices_go_stale_as_the_clock_advances() {
+        let clock = mock_clock();
+        let mut validator = PriceValidator::new().with_clock(clock.clone());
+        validator.validate_price("test_dex", dec!(2000)).unwrap();
+        assert_eq!(validator.get_stats().stale_prices, 0);
+
+        clock.advance(Duration::minutes(5));
+        assert_eq!(validator.get_stats().stale_prices, 0);
+        clock.advance(Duration::seconds(1));
+        assert_eq!(validator.get_stats().stale_prices, 1);
+
+        // A late price is still accepted, but counted as stale
+        assert!(validator.validate_price("test_dex", dec!(2001)).unwrap().is_valid());
+        let stats = validator.get_stats();
+        assert_eq!((stats.stale_prices, stats.rejections.stale), (0, 1));
+    }
+
+    #[test]
+    fn test_restores_circuit_breaker_state() {
+        let mut validator = PriceValidator::new();
+        validator.validate_price("test_dex", dec!(2000)).unwrap();
+        for _ in 0..6 {
+            validator.record_fetch_error("test_dex");
+        }
+        let health = validator.health("test_dex").unwrap();
+        assert_eq!((health.last_price, health.consecutive_errors), (dec!(2000), 6));
+        
+        let mut restarted = PriceValidator::new();
+        restarted.restore([health]);
+        assert!(restarted.is_circuit_breaker_tripped("test_dex"));
+        assert_eq!(restarted.get_last_price("test_dex").unwrap().0, dec!(2000));
+    }
+
+    #[test]
+    fn test_valid_price_after_a_first_error() {
+        let mut validator = PriceValidator::new();
+        validator.record_fetch_error("test_dex");
+        assert_eq!(validator.get_last_price("test_dex"), None);
+        assert!(validator.validate_price("test_dex", dec!(2000)).unwrap().is_valid());
+
+        // Nor does persisted error-only state bring back a zero price
+        let mut failing = PriceValidator::new();
+        failing.record_fetch_error("test_dex");
+        let health = failing.health("test_dex").unwrap();
+        assert_eq!(health.last_price, Decimal::ZERO);
+        let mut restarted = PriceValidator::new();
+        restarted.restore([health]);
+        assert!(restarted.validate_price("test_dex", dec!(2000)).unwrap().is_valid());
+    }
+
+    #[test]
+    fn test_reference_price_deviation() {
+        let mut validator = PriceValidator::new().with_max_reference_deviation(dec!(0.01));
+        
+        // No reference yet: only the other checks apply
+        assert!(validator.validate_price("test_dex", dec!(2100)).unwrap().is_valid());
+        
+        validator.set_reference_price(Some(dec!(2000)));
+        assert!(validator.validate_price("test_dex", dec!(2015)).unwrap().is_valid());
+        
+        let result = validator.validate_price("test_dex", dec!(2030)).unwrap();
+        assert!(result.error_message().unwrap().contains("deviates 1.50% from reference 2000"));
+        assert_eq!(validator.get_stats().rejections.reference, 1);
+    }
+    
+    #[test]
+    fn test_consensus_rejects_outlier_among_several_venues() {
+        let mut validator = PriceValidator::new().with_max_consensus_deviation(dec!(0.01));
+        
+        // QuickSwap is 3% off; the rest agree within the limit
+        let prices = [
+            ("Uniswap", dec!(2000), Some(dec!(5000000))),
+            ("SushiSwap", dec!(2004), Some(dec!(1000000))),
+            ("QuickSwap", dec!(2060), Some(dec!(2000000))),
+            ("ApeSwap", dec!(1996), None),
+        ];
+        let rejected = validator.check_consensus(&prices);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].0, "QuickSwap");
+        assert!(rejected[0].1.contains("from the other venues' consensus"));
+        assert_eq!(validator.get_stats().rejections.consensus, 1);
+        assert!(!validator.is_fresh("QuickSwap", Utc::now() - Duration::minutes(1), Utc::now()));
+        
+        // The consensus leans towards the deeper venues
+        let deep = [
+            ("Uniswap", dec!(2000), Some(dec!(9000000))),
+            ("SushiSwap", dec!(2030), Some(dec!(100000))),
+            ("QuickSwap", dec!(2001), Some(dec!(9000000))),
+        ];
+        let rejected: Vec<_> = validator.check_consensus(&deep).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(rejected, ["SushiSwap"]);
+        
+        // Two venues cannot outvote each other
+        assert!(validator.check_consensus(&prices[2..]).is_empty());
+    }
+    
+    #[test]
+    fn test_volatility_over_rolling_window() {
+        let mut validator = PriceValidator::new().with_volatility_window(4);
+        validator.record_pair_price("WETH/USDC", dec!(2000));
+        validator.record_pair_price("WETH/USDC", dec!(2002));
+        assert_eq!(validator.volatility_bps("WETH/USDC"), None);
+        
+        // Moves of +10 and -10 bps
+        validator.record_pair_price("WETH/USDC", dec!(2000));
+        assert_eq!(validator.volatility_bps("WETH/USDC"), Some(dec!(9.995)));
+        
+        // Only the last four prices count: 2000, 2000, 2000, 2000
+        for _ in 0..3 {
+            validator.record_pair_price("WETH/USDC", dec!(2000));
+        }
+        assert_eq!(validator.volatility_bps("WETH/USDC"), Some(Decimal::ZERO));
+        assert_eq!(validator.volatility_bps("WMATIC/USDC"), None);
+    }
+    
+    #[test]
+    fn test_cycle_status_line() {
+        let status = CycleStatus {
+            venues: vec![
+                ("Uniswap", VenueStatus::Valid(dec!(2001.25))),
+                ("SushiSwap", VenueStatus::TimedOut),
+                ("QuickSwap", VenueStatus::CircuitBreakerTripped),
+            ],
+            stats: ValidationStats {
+                total_dexes: 3,
+                active_dexes: 1,
+                circuit_breaker_tripped: 1,
+                stale_prices: 0,
+                rejections: RejectionCounts {
+                    price_change: 2,
+                    circuit_breaker: 1,
+                    ..RejectionCounts::default()
+                },
+            },
+        };
+
+        assert_eq!(
+            status.to_string(),
+            "venues Uniswap=ok@2001.25 SushiSwap=timeout QuickSwap=breaker valid=1/3 active=1 tripped=1 stale=0 \
+             rejections[bounds=0 reference=0 change=2 stale=0 breaker=1 consensus=0]"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::models::ArbitrageOpportunity;
+
+/// Capital available to a cycle's trades, per funding currency (an
+/// opportunity's `profit_currency`, which its round trip starts and ends in)
+pub type Balances = HashMap<String, Decimal>;
+
+/// How a cycle's profitable opportunities are prioritised for execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankStrategy {
+    /// Highest absolute net profit first
+    #[default]
+    Profit,
+    /// Highest net profit per unit of capital first, so limited capital goes
+    /// to the trades that earn the most from it
+    Capital,
+}
+
+impl FromStr for RankStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "profit" => Ok(RankStrategy::Profit),
+            "capital" => Ok(RankStrategy::Capital),
+            other => Err(format!("unknown ranking strategy {:?} (expected profit or capital)", other)),
+        }
+    }
+}
+
+/// Orders execution candidates. The planner then takes them in this order,
+/// skipping any that no longer fit the cycle's trade, gas or capital limits.
+pub trait Ranker: Send + Sync {
+    /// Ranker name used in logs
+    fn name(&self) -> &'static str;
+
+    /// `candidates` (indices into `opportunities`), best first
+    fn rank(&self, opportunities: &[ArbitrageOpportunity], candidates: &[usize]) -> Vec<usize>;
+}
+
+/// Ranks by absolute net profit
+pub struct ProfitRanker;
+
+impl Ranker for ProfitRanker {
+    fn name(&self) -> &'static str {
+        "profit"
+    }
+
+    fn rank(&self, opportunities: &[ArbitrageOpportunity], candidates: &[usize]) -> Vec<usize> {
+        let mut ranked = candidates.to_vec();
+        ranked.sort_by(|&a, &b| opportunities[b].estimated_profit.cmp(&opportunities[a].estimated_profit));
+        ranked
+    }
+}
+
+/// Greedy capital allocation: ranks by net profit per unit of trade size,
+/// breaking ties by absolute profit. Taking trades in this order until the
+/// capital runs out maximizes total profit whenever trade sizes divide the
+/// capital evenly, and stays close otherwise.
+pub struct CapitalRanker;
+
+impl CapitalRanker {
+    fn return_on_capital(opportunity: &ArbitrageOpportunity) -> Decimal {
+        if opportunity.trade_amount <= Decimal::ZERO {
+            return Decimal::ZERO;
+        }
+        opportunity.estimated_profit / opportunity.trade_amount
+    }
+}
+
+impl Ranker for CapitalRanker {
+    fn name(&self) -> &'static str {
+        "capital"
+    }
+
+    fn rank(&self, opportunities: &[ArbitrageOpportunity], candidates: &[usize]) -> Vec<usize> {
+        let mut ranked = candidates.to_vec();
+        ranked.sort_by(|&a, &b| {
+            let (a, b) = (&opportunities[a], &opportunities[b]);
+            match Self::return_on_capital(b).cmp(&Self::return_on_capital(a)) {
+                Ordering::Equal => b.estimated_profit.cmp(&a.estimated_profit),
+                order => order,
+            }
+        });
+        ranked
+    }
+}
+
+pub fn from_strategy(strategy: RankStrategy) -> Box<dyn Ranker> {
+    match strategy {
+        RankStrategy::Profit => Box::new(ProfitRanker),
+        RankStrategy::Capital => Box::new(CapitalRanker),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn opportunity(size: Decimal, profit: Decimal) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            trade_amount: size,
+            estimated_profit: profit,
+            gas_cost: dec!(1),
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[test]
+    fn test_capital_ranker_prefers_return_on_capital() {
+        let opportunities = [
+            opportunity(dec!(3000), dec!(12)), // 0.40%
+            opportunity(dec!(1000), dec!(6)),  // 0.60%
+            opportunity(dec!(2000), dec!(9)),  // 0.45%
+            opportunity(dec!(500), dec!(3)),   // 0.60%, less profit than the tie
+        ];
+        let all = [0, 1, 2, 3];
+
+        assert_eq!(ProfitRanker.rank(&opportunities, &all), [0, 2, 1, 3]);
+        assert_eq!(CapitalRanker.rank(&opportunities, &all), [1, 3, 2, 0]);
+        assert_eq!(CapitalRanker.rank(&opportunities, &[0, 2]), [2, 0]);
+    }
+}
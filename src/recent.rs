@@ -0,0 +1,68 @@
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::models::ArbitrageOpportunity;
+
+/// The last `capacity` recorded opportunities, newest last, kept whether or
+/// not a database records them too. Cloning shares the same buffer, so
+/// readers see what the bot pushes.
+#[derive(Debug, Clone)]
+pub struct RecentOpportunities {
+    buffer: Arc<RwLock<VecDeque<ArbitrageOpportunity>>>,
+    capacity: usize,
+}
+
+impl RecentOpportunities {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(RwLock::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// Append, evicting the oldest entry once full. A zero capacity keeps nothing.
+    pub fn push(&self, opportunity: ArbitrageOpportunity) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut buffer = self.buffer.write();
+        if buffer.len() == self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(opportunity);
+    }
+
+    /// Up to `limit` opportunities, newest first
+    pub fn latest(&self, limit: usize) -> Vec<ArbitrageOpportunity> {
+        self.buffer.read().iter().rev().take(limit).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn opportunity(trade_amount: u32) -> ArbitrageOpportunity {
+        ArbitrageOpportunity { trade_amount: Decimal::from(trade_amount), ..ArbitrageOpportunity::sample() }
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_full() {
+        let recent = RecentOpportunities::new(2);
+        let reader = recent.clone();
+        for size in [100, 200, 300] {
+            recent.push(opportunity(size));
+        }
+
+        let sizes: Vec<Decimal> = reader.latest(10).iter().map(|o| o.trade_amount).collect();
+        assert_eq!(sizes, vec![Decimal::from(300), Decimal::from(200)]);
+        assert_eq!(reader.latest(1).len(), 1);
+
+        let disabled = RecentOpportunities::new(0);
+        disabled.push(opportunity(100));
+        assert!(disabled.latest(10).is_empty());
+    }
+}
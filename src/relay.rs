@@ -0,0 +1,136 @@
+use anyhow::{Context, Result, anyhow};
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{Bytes, H256, U64},
+    utils::keccak256,
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+use crate::config::redact_url;
+use crate::rpc_budget::RpcCallKind;
+use crate::util::metered_call;
+
+/// Number of consecutive blocks a bundle is submitted for, so a single missed
+/// block doesn't require waiting for the executor's stuck-transaction timeout
+const BUNDLE_BLOCK_SPAN: u64 = 3;
+
+/// Where signed transactions are sent.
+///
+/// `Public` broadcasts through the regular RPC into the public mempool, where
+/// arbitrage transactions can be front-run or sandwiched. `PrivateRelay` hands
+/// them to a bundle relay (`PRIVATE_RELAY_URL`) via `eth_sendBundle`, so they are
+/// only revealed once included in a block.
+pub enum TxSubmitter {
+    Public(Arc<Provider<Http>>),
+    PrivateRelay {
+        provider: Arc<Provider<Http>>,
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+impl TxSubmitter {
+    pub fn new(provider: Arc<Provider<Http>>, relay_url: Option<&str>) -> Self {
+        match relay_url {
+            Some(url) => {
+                log::info!("Submitting transactions through private relay {}", redact_url(url));
+                TxSubmitter::PrivateRelay {
+                    provider,
+                    client: reqwest::Client::new(),
+                    url: url.to_string(),
+                }
+            }
+            None => TxSubmitter::Public(provider),
+        }
+    }
+
+    /// Submit one signed transaction, returning its hash
+    pub async fn submit(&self, raw_tx: Bytes) -> Result<H256> {
+        match self {
+            TxSubmitter::Public(provider) => {
+                let pending = provider.send_raw_transaction(raw_tx).await
+                    .context("Failed to submit transaction")?;
+                Ok(pending.tx_hash())
+            }
+            TxSubmitter::PrivateRelay { .. } => {
+                let hash = H256::from(keccak256(&raw_tx));
+                self.submit_bundle(&[raw_tx]).await?;
+                Ok(hash)
+            }
+        }
+    }
+
+    /// Submit signed transactions as one ordered bundle targeting the next blocks.
+    /// With the public submitter they are broadcast individually, in order.
+    pub async fn submit_bundle(&self, raw_txs: &[Bytes]) -> Result<Vec<H256>> {
+        let (provider, client, url) = match self {
+            TxSubmitter::Public(provider) => {
+                let mut hashes = Vec::with_capacity(raw_txs.len());
+                for raw_tx in raw_txs {
+                    let pending = provider.send_raw_transaction(raw_tx.clone()).await
+                        .context("Failed to submit transaction")?;
+                    hashes.push(pending.tx_hash());
+                }
+                return Ok(hashes);
+            }
+            TxSubmitter::PrivateRelay { provider, client, url } => (provider, client, url),
+        };
+
+        let current_block = metered_call(RpcCallKind::Block, || async {
+            provider.get_block_number().await
+                .context("Failed to fetch block number for bundle target")
+        })
+        .await?;
+
+        for offset in 1..=BUNDLE_BLOCK_SPAN {
+            let target = current_block + offset;
+            let response: Value = client
+                .post(url)
+                .json(&bundle_request(raw_txs, target))
+                .send()
+                .await
+                .context("Failed to reach private relay")?
+                .error_for_status()
+                .context("Private relay rejected request")?
+                .json()
+                .await
+                .context("Invalid response from private relay")?;
+
+            if let Some(error) = response.get("error") {
+                return Err(anyhow!("Private relay rejected bundle for block {}: {}", target, error));
+            }
+            log::debug!("Bundle submitted for block {}: {}", target, response["result"]);
+        }
+
+        Ok(raw_txs.iter().map(|raw_tx| H256::from(keccak256(raw_tx))).collect())
+    }
+}
+
+/// JSON-RPC body for `eth_sendBundle`
+fn bundle_request(raw_txs: &[Bytes], block: U64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_sendBundle",
+        "params": [{
+            "txs": raw_txs,
+            "blockNumber": block,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_request_format() {
+        let txs = vec![Bytes::from(vec![0x02, 0xf8]), Bytes::from(vec![0xab])];
+        let request = bundle_request(&txs, U64::from(50_000_001u64));
+
+        assert_eq!(request["method"], "eth_sendBundle");
+        assert_eq!(request["params"][0]["txs"], json!(["0x02f8", "0xab"]));
+        assert_eq!(request["params"][0]["blockNumber"], "0x2faf081");
+    }
+}
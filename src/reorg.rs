@@ -0,0 +1,220 @@
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{BlockNumber, H256},
+};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::database::OpportunityStore;
+use crate::models::{ArbitrageOpportunity, ExecutionStatus};
+use crate::rpc_budget::RpcCallKind;
+use crate::util::metered_call;
+
+/// Block lookups for recording opportunities against the chain and re-checking them
+#[async_trait]
+pub trait ChainView: Send + Sync {
+    /// Number and hash of the current head
+    async fn head(&self) -> Result<(u64, H256)>;
+
+    /// Hash of the canonical block at `number`, if the node has it
+    async fn block_hash(&self, number: u64) -> Result<Option<H256>>;
+}
+
+#[async_trait]
+impl ChainView for Provider<Http> {
+    async fn head(&self) -> Result<(u64, H256)> {
+        let block = metered_call(RpcCallKind::Block, || async {
+            self.get_block(BlockNumber::Latest).await.context("Failed to get latest block")
+        })
+        .await?
+        .ok_or_else(|| anyhow!("Node returned no latest block"))?;
+
+        match (block.number, block.hash) {
+            (Some(number), Some(hash)) => Ok((number.as_u64(), hash)),
+            _ => Err(anyhow!("Latest block is missing its number or hash")),
+        }
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<H256>> {
+        let block = metered_call(RpcCallKind::Block, || async {
+            self.get_block(number).await.with_context(|| format!("Failed to get block {}", number))
+        })
+        .await?;
+        Ok(block.and_then(|block| block.hash))
+    }
+}
+
+/// Hex form a block hash is stored in
+pub fn hash_text(hash: H256) -> String {
+    format!("{:?}", hash)
+}
+
+/// Re-checks recorded opportunities once their block is `confirmations`
+/// deep, and marks those whose block was reorged out as `reorged`
+pub struct ReorgChecker {
+    chain: Arc<dyn ChainView>,
+    store: Arc<dyn OpportunityStore>,
+    confirmations: u64,
+    /// Saved opportunities awaiting confirmation, oldest block first
+    pending: Mutex<VecDeque<ArbitrageOpportunity>>,
+}
+
+impl ReorgChecker {
+    pub fn new(chain: Arc<dyn ChainView>, store: Arc<dyn OpportunityStore>, confirmations: u64) -> Self {
+        log::info!("Checking recorded opportunities for reorgs after {} confirmations", confirmations);
+        Self {
+            chain,
+            store,
+            confirmations,
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Queue a saved opportunity for confirmation. Only its execution status
+    /// is rewritten if it gets reorged, so later settlement is kept.
+    pub fn track(&self, opportunity: ArbitrageOpportunity) {
+        if opportunity.block_number.is_some() && opportunity.block_hash.is_some() {
+            self.pending.lock().push_back(opportunity);
+        }
+    }
+
+    /// Verify every queued opportunity that now has enough confirmations,
+    /// returning how many were reorged out
+    pub async fn check(&self) -> Result<usize> {
+        let (head, _) = self.chain.head().await?;
+        let mut reorged = 0;
+
+        loop {
+            let due = {
+                let mut pending = self.pending.lock();
+                match pending.front().and_then(|opportunity| opportunity.block_number) {
+                    Some(block) if block as u64 + self.confirmations <= head => pending.pop_front(),
+                    _ => None,
+                }
+            };
+            let Some(mut opportunity) = due else {
+                break;
+            };
+            let block = opportunity.block_number.unwrap_or_default() as u64;
+
+            let canonical = match self.chain.block_hash(block).await {
+                Ok(hash) => hash,
+                Err(e) => {
+                    // Retry on the next check rather than guessing
+                    self.pending.lock().push_front(opportunity);
+                    return Err(e);
+                }
+            };
+            if canonical.map(hash_text) == opportunity.block_hash {
+                log::debug!("Opportunity {} confirmed at block {}", opportunity.id, block);
+                continue;
+            }
+
+            log::warn!(
+                "Block {} of opportunity {} was reorged out ({} -> {}), marking it reorged",
+                block,
+                opportunity.id,
+                opportunity.block_hash.as_deref().unwrap_or_default(),
+                canonical.map(hash_text).unwrap_or_else(|| "missing".to_string())
+            );
+            opportunity.execution_status = Some(ExecutionStatus::Reorged.as_str().to_string());
+            self.store.update_execution_status(&opportunity).await
+                .context("Failed to mark opportunity reorged")?;
+            reorged += 1;
+        }
+
+        Ok(reorged)
+    }
+
+    /// Run `check` every `interval` until the task is aborted
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            if let Err(e) = self.check().await {
+                log::warn!("Reorg check failed: {:#}", e);
+            }
+        }
+    }
+}
+
+/// Block lookups against `rpc_url`
+pub fn rpc_chain(rpc_url: &str) -> Result<Arc<dyn ChainView>> {
+    let provider = Provider::<Http>::try_from(rpc_url).context("Failed to create HTTP provider")?;
+    Ok(Arc::new(provider))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::MemoryStore;
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    /// Chain whose canonical hashes the test rewrites to simulate a reorg
+    #[derive(Default)]
+    struct MockChain {
+        head: Mutex<u64>,
+        hashes: Mutex<HashMap<u64, H256>>,
+    }
+
+    #[async_trait]
+    impl ChainView for MockChain {
+        async fn head(&self) -> Result<(u64, H256)> {
+            let head = *self.head.lock();
+            Ok((head, H256::from_low_u64_be(head)))
+        }
+
+        async fn block_hash(&self, number: u64) -> Result<Option<H256>> {
+            Ok(self.hashes.lock().get(&number).copied())
+        }
+    }
+
+    fn opportunity(block: u64, hash: H256) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            block_number: Some(block as i64),
+            block_hash: Some(hash_text(hash)),
+            execution_status: Some("dry_run".to_string()),
+            ..ArbitrageOpportunity::sample()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_marks_reorged_opportunities() {
+        let chain = Arc::new(MockChain::default());
+        let store = MemoryStore::default();
+        let saved = store.opportunities();
+        let store: Arc<dyn OpportunityStore> = Arc::new(store);
+        let checker = ReorgChecker::new(chain.clone(), store.clone(), 3);
+
+        let (original, replaced) = (H256::from_low_u64_be(100), H256::from_low_u64_be(0xbad));
+        let kept = opportunity(100, original);
+        let orphaned = opportunity(101, H256::from_low_u64_be(101));
+        for opportunity in [&kept, &orphaned] {
+            store.save_opportunity(opportunity).await.unwrap();
+            checker.track(opportunity.clone());
+        }
+
+        // Settled after it was tracked
+        let settled = ArbitrageOpportunity { realized_profit: Some(dec!(4.2)), ..orphaned.clone() };
+        store.update_execution(&settled).await.unwrap();
+
+        // Block 101 is replaced before either is deep enough
+        chain.hashes.lock().extend([(100, original), (101, replaced)]);
+        *chain.head.lock() = 103;
+        assert_eq!(checker.check().await.unwrap(), 0);
+        assert_eq!(checker.pending.lock().len(), 1);
+
+        *chain.head.lock() = 104;
+        assert_eq!(checker.check().await.unwrap(), 1);
+        assert!(checker.pending.lock().is_empty());
+
+        let saved = saved.lock();
+        assert_eq!(saved[0].execution_status.as_deref(), Some("dry_run"));
+        assert_eq!(saved[1].execution_status.as_deref(), Some("reorged"));
+        assert_eq!(saved[1].realized_profit, settled.realized_profit);
+    }
+}
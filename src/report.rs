@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::cex::CexReference;
+use crate::cli;
+use crate::config::Config;
+use crate::database::{self, OpportunityStore};
+use crate::models::{ArbitrageOpportunity, BaseCurrency};
+
+/// Sums over the opportunities recorded in one profit currency
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Totals {
+    pub opportunities: u64,
+    pub estimated_profit: Decimal,
+    pub gas_cost: Decimal,
+    pub realized_profit: Decimal,
+}
+
+impl Totals {
+    fn add(&mut self, opportunity: &ArbitrageOpportunity) {
+        self.opportunities += 1;
+        self.estimated_profit += opportunity.estimated_profit;
+        self.gas_cost += opportunity.gas_cost;
+        self.realized_profit += opportunity.realized_profit.unwrap_or_default();
+    }
+}
+
+/// Unit USDC amounts are presented in. Stored values stay in USDC; this is
+/// applied only when a report is written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayRate {
+    pub currency: String,
+    /// USDC per unit of `currency`
+    pub usdc_per_unit: Decimal,
+}
+
+impl DisplayRate {
+    pub fn usdc() -> Self {
+        Self {
+            currency: BaseCurrency::Usdc.as_str().to_string(),
+            usdc_per_unit: Decimal::ONE,
+        }
+    }
+
+    fn convert(&self, usdc: Decimal) -> Decimal {
+        usdc / self.usdc_per_unit
+    }
+}
+
+/// Rate for `REPORT_CURRENCY`, read from the `REPORT_RATE_URL` ticker. Falls
+/// back to plain USDC when the rate is unavailable, returning why as a note
+/// for the report.
+pub async fn display_rate(config: &Config) -> (DisplayRate, Option<String>) {
+    let currency = config.report_currency.to_uppercase();
+    if currency == BaseCurrency::Usdc.as_str() {
+        return (DisplayRate::usdc(), None);
+    }
+
+    let rate = match config.report_rate_url.as_deref() {
+        Some(url) => CexReference::new(url).mid_price().await,
+        None => Err(anyhow!("REPORT_RATE_URL is not set")),
+    };
+    match rate {
+        Ok(usdc_per_unit) => (DisplayRate { currency, usdc_per_unit }, None),
+        Err(e) => {
+            log::warn!("No USDC/{} rate, reporting in USDC: {:#}", currency, e);
+            (DisplayRate::usdc(), Some(format!("{} rate unavailable, amounts shown in USDC ({:#})", currency, e)))
+        }
+    }
+}
+
+/// Totals per profit currency of every opportunity recorded since `since`
+pub async fn totals_since(store: &dyn OpportunityStore, since: DateTime<Utc>) -> Result<BTreeMap<String, Totals>> {
+    let mut totals: BTreeMap<String, Totals> = BTreeMap::new();
+    let mut rows = store.opportunities_since(since);
+    while let Some(opportunity) = rows.try_next().await? {
+        totals.entry(opportunity.profit_currency.clone()).or_default().add(&opportunity);
+    }
+    Ok(totals)
+}
+
+/// Write the totals, converting USDC amounts with `rate`. Amounts recorded
+/// in other currencies (WETH round trips) are shown as recorded.
+pub fn write_report(
+    totals: &BTreeMap<String, Totals>,
+    rate: &DisplayRate,
+    note: Option<&str>,
+    decimals: u32,
+    mut writer: impl Write,
+) -> Result<()> {
+    if totals.is_empty() {
+        writeln!(writer, "No opportunities recorded")?;
+    }
+
+    for (recorded_in, totals) in totals {
+        let in_usdc = recorded_in == BaseCurrency::Usdc.as_str();
+        let amount = |value: Decimal| {
+            if in_usdc {
+                format!("{} {}", rate.convert(value).round_dp(decimals).normalize(), rate.currency)
+            } else {
+                format!("{} {}", value.round_dp(decimals).normalize(), recorded_in)
+            }
+        };
+
+        writeln!(writer, "{} opportunities:     {}", recorded_in, totals.opportunities)?;
+        writeln!(writer, "  estimated profit:  {}", amount(totals.estimated_profit))?;
+        writeln!(writer, "  gas cost:          {}", amount(totals.gas_cost))?;
+        writeln!(writer, "  realized profit:   {}", amount(totals.realized_profit))?;
+    }
+
+    if rate.currency != BaseCurrency::Usdc.as_str() {
+        writeln!(writer, "Rate: 1 {} = {} USDC", rate.currency, rate.usdc_per_unit.normalize())?;
+    }
+    if let Some(note) = note {
+        writeln!(writer, "Note: {}", note)?;
+    }
+    Ok(())
+}
+
+/// `report --since <date>`: totals of recorded opportunities in `REPORT_CURRENCY`
+pub async fn run(config: &Config, args: &[String]) -> Result<()> {
+    let since = cli::flag_value(args, "--since")
+        .ok_or_else(|| anyhow!("report requires --since <YYYY-MM-DD or RFC 3339>"))
+        .and_then(cli::parse_datetime)?;
+
+    let db = database::open(config).await?;
+    let totals = totals_since(db.as_ref(), since).await.context("Failed to total opportunities")?;
+    let (rate, note) = display_rate(config).await;
+
+    println!("Opportunities since {}", since.format("%Y-%m-%d %H:%M:%S UTC"));
+    write_report(&totals, &rate, note.as_deref(), config.usdc_decimals_display, std::io::stdout().lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn totals() -> BTreeMap<String, Totals> {
+        BTreeMap::from([(
+            "USDC".to_string(),
+            Totals {
+                opportunities: 3,
+                estimated_profit: dec!(46),
+                gas_cost: dec!(9.2),
+                realized_profit: dec!(23),
+            },
+        )])
+    }
+
+    #[test]
+    fn test_report_converts_usdc_totals() {
+        let rate = DisplayRate {
+            currency: "EUR".to_string(),
+            usdc_per_unit: dec!(1.15),
+        };
+
+        let mut out = Vec::new();
+        write_report(&totals(), &rate, None, 2, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "USDC opportunities:     3\n  \
+             estimated profit:  40 EUR\n  \
+             gas cost:          8 EUR\n  \
+             realized profit:   20 EUR\n\
+             Rate: 1 EUR = 1.15 USDC\n"
+        );
+    }
+
+    #[test]
+    fn test_report_falls_back_to_usdc_with_note() {
+        let mut out = Vec::new();
+        write_report(&totals(), &DisplayRate::usdc(), Some("POL rate unavailable"), 2, &mut out).unwrap();
+
+        let report = String::from_utf8(out).unwrap();
+        assert!(report.contains("estimated profit:  46 USDC"));
+        assert!(report.ends_with("Note: POL rate unavailable\n"));
+    }
+}
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::clock::Clock;
+
+/// Share of the daily budget at which a warning is logged
+const DAY_WARN_FRACTION: u64 = 80;
+
+/// The process-wide budget every metered RPC call draws from, once installed
+static BUDGET: OnceLock<RpcBudget> = OnceLock::new();
+
+/// Kinds of RPC call, which providers bill at different compute-unit rates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RpcCallKind {
+    /// A quoter or router `eth_call` pricing a swap
+    Quote,
+    /// An ERC20 `decimals()` call
+    Decimals,
+    /// Block number or header lookups
+    Block,
+    /// A V2 pair's `getReserves()`
+    Reserves,
+    /// Any other call
+    Call,
+}
+
+impl RpcCallKind {
+    /// Alchemy's published compute-unit costs
+    fn default_cost(self) -> u64 {
+        match self {
+            RpcCallKind::Block => 16,
+            RpcCallKind::Quote | RpcCallKind::Decimals | RpcCallKind::Reserves | RpcCallKind::Call => 26,
+        }
+    }
+}
+
+impl FromStr for RpcCallKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "quote" => Ok(RpcCallKind::Quote),
+            "decimals" => Ok(RpcCallKind::Decimals),
+            "block" => Ok(RpcCallKind::Block),
+            "reserves" => Ok(RpcCallKind::Reserves),
+            "call" => Ok(RpcCallKind::Call),
+            other => Err(format!("unknown RPC call kind {:?} (expected quote, decimals, block, reserves or call)", other)),
+        }
+    }
+}
+
+/// A configured compute-unit cost, as `kind:cu`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuCost {
+    pub kind: RpcCallKind,
+    pub cu: u64,
+}
+
+impl FromStr for CuCost {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, cu) = s.split_once(':').ok_or_else(|| format!("expected kind:cu, got {:?}", s))?;
+        Ok(Self {
+            kind: kind.trim().parse()?,
+            cu: cu.trim().parse().map_err(|e| format!("invalid cost {:?}: {}", cu, e))?,
+        })
+    }
+}
+
+/// The daily budget is spent; calls are refused until the UTC day rolls over
+#[derive(Debug, thiserror::Error)]
+#[error("daily RPC budget of {0} CU exhausted")]
+pub struct BudgetExhausted(pub u64);
+
+/// Whether `error` came from an exhausted RPC budget
+pub fn is_budget_exhausted(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<BudgetExhausted>())
+}
+
+/// Compute units spent, for the status line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcUsage {
+    pub today: u64,
+    pub per_day: u64,
+    pub by_kind: HashMap<RpcCallKind, u64>,
+}
+
+#[derive(Debug)]
+struct State {
+    window_start: Instant,
+    window_used: u64,
+    day: NaiveDate,
+    day_used: u64,
+    day_warned: bool,
+    by_kind: HashMap<RpcCallKind, u64>,
+}
+
+/// Compute-unit accounting against a provider's quotas. A call that would
+/// overrun the per-second budget waits for the next second; one that would
+/// overrun the daily budget fails with `BudgetExhausted` until the UTC day
+/// rolls over. A zero budget is unlimited. Safe to share across tasks.
+pub struct RpcBudget {
+    per_second: u64,
+    per_day: u64,
+    costs: HashMap<RpcCallKind, u64>,
+    clock: Arc<dyn Clock>,
+    state: Mutex<State>,
+}
+
+impl RpcBudget {
+    pub fn new(per_second: u64, per_day: u64, costs: &[CuCost], clock: Arc<dyn Clock>) -> Self {
+        let state = State {
+            window_start: Instant::now(),
+            window_used: 0,
+            day: clock.now().date_naive(),
+            day_used: 0,
+            day_warned: false,
+            by_kind: HashMap::new(),
+        };
+        Self {
+            per_second,
+            per_day,
+            costs: costs.iter().map(|cost| (cost.kind, cost.cu)).collect(),
+            clock,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn cost(&self, kind: RpcCallKind) -> u64 {
+        self.costs.get(&kind).copied().unwrap_or_else(|| kind.default_cost())
+    }
+
+    /// Charge one `kind` call at `now` if the budget allows it, else the
+    /// instant to try again at. An `essential` call is never refused by the
+    /// daily budget, only throttled.
+    fn reserve(&self, kind: RpcCallKind, now: Instant, essential: bool) -> Result<Option<Instant>> {
+        let cost = self.cost(kind);
+        let today = self.clock.now().date_naive();
+        let mut state = self.state.lock();
+        if today != state.day {
+            state.day = today;
+            state.day_used = 0;
+            state.day_warned = false;
+        }
+        if now >= state.window_start + Duration::from_secs(1) {
+            state.window_start = now;
+            state.window_used = 0;
+        }
+
+        if !essential && self.per_day > 0 && state.day_used + cost > self.per_day {
+            return Err(anyhow::Error::new(BudgetExhausted(self.per_day)));
+        }
+        // A call dearer than the whole per-second budget still goes alone
+        if self.per_second > 0 && state.window_used > 0 && state.window_used + cost > self.per_second {
+            return Ok(Some(state.window_start + Duration::from_secs(1)));
+        }
+
+        state.window_used += cost;
+        state.day_used += cost;
+        *state.by_kind.entry(kind).or_default() += cost;
+        if self.per_day > 0 && !state.day_warned && state.day_used * 100 >= self.per_day * DAY_WARN_FRACTION {
+            state.day_warned = true;
+            log::warn!("{} of the {} CU daily RPC budget used", state.day_used, self.per_day);
+        }
+        Ok(None)
+    }
+
+    /// Charge one `kind` call, waiting while the per-second budget is spent
+    pub async fn acquire(&self, kind: RpcCallKind) -> Result<()> {
+        while let Some(retry_at) = self.reserve(kind, Instant::now(), false)? {
+            log::debug!("RPC budget of {} CU/s reached, deferring a {:?} call", self.per_second, kind);
+            tokio::time::sleep_until(retry_at).await;
+        }
+        Ok(())
+    }
+
+    /// `acquire` for a call that must go through even once the daily budget
+    /// is spent, such as settling a trade already sent. It is still charged,
+    /// and waits while the per-second budget is spent.
+    pub async fn acquire_essential(&self, kind: RpcCallKind) {
+        while let Ok(Some(retry_at)) = self.reserve(kind, Instant::now(), true) {
+            log::debug!("RPC budget of {} CU/s reached, deferring a {:?} call", self.per_second, kind);
+            tokio::time::sleep_until(retry_at).await;
+        }
+    }
+
+    pub fn usage(&self) -> RpcUsage {
+        let state = self.state.lock();
+        RpcUsage { today: state.day_used, per_day: self.per_day, by_kind: state.by_kind.clone() }
+    }
+}
+
+/// Make `budget` the one every metered call draws from. Only the first
+/// install takes effect.
+pub fn install(budget: RpcBudget) {
+    if BUDGET.set(budget).is_err() {
+        log::warn!("RPC budget already installed, keeping the first");
+    }
+}
+
+/// The installed budget, if any
+pub fn global() -> Option<&'static RpcBudget> {
+    BUDGET.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TokioClock;
+    use chrono::{TimeZone, Utc};
+
+    fn budget(per_second: u64, per_day: u64) -> RpcBudget {
+        let clock = Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 23, 59, 0).unwrap()));
+        RpcBudget::new(per_second, per_day, &[CuCost { kind: RpcCallKind::Block, cu: 10 }], clock)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttles_at_the_per_second_limit() {
+        // Four 26 CU quotes fit in 100 CU/s; the fifth waits for the next second
+        let budget = budget(100, 0);
+        let start = Instant::now();
+        for _ in 0..3 {
+            budget.acquire(RpcCallKind::Quote).await.unwrap();
+        }
+        assert_eq!(budget.reserve(RpcCallKind::Block, start, false).unwrap(), None);
+        assert_eq!(budget.reserve(RpcCallKind::Quote, start, false).unwrap(), Some(start + Duration::from_secs(1)));
+
+        budget.acquire(RpcCallKind::Quote).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert_eq!(budget.usage().today, 3 * 26 + 10 + 26);
+        assert_eq!(budget.usage().by_kind[&RpcCallKind::Block], 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refuses_calls_past_the_daily_limit_until_the_day_rolls_over() {
+        let budget = budget(0, 60);
+        budget.acquire(RpcCallKind::Call).await.unwrap();
+        budget.acquire(RpcCallKind::Block).await.unwrap();
+        let refused = budget.acquire(RpcCallKind::Call).await.unwrap_err();
+        assert!(is_budget_exhausted(&refused));
+        budget.acquire(RpcCallKind::Block).await.unwrap();
+
+        // Settling a trade already sent still goes through, and is charged
+        budget.acquire_essential(RpcCallKind::Call).await;
+        assert_eq!(budget.usage().today, 26 + 10 + 10 + 26);
+        assert!(budget.acquire(RpcCallKind::Block).await.is_err());
+
+        // Midnight UTC a minute later
+        tokio::time::advance(Duration::from_secs(60)).await;
+        budget.acquire(RpcCallKind::Call).await.unwrap();
+        assert_eq!(budget.usage().today, 26);
+    }
+}
@@ -0,0 +1,117 @@
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use std::time::Duration;
+
+/// A change in the cycle's best spread of at least this fraction of the
+/// profit threshold counts as movement, even while still far below it
+const SIGNIFICANT_MOVE: Decimal = Decimal::from_parts(1, 0, 0, false, 1);
+
+/// Stretches the pause between cycles while the market is quiet, to save RPC
+/// quota when spreads sit far below the profit threshold. Flat cycles
+/// lengthen it by `backoff` up to `max`; a significant spread move shortens
+/// it by `speedup`, and a spread nearing the threshold (at `near` of it) or
+/// an opportunity snaps it back to the configured interval. Errors back off
+/// separately.
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampler {
+    max: Duration,
+    backoff: Decimal,
+    speedup: Decimal,
+    near: Decimal,
+    /// Multiple of the configured interval to wait, at least one
+    stretch: Decimal,
+    last_spread: Option<Decimal>,
+}
+
+impl AdaptiveSampler {
+    pub fn new(max: Duration, backoff: Decimal, speedup: Decimal, near: Decimal) -> Self {
+        Self {
+            max,
+            backoff: backoff.max(Decimal::ONE),
+            speedup: speedup.max(Decimal::ONE),
+            near,
+            stretch: Decimal::ONE,
+            last_spread: None,
+        }
+    }
+
+    /// Take in a cycle's best spread, measured against its profit threshold
+    pub fn observe(&mut self, spread: Decimal, threshold: Decimal) {
+        let moved = self
+            .last_spread
+            .is_some_and(|last| (spread - last).abs() >= threshold * SIGNIFICANT_MOVE);
+        self.last_spread = Some(spread);
+        if spread >= threshold * self.near {
+            self.stretch = Decimal::ONE;
+        } else if moved {
+            self.stretch = (self.stretch / self.speedup).max(Decimal::ONE);
+        } else {
+            self.stretch *= self.backoff;
+        }
+    }
+
+    /// An opportunity was found: sample at full rate again
+    pub fn reset(&mut self) {
+        self.stretch = Decimal::ONE;
+    }
+
+    /// Pause before the next cycle, given the configured interval
+    pub fn interval(&mut self, min: Duration) -> Duration {
+        let min_ms = Decimal::from(min.as_millis() as u64);
+        let max_ms = Decimal::from(self.max.max(min).as_millis() as u64);
+        if !min_ms.is_zero() {
+            self.stretch = self.stretch.min(max_ms / min_ms);
+        }
+        Duration::from_millis((min_ms * self.stretch).round().to_u64().unwrap_or(u64::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn sampler() -> AdaptiveSampler {
+        AdaptiveSampler::new(Duration::from_secs(120), dec!(2), dec!(4), dec!(0.5))
+    }
+
+    #[test]
+    fn test_interval_grows_while_flat_and_snaps_back_near_threshold() {
+        let min = Duration::from_secs(30);
+        let threshold = dec!(0.01);
+        let mut sampler = sampler();
+
+        // Flat at 0.1% against a 1% threshold: doubles up to the cap
+        let mut intervals = Vec::new();
+        for _ in 0..4 {
+            sampler.observe(dec!(0.001), threshold);
+            intervals.push(sampler.interval(min).as_secs());
+        }
+        assert_eq!(intervals, vec![60, 120, 120, 120]);
+
+        // Half the threshold is near enough
+        sampler.observe(dec!(0.005), threshold);
+        assert_eq!(sampler.interval(min), min);
+    }
+
+    #[test]
+    fn test_spread_movement_and_opportunities_speed_up() {
+        let min = Duration::from_secs(30);
+        let threshold = dec!(0.01);
+        let mut sampler = sampler();
+        for _ in 0..3 {
+            sampler.observe(dec!(0.001), threshold);
+        }
+        assert_eq!(sampler.interval(min).as_secs(), 120);
+
+        // Moving by a tenth of the threshold, still far below it
+        sampler.observe(dec!(0.002), threshold);
+        assert_eq!(sampler.interval(min).as_secs(), 30);
+        sampler.observe(dec!(0.002), threshold);
+        sampler.observe(dec!(0.002), threshold);
+        assert_eq!(sampler.interval(min).as_secs(), 120);
+
+        sampler.reset();
+        assert_eq!(sampler.interval(min), min);
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+/// What the operator asked for with a Unix signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperatorSignal {
+    /// SIGHUP: re-read the configuration
+    Reload,
+    /// SIGUSR1: halt trading
+    Halt,
+    /// SIGUSR2: resume trading
+    Resume,
+}
+
+/// The operator's signal handlers. Only Unix has these signals; elsewhere
+/// none ever arrives and the kill switch file is the way to halt.
+#[cfg(unix)]
+pub struct OperatorSignals {
+    hangup: tokio::signal::unix::Signal,
+    stop: tokio::signal::unix::Signal,
+    resume: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl OperatorSignals {
+    pub fn install() -> Result<Self> {
+        use anyhow::Context;
+        use tokio::signal::unix::{SignalKind, signal};
+
+        Ok(Self {
+            hangup: signal(SignalKind::hangup()).context("Failed to install SIGHUP handler")?,
+            stop: signal(SignalKind::user_defined1()).context("Failed to install SIGUSR1 handler")?,
+            resume: signal(SignalKind::user_defined2()).context("Failed to install SIGUSR2 handler")?,
+        })
+    }
+
+    /// The next signal received. Cancel-safe, for use in `tokio::select!`.
+    pub async fn recv(&mut self) -> OperatorSignal {
+        tokio::select! {
+            _ = self.hangup.recv() => OperatorSignal::Reload,
+            _ = self.stop.recv() => OperatorSignal::Halt,
+            _ = self.resume.recv() => OperatorSignal::Resume,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub struct OperatorSignals;
+
+#[cfg(not(unix))]
+impl OperatorSignals {
+    pub fn install() -> Result<Self> {
+        Ok(Self)
+    }
+
+    /// Never resolves: there are no operator signals to receive
+    pub async fn recv(&mut self) -> OperatorSignal {
+        std::future::pending().await
+    }
+}
@@ -0,0 +1,787 @@
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use parking_lot::Mutex;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::ArbitrageBot;
+use crate::clock::{Clock, TokioClock};
+use crate::config::Config;
+use crate::database::OpportunityStore;
+use crate::decisions::{Decision, DecisionEvent, DecisionRecorder};
+use crate::dex::{DexClient, SwapQuote, TradeSide, VenueKind};
+use crate::models::{ArbitrageOpportunity, DexHealth, TokenPair};
+use crate::util::{RPC_TIMEOUT, TimedOut};
+
+/// One scripted answer to a venue's reference price request
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Price(Decimal),
+    Fail,
+    /// Hang for the RPC timeout, then fail the way `rpc_call` does
+    Hang,
+}
+
+/// DEX client that replays a price series, one step per reference quote,
+/// and fills leg quotes at the last price it returned
+pub struct ScriptedVenue {
+    name: &'static str,
+    steps: Mutex<VecDeque<Step>>,
+    current: Mutex<Option<Decimal>>,
+    sell_markup: Decimal,
+    liquidity: Option<Decimal>,
+    size_cap: Option<Decimal>,
+    supported: bool,
+    calls: Arc<Mutex<Vec<Duration>>>,
+    sizes: Arc<Mutex<Vec<Decimal>>>,
+    origin: Instant,
+}
+
+impl ScriptedVenue {
+    pub fn new(name: &'static str, steps: impl IntoIterator<Item = Step>) -> Self {
+        Self {
+            name,
+            steps: Mutex::new(steps.into_iter().collect()),
+            current: Mutex::new(None),
+            sell_markup: Decimal::ONE,
+            liquidity: None,
+            size_cap: None,
+            supported: true,
+            calls: Arc::new(Mutex::new(Vec::new())),
+            sizes: Arc::new(Mutex::new(Vec::new())),
+            origin: Instant::now(),
+        }
+    }
+
+    /// Fill sells at `markup` times the price buys get, an inconsistency no
+    /// real venue has
+    pub fn with_sell_markup(mut self, markup: Decimal) -> Self {
+        self.sell_markup = markup;
+        self
+    }
+
+    /// Report `liquidity` quote tokens of depth
+    pub fn with_liquidity(mut self, liquidity: Decimal) -> Self {
+        self.liquidity = Some(liquidity);
+        self
+    }
+
+    /// Take at most `cap` quote tokens within any slippage limit
+    pub fn with_size_cap(mut self, cap: Decimal) -> Self {
+        self.size_cap = Some(cap);
+        self
+    }
+
+    /// Have no pool for any pair
+    pub fn unsupported(mut self) -> Self {
+        self.supported = false;
+        self
+    }
+
+    /// Virtual time, since the venue was built, of every reference quote
+    pub fn calls(&self) -> Arc<Mutex<Vec<Duration>>> {
+        self.calls.clone()
+    }
+
+    /// Quote size of every reference quote
+    pub fn sizes(&self) -> Arc<Mutex<Vec<Decimal>>> {
+        self.sizes.clone()
+    }
+}
+
+#[async_trait]
+impl DexClient for ScriptedVenue {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn venue_kind(&self) -> VenueKind {
+        VenueKind::UniswapV2Like
+    }
+
+    async fn get_price_for_size(&self, _pair: &TokenPair, size: Decimal) -> Result<Decimal> {
+        self.calls.lock().push(self.origin.elapsed());
+        self.sizes.lock().push(size);
+        let step = self.steps.lock().pop_front().ok_or_else(|| anyhow!("{} script exhausted", self.name))?;
+        match step {
+            Step::Price(price) => {
+                *self.current.lock() = Some(price);
+                Ok(price)
+            }
+            Step::Fail => Err(anyhow!("{} scripted failure", self.name)),
+            Step::Hang => {
+                tokio::time::sleep(RPC_TIMEOUT).await;
+                Err(anyhow::Error::new(TimedOut(RPC_TIMEOUT)))
+            }
+        }
+    }
+
+    async fn get_price_at_block(&self, _pair: &TokenPair, _size: Decimal, _block: u64) -> Result<Decimal> {
+        bail!("{} has no history", self.name)
+    }
+
+    async fn quote_exact_input(&self, _pair: &TokenPair, side: TradeSide, amount_in: Decimal) -> Result<SwapQuote> {
+        let price = self.current.lock().ok_or_else(|| anyhow!("{} has not been priced", self.name))?;
+        Ok(SwapQuote::new(match side {
+            TradeSide::Buy => amount_in / price,
+            TradeSide::Sell => amount_in * price * self.sell_markup,
+        }))
+    }
+
+    async fn supports_pair(&self, _pair: &TokenPair) -> Result<bool> {
+        Ok(self.supported)
+    }
+
+    async fn get_liquidity(&self, _pair: &TokenPair) -> Result<Option<Decimal>> {
+        Ok(self.liquidity)
+    }
+
+    async fn max_input_within_slippage(&self, _pair: &TokenPair, _max_bps: u16) -> Result<Option<Decimal>> {
+        Ok(self.size_cap)
+    }
+}
+
+/// In-memory store whose first `failing_saves` saves fail
+#[derive(Default)]
+pub struct MemoryStore {
+    opportunities: Arc<Mutex<Vec<ArbitrageOpportunity>>>,
+    failing_saves: Mutex<usize>,
+}
+
+impl MemoryStore {
+    pub fn failing_first(saves: usize) -> Self {
+        Self {
+            failing_saves: Mutex::new(saves),
+            ..Self::default()
+        }
+    }
+
+    /// Handle to the saved opportunities that outlives the boxed store
+    pub fn opportunities(&self) -> Arc<Mutex<Vec<ArbitrageOpportunity>>> {
+        self.opportunities.clone()
+    }
+}
+
+#[async_trait]
+impl OpportunityStore for MemoryStore {
+    async fn init(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn check_schema(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn save_opportunity(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let mut failing = self.failing_saves.lock();
+        if *failing > 0 {
+            *failing -= 1;
+            bail!("scripted database outage");
+        }
+        let mut opportunities = self.opportunities.lock();
+        if opportunities.iter().any(|saved| saved.id == opportunity.id) {
+            return Ok(false);
+        }
+        opportunities.push(opportunity.clone());
+        Ok(true)
+    }
+
+    async fn update_execution(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        if let Some(saved) = self.opportunities.lock().iter_mut().find(|saved| saved.id == opportunity.id) {
+            saved.clone_from(opportunity);
+        }
+        Ok(())
+    }
+
+    async fn update_execution_status(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        if let Some(saved) = self.opportunities.lock().iter_mut().find(|saved| saved.id == opportunity.id) {
+            saved.execution_status.clone_from(&opportunity.execution_status);
+        }
+        Ok(())
+    }
+
+    async fn get_recent_opportunities(&self, limit: i32) -> Result<Vec<ArbitrageOpportunity>> {
+        Ok(self.opportunities.lock().iter().rev().take(limit as usize).cloned().collect())
+    }
+
+    fn opportunities_since(&self, since: DateTime<Utc>) -> BoxStream<'_, Result<ArbitrageOpportunity>> {
+        let opportunities: Vec<_> = self.opportunities
+            .lock()
+            .iter()
+            .filter(|opportunity| opportunity.timestamp >= since)
+            .cloned()
+            .map(Ok)
+            .collect();
+        stream::iter(opportunities).boxed()
+    }
+
+    async fn delete_opportunities_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let mut opportunities = self.opportunities.lock();
+        let before = opportunities.len();
+        opportunities.retain(|opportunity| opportunity.timestamp >= cutoff);
+        Ok((before - opportunities.len()) as u64)
+    }
+
+    async fn upsert_dex_health(&self, _health: &DexHealth) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_dex_health(&self) -> Result<Vec<DexHealth>> {
+        Ok(Vec::new())
+    }
+}
+
+fn sim_config(overrides: &[(&str, &str)]) -> Config {
+    let vars: std::collections::HashMap<String, String> = [
+        ("DATABASE_URL", "sqlite::memory:"),
+        ("POLYGON_RPC_URL", "http://localhost:8545"),
+    ]
+    .iter()
+    .chain(overrides)
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+    Config::from_lookup(|key| vars.get(key).cloned()).unwrap()
+}
+
+fn seconds(times: &[Duration]) -> Vec<u64> {
+    times.iter().map(Duration::as_secs).collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scripted_scenario() {
+    use Step::{Fail, Hang, Price};
+
+    let uniswap = ScriptedVenue::new("Uniswap", [Price(dec!(2000)); 10]);
+    let sushiswap = ScriptedVenue::new(
+        "SushiSwap",
+        [
+            Price(dec!(2000)), // warmup, no spread
+            Price(dec!(2030)), // opportunity, but the save fails: 60s backoff
+            Price(dec!(2030)), // opportunity saved
+            Fail,
+            Hang,
+            Fail,
+            Fail,
+            Fail,
+            Fail, // sixth consecutive error
+            Price(dec!(2030)), // breaker tripped, price ignored
+        ],
+    );
+    let uniswap_calls = uniswap.calls();
+    let sushiswap_calls = sushiswap.calls();
+
+    let store = MemoryStore::failing_first(1);
+    let saved = store.opportunities();
+    let clock = Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()));
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        clock,
+    )
+    .await
+    .unwrap();
+
+    // The last cycle starts at 330s; stop during its sleep
+    bot.run_until(tokio::time::sleep(Duration::from_secs(340))).await.unwrap();
+
+    // 30s between cycles, 60s after the failed save, and the hung quote
+    // delays every later cycle by the RPC timeout
+    assert_eq!(seconds(&uniswap_calls.lock()), [0, 30, 90, 120, 150, 210, 240, 270, 300, 330]);
+    assert_eq!(seconds(&sushiswap_calls.lock()), seconds(&uniswap_calls.lock()));
+
+    let saved = saved.lock();
+    assert_eq!(saved.len(), 1);
+    assert_eq!((saved[0].buy_dex.as_str(), saved[0].sell_dex.as_str()), ("Uniswap", "SushiSwap"));
+    assert_eq!(saved[0].estimated_profit, dec!(10));
+
+    assert!(bot.price_validator.is_circuit_breaker_tripped("SushiSwap"));
+    assert!(!bot.price_validator.is_circuit_breaker_tripped("Uniswap"));
+    assert_eq!(bot.cycles_completed, 9);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_warmup_counts_only_cycles_with_valid_prices() {
+    use Step::{Fail, Price};
+
+    let uniswap = ScriptedVenue::new("Uniswap", [Fail, Fail, Price(dec!(2000)), Price(dec!(2000))]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Fail, Fail, Price(dec!(2030)), Price(dec!(2030))]);
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+    bot.run_until(tokio::time::sleep(Duration::from_secs(100))).await.unwrap();
+
+    // The failed cycles at 0s and 30s do not warm up; the one at 60s does,
+    // so only the spread at 90s is acted on
+    assert_eq!(saved.lock().len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_rejects_single_venue() {
+    let result = ArbitrageBot::from_parts(
+        sim_config(&[]),
+        Box::new(MemoryStore::default()),
+        vec![Box::new(ScriptedVenue::new("Uniswap", []))],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await;
+
+    let message = result.err().unwrap().to_string();
+    assert!(message.contains("arbitrage requires at least 2 venues, found 1"), "{}", message);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_monitor_only_records_prices() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)), Step::Price(dec!(2010))]);
+    let calls = uniswap.calls();
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("MONITOR_ONLY", "true"), ("WARMUP_CYCLES", "0")]),
+        Box::new(store),
+        vec![Box::new(uniswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    assert_eq!(seconds(&calls.lock()), [0, 30]);
+    assert!(saved.lock().is_empty());
+    let (price, updated) = bot.price_validator.get_last_price("Uniswap").unwrap();
+    assert_eq!(price, dec!(2010));
+    assert_eq!(updated, Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 30).unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_quotes_each_venue_at_its_reference_size() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000))]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2001))]);
+    let (uniswap_sizes, sushiswap_sizes) = (uniswap.sizes(), sushiswap.sizes());
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("MONITOR_ONLY", "true"), ("VENUE_QUOTE_SIZES", "sushiswap:100, QuickSwap:50")]),
+        Box::new(MemoryStore::default()),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(15))).await.unwrap();
+
+    // Unlisted venues keep the default size
+    assert_eq!(*uniswap_sizes.lock(), [dec!(1000)]);
+    assert_eq!(*sushiswap_sizes.lock(), [dec!(100)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_ladder_skips_sizes_past_a_venue_slippage_cap() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 3]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2001)); 3]).with_size_cap(dec!(2000));
+    let (uniswap_sizes, sushiswap_sizes) = (uniswap.sizes(), sushiswap.sizes());
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("TRADE_SIZE_LADDER", "500,5000"), ("WARMUP_CYCLES", "0")]),
+        Box::new(MemoryStore::default()),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(15))).await.unwrap();
+
+    // The reference quote, then only the ladder sizes within each cap
+    assert_eq!(*uniswap_sizes.lock(), [dec!(1000), dec!(500), dec!(5000)]);
+    assert_eq!(*sushiswap_sizes.lock(), [dec!(1000), dec!(500)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_static_backend_finds_scripted_spread() {
+    let config = sim_config(&[
+        ("DEX_BACKEND", "static"),
+        ("STATIC_VENUES", "Uniswap:series:2000,SushiSwap:series:2000/2030"),
+        ("WARMUP_CYCLES", "1"),
+    ]);
+    let venues = crate::dex::create_clients(&config).await.unwrap();
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    let mut bot = ArbitrageBot::from_parts(
+        config,
+        Box::new(store),
+        venues,
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    // Warmup at 0s, SushiSwap steps to 2030 for the cycle at 30s
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    let saved = saved.lock();
+    assert_eq!(saved.len(), 1);
+    assert_eq!((saved[0].buy_dex.as_str(), saved[0].sell_dex.as_str()), ("Uniswap", "SushiSwap"));
+    assert!(saved[0].estimated_profit > Decimal::ZERO);
+}
+
+/// Keeps recorded decisions in memory for assertions
+#[derive(Clone, Default)]
+struct MemoryRecorder {
+    events: Arc<Mutex<Vec<DecisionEvent>>>,
+}
+
+impl DecisionRecorder for MemoryRecorder {
+    fn record(&self, event: DecisionEvent) {
+        self.events.lock().push(event);
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_records_why_each_cycle_did_or_did_not_act() {
+    use Step::Price;
+
+    let uniswap = ScriptedVenue::new("Uniswap", [Price(dec!(2000)); 3]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Price(dec!(2000)), Price(dec!(2600)), Price(dec!(2030))]);
+    let recorder = MemoryRecorder::default();
+    let events = recorder.events.clone();
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(MemoryStore::default()),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap()
+    .with_decision_recorder(Box::new(recorder));
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(75))).await.unwrap();
+
+    let events = events.lock();
+    let trail: Vec<(u64, String)> = events
+        .iter()
+        .map(|event| {
+            let decision = serde_json::to_value(&event.decision).unwrap();
+            (event.cycle, decision["event"].as_str().unwrap().to_string())
+        })
+        .collect();
+    let expected = [
+        (0, "price_fetched"),
+        (0, "price_fetched"),
+        (0, "comparison_skipped"), // no spread
+        (1, "price_fetched"),
+        (1, "price_fetched"),
+        (1, "price_rejected"), // 30% jump
+        (1, "comparison_skipped"),
+        (2, "price_fetched"),
+        (2, "price_fetched"),
+        (2, "opportunity_detected"),
+        (2, "opportunity_recorded"),
+    ];
+    let expected: Vec<(u64, String)> = expected.iter().map(|&(cycle, event)| (cycle, event.to_string())).collect();
+    assert_eq!(trail, expected);
+
+    assert!(events.iter().all(|event| event.pair == "WETH/USDC"));
+    let Decision::PriceRejected { venue, reason } = &events[5].decision else {
+        panic!("expected a rejected price, got {:?}", events[5].decision);
+    };
+    assert_eq!(venue, "SushiSwap");
+    assert!(reason.contains("change"), "{}", reason);
+    assert_eq!(events[10].timestamp, Utc.with_ymd_and_hms(2024, 3, 1, 9, 1, 0).unwrap());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_round_trip_check_rejects_inconsistent_venue() {
+    async fn saved_with(overrides: &[(&str, &str)]) -> usize {
+        let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]);
+        // Its sells fill 5 bps above its buys: a free round trip
+        let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030))])
+            .with_sell_markup(dec!(1.0005));
+        let store = MemoryStore::default();
+        let saved = store.opportunities();
+
+        let mut bot = ArbitrageBot::from_parts(
+            sim_config(&[&[("WARMUP_CYCLES", "1")], overrides].concat()),
+            Box::new(store),
+            vec![Box::new(uniswap), Box::new(sushiswap)],
+            None,
+            Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+        )
+        .await
+        .unwrap();
+        bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+        saved.lock().len()
+    }
+
+    assert_eq!(saved_with(&[]).await, 1);
+    assert_eq!(saved_with(&[("MAX_ROUND_TRIP_LOSS_BPS", "100")]).await, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_consensus_check_rejects_shallow_outlier() {
+    async fn saved_with(overrides: &[(&str, &str)]) -> usize {
+        let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]).with_liquidity(dec!(5000000));
+        let quickswap = ScriptedVenue::new("QuickSwap", [Step::Price(dec!(2001)); 2]).with_liquidity(dec!(5000000));
+        // A shallow pool 2% above the deep ones
+        let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2040))])
+            .with_liquidity(dec!(500000));
+        let store = MemoryStore::default();
+        let saved = store.opportunities();
+
+        let mut bot = ArbitrageBot::from_parts(
+            sim_config(&[&[("WARMUP_CYCLES", "1")], overrides].concat()),
+            Box::new(store),
+            vec![Box::new(uniswap), Box::new(quickswap), Box::new(sushiswap)],
+            None,
+            Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+        )
+        .await
+        .unwrap();
+        bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+        let saved = saved.lock();
+        saved.iter().filter(|opportunity| opportunity.sell_dex == "SushiSwap").count()
+    }
+
+    assert!(saved_with(&[]).await > 0);
+    assert_eq!(saved_with(&[("CONSENSUS_CHECK", "true")]).await, 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_read_only_writes_nothing() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030))]);
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+    let sink_file = std::env::temp_dir().join(format!("read-only-sink-{}", uuid::Uuid::new_v4()));
+    let decision_file = std::env::temp_dir().join(format!("read-only-decisions-{}", uuid::Uuid::new_v4()));
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[
+            ("READ_ONLY", "true"),
+            ("WARMUP_CYCLES", "0"),
+            ("PERSIST_DEX_HEALTH", "true"),
+            ("OPPORTUNITY_SINKS", "database,file"),
+            ("OPPORTUNITY_SINK_FILE", sink_file.to_str().unwrap()),
+            ("DECISION_LOG_FILE", decision_file.to_str().unwrap()),
+        ]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+    // The 1.5% spread at 30s is only logged as prices
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    assert!(saved.lock().is_empty());
+    assert!(!sink_file.exists());
+    assert!(!decision_file.exists());
+    let (price, _) = bot.price_validator.get_last_price("SushiSwap").unwrap();
+    assert_eq!(price, dec!(2030));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_skips_venues_without_the_pair() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030))]);
+    let quickswap = ScriptedVenue::new("QuickSwap", []).unsupported();
+    let quickswap_calls = quickswap.calls();
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap), Box::new(quickswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    // Never quoted, and not a failing venue in the others' way
+    assert!(quickswap_calls.lock().is_empty());
+    assert_eq!(saved.lock().len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_kill_switch_halts_and_resumes_trading() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 3]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030)), Step::Price(dec!(2030))]);
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    // Halted through the second cycle (t=30s), resumed before the third (t=60s)
+    let kill_switch = bot.kill_switch.clone();
+    kill_switch.halt();
+    let resume = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(45)).await;
+        kill_switch.resume();
+    });
+    bot.run_until(tokio::time::sleep(Duration::from_secs(75))).await.unwrap();
+    resume.await.unwrap();
+
+    let saved = saved.lock();
+    assert!(!saved.is_empty());
+    assert!(saved.iter().all(|opportunity| opportunity.timestamp >= Utc.with_ymd_and_hms(2024, 3, 1, 9, 1, 0).unwrap()));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_alerts_on_wide_spread_without_trading_it() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2080))]);
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+    let recorder = MemoryRecorder::default();
+    let events = recorder.events.clone();
+
+    // A 4% spread clears the 3% alert threshold but not the 5% trade threshold
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1"), ("ALERT_SPREAD_PCT", "0.03"), ("MIN_PROFIT_THRESHOLD", "0.05")]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap()
+    .with_decision_recorder(Box::new(recorder));
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    let alerts: Vec<_> = events
+        .lock()
+        .iter()
+        .filter_map(|event| match &event.decision {
+            Decision::SpreadAlert { buy_dex, sell_dex, spread_pct } => {
+                Some((event.cycle, buy_dex.clone(), sell_dex.clone(), *spread_pct))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(alerts, [(1, "Uniswap".to_string(), "SushiSwap".to_string(), dec!(0.04))]);
+    assert!(saved.lock().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_keeps_only_the_most_profitable_opportunities_per_cycle() {
+    // A reference quote and one per ladder size each cycle
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 10]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [[Step::Price(dec!(2000)); 5], [Step::Price(dec!(2030)); 5]].concat());
+    let store = MemoryStore::default();
+    let saved = store.opportunities();
+
+    // Every ladder size is profitable, and profit grows with size
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[
+            ("WARMUP_CYCLES", "1"),
+            ("TRADE_SIZE_LADDER", "1000,2000,3000,4000"),
+            ("MAX_OPPORTUNITIES_PER_CYCLE", "2"),
+        ]),
+        Box::new(store),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+    )
+    .await
+    .unwrap();
+
+    bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+
+    let saved = saved.lock();
+    assert_eq!(saved.iter().map(|opportunity| opportunity.trade_amount).collect::<Vec<_>>(), [dec!(3000), dec!(4000)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_pair_threshold_overrides_global_one() {
+    // A 1.5% spread after warmup, below the global 2% threshold
+    let run = |overrides: &'static [(&'static str, &'static str)]| async move {
+        let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 2]);
+        let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030))]);
+        let store = MemoryStore::default();
+        let saved = store.opportunities();
+        let mut bot = ArbitrageBot::from_parts(
+            sim_config(&[[("WARMUP_CYCLES", "1"), ("MIN_PROFIT_THRESHOLD", "0.02")].as_slice(), overrides].concat()),
+            Box::new(store),
+            vec![Box::new(uniswap), Box::new(sushiswap)],
+            None,
+            Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap())),
+        )
+        .await
+        .unwrap();
+        bot.run_until(tokio::time::sleep(Duration::from_secs(45))).await.unwrap();
+        saved.lock().len()
+    };
+
+    assert_eq!(run(&[]).await, 0);
+    assert_eq!(run(&[("PAIR_THRESHOLDS", "WBTC/USDC:min_profit=0.01")]).await, 0);
+    assert_eq!(run(&[("PAIR_THRESHOLDS", "WETH/USDC:min_profit=0.01")]).await, 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_tracks_time_since_last_profitable_opportunity() {
+    let uniswap = ScriptedVenue::new("Uniswap", [Step::Price(dec!(2000)); 3]);
+    let sushiswap = ScriptedVenue::new("SushiSwap", [Step::Price(dec!(2000)), Step::Price(dec!(2030)), Step::Price(dec!(2000))]);
+    let clock = Arc::new(TokioClock::starting_at(Utc.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap()));
+
+    let mut bot = ArbitrageBot::from_parts(
+        sim_config(&[("WARMUP_CYCLES", "1")]),
+        Box::new(MemoryStore::default()),
+        vec![Box::new(uniswap), Box::new(sushiswap)],
+        None,
+        clock.clone(),
+    )
+    .await
+    .unwrap();
+    let last_profitable = bot.last_profitable.clone();
+
+    // Each run scans right away: no spread at 0s, the spread at 20s, none at 45s.
+    // Counted from startup until the detection, then from it.
+    bot.run_until(tokio::time::sleep(Duration::from_secs(20))).await.unwrap();
+    assert_eq!(last_profitable.since("WETH/USDC", clock.now()).num_seconds(), 20);
+    bot.run_until(tokio::time::sleep(Duration::from_secs(25))).await.unwrap();
+    assert_eq!(last_profitable.since("WETH/USDC", clock.now()).num_seconds(), 25);
+    bot.run_until(tokio::time::sleep(Duration::from_secs(30))).await.unwrap();
+    assert_eq!(last_profitable.since("WETH/USDC", clock.now()).num_seconds(), 55);
+}
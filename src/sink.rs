@@ -0,0 +1,233 @@
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::Config;
+use crate::database::OpportunityStore;
+use crate::models::ArbitrageOpportunity;
+
+/// Where detected opportunities are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    /// The `DATABASE_URL` store
+    Database,
+    /// JSON lines appended to `OPPORTUNITY_SINK_FILE`
+    File,
+    /// JSON lines on stdout
+    Stdout,
+}
+
+impl FromStr for SinkKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "database" => Ok(SinkKind::Database),
+            "file" => Ok(SinkKind::File),
+            "stdout" => Ok(SinkKind::Stdout),
+            other => Err(format!("unknown opportunity sink {:?} (expected database, file or stdout)", other)),
+        }
+    }
+}
+
+/// Destination for detected opportunities
+#[async_trait]
+pub trait OpportunitySink: Send + Sync {
+    /// Sink name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Send one opportunity, returning false when the sink already had it
+    /// (same id). Sinks that cannot tell always return true.
+    async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool>;
+}
+
+/// Saves opportunities to the opportunity store
+pub struct DatabaseSink {
+    store: Arc<dyn OpportunityStore>,
+}
+
+impl DatabaseSink {
+    pub fn new(store: Arc<dyn OpportunityStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl OpportunitySink for DatabaseSink {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        self.store.save_opportunity(opportunity).await
+            .context("Failed to save opportunity to database")
+    }
+}
+
+/// Appends one JSON object per line to a file, creating it if needed
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl OpportunitySink for FileSink {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let mut line = serde_json::to_vec(opportunity)?;
+        line.push(b'\n');
+
+        // Reopened per opportunity so the file can be rotated underneath the bot
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(&line).await
+            .with_context(|| format!("Failed to append to {}", self.path.display()))?;
+        Ok(true)
+    }
+}
+
+/// Prints one JSON object per line to stdout, for piping into other tools
+pub struct StdoutSink;
+
+#[async_trait]
+impl OpportunitySink for StdoutSink {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        println!("{}", serde_json::to_string(opportunity)?);
+        Ok(true)
+    }
+}
+
+/// Emits to every sink in turn. One failing sink does not stop the rest;
+/// the first failure is returned once all have been tried. The opportunity
+/// counts as new only if no sink had it already.
+pub struct FanOutSink {
+    sinks: Vec<Box<dyn OpportunitySink>>,
+}
+
+impl FanOutSink {
+    pub fn new(sinks: Vec<Box<dyn OpportunitySink>>) -> Self {
+        Self { sinks }
+    }
+}
+
+#[async_trait]
+impl OpportunitySink for FanOutSink {
+    fn name(&self) -> &'static str {
+        "fan-out"
+    }
+
+    async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+        let mut first_error = None;
+        let mut new = true;
+        for sink in &self.sinks {
+            match sink.emit(opportunity).await {
+                Ok(emitted) => new &= emitted,
+                Err(e) => {
+                    log::error!("Opportunity sink {} failed for {}: {:#}", sink.name(), opportunity.id, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(new),
+        }
+    }
+}
+
+/// The configured sinks, fanned out in order
+pub fn from_config(config: &Config, store: Arc<dyn OpportunityStore>) -> Result<Box<dyn OpportunitySink>> {
+    if config.opportunity_sinks.is_empty() {
+        bail!("OPPORTUNITY_SINKS must name at least one sink");
+    }
+
+    let sinks = config.opportunity_sinks
+        .iter()
+        .filter(|kind| {
+            let writes_file = **kind == SinkKind::File;
+            if writes_file && config.read_only {
+                log::warn!("Read-only mode, not writing opportunities to {}", config.opportunity_sink_file);
+            }
+            !(writes_file && config.read_only)
+        })
+        .map(|kind| -> Box<dyn OpportunitySink> {
+            match kind {
+                SinkKind::Database => Box::new(DatabaseSink::new(store.clone())),
+                SinkKind::File => Box::new(FileSink::new(&config.opportunity_sink_file)),
+                SinkKind::Stdout => Box::new(StdoutSink),
+            }
+        })
+        .collect();
+
+    Ok(Box::new(FanOutSink::new(sinks)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct MemorySink {
+        emitted: Arc<Mutex<Vec<ArbitrageOpportunity>>>,
+    }
+
+    #[async_trait]
+    impl OpportunitySink for MemorySink {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        async fn emit(&self, opportunity: &ArbitrageOpportunity) -> Result<bool> {
+            self.emitted.lock().push(opportunity.clone());
+            Ok(true)
+        }
+    }
+
+    struct FailingSink;
+
+    #[async_trait]
+    impl OpportunitySink for FailingSink {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        async fn emit(&self, _opportunity: &ArbitrageOpportunity) -> Result<bool> {
+            bail!("queue unavailable")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_reaches_every_sink() {
+        let first = MemorySink::default();
+        let second = MemorySink::default();
+        let (first_emitted, second_emitted) = (first.emitted.clone(), second.emitted.clone());
+        let sink = FanOutSink::new(vec![Box::new(first), Box::new(FailingSink), Box::new(second)]);
+
+        let opportunity = ArbitrageOpportunity::sample();
+        let err = sink.emit(&opportunity).await.unwrap_err();
+
+        assert!(err.to_string().contains("queue unavailable"));
+        assert_eq!(first_emitted.lock().len(), 1);
+        assert_eq!(second_emitted.lock()[0].id, opportunity.id);
+    }
+}
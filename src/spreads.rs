@@ -0,0 +1,88 @@
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Upper bounds (exclusive) of the spread buckets, as fractions. Spreads at or
+/// above the last bound fall into a final overflow bucket.
+const BUCKET_BOUNDS: [Decimal; 6] = [
+    Decimal::from_parts(1, 0, 0, false, 3),  // 0.1%
+    Decimal::from_parts(25, 0, 0, false, 4), // 0.25%
+    Decimal::from_parts(5, 0, 0, false, 3),  // 0.5%
+    Decimal::from_parts(1, 0, 0, false, 2),  // 1%
+    Decimal::from_parts(25, 0, 0, false, 3), // 2.5%
+    Decimal::from_parts(5, 0, 0, false, 2),  // 5%
+];
+
+/// Counts of observed cross-venue spreads, bucketed by size
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpreadHistogram {
+    counts: [u64; BUCKET_BOUNDS.len() + 1],
+}
+
+impl SpreadHistogram {
+    pub fn record(&mut self, spread: Decimal) {
+        let bucket = BUCKET_BOUNDS
+            .iter()
+            .position(|bound| spread < *bound)
+            .unwrap_or(BUCKET_BOUNDS.len());
+        self.counts[bucket] += 1;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl fmt::Display for SpreadHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |fraction: Decimal| (fraction * Decimal::ONE_HUNDRED).normalize();
+
+        let mut lower = Decimal::ZERO;
+        for (bound, count) in BUCKET_BOUNDS.iter().zip(&self.counts) {
+            write!(f, "{}-{}%: {}, ", percent(lower), percent(*bound), count)?;
+            lower = *bound;
+        }
+        write!(f, ">={}%: {}", percent(lower), self.counts[BUCKET_BOUNDS.len()])
+    }
+}
+
+/// Spread histograms per pair, fed from the best route of every cycle so the
+/// profit threshold can be judged against what the market actually offers
+#[derive(Debug, Default)]
+pub struct SpreadStats {
+    pairs: BTreeMap<String, SpreadHistogram>,
+}
+
+impl SpreadStats {
+    pub fn record(&mut self, pair: &str, spread: Decimal) {
+        self.pairs.entry(pair.to_string()).or_default().record(spread);
+    }
+
+    /// Log one line per pair
+    pub fn log_summary(&self) {
+        for (pair, histogram) in &self.pairs {
+            log::info!("Spread histogram {} ({} samples): {}", pair, histogram.total(), histogram);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_buckets_known_spreads() {
+        let mut histogram = SpreadHistogram::default();
+        for spread in [dec!(0), dec!(0.0005), dec!(0.001), dec!(0.002), dec!(0.0049), dec!(0.012), dec!(0.3)] {
+            histogram.record(spread);
+        }
+
+        assert_eq!(histogram.counts, [2, 2, 1, 0, 1, 0, 1]);
+        assert_eq!(histogram.total(), 7);
+        assert_eq!(
+            histogram.to_string(),
+            "0-0.1%: 2, 0.1-0.25%: 2, 0.25-0.5%: 1, 0.5-1%: 0, 1-2.5%: 1, 2.5-5%: 0, >=5%: 1"
+        );
+    }
+}
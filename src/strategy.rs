@@ -0,0 +1,281 @@
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+use crate::arbitrage::{LegQuotes, ProfitModel, VenuePrice};
+use crate::decisions::Decision;
+use crate::models::{ArbitrageOpportunity, TokenPair};
+
+/// Which detection strategy turns a cycle's market data into opportunities
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StrategyKind {
+    /// Trade whenever the best route's legs net a profit after gas
+    #[default]
+    SimpleSpread,
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "simple_spread" | "simple" => Ok(StrategyKind::SimpleSpread),
+            other => Err(format!("unknown strategy {:?} (expected simple_spread)", other)),
+        }
+    }
+}
+
+/// Every venue's price for one trade size
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeQuotes {
+    pub size: Decimal,
+    pub prices: Vec<VenuePrice>,
+}
+
+/// A route a strategy picked at one size, worth quoting both legs of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteChoice {
+    pub size: Decimal,
+    pub buy: VenuePrice,
+    pub sell: VenuePrice,
+    /// Spread between the two venues' prices, as a fraction
+    pub price_diff: Decimal,
+}
+
+/// A picked route with both legs quoted at its size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouteQuote {
+    pub route: RouteChoice,
+    pub legs: LegQuotes,
+}
+
+/// Why a size, or the route picked at it, offers no opportunity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub size: Decimal,
+    /// Buy and sell venue of the turned-down route, `None` when no route qualified
+    pub route: Option<(&'static str, &'static str)>,
+    pub reason: String,
+}
+
+impl Rejection {
+    fn of(route: &RouteChoice, reason: impl Into<String>) -> Self {
+        Self { size: route.size, route: Some((route.buy.0, route.sell.0)), reason: reason.into() }
+    }
+}
+
+impl From<Rejection> for Decision {
+    fn from(rejection: Rejection) -> Self {
+        match rejection.route {
+            Some((buy_dex, sell_dex)) => Decision::RouteRejected {
+                buy_dex: buy_dex.to_string(),
+                sell_dex: sell_dex.to_string(),
+                size: rejection.size,
+                reason: rejection.reason,
+            },
+            None => Decision::ComparisonSkipped { reason: rejection.reason },
+        }
+    }
+}
+
+/// What the bot knows about a pair's market at one cycle, gathered before
+/// any decision is made
+pub struct MarketSnapshot<'a> {
+    pub pair: &'a TokenPair,
+    /// The cycle's spread threshold, gas costs, rounding and route policy
+    pub model: &'a ProfitModel,
+    /// The pair's slippage limit, which also caps how far a leg may move its pool
+    pub max_slippage_bps: u16,
+    /// Every venue's price per trade size, in ladder order
+    pub sizes: Vec<SizeQuotes>,
+    /// Venues the cycle's market checks flagged (spot off its TWAP, an
+    /// inconsistent round trip), with the reason
+    pub flagged: Vec<(&'static str, &'static str)>,
+    /// The routes `Strategy::routes` picked, with both legs quoted; empty
+    /// until then. Routes whose legs could not be quoted are left out.
+    pub quoted: Vec<RouteQuote>,
+}
+
+/// Decides which opportunities a cycle's market data offers. Strategies see
+/// only the snapshot, so they can be tried out on synthetic ones.
+pub trait Strategy: Send + Sync {
+    /// Strategy name used in logs
+    fn name(&self) -> &'static str;
+
+    /// The routes worth quoting both legs of, at most one per size, and why
+    /// the other sizes offer none
+    fn routes(&self, snapshot: &MarketSnapshot) -> Vec<Result<RouteChoice, Rejection>>;
+
+    /// The opportunity each quoted route offers, or why it offers none
+    fn evaluate(&self, snapshot: &MarketSnapshot) -> Vec<Result<ArbitrageOpportunity, Rejection>>;
+}
+
+/// Quotes the best permitted route at each size whose spread clears the
+/// threshold, and takes it if its legs net a profit after fees and gas
+pub struct SimpleSpreadStrategy;
+
+impl Strategy for SimpleSpreadStrategy {
+    fn name(&self) -> &'static str {
+        "simple_spread"
+    }
+
+    fn routes(&self, snapshot: &MarketSnapshot) -> Vec<Result<RouteChoice, Rejection>> {
+        let model = snapshot.model;
+        snapshot
+            .sizes
+            .iter()
+            .map(|quotes| {
+                let Some((buy, sell, price_diff)) = model.route(&quotes.prices) else {
+                    return Err(Rejection {
+                        size: quotes.size,
+                        route: None,
+                        reason: format!("no allowed route clears the spread threshold at {} {}", quotes.size, model.base_currency),
+                    });
+                };
+                let route = RouteChoice { size: quotes.size, buy, sell, price_diff };
+                match snapshot.flagged.iter().find(|(venue, _)| *venue == buy.0 || *venue == sell.0) {
+                    Some((_, reason)) => Err(Rejection::of(&route, *reason)),
+                    None => Ok(route),
+                }
+            })
+            .collect()
+    }
+
+    fn evaluate(&self, snapshot: &MarketSnapshot) -> Vec<Result<ArbitrageOpportunity, Rejection>> {
+        snapshot
+            .quoted
+            .iter()
+            .map(|&RouteQuote { route, legs }| {
+                let max_impact_bps = Decimal::from(snapshot.max_slippage_bps);
+                if let Some(impact_bps) = legs.price_impact_bps
+                    && impact_bps > max_impact_bps
+                {
+                    log::debug!(
+                        "{} {} round trip {} -> {} moves the pool {} bps, past the {} bps slippage limit",
+                        route.size, snapshot.model.base_currency, route.buy.0, route.sell.0, impact_bps.round_dp(2), max_impact_bps
+                    );
+                    return Err(Rejection::of(
+                        &route,
+                        format!("price impact {} bps exceeds the {} bps slippage limit", impact_bps.round_dp(2), max_impact_bps),
+                    ));
+                }
+                snapshot
+                    .model
+                    .evaluate(snapshot.pair, (route.buy.0, route.sell.0), route.price_diff, route.size, legs)
+                    .ok_or_else(|| Rejection::of(&route, "unprofitable after fees and gas"))
+            })
+            .collect()
+    }
+}
+
+pub fn from_kind(kind: StrategyKind) -> Box<dyn Strategy> {
+    match kind {
+        StrategyKind::SimpleSpread => Box::new(SimpleSpreadStrategy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage::{RoundingMode, RoutePolicy};
+    use crate::models::{BaseCurrency, PairBase};
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    fn pair() -> TokenPair {
+        TokenPair {
+            token0: "0x7ceB23fD6bC0adD59E62ac25578270cFf1b9f619".to_string(),
+            token1: "0x2791Bca1f2de4661ED88A30C99A7a9449Aa84174".to_string(),
+            symbol: "WETH/USDC".to_string(),
+            base: PairBase::Token0,
+        }
+    }
+
+    fn model() -> ProfitModel {
+        ProfitModel {
+            min_profit_threshold: dec!(0.005),
+            gas_cost: dec!(5),
+            route_gas_costs: HashMap::new(),
+            base_currency: BaseCurrency::Usdc,
+            transfer_fee: Decimal::ZERO,
+            price_decimals: 6,
+            usdc_decimals: 6,
+            rounding: RoundingMode::Nearest,
+            routes: RoutePolicy::default(),
+            max_plausible_profit_pct: dec!(0.2),
+        }
+    }
+
+    fn snapshot<'a>(pair: &'a TokenPair, model: &'a ProfitModel, sizes: Vec<SizeQuotes>) -> MarketSnapshot<'a> {
+        MarketSnapshot { pair, model, max_slippage_bps: 100, sizes, flagged: Vec::new(), quoted: Vec::new() }
+    }
+
+    #[test]
+    fn test_simple_spread_picks_the_best_route_past_the_threshold() {
+        let (pair, model) = (pair(), model());
+        let spread = vec![("Uniswap", dec!(2000)), ("SushiSwap", dec!(2030)), ("QuickSwap", dec!(2010))];
+        let flat = vec![("Uniswap", dec!(2000)), ("SushiSwap", dec!(2001))];
+        let mut snapshot = snapshot(
+            &pair,
+            &model,
+            vec![SizeQuotes { size: dec!(1000), prices: spread.clone() }, SizeQuotes { size: dec!(5000), prices: flat }],
+        );
+
+        let routes = SimpleSpreadStrategy.routes(&snapshot);
+        assert_eq!(
+            routes[0],
+            Ok(RouteChoice { size: dec!(1000), buy: spread[0], sell: spread[1], price_diff: dec!(0.015) })
+        );
+        assert_eq!(
+            routes[1],
+            Err(Rejection {
+                size: dec!(5000),
+                route: None,
+                reason: "no allowed route clears the spread threshold at 5000 USDC".to_string(),
+            })
+        );
+
+        // A flagged venue rules out its routes
+        snapshot.flagged = vec![("SushiSwap", "spot price strays from TWAP")];
+        let rejection = SimpleSpreadStrategy.routes(&snapshot).remove(0).unwrap_err();
+        assert_eq!(rejection.route, Some(("Uniswap", "SushiSwap")));
+        assert_eq!(rejection.reason, "spot price strays from TWAP");
+    }
+
+    #[test]
+    fn test_simple_spread_takes_profitable_routes() {
+        let (pair, model) = (pair(), model());
+        // Buy at 2000 and sell at 2030 (fee-inclusive) at each size
+        let quoted = |size: Decimal| {
+            let tokens_bought = size / dec!(2000);
+            RouteQuote {
+                route: RouteChoice { size, buy: ("Uniswap", dec!(2000)), sell: ("SushiSwap", dec!(2030)), price_diff: dec!(0.015) },
+                legs: LegQuotes { tokens_bought, usdc_received: tokens_bought * dec!(2030), ..LegQuotes::default() },
+            }
+        };
+        let mut snapshot = snapshot(&pair, &model, Vec::new());
+        // 100 USDC earns 1.5, less than the gas; 5000 moves a pool too far
+        let mut deep = quoted(dec!(5000));
+        deep.legs.price_impact_bps = Some(dec!(120));
+        snapshot.quoted = vec![quoted(dec!(100)), quoted(dec!(1000)), deep];
+
+        let outcomes = SimpleSpreadStrategy.evaluate(&snapshot);
+        assert_eq!(outcomes.len(), 3);
+        let unprofitable = outcomes[0].as_ref().unwrap_err();
+        assert_eq!((unprofitable.size, unprofitable.route), (dec!(100), Some(("Uniswap", "SushiSwap"))));
+        assert_eq!(unprofitable.reason, "unprofitable after fees and gas");
+
+        let opportunity = outcomes[1].as_ref().unwrap();
+        assert_eq!(opportunity.trade_amount, dec!(1000));
+        assert_eq!(opportunity.estimated_profit, dec!(10));
+        assert_eq!((opportunity.buy_dex.as_str(), opportunity.sell_dex.as_str()), ("Uniswap", "SushiSwap"));
+
+        let too_deep = outcomes[2].as_ref().unwrap_err();
+        assert_eq!(too_deep.reason, "price impact 120 bps exceeds the 100 bps slippage limit");
+    }
+
+    #[test]
+    fn test_rejections_become_decisions() {
+        let rejection = Rejection { size: dec!(1000), route: None, reason: "no route".to_string() };
+        assert_eq!(Decision::from(rejection), Decision::ComparisonSkipped { reason: "no route".to_string() });
+    }
+}
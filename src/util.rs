@@ -0,0 +1,436 @@
+use anyhow::{Context, Result, anyhow, bail};
+use ethers::contract::ContractError;
+use ethers::providers::Middleware;
+use ethers::types::Bytes;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use crate::rpc_budget::{self, RpcCallKind};
+
+/// Per-attempt timeout for read-only RPC calls
+pub const RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Extra attempts after the first failure of a read-only RPC call
+pub const RPC_RETRIES: u32 = 2;
+
+/// Delay before the first retry, doubled for each one after
+pub const RPC_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Bound on one venue's whole price fetch, every call and retry inside it
+/// included, so a venue stuck retrying cannot hold up the cycle
+pub const VENUE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An attempt that ran past its timeout, typed so callers can tell a hung
+/// endpoint from one that answered with an error
+#[derive(Debug, thiserror::Error)]
+#[error("timed out after {0:?}")]
+pub struct TimedOut(pub Duration);
+
+/// Whether any attempt behind `error` timed out
+pub fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<TimedOut>())
+}
+
+/// A call the contract itself reverted, e.g. a quoter asked about a pool that
+/// does not exist or cannot fill the swap. Unlike a transport error the
+/// answer is deterministic, so it is never retried.
+#[derive(Debug, thiserror::Error)]
+#[error("contract call reverted (data: {0})")]
+pub struct Reverted(pub Bytes);
+
+/// Whether the call behind `error` reverted
+pub fn is_revert(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<Reverted>())
+}
+
+/// A node answered with something the call cannot return: an empty result,
+/// the wrong type or length, an implausible value. Typed so a flaky or
+/// non-compliant node can be told from a genuine pricing problem.
+#[derive(Debug, thiserror::Error)]
+#[error("malformed RPC response: {0}")]
+pub struct MalformedResponse(pub String);
+
+/// Whether `error` came from a malformed RPC response
+pub fn is_malformed(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<MalformedResponse>())
+}
+
+/// Convert a contract call error, typing reverts as `Reverted` and output
+/// that does not decode as the function's return type as `MalformedResponse`
+pub fn contract_error<M: Middleware + 'static>(error: ContractError<M>) -> anyhow::Error {
+    if let Some(data) = error.as_revert() {
+        return anyhow::Error::new(Reverted(data.clone()));
+    }
+    match error {
+        ContractError::DecodingError(_) | ContractError::AbiError(_) | ContractError::DetokenizationError(_) => {
+            anyhow::Error::new(MalformedResponse(error.to_string()))
+        }
+        other => other.into(),
+    }
+}
+
+/// `fut` cut off after `limit`, failing with `TimedOut` like a call attempt would
+pub async fn with_deadline<T>(limit: Duration, fut: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(limit, fut)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::Error::new(TimedOut(limit))))
+}
+
+/// Run `op` with a timeout on every attempt, retrying failures and timeouts up
+/// to `retries` more times with exponential backoff starting at `backoff`.
+/// Reverts and an exhausted RPC budget fail on the first attempt.
+///
+/// `op` is called once per attempt so each retry gets a fresh future. Only use
+/// this for idempotent calls: never for transaction submission.
+pub async fn with_timeout_and_retry<F, Fut, T>(
+    mut op: F,
+    timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut delay = backoff;
+    let mut attempt = 0;
+
+    loop {
+        let error = match tokio::time::timeout(timeout, op()).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(e)) => e,
+            Err(_) => anyhow::Error::new(TimedOut(timeout)),
+        };
+
+        if is_revert(&error) || rpc_budget::is_budget_exhausted(&error) {
+            return Err(error);
+        }
+        if attempt == retries {
+            return Err(error.context(format!("failed after {} attempt(s)", attempt + 1)));
+        }
+
+        attempt += 1;
+        log::debug!("Attempt {} failed, retrying in {:?}: {:#}", attempt, delay, error);
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+/// `with_timeout_and_retry` with the standard settings for read-only RPC calls
+pub async fn rpc_call<F, Fut, T>(op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    metered_call(RpcCallKind::Call, op).await
+}
+
+/// `rpc_call` for a call the provider bills as `kind`. Every attempt, retries
+/// included, is charged to the installed RPC budget first.
+pub async fn metered_call<F, Fut, T>(kind: RpcCallKind, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    budgeted_call(kind, false, op).await
+}
+
+/// `metered_call` for the reads that settle a trade already sent (receipts,
+/// confirmations, balances). They are charged and throttled like any other
+/// call but never refused by the daily budget, which would otherwise leave
+/// a mined trade unaccounted for.
+pub async fn settlement_call<F, Fut, T>(kind: RpcCallKind, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    budgeted_call(kind, true, op).await
+}
+
+async fn budgeted_call<F, Fut, T>(kind: RpcCallKind, essential: bool, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let attempt = || {
+        let call = op();
+        async move {
+            if let Some(budget) = rpc_budget::global() {
+                if essential {
+                    budget.acquire_essential(kind).await;
+                } else {
+                    budget.acquire(kind).await?;
+                }
+            }
+            call.await
+        }
+    };
+    with_timeout_and_retry(attempt, RPC_TIMEOUT, RPC_RETRIES, RPC_BACKOFF).await
+}
+
+/// `with_timeout_and_retry` with the standard settings, for HTTP APIs that
+/// do not draw on the RPC budget
+pub async fn http_call<F, Fut, T>(op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    with_timeout_and_retry(op, RPC_TIMEOUT, RPC_RETRIES, RPC_BACKOFF).await
+}
+
+/// Most decimals `Pct` shows, however small the percentage (`PCT_DISPLAY_MAX_DECIMALS`)
+static PCT_MAX_DECIMALS: AtomicU32 = AtomicU32::new(6);
+
+/// Decimals every percentage gets, enough for any tradable spread
+const PCT_MIN_DECIMALS: u32 = 2;
+
+/// Set the most decimals `Pct` shows, at least two
+pub fn set_pct_max_decimals(decimals: u32) {
+    PCT_MAX_DECIMALS.store(decimals.max(PCT_MIN_DECIMALS), Ordering::Relaxed);
+}
+
+/// A fraction (0.0125) displayed as a percentage ("1.25%"). Two decimals,
+/// or for spreads too small for that two significant figures, up to the
+/// configured maximum, so a tiny spread never prints as 0.00%. Never
+/// scientific notation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pct(pub Decimal);
+
+impl fmt::Display for Pct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_pct(self.0, PCT_MAX_DECIMALS.load(Ordering::Relaxed)))
+    }
+}
+
+/// `fraction` as a percentage with at most `max_decimals` decimals (see `Pct`)
+fn format_pct(fraction: Decimal, max_decimals: u32) -> String {
+    let Some(percent) = fraction.checked_mul(Decimal::ONE_HUNDRED) else {
+        // Beyond Decimal's range only as a percentage; whole units suffice
+        return format!("{:.0}00%", fraction.trunc());
+    };
+
+    // Position of the first significant decimal, e.g. 3 for 0.0042
+    let magnitude = percent.abs();
+    let mut first_significant = 1;
+    let mut threshold = Decimal::new(1, 1);
+    while !magnitude.is_zero() && magnitude < threshold && first_significant < max_decimals {
+        first_significant += 1;
+        threshold /= Decimal::TEN;
+    }
+
+    let decimals = if first_significant > PCT_MIN_DECIMALS {
+        (first_significant + 1).min(max_decimals.max(PCT_MIN_DECIMALS))
+    } else {
+        PCT_MIN_DECIMALS
+    };
+    let rounded = percent.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero);
+    format!("{:.*}%", decimals as usize, rounded)
+}
+
+/// Fail fast when the RPC endpoint serves a different network than the
+/// configured one, whose contract addresses would quote nonsense
+pub async fn verify_chain_id<M: Middleware>(provider: &M, expected: u64) -> Result<()> {
+    let chain_id = rpc_call(|| async {
+        provider.get_chainid().await.map_err(|e| anyhow!("{}", e)).context("Failed to get chain id")
+    })
+    .await?;
+
+    if chain_id != expected.into() {
+        bail!(
+            "RPC endpoint is on chain id {} but CHAIN_ID is {}; check POLYGON_RPC_URL",
+            chain_id,
+            expected
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::Abi;
+    use ethers::contract::Contract;
+    use ethers::providers::{JsonRpcError, MockResponse, Provider};
+    use ethers::types::Address;
+    use ethers::types::U256;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const BACKOFF: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn test_succeeds_first_time() {
+        let calls = AtomicU32::new(0);
+        let result = with_timeout_and_retry(
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(42)
+            },
+            Duration::from_secs(1),
+            3,
+            BACKOFF,
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_times_out_slow_attempts() {
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = with_timeout_and_retry(
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            },
+            Duration::from_millis(10),
+            1,
+            BACKOFF,
+        )
+        .await;
+
+        let error = result.unwrap_err().context("Failed to get price");
+        let message = format!("{:#}", error);
+        assert!(message.contains("failed after 2 attempt(s)"));
+        assert!(message.contains("timed out after 10ms"));
+        assert!(is_timeout(&error));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_bounds_the_retries_too() {
+        // Each attempt is within its own timeout, but three of them are not
+        let retried = with_timeout_and_retry(
+            || async {
+                tokio::time::sleep(Duration::from_secs(20)).await;
+                Err::<(), _>(anyhow!("connection reset"))
+            },
+            Duration::from_secs(25),
+            2,
+            BACKOFF,
+        );
+        let error = with_deadline(Duration::from_secs(30), retried).await.unwrap_err();
+        assert!(is_timeout(&error));
+
+        assert_eq!(with_deadline(Duration::from_secs(30), async { Ok(7) }).await.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_succeeds() {
+        let calls = AtomicU32::new(0);
+        let result = with_timeout_and_retry(
+            || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(anyhow!("connection reset")),
+                    // A hung attempt counts as a failure too
+                    1 => {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        Ok("late")
+                    }
+                    _ => Ok("ok"),
+                }
+            },
+            Duration::from_millis(10),
+            2,
+            BACKOFF,
+        )
+        .await;
+
+        assert_eq!(result.unwrap(), "ok");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_exhausts_retries_with_last_error() {
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = with_timeout_and_retry(
+            || async {
+                let attempt = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                Err(anyhow!("rate limited (attempt {})", attempt))
+            },
+            Duration::from_secs(1),
+            3,
+            BACKOFF,
+        )
+        .await;
+
+        let message = format!("{:#}", result.unwrap_err());
+        assert!(message.starts_with("failed after 4 attempt(s)"));
+        assert!(message.contains("rate limited (attempt 4)"));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_chain_id() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U256::from(1)).unwrap();
+        let message = verify_chain_id(&provider, 137).await.unwrap_err().to_string();
+        assert!(message.contains("chain id 1 but CHAIN_ID is 137"));
+
+        mock.push(U256::from(137)).unwrap();
+        verify_chain_id(&provider, 137).await.unwrap();
+    }
+
+    #[test]
+    fn test_formats_small_and_large_percentages() {
+        use rust_decimal_macros::dec;
+
+        assert_eq!(format_pct(dec!(0.0125), 6), "1.25%");
+        assert_eq!(format_pct(dec!(0.0001), 6), "0.01%");
+        assert_eq!(format_pct(Decimal::ZERO, 6), "0.00%");
+
+        // Two significant figures instead of 0.00%
+        assert_eq!(format_pct(dec!(0.0000042), 6), "0.00042%");
+        assert_eq!(format_pct(dec!(0.00000123456), 6), "0.00012%");
+        assert_eq!(format_pct(dec!(-0.00001), 6), "-0.0010%");
+        assert_eq!(format_pct(dec!(0.0000001), 6), "0.000010%");
+
+        // The cap wins over significant figures
+        assert_eq!(format_pct(dec!(0.0000042), 4), "0.0004%");
+        assert_eq!(format_pct(dec!(0.000000000001), 6), "0.000000%");
+
+        // Large values stay positional
+        assert_eq!(format_pct(dec!(12345.6789), 6), "1234567.89%");
+        assert_eq!(format_pct(dec!(1e27), 6), "100000000000000000000000000000%");
+        assert_eq!(Pct(dec!(0.5)).to_string(), "50.00%");
+    }
+
+    #[tokio::test]
+    async fn test_reverted_call_is_typed_and_not_retried() {
+        let (provider, mock) = Provider::mocked();
+        // Responses pop last-pushed first; this one is still queued
+        // afterwards unless the revert was retried
+        mock.push(U256::from(137)).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: None,
+        }));
+        let provider = std::sync::Arc::new(provider);
+
+        let abi: Abi = serde_json::from_str(crate::dex::abi::UNISWAP_V3_QUOTER_ABI).unwrap();
+        let quoter = Contract::new(Address::zero(), abi, provider.clone());
+        let call = quoter
+            .method::<_, U256>(
+                "quoteExactInputSingle",
+                (Address::zero(), Address::repeat_byte(1), 500u32, U256::from(1000), U256::zero()),
+            )
+            .unwrap();
+        let result = with_timeout_and_retry(
+            || async { call.call().await.map_err(contract_error).context("Failed to get Uniswap quote") },
+            Duration::from_secs(1),
+            2,
+            BACKOFF,
+        )
+        .await;
+
+        let error = result.unwrap_err();
+        assert!(is_revert(&error));
+        assert!(!format!("{:#}", error).contains("attempt"));
+        assert_eq!(provider.get_chainid().await.unwrap(), U256::from(137));
+    }
+}